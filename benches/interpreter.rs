@@ -0,0 +1,23 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use neander::bench::{self, workloads, Strategy};
+
+fn interpreter(c: &mut Criterion) {
+    for workload in workloads() {
+        let mut group = c.benchmark_group(workload.name);
+        for strategy in Strategy::ALL {
+            if workload.run(strategy).is_none() {
+                continue;
+            }
+            group.bench_function(strategy.name(), |b| {
+                b.iter(|| black_box(&workload).run(strategy))
+            });
+        }
+        group.finish();
+    }
+    c.bench_function("endless/step", |b| {
+        b.iter(|| bench::throughput(black_box(10_000)))
+    });
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
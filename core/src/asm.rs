@@ -0,0 +1,350 @@
+//! An assembler for Neander programs. The source is a sequence of
+//! tokens, like a memfile, that may also contain instructions:
+//!
+//! ```text
+//! start:  LDA x      ; mnemonics are case insensitive
+//!         ADD y
+//!         STA sum
+//!         HLT
+//!         org 128
+//! x:      5
+//! y:      0xFB       ; plain bytes, in decimal or hexadecimal
+//! sum:    0
+//! ```
+//!
+//! Instructions taking an address read the next token as their
+//! operand, either a byte or a label defined anywhere in the source.
+//! Every memfile is also a valid assembly source.
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::cpu::instr;
+use crate::image::{MemoryImage, Origin};
+use crate::memfile::{self, MemfileErrorKind};
+use crate::srcmap::SourceMap;
+use crate::symbols::{self, SymbolTable};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    /// Column of the token with the error, from 1
+    pub col: usize,
+    pub message: String,
+}
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// What a token of a line does, with its column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Op {
+    Label(usize, String),
+    Org(u8),
+    Byte(usize, u8),
+    /// A byte waiting for the labels to be known
+    Ref(usize, String),
+    Error(usize, String),
+}
+
+/// Assembles `source` into `mem`, returning the labels defined.
+/// On errors, `mem` is left untouched and every error is returned.
+pub fn assemble(mem: &mut [u8], source: &str) -> Result<SymbolTable, Vec<AsmError>> {
+    assemble_mapped(mem, source).map(|(symbols, _)| symbols)
+}
+
+/// Same as `assemble`, also returning the line each address came from.
+pub fn assemble_mapped(
+    mem: &mut [u8],
+    source: &str,
+) -> Result<(SymbolTable, SourceMap), Vec<AsmError>> {
+    let (image, symbols, map) = assemble_image(mem.len(), source, None)?;
+    image.commit(mem);
+    Ok((symbols, map))
+}
+
+/// Assembles `source`, read from `file` if given, into an image
+/// of `len` bytes, see `crate::image`.
+pub fn assemble_image(
+    len: usize,
+    source: &str,
+    file: Option<Arc<str>>,
+) -> Result<(MemoryImage, SymbolTable, SourceMap), Vec<AsmError>> {
+    let lines: Vec<_> = source.lines().map(parse_line).collect();
+    link(len, source, file, lines.iter().map(Vec::as_slice))
+}
+
+/// An assembler for sources edited a little at a time, like in an
+/// editor. Lines are parsed once and kept while they're unchanged,
+/// so reassembling after a keystroke only parses the edited line
+/// and resolves the labels again.
+#[derive(Debug, Default)]
+pub struct Assembler {
+    lines: BTreeMap<String, Rc<[Op]>>,
+}
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Same as `assemble_mapped`, reusing the lines parsed before.
+    pub fn assemble(
+        &mut self,
+        mem: &mut [u8],
+        source: &str,
+    ) -> Result<(SymbolTable, SourceMap), Vec<AsmError>> {
+        let (image, symbols, map) = self.assemble_image(mem.len(), source)?;
+        image.commit(mem);
+        Ok((symbols, map))
+    }
+    /// Same as `assemble_image`, reusing the lines parsed before.
+    pub fn assemble_image(
+        &mut self,
+        len: usize,
+        source: &str,
+    ) -> Result<(MemoryImage, SymbolTable, SourceMap), Vec<AsmError>> {
+        let mut old = core::mem::take(&mut self.lines);
+        let mut lines = Vec::new();
+        for text in source.lines() {
+            let ops = match (old.remove_entry(text), self.lines.get(text)) {
+                (_, Some(ops)) => ops.clone(),
+                (Some((text, ops)), None) => {
+                    self.lines.insert(text, ops.clone());
+                    ops
+                }
+                (None, None) => {
+                    let ops: Rc<[Op]> = parse_line(text).into();
+                    self.lines.insert(text.to_string(), ops.clone());
+                    ops
+                }
+            };
+            lines.push(ops);
+        }
+        link(len, source, None, lines.iter().map(|ops| &ops[..]))
+    }
+}
+
+/// Parses a line of source, without looking at the other lines.
+fn parse_line(text: &str) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let mut tokens = tokens(text);
+    while let Some((col, token)) = tokens.next() {
+        if let Some(name) = token.strip_suffix(':') {
+            if symbols::is_label(name) {
+                ops.push(Op::Label(col, name.to_string()));
+            } else {
+                ops.push(Op::Error(col, format!("invalid label: {name}")));
+            }
+            continue;
+        }
+        if token.eq_ignore_ascii_case("org") {
+            ops.push(match tokens.next() {
+                Some((col, addr)) => match byte(addr) {
+                    Ok(addr) => Op::Org(addr),
+                    Err(e) => Op::Error(col, e),
+                },
+                None => Op::Error(col, "org needs an address".to_string()),
+            });
+            continue;
+        }
+        if token.eq_ignore_ascii_case("db") {
+            continue;
+        }
+        let mnemonic = instr::INSTRUCTIONS
+            .iter()
+            .find(|(_, name)| token.eq_ignore_ascii_case(name));
+        match mnemonic {
+            Some(&(opcode, name)) => {
+                ops.push(Op::Byte(col, opcode));
+                if instr::has_operand(opcode) {
+                    ops.push(match tokens.next() {
+                        Some((col, op)) if symbols::is_label(op) => Op::Ref(col, op.to_string()),
+                        Some((col, op)) => match byte(op) {
+                            Ok(b) => Op::Byte(col, b),
+                            Err(e) => Op::Error(col, e),
+                        },
+                        None => Op::Error(col, format!("{name} needs an address")),
+                    });
+                }
+            }
+            None if symbols::is_label(token) => {
+                ops.push(Op::Error(col, format!("unknown instruction: {token}")));
+            }
+            None => ops.push(match byte(token) {
+                Ok(b) => Op::Byte(col, b),
+                Err(e) => Op::Error(col, e),
+            }),
+        }
+    }
+    ops
+}
+
+/// Places the parsed lines of `source` in memory and resolves their labels.
+fn link<'a>(
+    len: usize,
+    source: &str,
+    file: Option<Arc<str>>,
+    lines: impl Iterator<Item = &'a [Op]>,
+) -> Result<(MemoryImage, SymbolTable, SourceMap), Vec<AsmError>> {
+    let mut map = SourceMap::new(source);
+    let mut symbols = SymbolTable::new();
+    let mut errors = Vec::new();
+    // (address, line, column, label), for the labels used
+    let mut refs = Vec::new();
+    let mut image = MemoryImage::new(len);
+    let mut cursor = 0_usize;
+    let mut block = 0;
+    let mut overflow = false;
+    let origin = |line, block| Origin {
+        file: file.clone(),
+        line: Some(line),
+        block,
+    };
+    for (line, ops) in lines.enumerate() {
+        let line = line + 1;
+        let mut error = |col, message| errors.push(AsmError { line, col, message });
+        for op in ops {
+            match op {
+                Op::Label(col, name) => {
+                    if cursor > 255 {
+                        error(*col, format!("label past the end of memory: {name}"));
+                    } else if !symbols.insert(name, cursor as u8) {
+                        error(*col, format!("label defined twice: {name}"));
+                    }
+                }
+                Op::Org(addr) => {
+                    cursor = *addr as usize;
+                    block = cursor;
+                }
+                Op::Error(col, message) => error(*col, message.clone()),
+                Op::Byte(col, _) | Op::Ref(col, _) if cursor > 255 => {
+                    if !overflow {
+                        overflow = true;
+                        error(*col, "program doesn't fit in memory".to_string());
+                    }
+                }
+                Op::Byte(_, b) => {
+                    image.set(cursor, *b, origin(line, block));
+                    map.insert(cursor as u8, line);
+                    cursor += 1;
+                }
+                Op::Ref(col, name) => {
+                    image.set(cursor, 0, origin(line, block));
+                    refs.push((cursor, line, *col, name));
+                    map.insert(cursor as u8, line);
+                    cursor += 1;
+                }
+            }
+        }
+    }
+    for (addr, line, col, name) in refs {
+        match symbols.get(name) {
+            Some(value) => image.patch(addr, value),
+            None => {
+                let message = format!("unknown label: {name}");
+                errors.push(AsmError { line, col, message });
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok((image, symbols, map))
+    } else {
+        errors.sort_by_key(|e| e.line);
+        Err(errors)
+    }
+}
+
+/// Returns the tokens of a source line with their columns,
+/// from 1, leaving out its comment.
+pub fn tokens(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_sep = |c: char| c.is_whitespace() || c == ',';
+    let mut rest = text.split(';').next().unwrap_or_default();
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        let start = rest.find(|c| !is_sep(c))?;
+        let len = rest[start..].find(is_sep).unwrap_or(rest.len() - start);
+        let token = &rest[start..start + len];
+        let col = offset + start + 1;
+        rest = &rest[start + len..];
+        offset += start + len;
+        Some((col, token))
+    })
+}
+
+fn byte(token: &str) -> Result<u8, String> {
+    memfile::parse_byte(token).map_err(|e| match e {
+        MemfileErrorKind::OutOfRangeInteger(x) => format!("out of range integer: {x}"),
+        _ => format!("invalid number: {token}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_assemble() {
+        let mut mem = [0; 256];
+        let source = "start: lda x ; load\n ADD 0x81\n sta sum\n jmp start\n\
+                      org 128\nx: 5, -5\nsum: db 0";
+        let symbols = assemble(&mut mem, source).unwrap();
+        assert_eq!(mem[..8], [LDA, 128, ADD, 129, STA, 130, JMP, 0]);
+        assert_eq!(mem[128..131], [5, 251, 0]);
+        assert_eq!(symbols.get("sum"), Some(130));
+        let (_, map) = assemble_mapped(&mut mem, source).unwrap();
+        assert_eq!(map.describe(5).unwrap(), "line 3: sta sum");
+        assert_eq!(map.line(130), Some(7));
+
+        // memfiles assemble to the same memory
+        let mut memfile = [0; 256];
+        let source = "0x20 128 org 128 counter: 5";
+        memfile::parse_memfile(&mut memfile, source).unwrap();
+        let mut mem = [0; 256];
+        assemble(&mut mem, source).unwrap();
+        assert_eq!(mem, memfile);
+
+        let errors = assemble(&mut mem, "lda\nfoo 1\nx: 300\njmp nowhere\nx: 1").unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "line 1: LDA needs an address",
+                "line 2: unknown instruction: foo",
+                "line 3: out of range integer: 300",
+                "line 4: unknown label: nowhere",
+                "line 5: label defined twice: x",
+            ]
+        );
+        assert_eq!(mem, memfile);
+        assert!(assemble(&mut mem, "org 255 1 2").is_err());
+
+        let errors = assemble(&mut mem, "x: lda  nowhere ; y").unwrap_err();
+        assert_eq!(errors[0].col, 9);
+        let tokens: Vec<_> = tokens("  a: lda,b ;c").collect();
+        assert_eq!(tokens, [(3, "a:"), (6, "lda"), (10, "b")]);
+
+        // reassembling edits gives the same as assembling from scratch
+        let mut assembler = Assembler::new();
+        let edits = [
+            "lda x\nhlt\nx: 1",
+            "lda x\nhlt\nx: 1\n",
+            "lda y\nhlt\nx: 1",
+            "x: lda x\nhlt",
+        ];
+        for source in edits {
+            let (mut a, mut b) = ([0; 256], [0; 256]);
+            assert_eq!(
+                assembler.assemble(&mut a, source),
+                assemble_mapped(&mut b, source)
+            );
+            assert_eq!(a, b);
+        }
+        assert_eq!(assembler.lines.len(), 2);
+    }
+}
@@ -0,0 +1,73 @@
+//! The virtual clock of the devices. It counts the instructions
+//! executed instead of real time, and gives devices random numbers
+//! derived only from its seed and its count, so a run with devices
+//! is the same on every machine, every time it's replayed.
+
+/// A seed and the instructions executed since the run started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Clock {
+    pub seed: u64,
+    pub ticks: u64,
+}
+
+/// Mixes the bits of `x`, as in the splitmix64 generator.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+impl Clock {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, ticks: 0 }
+    }
+    pub fn advance(&mut self) {
+        self.ticks += 1;
+    }
+    /// Rewinds to the start of the run, keeping the seed.
+    pub fn reset(&mut self) {
+        self.ticks = 0;
+    }
+
+    /// A random number for the current tick. Devices pass
+    /// different streams so they don't see the same numbers.
+    pub fn random(&self, stream: u64) -> u64 {
+        mix(mix(self.seed ^ mix(stream)) ^ self.ticks)
+    }
+    /// Whether an event repeating every `period` ticks is due,
+    /// counting from the start of the run.
+    pub fn every(&self, period: u64) -> bool {
+        period != 0 && self.ticks.is_multiple_of(period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_clock() {
+        let mut clock = Clock::new(42);
+        let first: Vec<_> = (0..4)
+            .map(|_| {
+                clock.advance();
+                clock.random(1)
+            })
+            .collect();
+        assert_eq!(clock.ticks, 4);
+        assert!(clock.every(2) && !clock.every(3) && !clock.every(0));
+        assert_ne!(clock.random(1), clock.random(2));
+
+        clock.reset();
+        let again: Vec<_> = (0..4)
+            .map(|_| {
+                clock.advance();
+                clock.random(1)
+            })
+            .collect();
+        assert_eq!(first, again);
+        assert_ne!(Clock::new(43).random(1), Clock::new(42).random(1));
+    }
+}
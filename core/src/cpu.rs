@@ -0,0 +1,1261 @@
+pub mod instr {
+    pub const NOP: u8 = 0b0000_0000;
+    pub const STA: u8 = 0b0001_0000;
+    pub const LDA: u8 = 0b0010_0000;
+    pub const ADD: u8 = 0b0011_0000;
+    pub const OR: u8 = 0b0100_0000;
+    pub const AND: u8 = 0b0101_0000;
+    pub const NOT: u8 = 0b0110_0000;
+    pub const JMP: u8 = 0b1000_0000;
+    pub const JN: u8 = 0b1001_0000;
+    pub const JZ: u8 = 0b1010_0000;
+    pub const HLT: u8 = 0b1111_0000;
+    /// All instructions with their mnemonics.
+    pub const INSTRUCTIONS: [(u8, &str); 11] = [
+        (NOP, "NOP"),
+        (STA, "STA"),
+        (LDA, "LDA"),
+        (ADD, "ADD"),
+        (OR, "OR"),
+        (AND, "AND"),
+        (NOT, "NOT"),
+        (JMP, "JMP"),
+        (JN, "JN"),
+        (JZ, "JZ"),
+        (HLT, "HLT"),
+    ];
+    /// Returns the mnemonic of `opcode`, or None if it's not an instruction.
+    pub fn name(opcode: u8) -> Option<&'static str> {
+        INSTRUCTIONS
+            .iter()
+            .find(|(i, _)| *i == opcode)
+            .map(|(_, name)| *name)
+    }
+    /// Returns true if `opcode` takes an address as argument.
+    pub fn has_operand(opcode: u8) -> bool {
+        matches!(opcode, STA | LDA | ADD | OR | AND | JMP | JN | JZ)
+    }
+    /// Returns true if `opcode` updates the N and Z flags.
+    pub fn sets_flags(opcode: u8) -> bool {
+        matches!(opcode, LDA | ADD | OR | AND | NOT)
+    }
+    /// Returns what `opcode` does, as a register transfer,
+    /// or None if it's not an instruction.
+    pub fn describe(opcode: u8) -> Option<&'static str> {
+        Some(match opcode {
+            NOP => "does nothing",
+            STA => "MEM[addr] <- AC",
+            LDA => "AC <- MEM[addr]",
+            ADD => "AC <- AC + MEM[addr]",
+            OR => "AC <- AC OR MEM[addr]",
+            AND => "AC <- AC AND MEM[addr]",
+            NOT => "AC <- NOT AC",
+            JMP => "PC <- addr",
+            JN => "PC <- addr, if N is set",
+            JZ => "PC <- addr, if Z is set",
+            HLT => "halts the program",
+            _ => return None,
+        })
+    }
+    #[cfg(feature = "std")]
+    pub fn print_instr_table() {
+        std::println!("INSTR | DEC | HEX");
+        for (i, name) in INSTRUCTIONS {
+            std::println!("{name:5} | {i:3} | {i:X}");
+        }
+    }
+}
+use instr::*;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::formats;
+use crate::hooks::Hooks;
+#[cfg(feature = "std")]
+use crate::image::{self, MemoryImage};
+use crate::memfile::{self, MemfileError};
+use crate::micro::{self, MicroOp, Phase};
+#[cfg(feature = "std")]
+use crate::srcmap::SourceMap;
+#[cfg(feature = "std")]
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecResult {
+    Normal,
+    Halted,
+    MemWrite { addr: u16, value: i8 },
+    Exception(NeanderException),
+}
+impl ExecResult {
+    pub fn unwrap(self) {
+        if let ExecResult::Exception(e) = self {
+            panic!("ExecResult was an exception: {e}")
+        }
+    }
+}
+impl<T> From<Result<T, NeanderException>> for ExecResult {
+    fn from(value: Result<T, NeanderException>) -> Self {
+        if let Err(e) = value {
+            Self::Exception(e)
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// The Neander CPU. 8-bit based,
+/// with a program counter, accumulator
+/// and 256 bytes of RAM. 2-complement
+/// integer representation.
+///
+/// Extended variants set `MEM` to another power of two, up to 64K.
+/// Above 256 bytes, operands are addresses of two bytes, high byte
+/// first. The rest of the crate, and the methods with 8-bit
+/// addresses, work on the 256 byte machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Neander<const MEM: usize = 256> {
+    /// The Program Counter
+    pc: u16,
+    /// The Accumulator
+    acc: i8,
+    /// The status register.
+    /// bit 0 is the zero bit and
+    /// bit 1 is the negative bit.
+    /// bit 2 is set if the end of program is reached,
+    /// and used only by this implementation
+    status: u8,
+    /// RAM
+    #[cfg_attr(feature = "serde", serde(with = "memory_serde"))]
+    mem: Box<[u8; MEM]>,
+    /// Memory Address Register, used by micro-operations
+    #[cfg_attr(feature = "serde", serde(default))]
+    mar: u16,
+    /// Memory Data Register, used by micro-operations
+    #[cfg_attr(feature = "serde", serde(default))]
+    mdr: u8,
+    /// Instruction Register, used by micro-operations
+    #[cfg_attr(feature = "serde", serde(default))]
+    ir: u8,
+    /// Index of the next micro-operation of the current instruction.
+    /// 0 means the CPU is at an instruction boundary.
+    #[cfg_attr(feature = "serde", serde(default))]
+    uop: u8,
+}
+
+/// Serializes the memory as a sequence of bytes.
+#[cfg(feature = "serde")]
+mod memory_serde {
+    use alloc::boxed::Box;
+    use alloc::format;
+    use alloc::vec::Vec;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const MEM: usize>(
+        mem: &[u8; MEM],
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(mem)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>, const MEM: usize>(
+        d: D,
+    ) -> Result<Box<[u8; MEM]>, D::Error> {
+        let bytes = Vec::<u8>::deserialize(d)?;
+        let len = bytes.len();
+        bytes
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|_| D::Error::custom(format!("expected {MEM} bytes of memory, got {len}")))
+    }
+}
+
+/// Header of the binary state files, followed by the version.
+const STATE_MAGIC: &[u8; 4] = b"NEAN";
+
+/// A copy of every CPU register, used to save
+/// and restore the CPU state without its memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u8,
+    pub acc: i8,
+    pub status: u8,
+    pub mar: u8,
+    pub mdr: u8,
+    pub ir: u8,
+    /// Index of the next micro-operation
+    pub uop: u8,
+}
+
+/// An Error that occurred during execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeanderException {
+    EndOfProgram,
+    InvalidInstruction(u8),
+    MissingArgument,
+    /// The program ran for the given number of instructions without halting
+    StepLimitExceeded(u64),
+}
+impl core::fmt::Display for NeanderException {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::EndOfProgram => write!(f, "reached end of program"),
+            Self::InvalidInstruction(i) => write!(f, "invalid instruction: {i:x}"),
+            Self::MissingArgument => write!(f, "missing argument to instruction"),
+            Self::StepLimitExceeded(n) => write!(f, "did not halt after {n} instructions"),
+        }
+    }
+}
+
+/// Why a program couldn't be loaded into a new machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Memfile(MemfileError),
+    /// A binary longer than memory, with its length
+    TooLarge(usize),
+}
+impl core::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Memfile(e) => write!(f, "{e}"),
+            Self::TooLarge(len) => write!(f, "binary has {len} bytes, more than memory"),
+        }
+    }
+}
+impl core::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Memfile(e) => Some(e),
+            Self::TooLarge(_) => None,
+        }
+    }
+}
+impl From<MemfileError> for LoadError {
+    fn from(e: MemfileError) -> Self {
+        Self::Memfile(e)
+    }
+}
+
+/// Formats a byte in hexadecimal followed by its value in decimal,
+/// or by both its signed and unsigned values if they differ,
+/// like `F0 (-16 / 240)`.
+pub fn fmt_dual(val: u8) -> String {
+    if val < 128 {
+        format!("{val:02X} ({val})")
+    } else {
+        format!("{val:02X} ({} / {val})", val as i8)
+    }
+}
+
+macro_rules! or_bail {
+    ($x:expr) => {
+        match $x {
+            Ok(x) => x,
+            e => return e.into(),
+        }
+    };
+}
+/// What `execute` reports about the instruction it runs, with
+/// addresses as wide as the memory.
+trait Observer<const MEM: usize> {
+    fn fetch(&mut self, _cpu: &Neander<MEM>, _pc: usize, _opcode: u8) {}
+    fn mem_read(&mut self, _addr: usize, _value: u8) {}
+    fn mem_write(&mut self, _addr: usize, _old: u8, _new: u8) {}
+}
+impl<const MEM: usize> Observer<MEM> for () {}
+
+/// Passes what `execute` reports on to `Hooks`.
+struct Hooked<'a, H>(&'a mut H);
+impl<H: Hooks> Observer<256> for Hooked<'_, H> {
+    fn fetch(&mut self, cpu: &Neander, pc: usize, opcode: u8) {
+        self.0.on_fetch(cpu, pc as u8, opcode);
+    }
+    fn mem_read(&mut self, addr: usize, value: u8) {
+        self.0.on_mem_read(addr as u8, value);
+    }
+    fn mem_write(&mut self, addr: usize, old: u8, new: u8) {
+        self.0.on_mem_write(addr as u8, old, new);
+    }
+}
+
+impl<const MEM: usize> Neander<MEM> {
+    /// Bytes of an address operand.
+    pub const ADDR_BYTES: usize = if MEM <= 256 { 1 } else { 2 };
+
+    fn blank() -> Self {
+        const { assert!(MEM.is_power_of_two() && MEM <= 1 << 16) };
+        Self {
+            pc: 0,
+            acc: 0,
+            status: 0,
+            mem: vec![0; MEM].into_boxed_slice().try_into().unwrap(),
+            mar: 0,
+            mdr: 0,
+            ir: 0,
+            uop: 0,
+        }
+    }
+    pub fn acc(&self) -> i8 {
+        self.acc
+    }
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+    pub fn mdr(&self) -> u8 {
+        self.mdr
+    }
+    pub fn ir(&self) -> u8 {
+        self.ir
+    }
+    /// The program counter, for machines of any memory size.
+    pub fn program_counter(&self) -> usize {
+        self.pc as usize
+    }
+    /// Same as `set_pc`, for machines of any memory size.
+    pub fn set_program_counter(&mut self, pc: usize) {
+        self.pc = Self::wrap(pc);
+        self.status &= !4;
+    }
+    /// Sets the accumulator, updating the N and Z flags.
+    pub fn set_acc(&mut self, acc: i8) {
+        self.acc = acc;
+        self.set_status(acc);
+    }
+    /// Returns true if an instruction was started with
+    /// `step_uop` but not yet completed.
+    pub fn mid_instruction(&self) -> bool {
+        self.uop != 0
+    }
+    /// Returns the index of the next micro-operation
+    /// inside the current instruction.
+    pub fn micro_cycle(&self) -> u8 {
+        self.uop
+    }
+    pub fn run(&mut self) -> Result<(), NeanderException> {
+        loop {
+            match self.step() {
+                ExecResult::Halted => break Ok(()),
+                ExecResult::Exception(e) => return Err(e),
+                _ => (),
+            }
+        }
+    }
+    /// Same as `run`, but fails with `StepLimitExceeded` if the
+    /// program doesn't halt within `max_steps` instructions.
+    pub fn run_with_limit(&mut self, max_steps: u64) -> Result<(), NeanderException> {
+        for _ in 0..max_steps {
+            match self.step() {
+                ExecResult::Halted => return Ok(()),
+                ExecResult::Exception(e) => return Err(e),
+                _ => (),
+            }
+        }
+        Err(NeanderException::StepLimitExceeded(max_steps))
+    }
+    /// Executes the next instruction and updates the program counter.
+    /// Returns `Halted` on a HLT instruction, `MemWrite` when it stored
+    /// a byte, `Exception` if it failed, or `Normal` otherwise.
+    /// Stepping never allocates, which `tests/alloc.rs` checks.
+    pub fn step(&mut self) -> ExecResult {
+        if self.mid_instruction() {
+            return self.finish_instruction();
+        }
+        self.execute(&mut ())
+    }
+    fn execute(&mut self, obs: &mut impl Observer<MEM>) -> ExecResult {
+        let pc = self.pc as usize;
+        let instr = or_bail!(self.next_instr());
+        obs.fetch(self, pc, instr);
+        // reads the operand of an instruction from memory
+        let mut read = |cpu: &mut Self| -> Result<u8, NeanderException> {
+            let addr = cpu.arg()?;
+            let value = cpu.mem[addr];
+            obs.mem_read(addr, value);
+            Ok(value)
+        };
+        match instr {
+            // NOP
+            NOP => {}
+            // STA addr
+            STA => {
+                let arg = or_bail!(self.arg());
+                let old = self.mem[arg];
+                self.mem[arg] = self.acc as u8;
+                obs.mem_write(arg, old, self.acc as u8);
+                return ExecResult::MemWrite {
+                    addr: arg as u16,
+                    value: self.acc,
+                };
+            }
+            // LDA addr
+            LDA => {
+                self.acc = or_bail!(read(self)) as i8;
+                self.set_status(self.acc);
+            }
+            // ADD addr
+            ADD => {
+                let value = or_bail!(read(self));
+                self.acc = self.acc.wrapping_add(value as i8);
+                self.set_status(self.acc);
+            }
+            // OR addr
+            OR => {
+                self.acc |= or_bail!(read(self)) as i8;
+                self.set_status(self.acc);
+            }
+            // AND addr
+            AND => {
+                self.acc &= or_bail!(read(self)) as i8;
+                self.set_status(self.acc);
+            }
+            // NOT
+            NOT => {
+                self.acc = !self.acc;
+                self.set_status(self.acc);
+            }
+            // JMP addr
+            JMP => {
+                self.pc = or_bail!(self.arg()) as u16;
+            }
+            // JN addr
+            JN => {
+                let addr = or_bail!(self.arg());
+                if self.status_negative() {
+                    self.pc = addr as u16;
+                }
+            }
+            // JZ addr
+            JZ => {
+                let addr = or_bail!(self.arg());
+                if self.status_zero() {
+                    self.pc = addr as u16;
+                }
+            }
+            // HLT
+            HLT => return ExecResult::Halted,
+            i => return ExecResult::Exception(NeanderException::InvalidInstruction(i)),
+        }
+        ExecResult::Normal
+    }
+
+    /// Steps a micro-operation. Only the 256 byte machine starts
+    /// instructions this way, so operands are a single byte.
+    fn uop_step(&mut self) -> (MicroOp, ExecResult) {
+        let op = self.next_uop();
+        let res = self.exec_uop(op);
+        self.uop += 1;
+        let done = matches!(res, ExecResult::Exception(_))
+            || self.uop as usize >= micro::FETCH.len()
+                && self.uop as usize >= micro::microprogram(self.ir).unwrap().len();
+        if done {
+            self.uop = 0;
+        }
+        (op, res)
+    }
+    /// Returns the micro-operation `step_uop` executes next.
+    pub fn next_uop(&self) -> MicroOp {
+        if self.uop < micro::FETCH.len() as u8 {
+            micro::FETCH[self.uop as usize]
+        } else {
+            // IR is always valid past the fetch cycle
+            micro::microprogram(self.ir).unwrap()[self.uop as usize]
+        }
+    }
+    fn exec_uop(&mut self, op: MicroOp) -> ExecResult {
+        match op {
+            MicroOp::MarFromPc => {
+                if self.status_end_of_prog() {
+                    return ExecResult::Exception(if self.uop == 0 {
+                        NeanderException::EndOfProgram
+                    } else {
+                        NeanderException::MissingArgument
+                    });
+                }
+                self.mar = self.pc;
+            }
+            MicroOp::ReadIncPc => {
+                self.mdr = self.mem[self.mar as usize];
+                if self.pc as usize == MEM - 1 {
+                    self.set_end_of_program();
+                }
+                self.pc = Self::wrap(self.pc as usize + 1);
+            }
+            MicroOp::IrFromMdr => {
+                self.ir = self.mdr;
+                if micro::microprogram(self.ir).is_none() {
+                    return ExecResult::Exception(NeanderException::InvalidInstruction(self.ir));
+                }
+            }
+            MicroOp::MarFromMdr => self.mar = Self::wrap(self.mdr as usize),
+            MicroOp::Read => self.mdr = self.mem[self.mar as usize],
+            MicroOp::MdrFromAcc => self.mdr = self.acc as u8,
+            MicroOp::Write => {
+                self.mem[self.mar as usize] = self.mdr;
+                return ExecResult::MemWrite {
+                    addr: self.mar,
+                    value: self.mdr as i8,
+                };
+            }
+            MicroOp::AccFromMdr => {
+                self.acc = self.mdr as i8;
+                self.set_status(self.acc);
+            }
+            MicroOp::AccAdd => {
+                self.acc = self.acc.wrapping_add(self.mdr as i8);
+                self.set_status(self.acc);
+            }
+            MicroOp::AccOr => {
+                self.acc |= self.mdr as i8;
+                self.set_status(self.acc);
+            }
+            MicroOp::AccAnd => {
+                self.acc &= self.mdr as i8;
+                self.set_status(self.acc);
+            }
+            MicroOp::AccNot => {
+                self.acc = !self.acc;
+                self.set_status(self.acc);
+            }
+            MicroOp::PcFromMdr => self.pc = Self::wrap(self.mdr as usize),
+            MicroOp::PcFromMdrIfN => {
+                if self.status_negative() {
+                    self.pc = Self::wrap(self.mdr as usize);
+                }
+            }
+            MicroOp::PcFromMdrIfZ => {
+                if self.status_zero() {
+                    self.pc = Self::wrap(self.mdr as usize);
+                }
+            }
+            MicroOp::Halt => return ExecResult::Halted,
+        }
+        ExecResult::Normal
+    }
+    /// Runs the remaining micro-operations of the current instruction.
+    /// Cold, so stepping whole instructions stays small.
+    #[cold]
+    fn finish_instruction(&mut self) -> ExecResult {
+        let mut res = ExecResult::Normal;
+        while self.mid_instruction() {
+            match self.uop_step().1 {
+                ExecResult::Normal => (),
+                r => res = r,
+            }
+        }
+        res
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        self.mem.as_ref()
+    }
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.mem.as_mut()
+    }
+    /// Same as `ram`, for machines of any memory size.
+    pub fn byte(&self, addr: usize) -> u8 {
+        self.mem[addr]
+    }
+    /// Same as `set_ram`, for machines of any memory size.
+    pub fn set_byte(&mut self, addr: usize, val: u8) {
+        self.mem[addr] = val;
+    }
+
+    /// Returns the address of an instruction, which wraps around memory.
+    fn arg(&mut self) -> Result<usize, NeanderException> {
+        let mut addr = 0;
+        for _ in 0..Self::ADDR_BYTES {
+            let byte = match self.next_instr() {
+                Ok(byte) => byte,
+                Err(NeanderException::EndOfProgram) => {
+                    return Err(NeanderException::MissingArgument)
+                }
+                _ => unreachable!(),
+            };
+            addr = addr << 8 | byte as usize;
+        }
+        Ok(addr & (MEM - 1))
+    }
+    fn wrap(addr: usize) -> u16 {
+        (addr & (MEM - 1)) as u16
+    }
+    fn set_status(&mut self, val: i8) {
+        // clear bits 0 and 1, but leave bit 2 alone.
+        self.status &= 4;
+        if val == 0 {
+            self.status |= 1;
+        } else if val < 0 {
+            self.status |= 2;
+        }
+    }
+    fn set_end_of_program(&mut self) {
+        self.status |= 4;
+    }
+    pub fn status_zero(&self) -> bool {
+        self.status & 1 != 0
+    }
+    pub fn status_negative(&self) -> bool {
+        self.status & 2 != 0
+    }
+    pub fn status_end_of_prog(&self) -> bool {
+        self.status & 4 != 0
+    }
+    #[cfg(feature = "std")]
+    pub fn print_mem_range(&self, start: usize, end: usize) {
+        std::print!("{}", self.mem_range_string(start, end, false));
+    }
+    #[cfg(feature = "std")]
+    pub fn print_mem(&self) {
+        self.print_mem_range(0, MEM - 1);
+    }
+    /// Formats memory from `start` to `end` in lines of 4 bytes,
+    /// aligned to addresses divisible by 4. If `dual` is set, bytes
+    /// are followed by their decimal values, as in `fmt_dual`.
+    /// Addresses have 2 hexadecimal digits, or 4 above 256 bytes.
+    pub fn mem_range_string(&self, start: usize, end: usize, dual: bool) -> String {
+        let s = start - start % 4;
+        let e = (end | 3).min(MEM - 1);
+        let (hex, dec) = if MEM <= 256 { (2, 3) } else { (4, 5) };
+        let mut out = String::new();
+        for (i, line) in self.memory()[s..=e].chunks(4).enumerate() {
+            let bytes: Vec<_> = line
+                .iter()
+                .map(|&b| match dual {
+                    true => format!("{:15}", fmt_dual(b)),
+                    false => format!("{b:02X}"),
+                })
+                .collect();
+            out.push_str(&format!(
+                "{0:01$X} ({0:02$}): {3}\n",
+                s + i * 4,
+                hex,
+                dec,
+                bytes.join(" ").trim_end()
+            ));
+        }
+        out
+    }
+
+    /// Returns the next instruction, or none if PC is past the
+    /// end of memory. In this case, CPU should halt.
+    fn next_instr(&mut self) -> Result<u8, NeanderException> {
+        if self.status_end_of_prog() {
+            return Err(NeanderException::EndOfProgram);
+        }
+        // PC is always below MEM, masking only drops the bounds check
+        let instr = self.mem[self.pc as usize & (MEM - 1)];
+        if self.pc as usize == MEM - 1 {
+            self.set_end_of_program();
+        }
+        self.pc = Self::wrap(self.pc as usize + 1);
+        Ok(instr)
+    }
+}
+
+impl Neander {
+    pub fn new() -> Self {
+        Self::blank()
+    }
+    pub fn pc(&self) -> u8 {
+        self.pc as u8
+    }
+    pub fn mar(&self) -> u8 {
+        self.mar as u8
+    }
+    /// Sets the program counter, clearing the end of program flag.
+    pub fn set_pc(&mut self, pc: u8) {
+        self.set_program_counter(pc as usize);
+    }
+    pub fn registers(&self) -> Registers {
+        Registers {
+            pc: self.pc as u8,
+            acc: self.acc,
+            status: self.status,
+            mar: self.mar as u8,
+            mdr: self.mdr,
+            ir: self.ir,
+            uop: self.uop,
+        }
+    }
+    pub fn set_registers(&mut self, regs: Registers) {
+        self.pc = regs.pc as u16;
+        self.acc = regs.acc;
+        self.status = regs.status;
+        self.mar = regs.mar as u16;
+        self.mdr = regs.mdr;
+        self.ir = regs.ir;
+        self.uop = regs.uop;
+    }
+    /// Same as `step`, calling `hooks` as the instruction executes.
+    /// `on_exec` is called even if no instruction could be fetched.
+    pub fn step_hooked(&mut self, hooks: &mut impl Hooks) -> ExecResult {
+        if self.mid_instruction() {
+            let mut res = ExecResult::Normal;
+            while self.mid_instruction() {
+                match self.step_uop_hooked(hooks).1 {
+                    ExecResult::Normal => (),
+                    r => res = r,
+                }
+            }
+            return res;
+        }
+        let (pc, status) = (self.pc as u8, self.status);
+        let res = self.execute(&mut Hooked(hooks));
+        if self.status & 3 != status & 3 {
+            hooks.on_flag_change(status, self.status);
+        }
+        hooks.on_exec(self, pc, res);
+        res
+    }
+
+    /// Executes the next micro-operation of the current instruction,
+    /// starting a new one if at an instruction boundary.
+    /// Returns the micro-operation executed and its result.
+    /// The result of the instruction (halt, memory write or exception)
+    /// is returned by the micro-operation that caused it.
+    pub fn step_uop(&mut self) -> (MicroOp, ExecResult) {
+        self.uop_step()
+    }
+    /// Same as `step_uop`, calling `hooks` as the instruction executes:
+    /// `on_fetch` once the opcode is in IR, the memory hooks when an
+    /// operand is read or written and `on_exec` when it finishes.
+    pub fn step_uop_hooked(&mut self, hooks: &mut impl Hooks) -> (MicroOp, ExecResult) {
+        // the bytes of the instruction fetched so far, by ReadIncPc
+        let fetched = match self.uop {
+            0 | 1 => 0,
+            2..=4 => 1,
+            _ => 2,
+        };
+        let pc = (self.pc as u8).wrapping_sub(fetched);
+        let (status, old) = (self.status, self.mem[self.mar as usize]);
+        let (op, res) = self.uop_step();
+        match op {
+            MicroOp::IrFromMdr => hooks.on_fetch(self, pc, self.ir),
+            MicroOp::Read => hooks.on_mem_read(self.mar as u8, self.mdr),
+            MicroOp::Write => hooks.on_mem_write(self.mar as u8, old, self.mdr),
+            _ => (),
+        }
+        if self.status & 3 != status & 3 {
+            hooks.on_flag_change(status, self.status);
+        }
+        if !self.mid_instruction() {
+            hooks.on_exec(self, pc, res);
+        }
+        (op, res)
+    }
+    /// Runs the micro-operations of the next phase of the current
+    /// instruction: fetching the opcode, fetching the operand
+    /// or executing it. Returns the phase and its result.
+    pub fn micro_step(&mut self) -> (Phase, ExecResult) {
+        self.micro_step_with(|cpu| cpu.step_uop().1)
+    }
+    /// Same as `micro_step`, calling `hooks` like `step_uop_hooked`.
+    pub fn micro_step_hooked(&mut self, hooks: &mut impl Hooks) -> (Phase, ExecResult) {
+        self.micro_step_with(|cpu| cpu.step_uop_hooked(hooks).1)
+    }
+    /// Returns the phase `micro_step` runs next.
+    pub fn phase(&self) -> Phase {
+        micro::phase(self.ir, self.uop)
+    }
+    pub(crate) fn micro_step_with(
+        &mut self,
+        mut step_uop: impl FnMut(&mut Self) -> ExecResult,
+    ) -> (Phase, ExecResult) {
+        let phase = self.phase();
+        loop {
+            let res = step_uop(self);
+            if res != ExecResult::Normal || !self.mid_instruction() || self.phase() != phase {
+                return (phase, res);
+            }
+        }
+    }
+
+    /// Returns a machine with `image` as its memory.
+    pub fn from_image(image: [u8; 256]) -> Self {
+        Self {
+            mem: Box::new(image),
+            ..Self::new()
+        }
+    }
+    /// Returns a machine with a memfile loaded.
+    pub fn load_memfile(source: &str) -> Result<Self, LoadError> {
+        let mut image = [0; 256];
+        memfile::parse_memfile(&mut image, source)?;
+        Ok(Self::from_image(image))
+    }
+    /// Returns a machine with `bytes` loaded at the start of memory,
+    /// and zeros after them.
+    pub fn load_binary(bytes: &[u8]) -> Result<Self, LoadError> {
+        let mut image = [0; 256];
+        image
+            .get_mut(..bytes.len())
+            .ok_or(LoadError::TooLarge(bytes.len()))?
+            .copy_from_slice(bytes);
+        Ok(Self::from_image(image))
+    }
+
+    /// Reads `path` into memory. Logisim images are detected by their
+    /// header, `.asm` files are assembled and anything else is parsed
+    /// as a memfile.
+    #[cfg(feature = "std")]
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        self.load_file_symbols(path).map(|_| ())
+    }
+    /// Same as `load_file`, returning the labels defined in the file.
+    /// Logisim images have no labels.
+    #[cfg(feature = "std")]
+    pub fn load_file_symbols(&mut self, path: &Path) -> Result<SymbolTable, String> {
+        self.load_file_mapped(path).map(|(symbols, _)| symbols)
+    }
+    /// Same as `load_file_symbols`, also returning the source line
+    /// of each address. Only assembled files have source maps.
+    #[cfg(feature = "std")]
+    pub fn load_file_mapped(&mut self, path: &Path) -> Result<(SymbolTable, SourceMap), String> {
+        self.load_file_image(path)
+            .map(|(_, symbols, map)| (symbols, map))
+    }
+    /// Same as `load_file_mapped`, also returning the image loaded,
+    /// which knows where each byte came from, see `crate::image`.
+    #[cfg(feature = "std")]
+    pub fn load_file_image(
+        &mut self,
+        path: &Path,
+    ) -> Result<(MemoryImage, SymbolTable, SourceMap), String> {
+        let (image, symbols, map) = image::load(path, self.memory().len())?;
+        image.commit(self.memory_mut());
+        Ok((image, symbols, map))
+    }
+
+    /// Writes the whole machine state to `path`, as JSON if
+    /// the file ends in `.json` and in binary otherwise.
+    #[cfg(feature = "std")]
+    pub fn save_state(&self, path: &Path) -> Result<(), String> {
+        let bytes = if path.extension().is_some_and(|e| e == "json") {
+            self.state_json()?.into_bytes()
+        } else {
+            self.state_bytes()
+        };
+        std::fs::write(path, bytes).map_err(|e| e.to_string())
+    }
+    /// Reads a machine state written by `save_state`.
+    #[cfg(feature = "std")]
+    pub fn load_state(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        if path.extension().is_some_and(|e| e == "json") {
+            let json = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+            Self::from_state_json(&json)
+        } else {
+            Self::from_state_bytes(&bytes)
+        }
+    }
+
+    /// Encodes the state as a JSON object, with its format version.
+    #[cfg(feature = "serde")]
+    pub fn state_json(&self) -> Result<String, String> {
+        let mut json = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        json["version"] = formats::STATE.version.into();
+        serde_json::to_string_pretty(&json).map_err(|e| e.to_string())
+    }
+    #[cfg(not(feature = "serde"))]
+    pub fn state_json(&self) -> Result<String, String> {
+        Err("JSON states need the serde feature".to_string())
+    }
+    /// Decodes a JSON state. States written before the format had
+    /// a version have the same fields as version 1.
+    #[cfg(feature = "serde")]
+    pub fn from_state_json(json: &str) -> Result<Self, String> {
+        let mut json: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if let Some(version) = json.as_object_mut().and_then(|o| o.remove("version")) {
+            let version = version.as_u64().ok_or("invalid state version")?;
+            formats::STATE.check(version as u32)?;
+        }
+        let cpu: Self = serde_json::from_value(json).map_err(|e| e.to_string())?;
+        cpu.check_state()?;
+        Ok(cpu)
+    }
+    #[cfg(not(feature = "serde"))]
+    pub fn from_state_json(_json: &str) -> Result<Self, String> {
+        Err("JSON states need the serde feature".to_string())
+    }
+
+    /// Encodes the state as a header, the registers and the memory.
+    pub fn state_bytes(&self) -> Vec<u8> {
+        let mut bytes = STATE_MAGIC.to_vec();
+        bytes.push(formats::STATE.version as u8);
+        bytes.extend([
+            self.pc(),
+            self.acc as u8,
+            self.status,
+            self.mar(),
+            self.mdr,
+            self.ir,
+            self.uop,
+        ]);
+        bytes.extend_from_slice(self.memory());
+        bytes
+    }
+    pub fn from_state_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let Some(rest) = bytes.strip_prefix(STATE_MAGIC) else {
+            return Err("not a Neander state file".to_string());
+        };
+        let [version, pc, acc, status, mar, mdr, ir, uop, mem @ ..] = rest else {
+            return Err("truncated state file".to_string());
+        };
+        // version 1 is the only one so far
+        formats::STATE.check(*version as u32)?;
+        let mem: [u8; 256] = mem
+            .try_into()
+            .map_err(|_| format!("expected 256 bytes of memory, got {}", mem.len()))?;
+        let cpu = Self {
+            pc: *pc as u16,
+            acc: *acc as i8,
+            status: *status,
+            mem: Box::new(mem),
+            mar: *mar as u16,
+            mdr: *mdr,
+            ir: *ir,
+            uop: *uop,
+        };
+        cpu.check_state()?;
+        Ok(cpu)
+    }
+    /// Checks the fields of a decoded state, along with the version
+    /// check of `formats::STATE`, rejecting states that would panic
+    /// when stepped: addresses outside memory, which JSON states may
+    /// hold, and micro-operations past the fetch of an instruction
+    /// without a microprogram or past the end of its microprogram.
+    fn check_state(&self) -> Result<(), String> {
+        for (name, addr) in [("pc", self.pc), ("mar", self.mar)] {
+            if addr as usize >= self.mem.len() {
+                return Err(format!("invalid state: {name} {addr} is outside memory"));
+            }
+        }
+        // the IR is only loaded at the end of the fetch
+        if (self.uop as usize) < micro::FETCH.len() {
+            return Ok(());
+        }
+        match micro::microprogram(self.ir) {
+            None => Err(format!(
+                "invalid state: micro-operation {} of {:02X}, which isn't an instruction",
+                self.uop, self.ir
+            )),
+            Some(seq) if self.uop as usize >= seq.len() => Err(format!(
+                "invalid state: micro-operation {} is past the end of the instruction",
+                self.uop
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Gets the byte at position `idx` in RAM.
+    pub fn ram(&self, idx: u8) -> u8 {
+        self.mem[idx as usize]
+    }
+
+    /// Sets byte at `idx` to `val` in RAM.
+    pub fn set_ram(&mut self, idx: u8, val: u8) {
+        self.mem[idx as usize] = val;
+    }
+    /// Copies the content of `slice` into memory, starting at `idx`.
+    pub fn set_ram_slice(&mut self, idx: u8, slice: &[u8]) {
+        let start = idx as usize;
+        let end = slice.len() + start;
+        self.mem[start..end].copy_from_slice(slice);
+    }
+}
+
+impl<const MEM: usize> Default for Neander<MEM> {
+    fn default() -> Self {
+        Self::blank()
+    }
+}
+
+impl<const MEM: usize> core::fmt::Display for Neander<MEM> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "STATE:
+AC: {0} | 0x{0:X} | 0b{0:b}
+PC: {1} | 0x{1:X} | 0b{1:b}
+N: {2}, Z: {3}",
+            self.acc(),
+            self.program_counter(),
+            self.status_negative() as u8,
+            self.status_zero() as u8
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn assert_pc_acc_stt(cpu: &Neander, pc: u8, acc: i8, stt: u8) {
+        assert_eq!(cpu.pc(), pc);
+        assert_eq!(cpu.acc, acc);
+        assert_eq!(cpu.status, stt);
+    }
+
+    #[test]
+    fn test_nop() {
+        let mut cpu = Neander::new();
+        cpu.set_ram(0, 0);
+        assert_eq!(cpu.step(), ExecResult::Normal);
+        assert_pc_acc_stt(&cpu, 1, 0, 0);
+    }
+    #[test]
+    fn test_lda() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, LDA, 128, LDA, 128]);
+
+        cpu.set_ram(128, 10);
+        assert_eq!(cpu.step(), ExecResult::Normal);
+        assert_pc_acc_stt(&cpu, 2, 10, 0);
+
+        cpu.set_ram(128, 0);
+        assert_eq!(cpu.step(), ExecResult::Normal);
+        assert_pc_acc_stt(&cpu, 4, 0, 1);
+
+        cpu.set_ram(128, 240);
+        assert_eq!(cpu.step(), ExecResult::Normal);
+        assert_pc_acc_stt(&cpu, 6, -16, 2);
+    }
+    #[test]
+    fn test_sta() {
+        let mut cpu = Neander::new();
+        cpu.acc = 10;
+        cpu.set_ram_slice(0, &[STA, 128]);
+        assert_eq!(
+            cpu.step(),
+            ExecResult::MemWrite {
+                addr: 128,
+                value: 10
+            }
+        );
+        assert_eq!(cpu.ram(128), 10);
+        assert_pc_acc_stt(&cpu, 2, 10, 0);
+    }
+    #[test]
+    fn test_add() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(
+            0,
+            &[LDA, 128, ADD, 129, LDA, 130, ADD, 131, LDA, 132, ADD, 133],
+        );
+        cpu.set_ram_slice(128, &[0, 5, 251, 5, 10, 245]);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_pc_acc_stt(&cpu, 4, 5, 0);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_pc_acc_stt(&cpu, 8, 0, 1);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_pc_acc_stt(&cpu, 12, -1, 2);
+    }
+    #[test]
+    fn test_load() {
+        let cpu = Neander::load_memfile("org 128 x: 5 -1").unwrap();
+        assert_eq!(cpu.memory()[128..130], [5, 255]);
+        assert_eq!(cpu, Neander::from_image(cpu.memory().try_into().unwrap()));
+        let e = Neander::load_memfile("org 255 1 2").unwrap_err();
+        assert!(matches!(e, LoadError::Memfile(_)));
+
+        let cpu = Neander::load_binary(&[LDA, 3, HLT, 9]).unwrap();
+        assert_eq!(cpu.memory()[..5], [LDA, 3, HLT, 9, 0]);
+        assert!(Neander::load_binary(&[0; 256]).is_ok());
+        assert_eq!(
+            Neander::load_binary(&[0; 257]),
+            Err(LoadError::TooLarge(257))
+        );
+    }
+
+    #[test]
+    fn test_set_registers() {
+        let mut cpu = Neander::new();
+        cpu.set_acc(-3);
+        assert_pc_acc_stt(&cpu, 0, -3, 2);
+        cpu.set_pc(255);
+        cpu.step().unwrap();
+        assert!(cpu.status_end_of_prog());
+        cpu.set_pc(10);
+        assert_pc_acc_stt(&cpu, 10, -3, 2);
+    }
+    #[test]
+    fn test_uop_matches_step() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(
+            0,
+            &[
+                LDA, 128, ADD, 129, STA, 130, NOT, JN, 12, NOP, NOP, NOP, OR, 129, AND, 128, JZ,
+                20, NOP, NOP, JMP, 24, NOP, NOP, HLT,
+            ],
+        );
+        cpu.set_ram_slice(128, &[3, 4]);
+        let mut ucpu = cpu.clone();
+        loop {
+            let res = cpu.step();
+            let mut ures = ExecResult::Normal;
+            loop {
+                match ucpu.step_uop().1 {
+                    ExecResult::Normal => (),
+                    r => ures = r,
+                }
+                if !ucpu.mid_instruction() {
+                    break;
+                }
+            }
+            assert_eq!(res, ures);
+            assert_pc_acc_stt(&ucpu, cpu.pc(), cpu.acc, cpu.status);
+            assert_eq!(cpu.memory(), ucpu.memory());
+            if res == ExecResult::Halted {
+                break;
+            }
+        }
+        assert_eq!(cpu.ram(130), 7);
+    }
+    #[test]
+    fn test_step_finishes_uops() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128]);
+        cpu.set_ram(128, 42);
+        assert_eq!(cpu.step_uop(), (MicroOp::MarFromPc, ExecResult::Normal));
+        assert_eq!(cpu.step_uop(), (MicroOp::ReadIncPc, ExecResult::Normal));
+        assert!(cpu.mid_instruction());
+        assert_eq!(cpu.step(), ExecResult::Normal);
+        assert!(!cpu.mid_instruction());
+        assert_pc_acc_stt(&cpu, 2, 42, 0);
+    }
+    #[test]
+    fn test_uop_invalid_instruction() {
+        let mut cpu = Neander::new();
+        cpu.set_ram(0, 0x70);
+        cpu.step_uop();
+        cpu.step_uop();
+        assert_eq!(
+            cpu.step_uop(),
+            (
+                MicroOp::IrFromMdr,
+                ExecResult::Exception(NeanderException::InvalidInstruction(0x70))
+            )
+        );
+        assert!(!cpu.mid_instruction());
+    }
+    #[test]
+    fn test_fmt_dual() {
+        assert_eq!(fmt_dual(0xF0), "F0 (-16 / 240)");
+        assert_eq!(fmt_dual(0x70), "70 (112)");
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(4, &[1, 0x80]);
+        assert_eq!(cpu.mem_range_string(5, 5, false), "04 (004): 01 80 00 00\n");
+        assert_eq!(
+            cpu.mem_range_string(4, 7, true),
+            "04 (004): 01 (1)          80 (-128 / 128) 00 (0)          00 (0)\n"
+        );
+    }
+    #[test]
+    fn test_state() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, NOT, HLT]);
+        cpu.set_ram(128, 5);
+        cpu.step();
+        cpu.step_uop();
+        let bytes = cpu.state_bytes();
+        assert_eq!(bytes.len(), 4 + 8 + 256);
+        assert_eq!(Neander::from_state_bytes(&bytes), Ok(cpu.clone()));
+        assert!(Neander::from_state_bytes(&bytes[..100]).is_err());
+        assert!(Neander::from_state_bytes(b"nope").is_err());
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        let e = Neander::from_state_bytes(&newer).unwrap_err();
+        assert!(e.contains("newer neander"), "{e}");
+        #[cfg(feature = "serde")]
+        {
+            let json = cpu.state_json().unwrap();
+            assert!(json.contains("\"version\": 1"));
+            assert_eq!(Neander::from_state_json(&json), Ok(cpu.clone()));
+            // states from before the version was recorded
+            let mut old: serde_json::Value = serde_json::from_str(&json).unwrap();
+            old.as_object_mut().unwrap().remove("version");
+            assert_eq!(Neander::from_state_json(&old.to_string()), Ok(cpu));
+            old["version"] = 9.into();
+            assert!(Neander::from_state_json(&old.to_string()).is_err());
+            assert!(Neander::from_state_json(r#"{"pc":0,"acc":0,"status":0,"mem":[1]}"#).is_err());
+            let mut corrupt: serde_json::Value = serde_json::from_str(&json).unwrap();
+            corrupt["ir"] = 0xFF.into();
+            corrupt["uop"] = 5.into();
+            assert!(Neander::from_state_json(&corrupt.to_string()).is_err());
+            let mut outside: serde_json::Value = serde_json::from_str(&json).unwrap();
+            outside["mar"] = 300.into();
+            let e = Neander::from_state_json(&outside.to_string()).unwrap_err();
+            assert_eq!(e, "invalid state: mar 300 is outside memory");
+        }
+    }
+    #[test]
+    fn test_corrupt_state() {
+        let bytes = Neander::new().state_bytes();
+        let with = |ir: u8, uop: u8| {
+            let mut bytes = bytes.clone();
+            bytes[10] = ir;
+            bytes[11] = uop;
+            Neander::from_state_bytes(&bytes)
+        };
+        // an invalid opcode past the fetch, and past the end of LDA
+        let e = with(0xFF, 5).unwrap_err();
+        assert!(e.contains("isn't an instruction"), "{e}");
+        let len = micro::microprogram(LDA).unwrap().len() as u8;
+        assert!(with(LDA, len).is_err());
+        assert!(with(LDA, 200).is_err());
+        // still fetching, so the IR doesn't matter yet
+        assert!(with(0xFF, 1).is_ok());
+        assert!(with(LDA, len - 1).is_ok());
+        assert!(with(0xFF, 0).is_ok());
+    }
+    #[test]
+    fn test_memory_size() {
+        let mut cpu = Neander::<65536>::default();
+        assert_eq!(Neander::<65536>::ADDR_BYTES, 2);
+        cpu.memory_mut()[..8].copy_from_slice(&[LDA, 0x12, 0x34, STA, 0xFF, 0xFF, JMP, 0x80]);
+        cpu.memory_mut()[8] = 0;
+        cpu.set_byte(0x1234, 7);
+        assert_eq!(cpu.step(), ExecResult::Normal);
+        assert_eq!(cpu.acc(), 7);
+        let write = ExecResult::MemWrite {
+            addr: 0xFFFF,
+            value: 7,
+        };
+        assert_eq!(cpu.step(), write);
+        cpu.set_byte(0x8000, HLT);
+        assert_eq!(cpu.run(), Ok(()));
+        assert_eq!(cpu.program_counter(), 0x8001);
+        assert_eq!(
+            cpu.mem_range_string(0x1235, 0x1235, false),
+            "1234 (04660): 07 00 00 00\n"
+        );
+
+        // a small machine wraps its addresses around
+        let mut cpu = Neander::<16>::default();
+        cpu.memory_mut()[..3].copy_from_slice(&[LDA, 0x13, HLT]);
+        cpu.set_byte(3, 9);
+        assert_eq!(cpu.run(), Ok(()));
+        assert_eq!(cpu.acc(), 9);
+    }
+    #[test]
+    fn test_run_with_limit() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[JMP, 0]);
+        assert_eq!(
+            cpu.run_with_limit(100),
+            Err(NeanderException::StepLimitExceeded(100))
+        );
+        cpu.set_ram_slice(0, &[NOP, HLT]);
+        cpu.set_pc(0);
+        assert_eq!(cpu.run_with_limit(2), Ok(()));
+    }
+}
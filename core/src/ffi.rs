@@ -0,0 +1,235 @@
+//! A C interface to the machine, for frontends written in other
+//! languages. The `neander-ffi` crate builds it as a shared and a
+//! static library, with its declarations in `neander.h`.
+//!
+//! A machine is created with `neander_new` and released with
+//! `neander_free`. Functions that can fail return `NEANDER_ERROR`,
+//! and `neander_error` then describes what went wrong.
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ffi::{c_char, CStr};
+
+use crate::asm;
+use crate::cpu::{ExecResult, LoadError, Neander};
+
+/// The step or call went fine.
+pub const NEANDER_OK: i32 = 0;
+/// The machine executed HLT.
+pub const NEANDER_HALTED: i32 = 1;
+/// The instruction stored a byte to memory.
+pub const NEANDER_MEM_WRITE: i32 = 2;
+/// Something went wrong, see `neander_error`.
+pub const NEANDER_ERROR: i32 = -1;
+
+/// A machine and the description of its last error.
+pub struct NeanderHandle {
+    cpu: Neander,
+    error: CString,
+}
+impl NeanderHandle {
+    /// Keeps `message` for `neander_error` and returns `NEANDER_ERROR`.
+    fn fail(&mut self, message: String) -> i32 {
+        // a message can't have NULs inside, so they are dropped
+        let bytes: Vec<u8> = message
+            .into_bytes()
+            .into_iter()
+            .filter(|&b| b != 0)
+            .collect();
+        self.error = CString::new(bytes).unwrap_or_default();
+        NEANDER_ERROR
+    }
+    fn result(&mut self, res: ExecResult) -> i32 {
+        match res {
+            ExecResult::Normal => NEANDER_OK,
+            ExecResult::Halted => NEANDER_HALTED,
+            ExecResult::MemWrite { .. } => NEANDER_MEM_WRITE,
+            ExecResult::Exception(e) => self.fail(e.to_string()),
+        }
+    }
+}
+
+/// A new machine, with memory and registers zeroed.
+#[no_mangle]
+pub extern "C" fn neander_new() -> *mut NeanderHandle {
+    Box::into_raw(Box::new(NeanderHandle {
+        cpu: Neander::new(),
+        error: CString::default(),
+    }))
+}
+
+/// Releases a machine. Does nothing if `cpu` is NULL.
+///
+/// # Safety
+/// `cpu` must come from `neander_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn neander_free(cpu: *mut NeanderHandle) {
+    if !cpu.is_null() {
+        drop(Box::from_raw(cpu));
+    }
+}
+
+/// Executes one instruction, returning `NEANDER_OK`, `NEANDER_HALTED`,
+/// `NEANDER_MEM_WRITE` or `NEANDER_ERROR`.
+///
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_step(cpu: *mut NeanderHandle) -> i32 {
+    let cpu = &mut *cpu;
+    let res = cpu.cpu.step();
+    cpu.result(res)
+}
+
+/// Runs until HLT, returning `NEANDER_HALTED`, or `NEANDER_ERROR`
+/// if the program fails or doesn't halt after `max_steps` instructions.
+///
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_run(cpu: *mut NeanderHandle, max_steps: u64) -> i32 {
+    let cpu = &mut *cpu;
+    match cpu.cpu.run_with_limit(max_steps) {
+        Ok(()) => NEANDER_HALTED,
+        Err(e) => cpu.fail(e.to_string()),
+    }
+}
+
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_mem_read(cpu: *const NeanderHandle, addr: u8) -> u8 {
+    (*cpu).cpu.ram(addr)
+}
+
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_mem_write(cpu: *mut NeanderHandle, addr: u8, value: u8) {
+    (*cpu).cpu.set_ram(addr, value);
+}
+
+/// Copies `len` bytes to the start of memory, leaving the rest as it is.
+///
+/// # Safety
+/// `cpu` must be a machine from `neander_new` and `bytes` must
+/// point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn neander_load(
+    cpu: *mut NeanderHandle,
+    bytes: *const u8,
+    len: usize,
+) -> i32 {
+    let cpu = &mut *cpu;
+    if len > cpu.cpu.memory().len() {
+        return cpu.fail(LoadError::TooLarge(len).to_string());
+    }
+    let bytes = core::slice::from_raw_parts(bytes, len);
+    cpu.cpu.memory_mut()[..len].copy_from_slice(bytes);
+    NEANDER_OK
+}
+
+/// Assembles `source` into memory. On errors memory is left as it
+/// was, and `neander_error` has one error per line.
+///
+/// # Safety
+/// `cpu` must be a machine from `neander_new` and `source` a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn neander_assemble(cpu: *mut NeanderHandle, source: *const c_char) -> i32 {
+    let cpu = &mut *cpu;
+    let Ok(source) = CStr::from_ptr(source).to_str() else {
+        return cpu.fail("the source isn't valid UTF-8".to_string());
+    };
+    let mut mem = cpu.cpu.memory().to_vec();
+    match asm::assemble(&mut mem, source) {
+        Ok(_) => {
+            cpu.cpu.memory_mut().copy_from_slice(&mem);
+            NEANDER_OK
+        }
+        Err(errors) => {
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            cpu.fail(errors.join("\n"))
+        }
+    }
+}
+
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_pc(cpu: *const NeanderHandle) -> u8 {
+    (*cpu).cpu.pc()
+}
+
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_set_pc(cpu: *mut NeanderHandle, pc: u8) {
+    (*cpu).cpu.set_pc(pc);
+}
+
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_acc(cpu: *const NeanderHandle) -> i8 {
+    (*cpu).cpu.acc()
+}
+
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_set_acc(cpu: *mut NeanderHandle, acc: i8) {
+    (*cpu).cpu.set_acc(acc);
+}
+
+/// The flags, Z in bit 0 and N in bit 1.
+///
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_status(cpu: *const NeanderHandle) -> u8 {
+    (*cpu).cpu.status()
+}
+
+/// The last error of the machine, empty if there was none. The
+/// string lives until the next call that fails.
+///
+/// # Safety
+/// `cpu` must be a machine from `neander_new`.
+#[no_mangle]
+pub unsafe extern "C" fn neander_error(cpu: *const NeanderHandle) -> *const c_char {
+    (*cpu).error.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let cpu = neander_new();
+            let error = |cpu| CStr::from_ptr(neander_error(cpu)).to_str().unwrap();
+            let source = c"lda x\nadd x\nsta y\nhlt\nx: db 3\ny: db 0\n";
+            assert_eq!(neander_assemble(cpu, source.as_ptr()), NEANDER_OK);
+            assert_eq!(neander_step(cpu), NEANDER_OK);
+            assert_eq!(neander_acc(cpu), 3);
+            assert_eq!(neander_run(cpu, 10), NEANDER_HALTED);
+            assert_eq!(neander_mem_read(cpu, 8), 6);
+            assert_eq!(neander_status(cpu), 0);
+
+            assert_eq!(neander_assemble(cpu, c"lda".as_ptr()), NEANDER_ERROR);
+            assert!(error(cpu).starts_with("line 1: "));
+            assert_eq!(neander_mem_read(cpu, 0), LDA);
+
+            neander_load(cpu, [JMP, 0].as_ptr(), 2);
+            neander_set_pc(cpu, 0);
+            assert_eq!(neander_run(cpu, 5), NEANDER_ERROR);
+            assert_eq!(error(cpu), "did not halt after 5 instructions");
+            assert_eq!(neander_load(cpu, [0; 300].as_ptr(), 300), NEANDER_ERROR);
+            neander_free(cpu);
+        }
+    }
+}
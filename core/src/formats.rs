@@ -0,0 +1,89 @@
+//! Versions of the files the simulator writes. Text formats start
+//! with a header naming them and their version, like `neander-workspace 1`,
+//! and machine states keep the version in their binary header or in
+//! a `version` field. Files of older versions are migrated when read,
+//! and files of newer versions are rejected with an error asking to update.
+use alloc::format;
+use alloc::string::String;
+
+/// A file format and the version this build writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub name: &'static str,
+    pub version: u32,
+}
+
+/// Machine states, written by `save` and the GUI.
+pub const STATE: Format = Format {
+    name: "neander-state",
+    version: 1,
+};
+/// Breakpoints kept between sessions.
+pub const BREAKPOINTS: Format = Format {
+    name: "neander-breakpoints",
+    version: 2,
+};
+/// Saved GUI sessions.
+pub const WORKSPACE: Format = Format {
+    name: "neander-workspace",
+    version: 2,
+};
+/// Traces for differential testing, see `crate::conformance`.
+pub const TRACE: Format = Format {
+    name: "neander-trace",
+    version: 1,
+};
+
+impl Format {
+    /// The header line, without a newline.
+    pub fn header(&self) -> String {
+        format!("{} {}", self.name, self.version)
+    }
+
+    /// Reads the version of a header line, or None if the
+    /// line isn't a header of this format.
+    pub fn parse_header(&self, line: &str) -> Option<Result<u32, String>> {
+        let version = line.trim().strip_prefix(self.name)?;
+        if !version.starts_with(' ') {
+            return None;
+        }
+        Some(
+            version
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid {} version: {}", self.name, version.trim()))
+                .and_then(|v| self.check(v)),
+        )
+    }
+
+    /// Accepts `version` if this build can read it.
+    pub fn check(&self, version: u32) -> Result<u32, String> {
+        if version > self.version {
+            return Err(format!(
+                "this file is {} version {version}, from a newer neander; \
+                 this one reads up to version {}, update it to open the file",
+                self.name, self.version
+            ));
+        }
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(WORKSPACE.header(), "neander-workspace 2");
+        assert_eq!(WORKSPACE.parse_header("neander-workspace 1\n"), Some(Ok(1)));
+        assert_eq!(WORKSPACE.parse_header("neander-workspaces 1"), None);
+        assert_eq!(WORKSPACE.parse_header("registers 1 2"), None);
+        assert!(WORKSPACE
+            .parse_header("neander-workspace x")
+            .unwrap()
+            .is_err());
+        let newer = WORKSPACE.parse_header("neander-workspace 3").unwrap();
+        assert!(newer.unwrap_err().contains("update it"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Observers of the instructions a CPU executes. Tools that need
+//! to see every step, like traces, implement `Hooks` and are given
+//! to `Neander::step_hooked`, instead of running their own loops.
+use crate::cpu::{ExecResult, Neander};
+
+/// Callbacks made while an instruction executes, in the order
+/// they're declared. All of them do nothing by default.
+#[allow(unused_variables)]
+pub trait Hooks {
+    /// The opcode at `pc` was fetched. `cpu` is as it was
+    /// before the instruction, except for its PC.
+    fn on_fetch(&mut self, cpu: &Neander, pc: u8, opcode: u8) {}
+    /// The instruction read `value` from memory at `addr`.
+    fn on_mem_read(&mut self, addr: u8, value: u8) {}
+    /// The instruction wrote `new` over `old` at `addr`.
+    fn on_mem_write(&mut self, addr: u8, old: u8, new: u8) {}
+    /// The N or Z flags changed, from the status `old` to `new`.
+    fn on_flag_change(&mut self, old: u8, new: u8) {}
+    /// The instruction at `pc` finished, with `res`.
+    fn on_exec(&mut self, cpu: &Neander, pc: u8, res: ExecResult) {}
+}
+
+/// No hooks, what `Neander::step` runs with.
+impl Hooks for () {}
+
+/// Hooks borrowed from their owner.
+impl<H: Hooks + ?Sized> Hooks for &mut H {
+    fn on_fetch(&mut self, cpu: &Neander, pc: u8, opcode: u8) {
+        (**self).on_fetch(cpu, pc, opcode);
+    }
+    fn on_mem_read(&mut self, addr: u8, value: u8) {
+        (**self).on_mem_read(addr, value);
+    }
+    fn on_mem_write(&mut self, addr: u8, old: u8, new: u8) {
+        (**self).on_mem_write(addr, old, new);
+    }
+    fn on_flag_change(&mut self, old: u8, new: u8) {
+        (**self).on_flag_change(old, new);
+    }
+    fn on_exec(&mut self, cpu: &Neander, pc: u8, res: ExecResult) {
+        (**self).on_exec(cpu, pc, res);
+    }
+}
+
+/// Hooks that may be missing, doing nothing then.
+impl<H: Hooks> Hooks for Option<H> {
+    fn on_fetch(&mut self, cpu: &Neander, pc: u8, opcode: u8) {
+        if let Some(h) = self {
+            h.on_fetch(cpu, pc, opcode);
+        }
+    }
+    fn on_mem_read(&mut self, addr: u8, value: u8) {
+        if let Some(h) = self {
+            h.on_mem_read(addr, value);
+        }
+    }
+    fn on_mem_write(&mut self, addr: u8, old: u8, new: u8) {
+        if let Some(h) = self {
+            h.on_mem_write(addr, old, new);
+        }
+    }
+    fn on_flag_change(&mut self, old: u8, new: u8) {
+        if let Some(h) = self {
+            h.on_flag_change(old, new);
+        }
+    }
+    fn on_exec(&mut self, cpu: &Neander, pc: u8, res: ExecResult) {
+        if let Some(h) = self {
+            h.on_exec(cpu, pc, res);
+        }
+    }
+}
+
+/// Both hooks, the first one called first.
+impl<A: Hooks, B: Hooks> Hooks for (A, B) {
+    fn on_fetch(&mut self, cpu: &Neander, pc: u8, opcode: u8) {
+        self.0.on_fetch(cpu, pc, opcode);
+        self.1.on_fetch(cpu, pc, opcode);
+    }
+    fn on_mem_read(&mut self, addr: u8, value: u8) {
+        self.0.on_mem_read(addr, value);
+        self.1.on_mem_read(addr, value);
+    }
+    fn on_mem_write(&mut self, addr: u8, old: u8, new: u8) {
+        self.0.on_mem_write(addr, old, new);
+        self.1.on_mem_write(addr, old, new);
+    }
+    fn on_flag_change(&mut self, old: u8, new: u8) {
+        self.0.on_flag_change(old, new);
+        self.1.on_flag_change(old, new);
+    }
+    fn on_exec(&mut self, cpu: &Neander, pc: u8, res: ExecResult) {
+        self.0.on_exec(cpu, pc, res);
+        self.1.on_exec(cpu, pc, res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    /// Every call made, as text.
+    #[derive(Default)]
+    struct Log(Vec<String>);
+    impl Hooks for Log {
+        fn on_fetch(&mut self, _: &Neander, pc: u8, opcode: u8) {
+            self.0.push(format!("fetch {pc} {opcode:02X}"));
+        }
+        fn on_mem_read(&mut self, addr: u8, value: u8) {
+            self.0.push(format!("read {addr} {value}"));
+        }
+        fn on_mem_write(&mut self, addr: u8, old: u8, new: u8) {
+            self.0.push(format!("write {addr} {old} {new}"));
+        }
+        fn on_flag_change(&mut self, old: u8, new: u8) {
+            self.0.push(format!("flags {old} {new}"));
+        }
+        fn on_exec(&mut self, _: &Neander, pc: u8, res: ExecResult) {
+            self.0.push(format!("exec {pc} {res:?}"));
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, HLT]);
+        cpu.set_ram_slice(128, &[0xFF, 3]);
+        let mut log = Log::default();
+        while cpu.step_hooked(&mut log) != ExecResult::Halted {}
+        assert_eq!(
+            log.0,
+            [
+                "fetch 0 20",
+                "read 128 255",
+                "flags 0 2",
+                "exec 0 Normal",
+                "fetch 2 10",
+                "write 129 3 255",
+                "exec 2 MemWrite { addr: 129, value: -1 }",
+                "fetch 4 F0",
+                "exec 4 Halted",
+            ]
+        );
+
+        // micro-steps make the same calls, even after a jump
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, JMP, 7, NOP, HLT]);
+        cpu.set_ram_slice(128, &[0xFF, 3]);
+        let start = cpu.clone();
+        let mut steps = Log::default();
+        while cpu.step_hooked(&mut steps) != ExecResult::Halted {}
+        assert_eq!(steps.0[8], "exec 4 Normal");
+        let mut cpu = start;
+        let mut uops = Log::default();
+        while cpu.step_uop_hooked(&mut uops).1 != ExecResult::Halted {}
+        assert_eq!(uops.0, steps.0);
+
+        // both hooks of a pair see the step
+        let mut cpu = Neander::new();
+        let mut pair = (Log::default(), Log::default());
+        cpu.step_hooked(&mut pair);
+        assert_eq!(pair.0 .0, ["fetch 0 00", "exec 0 Normal"]);
+        assert_eq!(pair.0 .0, pair.1 .0);
+    }
+}
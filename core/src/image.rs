@@ -0,0 +1,210 @@
+//! Memory images that remember where each of their bytes came from:
+//! the file, its line and the ORG block. Loaders build an image and
+//! then commit it to memory, so bytes written twice can be reported
+//! and every byte can be traced back to its source.
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::asm;
+use crate::logisim;
+use crate::memfile;
+use crate::srcmap::SourceMap;
+use crate::symbols::SymbolTable;
+
+/// Where a byte of an image came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The path of the file, as it was given
+    pub file: Option<Arc<str>>,
+    /// The line of the file, from 1, if it has lines
+    pub line: Option<usize>,
+    /// The address the ORG block of the byte starts at,
+    /// or where the file started being written
+    pub block: usize,
+}
+impl Origin {
+    /// A sentence for tooltips, like "this byte came from prog.asm:12".
+    pub fn describe(&self) -> String {
+        format!(
+            "this byte came from {self}, in the block at {:02X}",
+            self.block
+        )
+    }
+}
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .file
+            .as_ref()
+            .map(|f| f.rsplit(['/', '\\']).next().unwrap_or_default());
+        match (name, self.line) {
+            (Some(name), Some(line)) => write!(f, "{name}:{line}"),
+            (Some(name), None) => write!(f, "{name}"),
+            (None, Some(line)) => write!(f, "line {line}"),
+            (None, None) => write!(f, "an unknown source"),
+        }
+    }
+}
+
+/// A byte written twice while building an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    pub addr: usize,
+    pub first: Origin,
+    pub second: Origin,
+}
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02X}: {} overwrites the byte from {}",
+            self.addr, self.second, self.first
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryImage {
+    bytes: Vec<u8>,
+    origins: Vec<Option<Origin>>,
+    overlaps: Vec<Overlap>,
+}
+impl MemoryImage {
+    /// An image of `len` bytes, none written yet.
+    pub fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len],
+            origins: vec![None; len],
+            overlaps: Vec::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    /// Writes a byte, recording an overlap if it was written before.
+    pub fn set(&mut self, addr: usize, value: u8, origin: Origin) {
+        if let Some(first) = self.origins[addr].take() {
+            let second = origin.clone();
+            self.overlaps.push(Overlap {
+                addr,
+                first,
+                second,
+            });
+        }
+        self.bytes[addr] = value;
+        self.origins[addr] = Some(origin);
+    }
+    /// Changes a byte already written, keeping its origin.
+    pub(crate) fn patch(&mut self, addr: usize, value: u8) {
+        self.bytes[addr] = value;
+    }
+    pub fn origin(&self, addr: usize) -> Option<&Origin> {
+        self.origins.get(addr)?.as_ref()
+    }
+    /// The bytes written twice, in the order they were.
+    pub fn overlaps(&self) -> &[Overlap] {
+        &self.overlaps
+    }
+    /// Writes the bytes of `other` over this image.
+    pub fn compose(&mut self, other: &MemoryImage) {
+        self.overlaps.extend(other.overlaps.iter().cloned());
+        for (addr, origin) in other.origins.iter().enumerate() {
+            if let Some(origin) = origin {
+                self.set(addr, other.bytes[addr], origin.clone());
+            }
+        }
+    }
+    /// Copies the bytes written into `mem`, leaving the others as they are.
+    pub fn commit(&self, mem: &mut [u8]) {
+        for (addr, origin) in self.origins.iter().enumerate() {
+            if origin.is_some() {
+                mem[addr] = self.bytes[addr];
+            }
+        }
+    }
+}
+
+/// Loads the program at `path` into an image of `len` bytes, along
+/// with its labels and source lines, from a Logisim image, an
+/// assembly source (.asm) or a memory file.
+#[cfg(feature = "std")]
+pub fn load(path: &Path, len: usize) -> Result<(MemoryImage, SymbolTable, SourceMap), String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse(&path.to_string_lossy(), &source, len)
+}
+
+/// Same as `load`, with the `source` of the file at `path` read already.
+pub fn parse(
+    path: &str,
+    source: &str,
+    len: usize,
+) -> Result<(MemoryImage, SymbolTable, SourceMap), String> {
+    let file = Some(Arc::from(path));
+    if logisim::is_image(source) {
+        let mut mem = vec![0; len];
+        let written = logisim::parse_image_len(&mut mem, source).map_err(|e| e.to_string())?;
+        let mut image = MemoryImage::new(len);
+        for (addr, &value) in mem[..written].iter().enumerate() {
+            let origin = Origin {
+                file: file.clone(),
+                line: None,
+                block: 0,
+            };
+            image.set(addr, value, origin);
+        }
+        Ok((image, SymbolTable::new(), SourceMap::default()))
+    } else if path.ends_with(".asm") {
+        asm::assemble_image(len, source, file).map_err(|errors| {
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            errors.join("\n")
+        })
+    } else {
+        let (image, symbols) =
+            memfile::parse_memfile_image(len, source, file).map_err(|e| e.to_string())?;
+        Ok((image, symbols, SourceMap::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image() {
+        let origin = |line| Origin {
+            file: Some(Arc::from("dir/prog.asm")),
+            line: Some(line),
+            block: 0x80,
+        };
+        let mut image = MemoryImage::new(256);
+        image.set(0x80, 5, origin(3));
+        let mut data = MemoryImage::new(256);
+        data.set(0x80, 7, origin(9));
+        data.set(0x81, 1, origin(10));
+        image.compose(&data);
+        assert_eq!(
+            image.overlaps()[0].to_string(),
+            "80: prog.asm:9 overwrites the byte from prog.asm:3"
+        );
+        assert_eq!(
+            image.origin(0x81).unwrap().describe(),
+            "this byte came from prog.asm:10, in the block at 80"
+        );
+        assert_eq!(image.origin(0x82), None);
+
+        let mut mem = [0xFF; 256];
+        image.commit(&mut mem);
+        assert_eq!(mem[0x7F..0x83], [0xFF, 7, 1, 0xFF]);
+    }
+}
@@ -0,0 +1,289 @@
+//! Memory-mapped I/O. A `Bus` routes some addresses to devices:
+//! instructions reading such an address get a byte from the device,
+//! and bytes stored to it are sent to the device. The memory cell
+//! keeps the last byte transferred, so it still shows in dumps.
+//!
+//! By convention, the character output is mapped to `OUTPUT_ADDR`
+//! and the keyboard to `KEYBOARD_ADDR`:
+//!
+//! ```text
+//! LDA 0xFF   ; read a key
+//! STA 0xFE   ; and echo it
+//! ```
+//!
+//! Devices that act over time are ticked after every instruction
+//! and take their timing and randomness from the bus `Clock` only,
+//! so runs with them can be replayed exactly from its seed.
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use crate::clock::Clock;
+use crate::cpu::{instr::*, ExecResult, Neander, NeanderException};
+use crate::hooks::Hooks;
+use crate::micro::{MicroOp, Phase};
+
+/// Address of the character output device.
+pub const OUTPUT_ADDR: u8 = 0xFE;
+/// Address of the keyboard device.
+pub const KEYBOARD_ADDR: u8 = 0xFF;
+
+/// A device mapped to a memory address.
+pub trait Device {
+    /// Returns the byte an instruction reads from the device.
+    fn read(&mut self) -> u8;
+    /// Receives the byte an instruction stores to the device.
+    fn write(&mut self, value: u8);
+    /// Called after every instruction, with the clock already advanced.
+    fn tick(&mut self, _clock: &Clock) {}
+}
+
+/// Writes every stored byte as a character. Reads return 0.
+#[cfg(feature = "std")]
+pub struct CharOutput<W: Write>(pub W);
+#[cfg(feature = "std")]
+impl<W: Write> Device for CharOutput<W> {
+    fn read(&mut self) -> u8 {
+        0
+    }
+    fn write(&mut self, value: u8) {
+        // the program can't handle a failed write, so it's dropped
+        let _ = self.0.write_all(&[value]).and_then(|_| self.0.flush());
+    }
+}
+
+/// Reads one byte per read, or 0 once the input ends. Stores are ignored.
+#[cfg(feature = "std")]
+pub struct Keyboard<R: Read>(pub R);
+#[cfg(feature = "std")]
+impl<R: Read> Device for Keyboard<R> {
+    fn read(&mut self) -> u8 {
+        let mut byte = [0];
+        match self.0.read(&mut byte) {
+            Ok(1) => byte[0],
+            _ => 0,
+        }
+    }
+    fn write(&mut self, _value: u8) {}
+}
+
+/// The devices mapped to memory, by address.
+#[derive(Default)]
+pub struct Bus {
+    devices: BTreeMap<u8, Box<dyn Device>>,
+    clock: Clock,
+}
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Maps `device` to `addr`, replacing the device already there.
+    pub fn attach(&mut self, addr: u8, device: impl Device + 'static) {
+        self.devices.insert(addr, Box::new(device));
+    }
+    pub fn detach(&mut self, addr: u8) {
+        self.devices.remove(&addr);
+    }
+    pub fn is_mapped(&self, addr: u8) -> bool {
+        self.devices.contains_key(&addr)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+    /// Sets the clock, to start a run from a seed or go back to a snapshot.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+    fn device(&mut self, addr: u8) -> Option<&mut Box<dyn Device>> {
+        self.devices.get_mut(&addr)
+    }
+    /// Advances the clock by an instruction and ticks the devices.
+    fn tick(&mut self) {
+        self.clock.advance();
+        for dev in self.devices.values_mut() {
+            dev.tick(&self.clock);
+        }
+    }
+}
+
+impl Neander {
+    /// Same as `step`, routing the memory accesses of the
+    /// instruction to the devices of `bus`.
+    pub fn step_bus(&mut self, bus: &mut Bus) -> ExecResult {
+        self.step_bus_hooked(bus, &mut ())
+    }
+    /// Same as `step_bus`, calling `hooks` like `step_hooked`.
+    pub fn step_bus_hooked(&mut self, bus: &mut Bus, hooks: &mut impl Hooks) -> ExecResult {
+        if bus.is_empty() {
+            let res = self.step_hooked(hooks);
+            bus.tick();
+            return res;
+        }
+        if self.mid_instruction() {
+            let mut res = ExecResult::Normal;
+            while self.mid_instruction() {
+                match self.step_uop_bus_hooked(bus, hooks).1 {
+                    ExecResult::Normal => (),
+                    r => res = r,
+                }
+            }
+            return res;
+        }
+        let pc = self.pc();
+        let reads = matches!(self.ram(pc), LDA | ADD | OR | AND) && pc != 255;
+        if reads {
+            let addr = self.ram(pc + 1);
+            if let Some(dev) = bus.device(addr) {
+                self.set_ram(addr, dev.read());
+            }
+        }
+        let res = self.step_hooked(hooks);
+        write_device(bus, res);
+        bus.tick();
+        res
+    }
+    /// Same as `step_uop`, routing memory accesses to the devices of `bus`.
+    pub fn step_uop_bus(&mut self, bus: &mut Bus) -> (MicroOp, ExecResult) {
+        self.step_uop_bus_hooked(bus, &mut ())
+    }
+    /// Same as `step_uop_bus`, calling `hooks` like `step_uop_hooked`.
+    pub fn step_uop_bus_hooked(
+        &mut self,
+        bus: &mut Bus,
+        hooks: &mut impl Hooks,
+    ) -> (MicroOp, ExecResult) {
+        if self.next_uop() == MicroOp::Read {
+            if let Some(dev) = bus.device(self.mar()) {
+                self.set_ram(self.mar(), dev.read());
+            }
+        }
+        let (op, res) = self.step_uop_hooked(hooks);
+        write_device(bus, res);
+        if !self.mid_instruction() {
+            bus.tick();
+        }
+        (op, res)
+    }
+    /// Same as `micro_step`, routing memory accesses to the devices of `bus`.
+    pub fn micro_step_bus(&mut self, bus: &mut Bus) -> (Phase, ExecResult) {
+        self.micro_step_bus_hooked(bus, &mut ())
+    }
+    /// Same as `micro_step_bus`, calling `hooks` like `step_uop_hooked`.
+    pub fn micro_step_bus_hooked(
+        &mut self,
+        bus: &mut Bus,
+        hooks: &mut impl Hooks,
+    ) -> (Phase, ExecResult) {
+        self.micro_step_with(|cpu| cpu.step_uop_bus_hooked(bus, hooks).1)
+    }
+    /// Same as `run_with_limit`, routing memory accesses to the devices of `bus`.
+    pub fn run_bus_with_limit(
+        &mut self,
+        bus: &mut Bus,
+        max_steps: u64,
+    ) -> Result<(), NeanderException> {
+        for _ in 0..max_steps {
+            match self.step_bus(bus) {
+                ExecResult::Halted => return Ok(()),
+                ExecResult::Exception(e) => return Err(e),
+                _ => (),
+            }
+        }
+        Err(NeanderException::StepLimitExceeded(max_steps))
+    }
+}
+
+fn write_device(bus: &mut Bus, res: ExecResult) {
+    if let ExecResult::MemWrite { addr, value } = res {
+        if let Some(dev) = bus.device(addr as u8) {
+            dev.write(value as u8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use {alloc::vec::Vec, std::cell::RefCell, std::rc::Rc};
+
+    /// Collects output into a buffer the test can still look at.
+    #[cfg(feature = "std")]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    #[cfg(feature = "std")]
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_echo() {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = Bus::new();
+        bus.attach(OUTPUT_ADDR, CharOutput(Shared(out.clone())));
+        bus.attach(KEYBOARD_ADDR, Keyboard(&b"hi"[..]));
+        // echo keys until a 0
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(
+            0,
+            &[LDA, KEYBOARD_ADDR, JZ, 8, STA, OUTPUT_ADDR, JMP, 0, HLT],
+        );
+        cpu.run_bus_with_limit(&mut bus, 100).unwrap();
+        assert_eq!(*out.borrow(), b"hi");
+
+        // the same, one micro-operation at a time
+        out.borrow_mut().clear();
+        bus.attach(KEYBOARD_ADDR, Keyboard(&b"yo"[..]));
+        cpu.set_pc(0);
+        while cpu.step_uop_bus(&mut bus).1 != ExecResult::Halted {}
+        assert_eq!(*out.borrow(), b"yo");
+    }
+
+    /// Reads random bytes, changing every other tick.
+    struct Noise(u8);
+    impl Device for Noise {
+        fn read(&mut self) -> u8 {
+            self.0
+        }
+        fn write(&mut self, _value: u8) {}
+        fn tick(&mut self, clock: &Clock) {
+            if clock.every(2) {
+                self.0 = clock.random(0) as u8;
+            }
+        }
+    }
+
+    #[test]
+    fn test_clock() {
+        // sums 6 bytes of noise
+        let run = |seed| {
+            let mut bus = Bus::new();
+            bus.attach(0xF0, Noise(0));
+            bus.set_clock(Clock::new(seed));
+            let mut cpu = Neander::new();
+            cpu.set_ram_slice(0, &[ADD, 0xF0, ADD, 0xF0, ADD, 0xF0]);
+            cpu.set_ram_slice(6, &[ADD, 0xF0, ADD, 0xF0, ADD, 0xF0, HLT]);
+            cpu.run_bus_with_limit(&mut bus, 100).unwrap();
+            assert_eq!(bus.clock().ticks, 7);
+            cpu.acc()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+
+        // micro-steps tick once per instruction
+        let mut bus = Bus::new();
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 0x80, HLT]);
+        while cpu.step_uop_bus(&mut bus).1 != ExecResult::Halted {}
+        assert_eq!(bus.clock().ticks, 2);
+    }
+}
@@ -0,0 +1,29 @@
+//! The Neander CPU, its assembler and memory files, for tools that
+//! embed the simulator without its command line and GUI, which live
+//! in the `neander` crate.
+//!
+//! Only `alloc` is needed: without the default `std` feature, programs
+//! are loaded from sources and images already in memory instead of
+//! from files.
+#![no_std]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+pub mod asm;
+pub mod clock;
+pub mod cpu;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod formats;
+pub mod hooks;
+pub mod image;
+pub mod io;
+pub mod logisim;
+pub mod memfile;
+pub mod micro;
+pub mod predecode;
+pub mod srcmap;
+pub mod symbols;
+pub mod trace;
@@ -0,0 +1,149 @@
+//! Logisim-evolution `v2.0 raw` memory images.
+//! An image starts with a `v2.0 raw` header line, followed by
+//! hexadecimal bytes separated by whitespace. `n*xx` repeats the
+//! byte `xx` `n` times, and `#` starts a comment.
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// The first line of every image.
+pub const HEADER: &str = "v2.0 raw";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogisimError {
+    kind: LogisimErrorKind,
+    line: usize,
+}
+impl LogisimError {
+    pub fn new(line: usize, kind: LogisimErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogisimErrorKind {
+    MissingHeader,
+    InvalidValue(String),
+    MemoryOverflow,
+}
+impl core::fmt::Display for LogisimError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.kind {
+            LogisimErrorKind::MissingHeader => {
+                write!(f, "missing `{HEADER}` header in line {}", self.line)
+            }
+            LogisimErrorKind::InvalidValue(x) => {
+                write!(f, "invalid value in line {}: {x}", self.line)
+            }
+            LogisimErrorKind::MemoryOverflow => {
+                write!(f, "image larger than memory in line {}", self.line)
+            }
+        }
+    }
+}
+
+/// Returns true if `source` starts with the image header.
+pub fn is_image(source: &str) -> bool {
+    source.trim_start().starts_with(HEADER)
+}
+
+/// Parses a `v2.0 raw` image into `mem`, starting at address 0.
+/// Memory past the end of the image is left untouched.
+pub fn parse_image(mem: &mut [u8], source: &str) -> Result<(), LogisimError> {
+    parse_image_len(mem, source).map(|_| ())
+}
+
+/// Same as `parse_image`, returning how many bytes the image has.
+pub fn parse_image_len(mem: &mut [u8], source: &str) -> Result<usize, LogisimError> {
+    let mut lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.split('#').next().unwrap().trim()))
+        .filter(|(_, l)| !l.is_empty());
+    match lines.next() {
+        Some((_, HEADER)) => (),
+        Some((line, _)) => return Err(LogisimError::new(line, LogisimErrorKind::MissingHeader)),
+        None => return Err(LogisimError::new(1, LogisimErrorKind::MissingHeader)),
+    }
+    let mut cursor = 0;
+    for (line, content) in lines {
+        for word in content.split_whitespace() {
+            let err = |kind| LogisimError::new(line, kind);
+            let invalid = || err(LogisimErrorKind::InvalidValue(word.to_string()));
+            let (count, value) = match word.split_once('*') {
+                Some((n, v)) => (n.parse::<usize>().map_err(|_| invalid())?, v),
+                None => (1, word),
+            };
+            let value = u8::from_str_radix(value, 16).map_err(|_| invalid())?;
+            if cursor + count > mem.len() {
+                return Err(err(LogisimErrorKind::MemoryOverflow));
+            }
+            mem[cursor..cursor + count].fill(value);
+            cursor += count;
+        }
+    }
+    Ok(cursor)
+}
+
+/// Writes `mem` as a `v2.0 raw` image. Runs of 4 or more equal
+/// bytes are compressed, and trailing zeros are omitted.
+pub fn write_image(mem: &[u8]) -> String {
+    let len = mem.len() - mem.iter().rev().take_while(|b| **b == 0).count();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < len {
+        let run = mem[i..len].iter().take_while(|b| **b == mem[i]).count();
+        if run >= 4 {
+            words.push(format!("{run}*{:x}", mem[i]));
+            i += run;
+        } else {
+            words.push(format!("{:x}", mem[i]));
+            i += 1;
+        }
+    }
+    let mut out = format!("{HEADER}\n");
+    for line in words.chunks(16) {
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_parsing() {
+        let mut mem = [0_u8; 256];
+        let source = "v2.0 raw\n20 80 # load\nf0 3*1\n ff";
+        assert_eq!(parse_image(&mut mem, source), Ok(()));
+        assert_eq!(&mem[..8], [0x20, 0x80, 0xf0, 1, 1, 1, 0xff, 0]);
+        assert_eq!(
+            parse_image(&mut mem, "20 80"),
+            Err(LogisimError::new(1, LogisimErrorKind::MissingHeader))
+        );
+        assert_eq!(
+            parse_image(&mut mem, "v2.0 raw\n\n1 2 g"),
+            Err(LogisimError::new(
+                3,
+                LogisimErrorKind::InvalidValue("g".to_string())
+            ))
+        );
+        assert_eq!(
+            parse_image(&mut mem, "v2.0 raw\n250*0 7*1"),
+            Err(LogisimError::new(2, LogisimErrorKind::MemoryOverflow))
+        );
+    }
+    #[test]
+    fn test_image_writing() {
+        let mut mem = [0_u8; 256];
+        mem[..3].copy_from_slice(&[0x20, 0x80, 0xf0]);
+        mem[128] = 5;
+        let image = write_image(&mem);
+        assert_eq!(image, "v2.0 raw\n20 80 f0 125*0 5\n");
+        let mut parsed = [0_u8; 256];
+        parse_image(&mut parsed, &image).unwrap();
+        assert_eq!(parsed, mem);
+        assert_eq!(write_image(&[0; 256]), "v2.0 raw\n");
+    }
+}
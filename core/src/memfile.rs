@@ -0,0 +1,465 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::num::{IntErrorKind, ParseIntError};
+
+use crate::image::{MemoryImage, Origin};
+use crate::symbols::{self, SymbolTable};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemfileError {
+    kind: MemfileErrorKind,
+    line: usize,
+}
+impl MemfileError {
+    pub fn new(line: usize, kind: MemfileErrorKind) -> Self {
+        Self { line, kind }
+    }
+    /// The line of the error, from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn kind(&self) -> &MemfileErrorKind {
+        &self.kind
+    }
+}
+impl core::error::Error for MemfileError {}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemfileErrorKind {
+    InvalidDigit(String),
+    OutOfRangeInteger(String),
+    DuplicateLabel(String),
+    MemoryOverflow,
+}
+impl core::fmt::Display for MemfileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.kind {
+            MemfileErrorKind::InvalidDigit(x) => {
+                write!(f, "invalid number in line {}: {x}", self.line)
+            }
+            MemfileErrorKind::OutOfRangeInteger(x) => {
+                write!(f, "out of range integer in line {}: {x}", self.line)
+            }
+            MemfileErrorKind::DuplicateLabel(x) => {
+                write!(f, "label defined twice in line {}: {x}", self.line)
+            }
+            MemfileErrorKind::MemoryOverflow => {
+                write!(f, "Memory cursor overflow")
+            }
+        }
+    }
+}
+
+enum ParserState {
+    Org,
+    Normal,
+}
+
+/// Parses a memory file in the following format:
+/// A sequence of tokens, being one of:
+/// - byte: A number in decimal (positive or negative) or hexadecimal,
+///   that will be inserted at the memory cursor position.
+/// - ORG address: Changes the memory cursor to this position. Memories
+///   larger than 256 bytes take addresses up to their size, like `ORG 0x1000`.
+/// - name: A label naming the memory cursor position. Symbol tables
+///   hold 8-bit addresses, so labels past the first 256 bytes aren't
+///   returned.
+pub fn parse_memfile(mem: &mut [u8], source: &str) -> Result<(), MemfileError> {
+    parse_memfile_at(mem, source, 0)
+}
+
+/// Same as `parse_memfile`, but with the memory cursor starting at `start`.
+pub fn parse_memfile_at(mem: &mut [u8], source: &str, start: u8) -> Result<(), MemfileError> {
+    let mut image = MemoryImage::new(mem.len());
+    parse(&mut image, source, start, &mut SymbolTable::new(), None)?;
+    image.commit(mem);
+    Ok(())
+}
+
+/// Same as `parse_memfile`, returning the labels defined in the file.
+pub fn parse_memfile_symbols(mem: &mut [u8], source: &str) -> Result<SymbolTable, MemfileError> {
+    let (image, symbols) = parse_memfile_image(mem.len(), source, None)?;
+    image.commit(mem);
+    Ok(symbols)
+}
+
+/// Same as `parse_memfile_symbols`, into an image of `len` bytes
+/// recording the lines of `file` the bytes came from.
+pub fn parse_memfile_image(
+    len: usize,
+    source: &str,
+    file: Option<Arc<str>>,
+) -> Result<(MemoryImage, SymbolTable), MemfileError> {
+    let mut image = MemoryImage::new(len);
+    let mut symbols = SymbolTable::new();
+    parse(&mut image, source, 0, &mut symbols, file)?;
+    Ok((image, symbols))
+}
+
+fn parse(
+    image: &mut MemoryImage,
+    source: &str,
+    start: u8,
+    symbols: &mut SymbolTable,
+    file: Option<Arc<str>>,
+) -> Result<(), MemfileError> {
+    let filtered = remove_comments(source);
+    let source = &filtered;
+    let mut mem_cursor = start as usize;
+    let mut block = mem_cursor;
+    let mut stt = ParserState::Normal;
+    let mut labels = BTreeSet::new();
+    // the line of the last word, counted from the last offset
+    let (mut line, mut counted) = (1, 0);
+    let words = source.split_whitespace();
+    for word in words {
+        if mem_cursor == image.len() {
+            return Err(err(source, word, MemfileErrorKind::MemoryOverflow));
+        }
+        match stt {
+            ParserState::Normal if parse_org(word) => {
+                stt = ParserState::Org;
+            }
+            ParserState::Normal => match parse_label(word) {
+                Some(label) => {
+                    if !labels.insert(label) {
+                        let kind = MemfileErrorKind::DuplicateLabel(label.to_string());
+                        return Err(err(source, word, kind));
+                    }
+                    if let Ok(addr) = u8::try_from(mem_cursor) {
+                        symbols.insert(label, addr);
+                    }
+                }
+                None => {
+                    let value = parse_byte(word).map_err(|e| err(source, word, e))?;
+                    let offset = word.as_ptr() as usize - source.as_ptr() as usize;
+                    line += source[counted..offset].matches('\n').count();
+                    counted = offset;
+                    let origin = Origin {
+                        file: file.clone(),
+                        line: Some(line),
+                        block,
+                    };
+                    image.set(mem_cursor, value, origin);
+                    mem_cursor += 1;
+                }
+            },
+            ParserState::Org => {
+                mem_cursor = parse_address(word, image.len()).map_err(|e| err(source, word, e))?;
+                block = mem_cursor;
+                stt = ParserState::Normal;
+            }
+        }
+    }
+    Ok(())
+}
+fn err(source: &str, word: &str, kind: MemfileErrorKind) -> MemfileError {
+    let offset = word.as_ptr() as usize - source.as_ptr() as usize;
+    let line = source[..offset].chars().filter(|c| *c == '\n').count() + 1;
+    MemfileError::new(line, kind)
+}
+
+fn parse_org(token: &str) -> bool {
+    token == "org" || token == "ORG"
+}
+
+fn parse_label(token: &str) -> Option<&str> {
+    token.strip_suffix(':').filter(|l| symbols::is_label(l))
+}
+
+/// Parses a byte in decimal, negative numbers as two's complement,
+/// or in hexadecimal with `0x`.
+pub fn parse_byte(token: &str) -> Result<u8, MemfileErrorKind> {
+    if let Some(hex) = token.strip_prefix("0x") {
+        u8::from_str_radix(hex, 16).map_err(|e| parse_int_err(e, token))
+    } else if token.starts_with('-') {
+        token
+            .parse::<i8>()
+            .map(|x| x as u8)
+            .map_err(|e| parse_int_err(e, token))
+    } else {
+        token.parse::<u8>().map_err(|e| parse_int_err(e, token))
+    }
+}
+/// Parses the address of an ORG, which is a byte unless
+/// memory is larger than 256 bytes.
+fn parse_address(token: &str, len: usize) -> Result<usize, MemfileErrorKind> {
+    let addr = if len <= 256 {
+        parse_byte(token)? as usize
+    } else {
+        match token.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => token.parse::<u16>(),
+        }
+        .map_err(|e| parse_int_err(e, token))? as usize
+    };
+    match addr {
+        addr if addr < len => Ok(addr),
+        _ => Err(MemfileErrorKind::OutOfRangeInteger(token.to_string())),
+    }
+}
+fn parse_int_err(e: ParseIntError, token: &str) -> MemfileErrorKind {
+    match e.kind() {
+        IntErrorKind::Empty | IntErrorKind::InvalidDigit => {
+            MemfileErrorKind::InvalidDigit(token.to_string())
+        }
+        IntErrorKind::NegOverflow | IntErrorKind::PosOverflow => {
+            MemfileErrorKind::OutOfRangeInteger(token.to_string())
+        }
+        _ => unreachable!(),
+    }
+}
+fn remove_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut comment = false;
+    for c in source.chars() {
+        if c == ';' {
+            comment = true;
+        } else if c == '\n' {
+            comment = false;
+        }
+        if !comment {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// A byte or label of a memfile being formatted.
+struct Item {
+    addr: usize,
+    label: Option<String>,
+    /// The byte as written, with hexadecimal digits in uppercase
+    byte: String,
+    /// Comment lines right before the item
+    before: Vec<String>,
+    /// The comment ending the line of the item
+    after: Option<String>,
+}
+
+/// Formats a memory file: bytes are aligned in rows of `width`,
+/// hexadecimal digits are uppercased, labels get their own line
+/// and ORG blocks are sorted by address. Bytes written twice to
+/// the same address keep only the last value, as when loading.
+/// Comments are kept next to the bytes that followed them.
+pub fn format_memfile(source: &str, width: usize) -> Result<String, MemfileError> {
+    parse_memfile_symbols(&mut [0; 256], source)?;
+    let mut items: Vec<Item> = Vec::new();
+    let mut pending = Vec::new();
+    let mut cursor = 0;
+    let mut org = false;
+    for line in source.lines() {
+        let (code, comment) = match line.find(';') {
+            Some(i) => (&line[..i], Some(line[i..].trim_end().to_string())),
+            None => (line, None),
+        };
+        let first = items.len();
+        for word in code.split_whitespace() {
+            if org {
+                // checked by parsing above
+                cursor = parse_byte(word).unwrap() as usize;
+                org = false;
+                continue;
+            } else if parse_org(word) {
+                org = true;
+                continue;
+            }
+            let (label, byte) = match parse_label(word) {
+                Some(label) => (Some(label.to_string()), String::new()),
+                None => (None, canonical_byte(word)),
+            };
+            items.push(Item {
+                addr: cursor,
+                label,
+                byte,
+                before: core::mem::take(&mut pending),
+                after: None,
+            });
+            if items.last().is_some_and(|i| i.label.is_none()) {
+                cursor += 1;
+            }
+        }
+        match (comment, items.len() > first) {
+            (Some(c), true) => items.last_mut().unwrap().after = Some(c),
+            (Some(c), false) => pending.push(c),
+            (None, _) => (),
+        }
+    }
+    // labels first, then the last byte written at each address
+    items.sort_by_key(|i| (i.addr, i.label.is_none()));
+    let mut merged: Vec<Item> = Vec::new();
+    for item in items {
+        match merged.last_mut() {
+            Some(last)
+                if last.label.is_none() && item.label.is_none() && last.addr == item.addr =>
+            {
+                let mut before = core::mem::take(&mut last.before);
+                before.extend(item.before);
+                let after = match (last.after.take(), item.after) {
+                    (Some(a), Some(b)) => Some(format!("{a} {b}")),
+                    (a, b) => a.or(b),
+                };
+                *last = Item {
+                    before,
+                    after,
+                    ..item
+                };
+            }
+            _ => merged.push(item),
+        }
+    }
+
+    let pad = merged.iter().map(|i| i.byte.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    let mut row: Vec<String> = Vec::new();
+    let flush = |out: &mut String, row: &mut Vec<String>, comment: Option<&str>| {
+        if !row.is_empty() {
+            out.push_str(&row.join(" "));
+            if let Some(c) = comment {
+                out.push(' ');
+                out.push_str(c);
+            }
+            out.push('\n');
+            row.clear();
+        }
+    };
+    let mut next = None;
+    for item in &merged {
+        if next != Some(item.addr) {
+            flush(&mut out, &mut row, None);
+            if next.is_some() || item.addr != 0 {
+                if next.is_some() {
+                    out.push('\n');
+                }
+                out.push_str(&format!("org {}\n", item.addr));
+            }
+        }
+        if !item.before.is_empty() {
+            flush(&mut out, &mut row, None);
+            for c in &item.before {
+                out.push_str(c);
+                out.push('\n');
+            }
+        }
+        if let Some(label) = &item.label {
+            flush(&mut out, &mut row, None);
+            out.push_str(&format!("{label}:"));
+            match &item.after {
+                Some(c) => out.push_str(&format!(" {c}\n")),
+                None => out.push('\n'),
+            }
+            next = Some(item.addr);
+            continue;
+        }
+        row.push(format!("{:>pad$}", item.byte));
+        if item.after.is_some() || row.len() == width.max(1) {
+            flush(&mut out, &mut row, item.after.as_deref());
+        }
+        next = Some(item.addr + 1);
+    }
+    flush(&mut out, &mut row, None);
+    for c in pending {
+        out.push_str(&c);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Writes hexadecimal bytes with uppercase digits.
+fn canonical_byte(token: &str) -> String {
+    match token.strip_prefix("0x") {
+        Some(hex) => format!("0x{}", hex.to_ascii_uppercase()),
+        None => token.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn memfile_parsing() {
+        let mut mem = [0_u8; 256];
+        let source = r#"
+        1 2 3
+        0x4 0x5 0x6
+        0xff -10 0
+        org 20
+        7 8 9
+        "#;
+        let res = parse_memfile(&mut mem, source);
+        assert_eq!(res, Ok(()));
+        assert_eq!(&mem[0..9], [1, 2, 3, 4, 5, 6, 255, 246, 0]);
+        assert_eq!(&mem[20..23], [7, 8, 9]);
+
+        let (image, _) = parse_memfile_image(256, source, None).unwrap();
+        let origin = image.origin(21).unwrap();
+        assert_eq!((origin.line, origin.block), (Some(6), 20));
+        assert_eq!(image.origin(7).unwrap().line, Some(4));
+    }
+    #[test]
+    fn memfile_parsing_at() {
+        let mut mem = [0_u8; 256];
+        assert_eq!(parse_memfile_at(&mut mem, "1 2", 254), Ok(()));
+        assert_eq!(parse_memfile_at(&mut mem, "org 0 3", 254), Ok(()));
+        assert_eq!((mem[0], mem[254], mem[255]), (3, 1, 2));
+    }
+    #[test]
+    fn memfile_labels() {
+        let mut mem = [0_u8; 256];
+        let source = "start: 0x20 128\norg 128 counter: 5";
+        let syms = parse_memfile_symbols(&mut mem, source).unwrap();
+        assert_eq!(
+            (syms.get("start"), syms.get("counter")),
+            (Some(0), Some(128))
+        );
+        assert_eq!(mem[..2], [0x20, 128]);
+        assert_eq!(mem[128], 5);
+        // labels don't take memory, and are not valid operands
+        assert!(parse_memfile(&mut mem, "lda: 0x20 counter").is_err());
+        assert_eq!(
+            parse_memfile(&mut mem, "a: 1\na: 2"),
+            Err(MemfileError::new(
+                2,
+                MemfileErrorKind::DuplicateLabel("a".into())
+            ))
+        );
+    }
+    #[test]
+    fn memfile_wide() {
+        let mut mem = vec![0_u8; 1 << 16];
+        let source = "start: 1 org 0x1234 data: 2 org 65535 3";
+        let syms = parse_memfile_symbols(&mut mem, source).unwrap();
+        assert_eq!((mem[0x1234], mem[0xFFFF]), (2, 3));
+        // past 8-bit addresses, labels have no symbol
+        assert_eq!((syms.get("start"), syms.get("data")), (Some(0), None));
+        assert!(parse_memfile(&mut mem, "org 65535 1 2").is_err());
+        assert!(parse_memfile(&mut [0; 16], "org 16").is_err());
+    }
+    #[test]
+    fn test_commented() {
+        let src = "abc; 123; 45\ndef";
+        assert_eq!(remove_comments(src), "abc\ndef");
+    }
+    #[test]
+    fn test_format() {
+        let source = "; sum\n0x20 0x80 0x30 0x81 ; add\n0x10 0x82 0xf0\n\
+                      org 0x82 0 org 128 x: 5 -3 org 130 7\n; end";
+        let formatted = format_memfile(source, 4).unwrap();
+        assert_eq!(
+            formatted,
+            "; sum\n0x20 0x80 0x30 0x81 ; add\n0x10 0x82 0xF0\n\
+             \norg 128\nx:\n   5   -3    7\n; end\n"
+        );
+        let mut mem = [0; 256];
+        let mut expected = [0; 256];
+        parse_memfile(&mut mem, &formatted).unwrap();
+        parse_memfile(&mut expected, source).unwrap();
+        assert_eq!(mem, expected);
+        assert_eq!(format_memfile(&formatted, 4).unwrap(), formatted);
+        assert!(format_memfile("0x", 4).is_err());
+    }
+}
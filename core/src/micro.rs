@@ -0,0 +1,454 @@
+//! Register-transfer level description of the Neander control unit.
+//! Every instruction is a fixed sequence of micro-operations over the
+//! datapath registers (PC, MAR, MDR, IR and AC).
+use crate::cpu::instr::*;
+use crate::cpu::{ExecResult, Neander, NeanderException};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A single register transfer of the Neander datapath.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicroOp {
+    /// MAR <- PC
+    MarFromPc,
+    /// MDR <- mem[MAR], PC <- PC + 1
+    ReadIncPc,
+    /// IR <- MDR, and decode
+    IrFromMdr,
+    /// MAR <- MDR
+    MarFromMdr,
+    /// MDR <- mem[MAR]
+    Read,
+    /// MDR <- AC
+    MdrFromAcc,
+    /// mem[MAR] <- MDR
+    Write,
+    /// AC <- MDR
+    AccFromMdr,
+    /// AC <- AC + MDR
+    AccAdd,
+    /// AC <- AC OR MDR
+    AccOr,
+    /// AC <- AC AND MDR
+    AccAnd,
+    /// AC <- NOT AC
+    AccNot,
+    /// PC <- MDR
+    PcFromMdr,
+    /// PC <- MDR if N
+    PcFromMdrIfN,
+    /// PC <- MDR if Z
+    PcFromMdrIfZ,
+    /// Stops the machine
+    Halt,
+}
+impl core::fmt::Display for MicroOp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let s = match self {
+            Self::MarFromPc => "MAR <- PC",
+            Self::ReadIncPc => "MDR <- mem[MAR], PC <- PC + 1",
+            Self::IrFromMdr => "IR <- MDR",
+            Self::MarFromMdr => "MAR <- MDR",
+            Self::Read => "MDR <- mem[MAR]",
+            Self::MdrFromAcc => "MDR <- AC",
+            Self::Write => "mem[MAR] <- MDR",
+            Self::AccFromMdr => "AC <- MDR",
+            Self::AccAdd => "AC <- AC + MDR",
+            Self::AccOr => "AC <- AC OR MDR",
+            Self::AccAnd => "AC <- AC AND MDR",
+            Self::AccNot => "AC <- NOT AC",
+            Self::PcFromMdr => "PC <- MDR",
+            Self::PcFromMdrIfN => "if N: PC <- MDR",
+            Self::PcFromMdrIfZ => "if Z: PC <- MDR",
+            Self::Halt => "halt",
+        };
+        f.write_str(s)
+    }
+}
+
+impl MicroOp {
+    /// Returns true if the operation reads from memory.
+    pub fn reads_mem(self) -> bool {
+        matches!(self, Self::ReadIncPc | Self::Read)
+    }
+    /// Returns true if the operation writes to memory.
+    pub fn writes_mem(self) -> bool {
+        self == Self::Write
+    }
+}
+
+use MicroOp::*;
+
+/// The micro-operations shared by every instruction,
+/// that fetch the opcode into IR.
+pub const FETCH: [MicroOp; 3] = [MarFromPc, ReadIncPc, IrFromMdr];
+
+const NOP_SEQ: [MicroOp; 3] = FETCH;
+const STA_SEQ: [MicroOp; 8] = [
+    MarFromPc, ReadIncPc, IrFromMdr, MarFromPc, ReadIncPc, MarFromMdr, MdrFromAcc, Write,
+];
+const LDA_SEQ: [MicroOp; 8] = [
+    MarFromPc, ReadIncPc, IrFromMdr, MarFromPc, ReadIncPc, MarFromMdr, Read, AccFromMdr,
+];
+const ADD_SEQ: [MicroOp; 8] = [
+    MarFromPc, ReadIncPc, IrFromMdr, MarFromPc, ReadIncPc, MarFromMdr, Read, AccAdd,
+];
+const OR_SEQ: [MicroOp; 8] = [
+    MarFromPc, ReadIncPc, IrFromMdr, MarFromPc, ReadIncPc, MarFromMdr, Read, AccOr,
+];
+const AND_SEQ: [MicroOp; 8] = [
+    MarFromPc, ReadIncPc, IrFromMdr, MarFromPc, ReadIncPc, MarFromMdr, Read, AccAnd,
+];
+const NOT_SEQ: [MicroOp; 4] = [MarFromPc, ReadIncPc, IrFromMdr, AccNot];
+const JMP_SEQ: [MicroOp; 6] = [
+    MarFromPc, ReadIncPc, IrFromMdr, MarFromPc, ReadIncPc, PcFromMdr,
+];
+const JN_SEQ: [MicroOp; 6] = [
+    MarFromPc,
+    ReadIncPc,
+    IrFromMdr,
+    MarFromPc,
+    ReadIncPc,
+    PcFromMdrIfN,
+];
+const JZ_SEQ: [MicroOp; 6] = [
+    MarFromPc,
+    ReadIncPc,
+    IrFromMdr,
+    MarFromPc,
+    ReadIncPc,
+    PcFromMdrIfZ,
+];
+const HLT_SEQ: [MicroOp; 4] = [MarFromPc, ReadIncPc, IrFromMdr, Halt];
+
+/// Returns the complete micro-program of `opcode`, starting
+/// with the fetch cycle, or None if it is not a valid instruction.
+pub fn microprogram(opcode: u8) -> Option<&'static [MicroOp]> {
+    let seq: &'static [MicroOp] = match opcode {
+        NOP => &NOP_SEQ,
+        STA => &STA_SEQ,
+        LDA => &LDA_SEQ,
+        ADD => &ADD_SEQ,
+        OR => &OR_SEQ,
+        AND => &AND_SEQ,
+        NOT => &NOT_SEQ,
+        JMP => &JMP_SEQ,
+        JN => &JN_SEQ,
+        JZ => &JZ_SEQ,
+        HLT => &HLT_SEQ,
+        _ => return None,
+    };
+    Some(seq)
+}
+
+/// The stages of an instruction, each made of one or more micro-cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reads the opcode into IR
+    FetchOpcode,
+    /// Reads the operand of an instruction that has one
+    FetchOperand,
+    Execute,
+}
+impl core::fmt::Display for Phase {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::FetchOpcode => "fetch opcode",
+            Self::FetchOperand => "fetch operand",
+            Self::Execute => "execute",
+        })
+    }
+}
+
+/// Returns the phase of micro-cycle `uop` of instruction `ir`.
+pub fn phase(ir: u8, uop: u8) -> Phase {
+    match uop as usize {
+        n if n < FETCH.len() => Phase::FetchOpcode,
+        n if n < FETCH.len() + 2 && has_operand(ir) => Phase::FetchOperand,
+        _ => Phase::Execute,
+    }
+}
+
+/// The datapath state after a single micro-cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtlEntry {
+    /// Index of the micro-cycle inside its instruction (t0, t1, ...)
+    pub t: u8,
+    pub op: MicroOp,
+    pub pc: u8,
+    pub mar: u8,
+    pub mdr: u8,
+    pub ir: u8,
+    pub acc: i8,
+    pub status: u8,
+    /// The value carried by the internal bus during the cycle,
+    /// or None if no transfer happened.
+    pub bus: Option<u8>,
+}
+
+/// A log of every micro-cycle executed, in execution order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RtlTrace {
+    entries: Vec<RtlEntry>,
+}
+impl RtlTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn entries(&self) -> &[RtlEntry] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Formats the trace as CSV, one micro-cycle per row.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("cycle,t,op,pc,mar,mdr,ir,ac,n,z,read,write,bus\n");
+        for (i, e) in self.entries.iter().enumerate() {
+            let bus = e.bus.map(|b| format!("{b:02X}")).unwrap_or_default();
+            out.push_str(&format!(
+                "{i},{},\"{}\",{:02X},{:02X},{:02X},{:02X},{:02X},{},{},{},{},{bus}\n",
+                e.t,
+                e.op,
+                e.pc,
+                e.mar,
+                e.mdr,
+                e.ir,
+                e.acc as u8,
+                (e.status & 2 != 0) as u8,
+                (e.status & 1 != 0) as u8,
+                e.op.reads_mem() as u8,
+                e.op.writes_mem() as u8,
+            ));
+        }
+        out
+    }
+
+    /// Formats the trace as a Value Change Dump, with one
+    /// time unit per micro-cycle. `initial` is the state of the
+    /// CPU before the first traced cycle.
+    pub fn to_vcd(&self, initial: &Neander) -> String {
+        const SIGNALS: [(&str, u8, char); 11] = [
+            ("t", 3, '!'),
+            ("pc", 8, '"'),
+            ("mar", 8, '#'),
+            ("mdr", 8, '$'),
+            ("ir", 8, '%'),
+            ("ac", 8, '&'),
+            ("n", 1, '\''),
+            ("z", 1, '('),
+            ("read", 1, ')'),
+            ("write", 1, '*'),
+            ("bus", 8, '+'),
+        ];
+        fn values(e: &RtlEntry) -> [Option<u8>; 11] {
+            [
+                Some(e.t),
+                Some(e.pc),
+                Some(e.mar),
+                Some(e.mdr),
+                Some(e.ir),
+                Some(e.acc as u8),
+                Some((e.status & 2 != 0) as u8),
+                Some((e.status & 1 != 0) as u8),
+                Some(e.op.reads_mem() as u8),
+                Some(e.op.writes_mem() as u8),
+                e.bus,
+            ]
+        }
+        fn dump(out: &mut String, width: u8, id: char, val: Option<u8>) {
+            match (width, val) {
+                (1, Some(v)) => out.push_str(&format!("{v}{id}\n")),
+                (1, None) => out.push_str(&format!("x{id}\n")),
+                (_, Some(v)) => out.push_str(&format!("b{v:b} {id}\n")),
+                (_, None) => out.push_str(&format!("bx {id}\n")),
+            }
+        }
+
+        let mut out = String::new();
+        out.push_str("$version neander $end\n$timescale 1ns $end\n$scope module neander $end\n");
+        for (name, width, id) in SIGNALS {
+            out.push_str(&format!("$var wire {width} {id} {name} $end\n"));
+        }
+        out.push_str("$upscope $end\n$enddefinitions $end\n");
+
+        let start = RtlEntry {
+            t: 0,
+            op: MarFromPc,
+            pc: initial.pc(),
+            mar: initial.mar(),
+            mdr: initial.mdr(),
+            ir: initial.ir(),
+            acc: initial.acc(),
+            status: initial.status(),
+            bus: None,
+        };
+        let mut prev = values(&start);
+        // no memory access happened before the first cycle
+        prev[8] = Some(0);
+        out.push_str("#0\n$dumpvars\n");
+        for ((_, width, id), val) in SIGNALS.iter().zip(prev) {
+            dump(&mut out, *width, *id, val);
+        }
+        out.push_str("$end\n");
+        for (i, e) in self.entries.iter().enumerate() {
+            out.push_str(&format!("#{}\n", i + 1));
+            let vals = values(e);
+            for ((_, width, id), (val, old)) in SIGNALS.iter().zip(vals.iter().zip(prev)) {
+                if *val != old {
+                    dump(&mut out, *width, *id, *val);
+                }
+            }
+            prev = vals;
+        }
+        out.push_str(&format!("#{}\n", self.entries.len() + 1));
+        out
+    }
+}
+
+impl Neander {
+    /// Same as `step_uop`, but records the state of the
+    /// datapath after the micro-operation in `trace`.
+    pub fn step_uop_traced(&mut self, trace: &mut RtlTrace) -> (MicroOp, ExecResult) {
+        let t = self.micro_cycle();
+        let (op, res) = self.step_uop();
+        if res == ExecResult::Exception(NeanderException::EndOfProgram) {
+            return (op, res);
+        }
+        let bus = match op {
+            MarFromPc | MarFromMdr => Some(self.mar()),
+            ReadIncPc | Read | MdrFromAcc | Write => Some(self.mdr()),
+            IrFromMdr => Some(self.ir()),
+            AccFromMdr | AccAdd | AccOr | AccAnd | AccNot => Some(self.acc() as u8),
+            PcFromMdr | PcFromMdrIfN | PcFromMdrIfZ => Some(self.mdr()),
+            Halt => None,
+        };
+        trace.entries.push(RtlEntry {
+            t,
+            op,
+            pc: self.pc(),
+            mar: self.mar(),
+            mdr: self.mdr(),
+            ir: self.ir(),
+            acc: self.acc(),
+            status: self.status(),
+            bus,
+        });
+        (op, res)
+    }
+    /// Runs the program one micro-operation at a time,
+    /// recording every micro-cycle in `trace`.
+    pub fn run_uops_traced(&mut self, trace: &mut RtlTrace) -> Result<(), NeanderException> {
+        self.run_uops_traced_with_limit(trace, u64::MAX)
+    }
+    /// Same as `run_uops_traced`, but stops after `max_steps`
+    /// whole instructions like `run_with_limit`.
+    pub fn run_uops_traced_with_limit(
+        &mut self,
+        trace: &mut RtlTrace,
+        max_steps: u64,
+    ) -> Result<(), NeanderException> {
+        let mut steps = 0;
+        while steps < max_steps {
+            match self.step_uop_traced(trace).1 {
+                ExecResult::Halted => return Ok(()),
+                ExecResult::Exception(e) => return Err(e),
+                _ => (),
+            }
+            if !self.mid_instruction() {
+                steps += 1;
+            }
+        }
+        Err(NeanderException::StepLimitExceeded(max_steps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn traced_program() -> (Neander, RtlTrace) {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, HLT]);
+        cpu.set_ram(128, 7);
+        let initial = cpu.clone();
+        let mut trace = RtlTrace::new();
+        assert_eq!(cpu.run_uops_traced(&mut trace), Ok(()));
+        (initial, trace)
+    }
+
+    #[test]
+    fn test_micro_step() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[STA, 128, NOT, HLT]);
+        cpu.set_acc(3);
+        let mut phases = vec![];
+        loop {
+            let (phase, res) = cpu.micro_step();
+            phases.push(phase);
+            if res == ExecResult::Halted {
+                break;
+            }
+            if phase == Phase::Execute && phases.len() == 3 {
+                assert_eq!(
+                    res,
+                    ExecResult::MemWrite {
+                        addr: 128,
+                        value: 3
+                    }
+                );
+            }
+        }
+        use Phase::*;
+        let expected = [
+            FetchOpcode,
+            FetchOperand,
+            Execute,
+            FetchOpcode,
+            Execute,
+            FetchOpcode,
+            Execute,
+        ];
+        assert_eq!(phases, expected);
+        assert_eq!(cpu.acc(), -4);
+    }
+    #[test]
+    fn test_rtl_trace() {
+        let (_, trace) = traced_program();
+        assert_eq!(trace.len(), LDA_SEQ.len() + HLT_SEQ.len());
+        let ops: Vec<_> = trace.entries().iter().map(|e| e.op).collect();
+        assert_eq!(&ops[..8], LDA_SEQ);
+        let read = trace.entries()[6];
+        assert_eq!((read.t, read.op, read.bus), (6, Read, Some(7)));
+        assert_eq!(trace.entries()[8].t, 0);
+    }
+    #[test]
+    fn test_rtl_csv() {
+        let (_, trace) = traced_program();
+        let csv = trace.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("cycle,t,op,pc,mar,mdr,ir,ac,n,z,read,write,bus")
+        );
+        assert_eq!(
+            lines.nth(1),
+            Some("1,1,\"MDR <- mem[MAR], PC <- PC + 1\",01,00,20,00,00,0,0,1,0,20")
+        );
+    }
+    #[test]
+    fn test_rtl_vcd() {
+        let (initial, trace) = traced_program();
+        let vcd = trace.to_vcd(&initial);
+        assert!(vcd.contains("$var wire 8 % ir $end"));
+        // IR <- MDR happens at cycle 2, ending at time 3
+        let t3 = vcd.split("#3\n").nth(1).unwrap();
+        assert!(t3.starts_with("b10 !\nb100000 %\n"));
+    }
+}
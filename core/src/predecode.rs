@@ -0,0 +1,175 @@
+//! A faster interpreter for long runs, that decodes each instruction
+//! once and keeps it until the program writes over its bytes.
+use crate::cpu::instr::*;
+use crate::cpu::{ExecResult, Neander, NeanderException};
+
+/// An instruction with its operand, as found in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decoded {
+    /// Not decoded yet, or written over
+    Unknown,
+    /// Runs with `Neander::step`, like the instructions
+    /// at the end of memory
+    Slow,
+    Nop,
+    Sta(u8),
+    Lda(u8),
+    Add(u8),
+    Or(u8),
+    And(u8),
+    Not,
+    Jmp(u8),
+    Jn(u8),
+    Jz(u8),
+    Hlt,
+}
+
+/// The decoded instructions of a memory, by address.
+/// Writes made by the program invalidate the instructions they
+/// overwrite; after changing memory any other way, call `clear`.
+#[derive(Debug, Clone)]
+pub struct Predecoded {
+    cache: [Decoded; 256],
+}
+impl Default for Predecoded {
+    fn default() -> Self {
+        Self {
+            cache: [Decoded::Unknown; 256],
+        }
+    }
+}
+impl Predecoded {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clear(&mut self) {
+        self.cache = [Decoded::Unknown; 256];
+    }
+    /// Forgets the instructions that include the byte at `addr`.
+    pub fn invalidate(&mut self, addr: u8) {
+        self.cache[addr as usize] = Decoded::Unknown;
+        self.cache[addr.wrapping_sub(1) as usize] = Decoded::Unknown;
+    }
+
+    fn decode(mem: &[u8], pc: u8) -> Decoded {
+        let opcode = mem[pc as usize];
+        let len = if has_operand(opcode) { 2 } else { 1 };
+        // past 255 the end of program flag is involved
+        if pc as usize + len > 255 {
+            return Decoded::Slow;
+        }
+        let arg = mem[(pc as usize + 1).min(255)];
+        match opcode {
+            NOP => Decoded::Nop,
+            STA => Decoded::Sta(arg),
+            LDA => Decoded::Lda(arg),
+            ADD => Decoded::Add(arg),
+            OR => Decoded::Or(arg),
+            AND => Decoded::And(arg),
+            NOT => Decoded::Not,
+            JMP => Decoded::Jmp(arg),
+            JN => Decoded::Jn(arg),
+            JZ => Decoded::Jz(arg),
+            HLT => Decoded::Hlt,
+            _ => Decoded::Slow,
+        }
+    }
+}
+
+impl Neander {
+    /// Same as `step`, using and filling the instructions of `cache`.
+    pub fn step_predecoded(&mut self, cache: &mut Predecoded) -> ExecResult {
+        if self.mid_instruction() || self.status_end_of_prog() {
+            return self.step_invalidating(cache);
+        }
+        let pc = self.pc();
+        let mut decoded = cache.cache[pc as usize];
+        if decoded == Decoded::Unknown {
+            decoded = Predecoded::decode(self.memory(), pc);
+            cache.cache[pc as usize] = decoded;
+        }
+        let (next, acc) = match decoded {
+            Decoded::Unknown | Decoded::Slow => return self.step_invalidating(cache),
+            Decoded::Nop => (pc + 1, None),
+            Decoded::Sta(addr) => {
+                self.set_pc(pc + 2);
+                self.set_ram(addr, self.acc() as u8);
+                cache.invalidate(addr);
+                return ExecResult::MemWrite {
+                    addr: addr as u16,
+                    value: self.acc(),
+                };
+            }
+            Decoded::Lda(addr) => (pc + 2, Some(self.ram(addr) as i8)),
+            Decoded::Add(addr) => (pc + 2, Some(self.acc().wrapping_add(self.ram(addr) as i8))),
+            Decoded::Or(addr) => (pc + 2, Some(self.acc() | self.ram(addr) as i8)),
+            Decoded::And(addr) => (pc + 2, Some(self.acc() & self.ram(addr) as i8)),
+            Decoded::Not => (pc + 1, Some(!self.acc())),
+            Decoded::Jmp(addr) => (addr, None),
+            Decoded::Jn(addr) => (if self.status_negative() { addr } else { pc + 2 }, None),
+            Decoded::Jz(addr) => (if self.status_zero() { addr } else { pc + 2 }, None),
+            Decoded::Hlt => {
+                self.set_pc(pc + 1);
+                return ExecResult::Halted;
+            }
+        };
+        self.set_pc(next);
+        if let Some(acc) = acc {
+            // also updates the flags
+            self.set_acc(acc);
+        }
+        ExecResult::Normal
+    }
+    /// Same as `run_with_limit`, decoding every instruction only once.
+    pub fn run_predecoded(&mut self, max_steps: u64) -> Result<(), NeanderException> {
+        let mut cache = Predecoded::new();
+        for _ in 0..max_steps {
+            match self.step_predecoded(&mut cache) {
+                ExecResult::Halted => return Ok(()),
+                ExecResult::Exception(e) => return Err(e),
+                _ => (),
+            }
+        }
+        Err(NeanderException::StepLimitExceeded(max_steps))
+    }
+
+    fn step_invalidating(&mut self, cache: &mut Predecoded) -> ExecResult {
+        let res = self.step();
+        if let ExecResult::MemWrite { addr, .. } = res {
+            cache.invalidate(addr as u8);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same_run(program: &[u8], start: u8) {
+        let mut slow = Neander::new();
+        slow.set_ram_slice(start, program);
+        slow.set_pc(start);
+        let mut fast = slow.clone();
+        let res = slow.run_with_limit(10_000);
+        assert_eq!(fast.run_predecoded(10_000), res);
+        assert_eq!(fast, slow);
+    }
+
+    #[test]
+    fn test_predecoded() {
+        // counts down from 10
+        same_run(
+            &[
+                LDA, 20, ADD, 21, STA, 20, JZ, 10, JMP, 0, HLT, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10, 0xFF,
+            ],
+            0,
+        );
+        // overwrites its first instruction, already decoded, with a HLT
+        same_run(&[NOP, LDA, 8, STA, 0, JMP, 0, 0, HLT], 0);
+        // runs off the end of memory
+        same_run(&[NOT, NOT, NOP, LDA], 252);
+        same_run(&[0x70], 0);
+        same_run(&[JMP, 0], 0);
+    }
+}
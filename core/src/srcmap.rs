@@ -0,0 +1,62 @@
+//! Source maps of assembled programs, from the addresses the
+//! assembler wrote to the lines they came from, so debuggers can
+//! show the line being executed instead of only its bytes.
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// The line of each address, from 1
+    lines: BTreeMap<u8, usize>,
+    source: Vec<String>,
+}
+impl SourceMap {
+    /// An empty map of `source`.
+    pub fn new(source: &str) -> Self {
+        Self {
+            lines: BTreeMap::new(),
+            source: source.lines().map(str::to_string).collect(),
+        }
+    }
+    /// Records that the byte at `addr` came from `line`. When
+    /// several lines write the same address, the last one wins.
+    pub fn insert(&mut self, addr: u8, line: usize) {
+        self.lines.insert(addr, line);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+    /// The line `addr` came from, from 1.
+    pub fn line(&self, addr: u8) -> Option<usize> {
+        self.lines.get(&addr).copied()
+    }
+    /// The text of the line `addr` came from, trimmed.
+    pub fn text(&self, addr: u8) -> Option<&str> {
+        let line = self.line(addr)?;
+        self.source.get(line - 1).map(|s| s.trim())
+    }
+    /// `addr` as "line N: text", if it came from the source.
+    pub fn describe(&self, addr: u8) -> Option<String> {
+        Some(format!("line {}: {}", self.line(addr)?, self.text(addr)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map() {
+        let mut map = SourceMap::new("start: lda x\n  hlt ; done\n");
+        assert!(map.is_empty());
+        map.insert(0, 1);
+        map.insert(1, 1);
+        map.insert(2, 2);
+        assert_eq!(map.line(1), Some(1));
+        assert_eq!(map.text(2), Some("hlt ; done"));
+        assert_eq!(map.describe(0).unwrap(), "line 1: start: lda x");
+        assert_eq!(map.describe(3), None);
+    }
+}
@@ -0,0 +1,77 @@
+//! Symbol tables, mapping label names to memory addresses.
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    by_name: BTreeMap<String, u8>,
+    by_addr: BTreeMap<u8, String>,
+}
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Defines `name` at `addr`. Returns false if `name` was already defined.
+    /// When several names share an address, the first one is shown for it.
+    pub fn insert(&mut self, name: &str, addr: u8) -> bool {
+        if self.by_name.contains_key(name) {
+            return false;
+        }
+        self.by_name.insert(name.to_string(), addr);
+        self.by_addr.entry(addr).or_insert_with(|| name.to_string());
+        true
+    }
+    /// The address of `name`.
+    pub fn get(&self, name: &str) -> Option<u8> {
+        self.by_name.get(name).copied()
+    }
+    /// The name shown for `addr`.
+    pub fn name_at(&self, addr: u8) -> Option<&str> {
+        self.by_addr.get(&addr).map(String::as_str)
+    }
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+    /// All symbols, sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u8)> {
+        let mut syms: Vec<_> = self.by_name.iter().map(|(n, a)| (n.as_str(), *a)).collect();
+        syms.sort_by_key(|(_, a)| *a);
+        syms.into_iter()
+    }
+}
+
+/// Returns true if `name` is a valid label: a letter or underscore
+/// followed by letters, digits or underscores.
+pub fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols() {
+        let mut syms = SymbolTable::new();
+        assert!(syms.insert("loop", 4));
+        assert!(syms.insert("start", 0));
+        assert!(syms.insert("again", 4));
+        assert!(!syms.insert("loop", 8));
+        assert_eq!(syms.get("loop"), Some(4));
+        assert_eq!(syms.name_at(4), Some("loop"));
+        assert_eq!(syms.name_at(1), None);
+        let all: Vec<_> = syms.iter().collect();
+        assert_eq!(all, [("start", 0), ("again", 4), ("loop", 4)]);
+        assert!(is_label("_x1"));
+        assert!(!is_label("1x"));
+        assert!(!is_label(""));
+    }
+}
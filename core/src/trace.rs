@@ -0,0 +1,189 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::cpu::{instr, ExecResult, Neander, NeanderException};
+use crate::hooks::Hooks;
+
+/// A record of a single executed instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Address of the instruction
+    pub pc: u8,
+    pub opcode: u8,
+    /// The argument of the instruction, if it takes one
+    pub operand: Option<u8>,
+    pub acc_before: i8,
+    pub acc_after: i8,
+    pub status_before: u8,
+    pub status_after: u8,
+    pub result: ExecResult,
+}
+
+/// A log of executed instructions, in execution order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    /// The entry of the instruction being executed
+    pending: Option<TraceEntry>,
+}
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, entry: TraceEntry) {
+        self.entries.push(entry);
+    }
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Records every instruction fetched. Reaching the end of
+/// program is not recorded, since no instruction was executed.
+impl Hooks for Trace {
+    fn on_fetch(&mut self, cpu: &Neander, pc: u8, opcode: u8) {
+        let operand = if instr::has_operand(opcode) && pc != 255 {
+            Some(cpu.ram(pc.wrapping_add(1)))
+        } else {
+            None
+        };
+        self.pending = Some(TraceEntry {
+            pc,
+            opcode,
+            operand,
+            acc_before: cpu.acc(),
+            acc_after: cpu.acc(),
+            status_before: cpu.status(),
+            status_after: cpu.status(),
+            result: ExecResult::Normal,
+        });
+    }
+    fn on_exec(&mut self, cpu: &Neander, _pc: u8, result: ExecResult) {
+        if let Some(entry) = self.pending.take() {
+            self.push(TraceEntry {
+                acc_after: cpu.acc(),
+                status_after: cpu.status(),
+                result,
+                ..entry
+            });
+        }
+    }
+}
+
+impl Neander {
+    /// Same as `step`, but records the executed instruction in `trace`.
+    pub fn step_traced(&mut self, trace: &mut Trace) -> ExecResult {
+        self.step_hooked(trace)
+    }
+    /// Same as `run`, but records every executed instruction in `trace`.
+    pub fn run_traced(&mut self, trace: &mut Trace) -> Result<(), NeanderException> {
+        self.run_traced_with_limit(trace, u64::MAX)
+    }
+    /// Same as `run_traced`, but stops after `max_steps` instructions
+    /// like `run_with_limit`.
+    pub fn run_traced_with_limit(
+        &mut self,
+        trace: &mut Trace,
+        max_steps: u64,
+    ) -> Result<(), NeanderException> {
+        for _ in 0..max_steps {
+            match self.step_traced(trace) {
+                ExecResult::Halted => return Ok(()),
+                ExecResult::Exception(e) => return Err(e),
+                _ => (),
+            }
+        }
+        Err(NeanderException::StepLimitExceeded(max_steps))
+    }
+}
+
+impl core::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = instr::name(self.opcode).unwrap_or("???");
+        let instr = match self.operand {
+            Some(arg) => format!("{name} {arg:02X}"),
+            None => name.to_string(),
+        };
+        write!(
+            f,
+            "{:02X}: {instr:8} | AC: {} -> {}",
+            self.pc, self.acc_before, self.acc_after
+        )?;
+        for (flag, bit) in [("N", 2), ("Z", 1)] {
+            let before = (self.status_before & bit != 0) as u8;
+            let after = (self.status_after & bit != 0) as u8;
+            if before == after {
+                write!(f, " | {flag}: {after}")?;
+            } else {
+                write!(f, " | {flag}: {before} -> {after}")?;
+            }
+        }
+        match self.result {
+            ExecResult::Normal => Ok(()),
+            ExecResult::Halted => write!(f, " | halted"),
+            ExecResult::MemWrite { addr, value } => write!(f, " | mem[{addr}] <- {value}"),
+            ExecResult::Exception(e) => write!(f, " | exception: {e}"),
+        }
+    }
+}
+impl core::fmt::Display for Trace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_run_traced() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, NOT, STA, 129, HLT]);
+        cpu.set_ram(128, 5);
+        let mut trace = Trace::new();
+        assert_eq!(cpu.run_traced(&mut trace), Ok(()));
+        assert_eq!(trace.len(), 4);
+        let e = trace.entries()[1];
+        assert_eq!(e.pc, 2);
+        assert_eq!(e.opcode, NOT);
+        assert_eq!(e.operand, None);
+        assert_eq!((e.acc_before, e.acc_after), (5, -6));
+        assert_eq!((e.status_before, e.status_after), (0, 2));
+        assert_eq!(
+            trace.entries()[2].result,
+            ExecResult::MemWrite {
+                addr: 129,
+                value: -6
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "02: NOT      | AC: 5 -> -6 | N: 0 -> 1 | Z: 0"
+        );
+    }
+    #[test]
+    fn test_end_of_program_not_traced() {
+        let mut cpu = Neander::new();
+        let mut trace = Trace::new();
+        assert_eq!(
+            cpu.run_traced(&mut trace),
+            Err(NeanderException::EndOfProgram)
+        );
+        assert_eq!(trace.len(), 256);
+    }
+}
@@ -0,0 +1,149 @@
+/* The Neander simulator, see the neander-ffi crate. */
+
+#ifndef NEANDER_H
+#define NEANDER_H
+
+/* Generated with cbindgen from neander_core::ffi, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The step or call went fine.
+ */
+#define NEANDER_OK 0
+
+/**
+ * The machine executed HLT.
+ */
+#define NEANDER_HALTED 1
+
+/**
+ * The instruction stored a byte to memory.
+ */
+#define NEANDER_MEM_WRITE 2
+
+/**
+ * Something went wrong, see `neander_error`.
+ */
+#define NEANDER_ERROR -1
+
+/**
+ * A machine and the description of its last error.
+ */
+typedef struct NeanderHandle NeanderHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A new machine, with memory and registers zeroed.
+ */
+NeanderHandle *neander_new(void);
+
+/**
+ * Releases a machine. Does nothing if `cpu` is NULL.
+ *
+ * # Safety
+ * `cpu` must come from `neander_new` and not be used afterwards.
+ */
+void neander_free(NeanderHandle *cpu);
+
+/**
+ * Executes one instruction, returning `NEANDER_OK`, `NEANDER_HALTED`,
+ * `NEANDER_MEM_WRITE` or `NEANDER_ERROR`.
+ *
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+int32_t neander_step(NeanderHandle *cpu);
+
+/**
+ * Runs until HLT, returning `NEANDER_HALTED`, or `NEANDER_ERROR`
+ * if the program fails or doesn't halt after `max_steps` instructions.
+ *
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+int32_t neander_run(NeanderHandle *cpu, uint64_t max_steps);
+
+/**
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+uint8_t neander_mem_read(const NeanderHandle *cpu, uint8_t addr);
+
+/**
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+void neander_mem_write(NeanderHandle *cpu, uint8_t addr, uint8_t value);
+
+/**
+ * Copies `len` bytes to the start of memory, leaving the rest as it is.
+ *
+ * # Safety
+ * `cpu` must be a machine from `neander_new` and `bytes` must
+ * point to `len` bytes.
+ */
+int32_t neander_load(NeanderHandle *cpu, const uint8_t *bytes, uintptr_t len);
+
+/**
+ * Assembles `source` into memory. On errors memory is left as it
+ * was, and `neander_error` has one error per line.
+ *
+ * # Safety
+ * `cpu` must be a machine from `neander_new` and `source` a
+ * NUL-terminated string.
+ */
+int32_t neander_assemble(NeanderHandle *cpu, const char *source);
+
+/**
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+uint8_t neander_pc(const NeanderHandle *cpu);
+
+/**
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+void neander_set_pc(NeanderHandle *cpu, uint8_t pc);
+
+/**
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+int8_t neander_acc(const NeanderHandle *cpu);
+
+/**
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+void neander_set_acc(NeanderHandle *cpu, int8_t acc);
+
+/**
+ * The flags, Z in bit 0 and N in bit 1.
+ *
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+uint8_t neander_status(const NeanderHandle *cpu);
+
+/**
+ * The last error of the machine, empty if there was none. The
+ * string lives until the next call that fails.
+ *
+ * # Safety
+ * `cpu` must be a machine from `neander_new`.
+ */
+const char *neander_error(const NeanderHandle *cpu);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NEANDER_H */
@@ -0,0 +1,27 @@
+//! The simulator as a C library, `libneander_ffi`, for frontends
+//! that embed it, like lab tools written in Qt. The functions are
+//! in `neander_core::ffi`, declared for C in `neander.h`, which is
+//! generated with `cbindgen --config cbindgen.toml -o neander.h`.
+pub use neander_core::ffi::*;
+
+#[cfg(test)]
+mod tests {
+    /// The header must be generated again when a function changes.
+    #[test]
+    fn test_header() {
+        let header = include_str!("../neander.h");
+        let source = include_str!("../../core/src/ffi.rs");
+        let functions: Vec<_> = source
+            .lines()
+            .filter_map(|l| l.split(r#"extern "C" fn "#).nth(1))
+            .map(|l| &l[..l.find('(').unwrap()])
+            .collect();
+        assert_eq!(functions.len(), 14);
+        for name in functions {
+            assert!(
+                header.contains(&format!("{name}(")),
+                "{name} isn't in neander.h"
+            );
+        }
+    }
+}
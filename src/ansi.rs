@@ -0,0 +1,95 @@
+//! ANSI colors for terminal output. The CLI decides once whether to
+//! color, from `--color` and `NO_COLOR`, and printers take a `color`
+//! flag, so what they write to files and pipes stays plain.
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cpu::{Neander, Registers};
+
+/// Mnemonics of instructions
+pub const OPCODE: &str = "36";
+/// Registers that changed in the last step
+pub const CHANGED: &str = "1;33";
+/// Breakpoints, when they're reached
+pub const BREAKPOINT: &str = "1;31";
+pub const LABEL: &str = "35";
+pub const HIGHLIGHT: &str = "7";
+pub const DIM: &str = "2";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the CLI colors its output.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+pub fn set_enabled(color: bool) {
+    ENABLED.store(color, Ordering::Relaxed);
+}
+
+/// `text` in the style of `code`, if `color` is set.
+pub fn paint(code: &str, text: impl Display, color: bool) -> String {
+    match color {
+        true => format!("\x1b[{code}m{text}\x1b[0m"),
+        false => text.to_string(),
+    }
+}
+
+/// A disassembled instruction with its mnemonic painted.
+pub fn instruction(text: &str, color: bool) -> String {
+    match text.split_once(' ') {
+        Some((mnemonic, rest)) => format!("{} {rest}", paint(OPCODE, mnemonic, color)),
+        None => paint(OPCODE, text, color),
+    }
+}
+
+/// The registers of `cpu`, like its `Display`, painting the ones
+/// that differ from `before`.
+pub fn registers(cpu: &Neander, before: Option<&Registers>, color: bool) -> String {
+    let now = cpu.registers();
+    let changed = |f: fn(&Registers) -> u8| before.is_some_and(|b| f(b) != f(&now));
+    let line = |text: String, changed: bool| match changed {
+        true => paint(CHANGED, text, color),
+        false => text,
+    };
+    let acc = cpu.acc();
+    let pc = cpu.program_counter();
+    format!(
+        "STATE:\n{}\n{}\n{}",
+        line(
+            format!("AC: {acc} | 0x{acc:X} | 0b{acc:b}"),
+            changed(|r| r.acc as u8)
+        ),
+        line(format!("PC: {pc} | 0x{pc:X} | 0b{pc:b}"), changed(|r| r.pc)),
+        line(
+            format!(
+                "N: {}, Z: {}",
+                cpu.status_negative() as u8,
+                cpu.status_zero() as u8
+            ),
+            changed(|r| r.status & 3)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_ansi() {
+        assert_eq!(paint(DIM, 5, false), "5");
+        assert_eq!(paint(DIM, 5, true), "\x1b[2m5\x1b[0m");
+        assert_eq!(instruction("LDA x", true), "\x1b[36mLDA\x1b[0m x");
+        assert_eq!(instruction("HLT", false), "HLT");
+
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 2, 7]);
+        let before = cpu.registers();
+        cpu.step();
+        assert_eq!(registers(&cpu, Some(&before), false), cpu.to_string());
+        let text = registers(&cpu, Some(&before), true);
+        assert!(text.contains("\x1b[1;33mAC: 7 | 0x7 | 0b111\x1b[0m"));
+        assert!(text.contains("\nN: 0, Z: 0"));
+    }
+}
@@ -0,0 +1,270 @@
+//! Workloads for comparing the interpreters, shared by the criterion
+//! benches in `benches/` and `neander bench --internal`, so changes
+//! made for speed can be measured instead of guessed. `neander bench`
+//! alone measures `Neander::step` on an endless program.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::cpu::instr::*;
+use crate::cpu::{ExecResult, Neander};
+use crate::io::{Bus, CharOutput, Keyboard, KEYBOARD_ADDR, OUTPUT_ADDR};
+use crate::predecode::Predecoded;
+
+/// Instructions a program may run before it's stopped.
+const MAX_STEPS: u64 = 1_000_000;
+
+/// A way of running programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// `Neander::step`, or `step_bus` with devices
+    Step,
+    /// `Neander::step_predecoded`, which can't use devices
+    Predecoded,
+    /// One micro-operation at a time
+    MicroSteps,
+}
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [Strategy::Step, Strategy::Predecoded, Strategy::MicroSteps];
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Step => "step",
+            Strategy::Predecoded => "predecoded",
+            Strategy::MicroSteps => "micro-steps",
+        }
+    }
+}
+
+/// Programs run together, as one measurement.
+pub struct Workload {
+    pub name: &'static str,
+    pub description: &'static str,
+    programs: Vec<Neander>,
+    /// Whether the programs use the keyboard and the character output
+    devices: bool,
+}
+
+/// The machines after a run of a workload.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// Instructions executed by all the programs
+    pub instructions: u64,
+    /// Programs that halted within the step limit
+    pub halted: usize,
+    pub machines: Vec<Neander>,
+}
+
+impl Workload {
+    fn bus(&self) -> Bus {
+        let mut bus = Bus::new();
+        if self.devices {
+            bus.attach(KEYBOARD_ADDR, Keyboard(std::io::repeat(b'a')));
+            bus.attach(OUTPUT_ADDR, CharOutput(std::io::sink()));
+        }
+        bus
+    }
+
+    /// Runs every program with `strategy`, or returns None if
+    /// the strategy can't run this workload.
+    pub fn run(&self, strategy: Strategy) -> Option<Outcome> {
+        if self.devices && strategy == Strategy::Predecoded {
+            return None;
+        }
+        let mut outcome = Outcome {
+            instructions: 0,
+            halted: 0,
+            machines: Vec::with_capacity(self.programs.len()),
+        };
+        for program in &self.programs {
+            let mut cpu = program.clone();
+            let mut bus = self.bus();
+            let mut cache = Predecoded::new();
+            for _ in 0..MAX_STEPS {
+                let res = match strategy {
+                    Strategy::Step if self.devices => cpu.step_bus(&mut bus),
+                    Strategy::Step => cpu.step(),
+                    Strategy::Predecoded => cpu.step_predecoded(&mut cache),
+                    Strategy::MicroSteps => {
+                        let mut res = ExecResult::Normal;
+                        loop {
+                            let step = if self.devices {
+                                cpu.step_uop_bus(&mut bus).1
+                            } else {
+                                cpu.step_uop().1
+                            };
+                            if step != ExecResult::Normal {
+                                res = step;
+                            }
+                            if !cpu.mid_instruction() {
+                                break res;
+                            }
+                        }
+                    }
+                };
+                outcome.instructions += 1;
+                if res == ExecResult::Halted {
+                    outcome.halted += 1;
+                }
+                if matches!(res, ExecResult::Halted | ExecResult::Exception(_)) {
+                    break;
+                }
+            }
+            outcome.machines.push(cpu);
+        }
+        Some(outcome)
+    }
+
+    /// The best time of `runs` runs with `strategy`, and the
+    /// instructions each run executes.
+    pub fn measure(&self, strategy: Strategy, runs: u32) -> Option<(Duration, u64)> {
+        let mut best = Duration::MAX;
+        let mut instructions = 0;
+        for _ in 0..runs.max(1) {
+            let start = Instant::now();
+            instructions = self.run(strategy)?.instructions;
+            best = best.min(start.elapsed());
+        }
+        Some((best, instructions))
+    }
+}
+
+/// Counts up forever, complementing the accumulator
+/// while the count is negative. Never halts.
+fn endless() -> Neander {
+    let mut cpu = Neander::new();
+    #[rustfmt::skip]
+    cpu.set_ram_slice(0, &[
+        LDA, 0x80, ADD, 0x81, STA, 0x80,
+        JN, 0x0A, JMP, 0x00, NOT, JMP, 0x00,
+    ]);
+    cpu.set_ram_slice(0x80, &[0, 1]);
+    cpu
+}
+
+/// The time `Neander::step` takes to run `steps` instructions
+/// of an endless program.
+pub fn throughput(steps: u64) -> Duration {
+    let mut cpu = endless();
+    let start = Instant::now();
+    for _ in 0..steps {
+        black_box(cpu.step());
+    }
+    start.elapsed()
+}
+
+/// Adds -1 to a counter until it wraps around
+/// back to 0, about 1000 instructions.
+fn tight_loop() -> Neander {
+    let mut cpu = Neander::new();
+    cpu.set_ram_slice(0, &[LDA, 128, ADD, 129, STA, 128, JZ, 10, JMP, 2, HLT]);
+    cpu.set_ram_slice(128, &[0, 0xFF]);
+    cpu
+}
+
+/// Sums the bytes from 0x90 on by moving the operand of
+/// its ADD, which the predecoder has to decode again.
+fn self_modifying() -> Neander {
+    let mut cpu = Neander::new();
+    #[rustfmt::skip]
+    cpu.set_ram_slice(0, &[
+        LDA, 0x80, ADD, 0x90, STA, 0x80, // sum += [operand]
+        LDA, 0x03, ADD, 0x81, STA, 0x03, // operand += 1
+        LDA, 0x82, ADD, 0x81, STA, 0x82, // count += 1
+        JZ, 0x16, JMP, 0x00, HLT,
+    ]);
+    // -112 counts up to 0, the bytes 0x90 to 0xFF
+    cpu.set_ram_slice(0x80, &[0, 1, 0x90]);
+    for addr in 0x90..=0xFF {
+        cpu.set_ram(addr, addr);
+    }
+    cpu
+}
+
+/// Echoes 256 keys to the character output.
+fn echo() -> Neander {
+    let mut cpu = Neander::new();
+    #[rustfmt::skip]
+    cpu.set_ram_slice(0, &[
+        LDA, KEYBOARD_ADDR, STA, OUTPUT_ADDR,
+        LDA, 0x80, ADD, 0x81, STA, 0x80, JZ, 0x0E, JMP, 0x00, HLT,
+    ]);
+    cpu.set_ram_slice(0x80, &[0, 0xFF]);
+    cpu
+}
+
+/// Multiplies [0x80] by [0x81] into 0x82, by repeated addition.
+fn multiply(a: u8, b: u8) -> Neander {
+    let mut cpu = Neander::new();
+    #[rustfmt::skip]
+    cpu.set_ram_slice(0, &[
+        LDA, 0x84, STA, 0x82,
+        LDA, 0x81, JZ, 0x14, ADD, 0x85, STA, 0x81,
+        LDA, 0x82, ADD, 0x80, STA, 0x82, JMP, 0x04, HLT,
+    ]);
+    cpu.set_ram_slice(0x80, &[a, b, 0, 0, 0, 0xFF]);
+    cpu
+}
+
+/// The workloads, in the order they are reported.
+pub fn workloads() -> Vec<Workload> {
+    vec![
+        Workload {
+            name: "tight-loop",
+            description: "a counting loop",
+            programs: vec![tight_loop()],
+            devices: false,
+        },
+        Workload {
+            name: "self-modifying",
+            description: "a sum that rewrites its own operand",
+            programs: vec![self_modifying()],
+            devices: false,
+        },
+        Workload {
+            name: "devices",
+            description: "echoing keys to the output",
+            programs: vec![echo()],
+            devices: true,
+        },
+        Workload {
+            name: "batch",
+            description: "a multiplication for 256 inputs, like grading a class",
+            programs: (0..=255).map(|i| multiply(i >> 4, i & 15)).collect(),
+            devices: false,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workloads() {
+        for workload in workloads() {
+            let step = workload.run(Strategy::Step).unwrap();
+            assert_eq!(step.halted, step.machines.len(), "{}", workload.name);
+            for strategy in Strategy::ALL {
+                let Some(outcome) = workload.run(strategy) else {
+                    assert!(workload.devices);
+                    continue;
+                };
+                assert_eq!(outcome.instructions, step.instructions, "{}", workload.name);
+                for (a, b) in outcome.machines.iter().zip(&step.machines) {
+                    assert_eq!(a.memory(), b.memory(), "{}", workload.name);
+                }
+            }
+        }
+        let batch = &workloads()[3].run(Strategy::Step).unwrap().machines;
+        assert_eq!(batch[0x7B].ram(0x82), 7 * 11);
+        let sum = workloads()[1].run(Strategy::Predecoded).unwrap().machines[0].ram(0x80);
+        assert_eq!(sum, (0x90..=0xFFu32).sum::<u32>() as u8);
+        let mut cpu = endless();
+        for _ in 0..10_000 {
+            let res = cpu.step();
+            assert!(!matches!(
+                res,
+                ExecResult::Halted | ExecResult::Exception(_)
+            ));
+        }
+    }
+}
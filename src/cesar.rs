@@ -0,0 +1,483 @@
+//! The Cesar machine, the PDP-11-like sibling of the Neander taught
+//! in the same courses: eight 16-bit registers, R6 being the stack
+//! pointer and R7 the program counter, the NZVC condition codes, and
+//! 64K of memory holding big-endian words.
+//!
+//! Instructions start with their opcode in the high 4 bits of the
+//! first byte. Two-operand instructions take a word with the modes
+//! and registers of the source and the destination, `oooo sss SSS
+//! ddd DDD`. Words in the I/O area, from `IO_START` on, are accessed
+//! a byte at a time; the last 36 bytes are the display.
+use std::fmt;
+
+use crate::cpu::{ExecResult, NeanderException};
+
+/// The start of the byte-accessed I/O area.
+pub const IO_START: u16 = 0xFFDA;
+/// The first of the 36 characters of the display.
+pub const DISPLAY_START: u16 = 0xFFDC;
+/// Characters on the display.
+pub const DISPLAY_LEN: usize = 36;
+
+/// Opcodes, the high 4 bits of the first byte.
+pub mod op {
+    pub const NOP: u8 = 0x0;
+    pub const CCC: u8 = 0x1;
+    pub const SCC: u8 = 0x2;
+    pub const BRANCH: u8 = 0x3;
+    pub const JMP: u8 = 0x4;
+    pub const SOB: u8 = 0x5;
+    pub const JSR: u8 = 0x6;
+    pub const RTS: u8 = 0x7;
+    pub const ONE_OPERAND: u8 = 0x8;
+    pub const MOV: u8 = 0x9;
+    pub const ADD: u8 = 0xA;
+    pub const SUB: u8 = 0xB;
+    pub const CMP: u8 = 0xC;
+    pub const AND: u8 = 0xD;
+    pub const OR: u8 = 0xE;
+    pub const HLT: u8 = 0xF;
+}
+
+/// Condition code bits, as in CCC and SCC.
+const N: u8 = 8;
+const Z: u8 = 4;
+const V: u8 = 2;
+const C: u8 = 1;
+
+const SP: usize = 6;
+const PC: usize = 7;
+
+/// Where an operand is.
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Reg(usize),
+    Mem(u16),
+}
+
+/// The Cesar CPU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cesar {
+    regs: [u16; 8],
+    /// NZVC, in the low 4 bits
+    flags: u8,
+    mem: Box<[u8; 1 << 16]>,
+}
+
+impl Cesar {
+    pub fn new() -> Self {
+        Self {
+            regs: [0; 8],
+            flags: 0,
+            mem: vec![0; 1 << 16].into_boxed_slice().try_into().unwrap(),
+        }
+    }
+    pub fn reg(&self, r: usize) -> u16 {
+        self.regs[r]
+    }
+    pub fn set_reg(&mut self, r: usize, value: u16) {
+        self.regs[r] = value;
+    }
+    pub fn pc(&self) -> u16 {
+        self.regs[PC]
+    }
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+    pub fn negative(&self) -> bool {
+        self.flags & N != 0
+    }
+    pub fn zero(&self) -> bool {
+        self.flags & Z != 0
+    }
+    pub fn overflow(&self) -> bool {
+        self.flags & V != 0
+    }
+    pub fn carry(&self) -> bool {
+        self.flags & C != 0
+    }
+    pub fn memory(&self) -> &[u8] {
+        self.mem.as_ref()
+    }
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.mem.as_mut()
+    }
+
+    /// Reads the word at `addr`, or the byte if it's in the I/O area.
+    pub fn read(&self, addr: u16) -> u16 {
+        if addr >= IO_START {
+            return self.mem[addr as usize] as u16;
+        }
+        u16::from_be_bytes([self.mem[addr as usize], self.mem[addr as usize + 1]])
+    }
+    /// Writes the word at `addr`, or its low byte if it's in the I/O area.
+    pub fn write(&mut self, addr: u16, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        if addr >= IO_START {
+            self.mem[addr as usize] = low;
+        } else {
+            self.mem[addr as usize] = high;
+            self.mem[addr as usize + 1] = low;
+        }
+    }
+    /// The characters on the display, with unprintable bytes as spaces.
+    pub fn display(&self) -> String {
+        let start = DISPLAY_START as usize;
+        self.mem[start..start + DISPLAY_LEN]
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7E => b as char,
+                _ => ' ',
+            })
+            .collect()
+    }
+
+    fn fetch_byte(&mut self) -> u8 {
+        let byte = self.mem[self.regs[PC] as usize];
+        self.regs[PC] = self.regs[PC].wrapping_add(1);
+        byte
+    }
+    fn fetch_word(&mut self) -> u16 {
+        let word = self.read(self.regs[PC]);
+        self.regs[PC] = self.regs[PC].wrapping_add(2);
+        word
+    }
+    /// Finds the operand of addressing `mode` on register `r`,
+    /// updating the register as the mode does.
+    fn operand(&mut self, mode: u8, r: usize) -> Operand {
+        let addr = match mode {
+            0 => return Operand::Reg(r),
+            // (R)+
+            1 => {
+                let addr = self.regs[r];
+                self.regs[r] = addr.wrapping_add(2);
+                addr
+            }
+            // -(R)
+            2 => {
+                self.regs[r] = self.regs[r].wrapping_sub(2);
+                self.regs[r]
+            }
+            // d(R)
+            3 => {
+                let d = self.fetch_word();
+                self.regs[r].wrapping_add(d)
+            }
+            // (R)
+            4 => self.regs[r],
+            // ((R)+)
+            5 => {
+                let addr = self.regs[r];
+                self.regs[r] = addr.wrapping_add(2);
+                self.read(addr)
+            }
+            // (-(R))
+            6 => {
+                self.regs[r] = self.regs[r].wrapping_sub(2);
+                self.read(self.regs[r])
+            }
+            // (d(R))
+            _ => {
+                let d = self.fetch_word();
+                self.read(self.regs[r].wrapping_add(d))
+            }
+        };
+        Operand::Mem(addr)
+    }
+    fn get(&self, operand: Operand) -> u16 {
+        match operand {
+            Operand::Reg(r) => self.regs[r],
+            Operand::Mem(addr) => self.read(addr),
+        }
+    }
+    fn set(&mut self, operand: Operand, value: u16) {
+        match operand {
+            Operand::Reg(r) => self.regs[r] = value,
+            Operand::Mem(addr) => self.write(addr, value),
+        }
+    }
+    /// Sets N and Z from `value`, and V and C as given,
+    /// or leaves them if None.
+    fn set_flags(&mut self, value: u16, v: Option<bool>, c: Option<bool>) {
+        let mut flags = self.flags & (V | C);
+        if value & 0x8000 != 0 {
+            flags |= N;
+        }
+        if value == 0 {
+            flags |= Z;
+        }
+        for (bit, set) in [(V, v), (C, c)] {
+            match set {
+                Some(true) => flags |= bit,
+                Some(false) => flags &= !bit,
+                None => (),
+            }
+        }
+        self.flags = flags;
+    }
+    fn push(&mut self, value: u16) {
+        self.regs[SP] = self.regs[SP].wrapping_sub(2);
+        self.write(self.regs[SP], value);
+    }
+    fn pop(&mut self) -> u16 {
+        let value = self.read(self.regs[SP]);
+        self.regs[SP] = self.regs[SP].wrapping_add(2);
+        value
+    }
+    fn condition(&self, branch: u8) -> bool {
+        let (n, z, v, c) = (self.negative(), self.zero(), self.overflow(), self.carry());
+        match branch {
+            0 => true,
+            1 => !z,
+            2 => z,
+            3 => !n,
+            4 => n,
+            5 => !v,
+            6 => v,
+            7 => !c,
+            8 => c,
+            9 => n == v,
+            10 => n != v,
+            11 => !z && n == v,
+            12 => z || n != v,
+            13 => !c && !z,
+            14 => c || z,
+            _ => false,
+        }
+    }
+
+    /// Executes the next instruction. Undefined instructions, and
+    /// jumps to a register, do nothing.
+    pub fn step(&mut self) -> ExecResult {
+        let first = self.fetch_byte();
+        let (opcode, low) = (first >> 4, first & 0xF);
+        let r = (low & 7) as usize;
+        match opcode {
+            op::NOP => (),
+            op::CCC => self.flags &= !low,
+            op::SCC => self.flags |= low,
+            op::BRANCH => {
+                let d = self.fetch_byte() as i8;
+                if self.condition(low) {
+                    self.regs[PC] = self.regs[PC].wrapping_add_signed(d as i16);
+                }
+            }
+            op::JMP | op::JSR => {
+                let second = self.fetch_byte();
+                let mode = (second >> 3) & 7;
+                if mode == 0 {
+                    return ExecResult::Normal;
+                }
+                let Operand::Mem(target) = self.operand(mode, (second & 7) as usize) else {
+                    unreachable!()
+                };
+                if opcode == op::JSR {
+                    self.push(self.regs[r]);
+                    self.regs[r] = self.regs[PC];
+                }
+                self.regs[PC] = target;
+            }
+            op::SOB => {
+                let d = self.fetch_byte();
+                self.regs[r] = self.regs[r].wrapping_sub(1);
+                if self.regs[r] != 0 {
+                    self.regs[PC] = self.regs[PC].wrapping_sub(d as u16);
+                }
+            }
+            op::RTS => {
+                self.regs[PC] = self.regs[r];
+                self.regs[r] = self.pop();
+            }
+            op::ONE_OPERAND => {
+                let second = self.fetch_byte();
+                let dst = self.operand((second >> 3) & 7, (second & 7) as usize);
+                self.one_operand(low, dst);
+            }
+            op::HLT => return ExecResult::Halted,
+            _ => {
+                let word = u16::from_be_bytes([first, self.fetch_byte()]);
+                let field = |shift: u16| ((word >> shift) & 7) as u8;
+                let src = self.operand(field(9), field(6) as usize);
+                let dst = self.operand(field(3), field(0) as usize);
+                self.two_operand(opcode, src, dst);
+            }
+        }
+        ExecResult::Normal
+    }
+    fn one_operand(&mut self, instr: u8, dst: Operand) {
+        let x = self.get(dst);
+        let carry = self.carry() as u16;
+        let sign = |v: u16| v & 0x8000 != 0;
+        let (value, v, c) = match instr {
+            // CLR
+            0 => (0, false, false),
+            // NOT
+            1 => (!x, false, true),
+            // INC
+            2 => (x.wrapping_add(1), x == 0x7FFF, x == 0xFFFF),
+            // DEC
+            3 => (x.wrapping_sub(1), x == 0x8000, x == 0),
+            // NEG
+            4 => (x.wrapping_neg(), x == 0x8000, x != 0),
+            // TST
+            5 => (x, false, false),
+            // ROR, ROL, ASR and ASL, with V = N xor C
+            6..=9 => {
+                let (value, c) = match instr {
+                    6 => (x >> 1 | carry << 15, x & 1 != 0),
+                    7 => (x << 1 | carry, sign(x)),
+                    8 => (x >> 1 | x & 0x8000, x & 1 != 0),
+                    _ => (x << 1, sign(x)),
+                };
+                (value, sign(value) != c, c)
+            }
+            // ADC
+            10 => (
+                x.wrapping_add(carry),
+                x == 0x7FFF && carry == 1,
+                x == 0xFFFF && carry == 1,
+            ),
+            // SBC
+            11 => (
+                x.wrapping_sub(carry),
+                x == 0x8000 && carry == 1,
+                x == 0 && carry == 1,
+            ),
+            _ => return,
+        };
+        if instr != 5 {
+            self.set(dst, value);
+        }
+        self.set_flags(value, Some(v), Some(c));
+    }
+    fn two_operand(&mut self, opcode: u8, src: Operand, dst: Operand) {
+        let (a, b) = (self.get(src), self.get(dst));
+        let sign = |v: u16| v & 0x8000 != 0;
+        match opcode {
+            op::MOV => {
+                self.set(dst, a);
+                self.set_flags(a, Some(false), None);
+            }
+            op::ADD => {
+                let (value, c) = b.overflowing_add(a);
+                let v = sign(a) == sign(b) && sign(value) != sign(b);
+                self.set(dst, value);
+                self.set_flags(value, Some(v), Some(c));
+            }
+            // SUB is dst - src, and CMP is src - dst
+            op::SUB | op::CMP => {
+                let (x, y) = if opcode == op::SUB { (b, a) } else { (a, b) };
+                let (value, c) = x.overflowing_sub(y);
+                let v = sign(x) != sign(y) && sign(value) != sign(x);
+                if opcode == op::SUB {
+                    self.set(dst, value);
+                }
+                self.set_flags(value, Some(v), Some(c));
+            }
+            op::AND | op::OR => {
+                let value = if opcode == op::AND { a & b } else { a | b };
+                self.set(dst, value);
+                self.set_flags(value, Some(false), None);
+            }
+            _ => unreachable!(),
+        }
+    }
+    /// Runs until HLT, or fails with `StepLimitExceeded`
+    /// after `max_steps` instructions.
+    pub fn run_with_limit(&mut self, max_steps: u64) -> Result<(), NeanderException> {
+        for _ in 0..max_steps {
+            if self.step() == ExecResult::Halted {
+                return Ok(());
+            }
+        }
+        Err(NeanderException::StepLimitExceeded(max_steps))
+    }
+}
+
+impl Default for Cesar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Cesar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (r, value) in self.regs.iter().enumerate() {
+            writeln!(f, "R{r}: {value:5} | 0x{value:04X}")?;
+        }
+        let flag = |set: bool, name| if set { name } else { '-' };
+        writeln!(
+            f,
+            "NZVC: {}{}{}{}",
+            flag(self.negative(), 'N'),
+            flag(self.zero(), 'Z'),
+            flag(self.overflow(), 'V'),
+            flag(self.carry(), 'C')
+        )?;
+        write!(f, "display: [{}]", self.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A two-operand instruction.
+    fn two(opcode: u8, src: (u8, u8), dst: (u8, u8)) -> [u8; 2] {
+        let word = (opcode as u16) << 12
+            | (src.0 as u16) << 9
+            | (src.1 as u16) << 6
+            | (dst.0 as u16) << 3
+            | dst.1 as u16;
+        word.to_be_bytes()
+    }
+
+    #[test]
+    fn test_cesar() {
+        let mut cpu = Cesar::new();
+        let mut program = Vec::new();
+        // MOV #0x1000, R1; CLR R0; MOV #4, R2
+        program.extend(two(op::MOV, (1, 7), (0, 1)));
+        program.extend([0x10, 0x00, 0x80, 0x00]);
+        program.extend(two(op::MOV, (1, 7), (0, 2)));
+        program.extend([0x00, 0x04]);
+        // loop: ADD (R1)+, R0; SOB R2, loop
+        program.extend(two(op::ADD, (1, 1), (0, 0)));
+        program.extend([0x52, 4]);
+        // MOV R0, display; JSR R5, sub; HLT
+        program.extend(two(op::MOV, (0, 0), (5, 7)));
+        program.extend(DISPLAY_START.to_be_bytes());
+        program.extend([0x65, 0o57, 0x00, 0x40, 0xF0]);
+        cpu.memory_mut()[..program.len()].copy_from_slice(&program);
+        // sub: NOT R0; RTS R5
+        cpu.memory_mut()[0x40..0x43].copy_from_slice(&[0x81, 0x00, 0x75]);
+        for (i, value) in [0x10, 0x11, 0, 0].into_iter().enumerate() {
+            cpu.write(0x1000 + 2 * i as u16, value);
+        }
+        cpu.set_reg(SP, 0x8000);
+
+        assert_eq!(cpu.run_with_limit(100), Ok(()));
+        assert_eq!(cpu.display().chars().next(), Some('!'));
+        assert_eq!(cpu.reg(0), !0x21);
+        assert_eq!((cpu.reg(2), cpu.reg(SP)), (0, 0x8000));
+        assert_eq!(cpu.pc() as usize, program.len());
+        assert!(cpu.negative() && cpu.carry() && !cpu.zero());
+
+        // CMP is src - dst, and signed overflow sets V
+        let mut cpu = Cesar::new();
+        cpu.set_reg(0, 0x7FFF);
+        cpu.set_reg(1, 0xFFFF);
+        let mut program = two(op::CMP, (0, 0), (0, 1)).to_vec();
+        program.extend([0x39, 2]);
+        program.extend(two(op::ADD, (0, 1), (0, 0)));
+        program.extend([0xF0, 0xF0]);
+        cpu.memory_mut()[..program.len()].copy_from_slice(&program);
+        // 0x7FFF - (-1) overflows, so BGE is taken over the ADD
+        cpu.step();
+        assert!(cpu.overflow() && cpu.negative() && cpu.carry());
+        cpu.step();
+        assert_eq!(cpu.pc(), 6);
+        assert_eq!(cpu.step(), ExecResult::Halted);
+        assert_eq!(cpu.reg(0), 0x7FFF);
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -6,6 +6,9 @@ use std::path::PathBuf;
 pub struct CliArgs {
     #[command(subcommand)]
     pub command: Commands,
+    /// When to color the output. NO_COLOR turns off the automatic colors
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
 }
 
 #[derive(Subcommand)]
@@ -14,13 +17,406 @@ pub enum Commands {
     /// displaying the CPU state afterwards.
     Run {
         /// Memory file to load
-        file: PathBuf,
+        #[arg(required_unless_present = "batch")]
+        file: Option<PathBuf>,
+        /// Runs every program in DIR instead, each in a fresh
+        /// machine, and prints a table of how they ended
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with_all = ["file", "coverage", "render", "io", "trace", "rtl_trace", "timeline", "traps", "explain", "data_out", "diff"]
+        )]
+        batch: Option<PathBuf>,
+        #[command(flatten)]
+        opts: RunOptions,
     },
     /// Loads the file and starts a interactive session.
     Load {
         /// Memory file to load
         file: PathBuf,
+        #[command(flatten)]
+        opts: LoadOptions,
+    },
+    /// Opens the graphical simulator.
+    #[cfg(feature = "gui")]
+    Gui {
+        /// Memory file to load
+        file: Option<PathBuf>,
+        /// Exercise whose data is locked and whose goal is checked
+        #[arg(long, value_name = "FILE")]
+        exercise: Option<PathBuf>,
+        /// Memory file of a second machine, run side by side
+        /// with this one and sharing a mailbox
+        #[arg(long, value_name = "FILE")]
+        partner: Option<PathBuf>,
+        /// Course pack to open, a .neanderpack file or an installed pack
+        #[arg(long, value_name = "PACK")]
+        pack: Option<PathBuf>,
+    },
+    /// Converts the memory file into a Logisim-evolution `v2.0 raw` image.
+    Export {
+        /// Memory file to load
+        file: PathBuf,
+        /// Where to write the image. Prints it if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Draws the registers and memory as an SVG image, after
+    /// running the program for some instructions or until it halts.
+    Render {
+        /// Memory file to load
+        file: PathBuf,
+        /// Instructions to run before drawing. Runs until
+        /// the program halts if not given
+        #[arg(long, value_name = "N")]
+        at_step: Option<u64>,
+        /// Where to write the SVG image. Prints it if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Checks a program for likely mistakes without running it:
+    /// jumps to data, stores into instructions, HLTs never reached.
+    /// Exits with an error if there are any.
+    Check {
+        /// Memory file or assembly source to check
+        file: PathBuf,
+        /// Format of the warnings
+        #[arg(long, value_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+    },
+    /// Prints everything known about one byte of a program: its value
+    /// in every base, how it decodes, whether the program reaches it,
+    /// its label and the source line it came from.
+    ExplainByte {
+        /// Memory file or assembly source to load
+        file: PathBuf,
+        /// Address of the byte, a number or a label
+        addr: String,
+    },
+    /// Formats a memory file, aligning its bytes in rows
+    /// and sorting its ORG blocks.
+    Fmt {
+        /// Memory file to format
+        file: PathBuf,
+        /// Bytes per row
+        #[arg(long, default_value_t = 8)]
+        width: usize,
+        /// Rewrites the file instead of printing it
+        #[arg(short, long, conflicts_with = "check")]
+        write: bool,
+        /// Exits with an error if the file isn't formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Runs the test cases of a TOML or JSON spec, checking
+    /// memory and registers after the program halts.
+    Test {
+        /// Spec file to run, or the program to test with --builtin
+        file: PathBuf,
+        /// Runs the built-in spec of a classic assignment: sum,
+        /// maximum, multiply or divide
+        #[arg(long, value_name = "NAME")]
+        builtin: Option<String>,
+        /// Runs the cases again whenever the spec or the program
+        /// changes, showing the cases fixed and broken since the last run
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Builds a course pack from a directory with a manifest.toml
+    /// listing its examples, exercises, specs and tutorials.
+    BuildPack {
+        /// Directory with the files of the pack
+        dir: PathBuf,
+        /// Where to write the pack, usually ending in .neanderpack
+        output: PathBuf,
+    },
+    /// Checks a course pack and extracts it into the packs directory,
+    /// replacing an older version of it.
+    InstallPack {
+        /// The .neanderpack file
+        file: PathBuf,
+        /// Directory to install into, instead of NEANDER_PACKS
+        /// or .neander/packs in the home directory
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Checks that this simulator is the version pinned by the
+    /// neander.toml of the current directory or its parents, and by
+    /// a course pack. Exits with an error on a mismatch, never
+    /// downloading another version.
+    SelfCheck {
+        /// Course pack to check too, a .neanderpack file or an installed pack
+        #[arg(long, value_name = "PACK")]
+        pack: Option<PathBuf>,
+    },
+    /// Summarizes the sessions recorded in a directory. Sessions
+    /// are recorded only if NEANDER_STATS_DIR is set. Given a program,
+    /// writes a report of it for lab submissions instead.
+    Report {
+        /// Directory with the recorded sessions, or a program
+        path: PathBuf,
+        /// Where to write the program report, as HTML or PDF
+        /// by its extension. Prints it as text if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Spec whose cases are run on the program for the report
+        #[arg(long, conflicts_with = "builtin")]
+        spec: Option<PathBuf>,
+        /// Runs the built-in spec of a classic assignment for the report
+        #[arg(long, value_name = "NAME")]
+        builtin: Option<String>,
     },
     /// Prints a table containing all instructions and its codes.
-    ISA,
+    Isa {
+        /// Format of the table
+        #[arg(long, value_enum, default_value_t = IsaFormat::Text)]
+        format: IsaFormat,
+    },
+    /// Prints random questions on encoding and decoding
+    /// instructions, followed by an answer key.
+    Quiz {
+        /// Number of questions
+        #[arg(long, default_value_t = 10)]
+        count: usize,
+        /// Seed of the questions, to print the same quiz again.
+        /// Random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Replays the trace of another Neander implementation, flagging
+    /// the registers and memory that differ after each instruction.
+    /// Without a trace, prints the trace of this one.
+    Conformance {
+        /// Memory file to load
+        file: PathBuf,
+        /// Reference trace, a line of `pc=.. ac=.. n=. z=. mem[..]=..`
+        /// per instruction
+        trace: Option<PathBuf>,
+        /// Instructions to trace at most, without a reference trace
+        #[arg(long, value_name = "N", default_value_t = 10_000)]
+        max_steps: u64,
+    },
+    /// Runs random valid programs, checking the simulator against
+    /// the semantics of every instruction and reporting violations.
+    Fuzz {
+        /// Number of programs to run
+        #[arg(long, default_value_t = 1000)]
+        programs: u64,
+        /// Seed of the first program. A violation names the seed
+        /// of its program, which runs alone with --programs 1.
+        /// Random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Instructions each program may execute
+        #[arg(long, value_name = "N", default_value_t = 1000)]
+        max_steps: u64,
+    },
+    /// Runs a program for the Cesar machine, displaying its
+    /// registers and display afterwards.
+    Cesar {
+        /// Memory file to load, with ORG addresses up to 65535
+        file: PathBuf,
+        /// Instructions to run before giving up
+        #[arg(long, value_name = "N", default_value_t = 1_000_000)]
+        max_steps: u64,
+    },
+    /// Runs two programs on machines taking turns, sharing a range of
+    /// memory as a mailbox, until both halt. Displays both machines.
+    Cosim {
+        /// Memory file of machine A, which runs first
+        a: PathBuf,
+        /// Memory file of machine B
+        b: PathBuf,
+        /// Addresses both machines share, as START..END
+        #[arg(long, value_name = "RANGE", default_value = "0xF0..0xFF")]
+        mailbox: String,
+        /// Instructions of both machines to run before giving up
+        #[arg(long, value_name = "N", default_value_t = 1_000_000)]
+        max_steps: u64,
+    },
+    /// Measures how many instructions per second the simulator runs,
+    /// on an endless synthetic program.
+    Bench {
+        /// Runs the built-in workloads instead, which compare
+        /// the ways of stepping the machine
+        #[arg(long)]
+        internal: bool,
+        /// Runs of each workload; the fastest one is reported
+        #[arg(long, default_value_t = 10, requires = "internal")]
+        runs: u32,
+        /// Millions of instructions to run the synthetic program for
+        #[arg(
+            long,
+            value_name = "MILLIONS",
+            default_value_t = 100,
+            conflicts_with = "internal"
+        )]
+        steps: u64,
+    },
+    /// Starts a language server for assembly sources and memfiles,
+    /// talking LSP over stdin and stdout.
+    Lsp,
+}
+
+#[derive(Args)]
+pub struct LoadOptions {
+    /// Exercise whose data is locked and whose goal is checked
+    #[arg(long, value_name = "FILE")]
+    pub exercise: Option<PathBuf>,
+    /// Where breakpoints are restored from and saved on quit.
+    /// Defaults to .neander_bps next to the memory file
+    #[arg(long, value_name = "FILE")]
+    pub breakpoints: Option<PathBuf>,
+    /// Directives to execute before the interactive session,
+    /// one per line
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+    /// Reads JSON commands and writes JSON events, one per line,
+    /// for editors and other frontends
+    #[arg(long)]
+    pub mi: bool,
+    /// Seed of the clock devices take their timing and
+    /// randomness from, to replay a run exactly
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+#[derive(Args)]
+pub struct RunOptions {
+    /// Aborts the run if the program doesn't halt
+    /// within N instructions, exiting with an error
+    #[arg(long, value_name = "N")]
+    pub max_steps: Option<u64>,
+    /// How to print the machine after the run
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+    pub output_format: OutputFormat,
+    /// Explains exceptions: the byte the CPU stopped at,
+    /// how it decoded and what likely went wrong
+    #[arg(long)]
+    pub explain: bool,
+    /// Prints a map of the addresses executed and those read
+    /// or written, and the branches that only went one way
+    #[arg(long, conflicts_with_all = ["io", "trace", "rtl_trace"])]
+    pub coverage: bool,
+    /// Prints how many times each address was executed,
+    /// hottest first, to find the loops the run spent its time in
+    #[arg(long, conflicts_with_all = ["trace", "rtl_trace"])]
+    pub profile: bool,
+    /// Warns when the run executes an instruction from an address
+    /// it stored to, usually a store over the program by mistake
+    #[arg(long, conflicts_with_all = ["trace", "rtl_trace"])]
+    pub warn_smc: bool,
+    /// Draws the machine after the run instead of printing it
+    /// in the output format
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub render: Option<Render>,
+    /// Prints only the memory cells the run changed, with their
+    /// values before and after, instead of all of memory
+    #[arg(long, conflicts_with_all = ["output_format", "render"])]
+    pub diff: bool,
+    /// Maps a character output to 0xFE, printing to stdout,
+    /// and a keyboard to 0xFF, reading from stdin
+    #[arg(long, conflicts_with_all = ["trace", "rtl_trace"])]
+    pub io: bool,
+    /// Seed of the clock devices take their timing and
+    /// randomness from, to replay a run exactly
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Records every executed instruction, printing
+    /// the trace or writing it to FILE
+    #[arg(long, value_name = "FILE", conflicts_with = "rtl_trace")]
+    pub trace: Option<Option<PathBuf>>,
+    /// Runs one micro-operation at a time, writing the
+    /// register-transfer trace of every micro-cycle to FILE
+    #[arg(long, value_name = "FILE")]
+    pub rtl_trace: Option<PathBuf>,
+    /// Format of the register-transfer trace
+    #[arg(long, value_enum, default_value_t = RtlFormat::Csv, requires = "rtl_trace")]
+    pub rtl_format: RtlFormat,
+    /// Records the memory accesses of every instruction, writing
+    /// them to FILE as CSV if it ends in .csv, or plotted as SVG
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["coverage", "trace", "rtl_trace"])]
+    pub timeline: Option<PathBuf>,
+    /// Stops the run with an error if it raises one of the
+    /// teacher traps in FILE, like writing to a range
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["coverage", "trace", "rtl_trace"])]
+    pub traps: Option<PathBuf>,
+    /// Loads the data in FILE into memory before the run, as raw
+    /// bytes or, if it ends in .csv, as comma-separated values
+    #[arg(long, value_name = "FILE")]
+    pub data_in: Option<PathBuf>,
+    /// Address the data of --data-in is loaded at
+    #[arg(long, value_name = "ADDR", default_value = "0x80", value_parser = parse_addr, requires = "data_in")]
+    pub data_at: u8,
+    /// Writes the memory from START to END after the run to FILE,
+    /// in the same formats as --data-in
+    #[arg(long, num_args = 2, value_names = ["START..END", "FILE"])]
+    pub data_out: Option<Vec<String>>,
+    /// Makes the output depend only on the program and the options,
+    /// for grading: numbers aren't formatted for the locale, no
+    /// statistics are saved, and options reading the keyboard are
+    /// refused. Devices are seeded by --seed either way
+    #[arg(long, conflicts_with = "io")]
+    pub deterministic: bool,
+}
+
+fn parse_addr(addr: &str) -> Result<u8, String> {
+    crate::spec::parse_addr(addr)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// The memory dump and the registers
+    Pretty,
+    /// A hexdump of the memory, 16 bytes per line
+    Hex,
+    /// The 256 bytes of memory, unformatted
+    Raw,
+    /// The registers and memory as a JSON object
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// If printing to a terminal and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Render {
+    /// Registers and a memory grid in box characters,
+    /// colored as set by --color
+    Ascii,
+    /// The image of the render command
+    Svg,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CheckFormat {
+    /// One warning per line, with the source line if assembled
+    Text,
+    /// An array of objects, one per warning
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IsaFormat {
+    /// The instructions and their codes, aligned
+    Text,
+    /// An array of objects, one per instruction
+    Json,
+    /// One row per instruction, with a header
+    Csv,
+    /// A Markdown table, for course materials
+    Markdown,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RtlFormat {
+    /// One row per micro-cycle
+    Csv,
+    /// Value Change Dump, for waveform viewers
+    Vcd,
 }
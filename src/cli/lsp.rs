@@ -0,0 +1,246 @@
+//! A language server for assembly sources and memfiles, speaking
+//! LSP over stdin and stdout. It reports the errors of the assembler,
+//! describes instructions and labels on hover and jumps to the
+//! definitions of labels.
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+use serde_json::{json, Value};
+
+use crate::asm::{self, AsmError};
+use crate::cpu::{fmt_dual, instr};
+use crate::symbols;
+
+/// Reads a message, returning None at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut len = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value.trim().parse().ok();
+            }
+        }
+    }
+    let mut body = vec![0; len?];
+    input.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn send(message: Value) {
+    let body = message.to_string();
+    let mut out = std::io::stdout().lock();
+    let _ = write!(out, "Content-Length: {}\r\n\r\n{body}", body.len());
+    let _ = out.flush();
+}
+
+/// The token of `text` at a zero based position.
+fn token_at(text: &str, line: usize, character: usize) -> Option<(usize, &str)> {
+    let line = text.lines().nth(line)?;
+    asm::tokens(line).find(|&(col, token)| (col - 1..=col - 1 + token.len()).contains(&character))
+}
+
+/// The range of the token at `col`, empty if there is none.
+fn range(text: &str, line: usize, col: usize) -> Value {
+    let len = token_at(text, line, col - 1).map_or(0, |(_, token)| token.len());
+    json!({
+        "start": { "line": line, "character": col - 1 },
+        "end": { "line": line, "character": col - 1 + len },
+    })
+}
+
+fn diagnostics(text: &str) -> Vec<Value> {
+    let mut mem = [0; 256];
+    let Err(errors) = asm::assemble(&mut mem, text) else {
+        return Vec::new();
+    };
+    errors
+        .iter()
+        .map(|AsmError { line, col, message }| {
+            json!({
+                "range": range(text, line - 1, *col),
+                "severity": 1,
+                "source": "neander",
+                "message": message,
+            })
+        })
+        .collect()
+}
+
+/// Returns the position of the definition of `label`, zero based.
+fn definition(text: &str, label: &str) -> Option<(usize, usize)> {
+    text.lines().enumerate().find_map(|(i, line)| {
+        asm::tokens(line)
+            .find(|(_, token)| token.strip_suffix(':') == Some(label))
+            .map(|(col, _)| (i, col - 1))
+    })
+}
+
+/// The markdown shown when hovering a position.
+fn hover(text: &str, line: usize, character: usize) -> Option<String> {
+    let (_, token) = token_at(text, line, character)?;
+    let label = token.strip_suffix(':').unwrap_or(token);
+    if let Some(&(opcode, name)) = instr::INSTRUCTIONS
+        .iter()
+        .find(|(_, name)| token.eq_ignore_ascii_case(name))
+    {
+        let (operand, size) = match instr::has_operand(opcode) {
+            true => (" addr", 2),
+            false => ("", 1),
+        };
+        let effect = instr::describe(opcode)?;
+        return Some(format!(
+            "`{name}{operand}`: {effect}\n\nopcode `0x{opcode:02X}`, {size} bytes"
+        ));
+    }
+    if !symbols::is_label(label) {
+        return None;
+    }
+    let mut mem = [0; 256];
+    let addr = asm::assemble(&mut mem, text).ok()?.get(label)?;
+    Some(format!(
+        "`{label}` at `{}`, holding `{}`",
+        fmt_dual(addr),
+        fmt_dual(mem[addr as usize])
+    ))
+}
+
+/// Answers a request, returning its result.
+fn respond(docs: &HashMap<String, String>, method: &str, params: &Value) -> Result<Value, String> {
+    if method == "initialize" {
+        return Ok(json!({
+            "capabilities": {
+                // the whole document is sent on every change
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "definitionProvider": true,
+            },
+            "serverInfo": { "name": "neander", "version": env!("CARGO_PKG_VERSION") },
+        }));
+    }
+    if method == "shutdown" {
+        return Ok(Value::Null);
+    }
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+    let text = docs.get(uri).map_or("", String::as_str);
+    let pos = &params["position"];
+    let line = pos["line"].as_u64().unwrap_or_default() as usize;
+    let character = pos["character"].as_u64().unwrap_or_default() as usize;
+    match method {
+        "textDocument/hover" => Ok(match hover(text, line, character) {
+            Some(value) => json!({ "contents": { "kind": "markdown", "value": value } }),
+            None => Value::Null,
+        }),
+        "textDocument/definition" => {
+            let target = token_at(text, line, character)
+                .map(|(_, token)| token.strip_suffix(':').unwrap_or(token))
+                .and_then(|label| Some((label, definition(text, label)?)));
+            Ok(match target {
+                Some((label, (line, character))) => json!({
+                    "uri": uri,
+                    "range": {
+                        "start": { "line": line, "character": character },
+                        "end": { "line": line, "character": character + label.len() },
+                    },
+                }),
+                None => Value::Null,
+            })
+        }
+        _ => Err(format!("unsupported method: {method}")),
+    }
+}
+
+/// Serves a client on stdin and stdout until it exits.
+pub fn run() -> ExitCode {
+    let mut docs = HashMap::new();
+    let mut input = std::io::stdin().lock();
+    let mut shutdown = false;
+    while let Some(msg) = read_message(&mut input) {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = match method {
+            "exit" => break,
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            "textDocument/didChange" => params["contentChanges"][0]["text"].as_str(),
+            "textDocument/didClose" => {
+                docs.remove(uri);
+                Some("")
+            }
+            _ => None,
+        };
+        if let Some(text) = text {
+            if method != "textDocument/didClose" {
+                docs.insert(uri.to_string(), text.to_string());
+            }
+            send(json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": diagnostics(text) },
+            }));
+            continue;
+        }
+        // notifications have no id and need no answer
+        let Some(id) = msg.get("id") else {
+            continue;
+        };
+        shutdown |= method == "shutdown";
+        send(match respond(&docs, method, params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            // method not found
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": e },
+            }),
+        });
+    }
+    if shutdown {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lsp() {
+        let text = "start: lda x\n  jmp start\nx: 5\n  add nowhere";
+        let diags = diagnostics(text);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0]["message"], "unknown label: nowhere");
+        assert_eq!(
+            diags[0]["range"],
+            json!({
+                "start": { "line": 3, "character": 6 },
+                "end": { "line": 3, "character": 13 },
+            })
+        );
+
+        assert_eq!(definition(text, "start"), Some((0, 0)));
+        assert_eq!(definition(text, "x"), Some((2, 0)));
+        assert_eq!(token_at(text, 1, 6), Some((7, "start")));
+        assert_eq!(token_at(text, 1, 1), None);
+
+        let hover_lda = hover(text, 0, 8).unwrap();
+        assert!(hover_lda.contains("AC <- MEM[addr]"), "{hover_lda}");
+        assert_eq!(hover(text, 3, 6), None);
+        let text = "lda x\nhlt\nx: 5";
+        assert_eq!(
+            hover(text, 0, 4).unwrap(),
+            "`x` at `03 (3)`, holding `05 (5)`"
+        );
+    }
+}
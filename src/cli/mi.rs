@@ -0,0 +1,149 @@
+//! The machine interface of the REPL, for editors and IDEs.
+//! Commands are JSON objects, one per line, with a directive and
+//! an optional id: `{"id": 1, "directive": "step"}`. Every command
+//! is answered by JSON events carrying its id, the last of which
+//! is `done`:
+//!
+//! - `output`: the text the directive printed
+//! - `device`: the text written to the character output
+//! - `write`: a memory cell that changed, with `addr` and `value`
+//! - `stopped`: why the CPU stopped, with the registers and the
+//!   source line of the PC if the program was assembled
+//! - `registers`: the registers, if they changed without running
+//! - `error`: a command that couldn't be read
+//! - `done`: with the `flow` of the session
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+
+use crate::cpu::Neander;
+use crate::debugger::{self, Debugger, Directive, Flow, Stop};
+
+/// The output of a device, collected to be sent as events.
+#[derive(Debug, Clone, Default)]
+pub struct Captured(Rc<RefCell<Vec<u8>>>);
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn emit(event: Value) {
+    println!("{event}");
+}
+
+/// Sends `text` as an output event, unless it's empty.
+pub fn output(id: &Value, text: &str) {
+    if !text.is_empty() {
+        emit(json!({ "event": "output", "id": id, "text": text }));
+    }
+}
+
+fn registers(cpu: &Neander) -> Value {
+    json!({
+        "pc": cpu.pc(),
+        "acc": cpu.acc(),
+        "z": cpu.status_zero(),
+        "n": cpu.status_negative(),
+    })
+}
+
+/// Executes `dir`, sending the events it caused.
+pub fn execute(
+    dbg: &mut Debugger,
+    cpu: &mut Neander,
+    device: &Captured,
+    id: &Value,
+    dir: Directive,
+) -> Flow {
+    let mut old = [0; 256];
+    old.copy_from_slice(cpu.memory());
+    let regs = cpu.registers();
+    let mut out = String::new();
+    let flow = dbg
+        .execute(cpu, dir, &mut out)
+        .expect("writing to a String");
+    output(id, &out);
+    let text = String::from_utf8_lossy(&device.0.take()).into_owned();
+    if !text.is_empty() {
+        emit(json!({ "event": "device", "id": id, "text": text }));
+    }
+    for (addr, (&a, &b)) in old.iter().zip(cpu.memory()).enumerate() {
+        if a != b {
+            emit(json!({ "event": "write", "id": id, "addr": addr, "value": b }));
+        }
+    }
+    match dbg.take_stop() {
+        Some(stop) => {
+            let (reason, exception) = match stop {
+                Stop::Step => ("step", None),
+                Stop::Halted => ("halted", None),
+                Stop::Breakpoint => ("breakpoint", None),
+                Stop::Watchpoint => ("watchpoint", None),
+                Stop::Trap => ("trap", None),
+                Stop::Exception(e) => ("exception", Some(e.to_string())),
+            };
+            emit(json!({
+                "event": "stopped",
+                "id": id,
+                "reason": reason,
+                "exception": exception,
+                "line": dbg.source_map.line(cpu.pc()),
+                "registers": registers(cpu),
+            }));
+        }
+        None if cpu.registers() != regs => {
+            emit(json!({ "event": "registers", "id": id, "registers": registers(cpu) }));
+        }
+        None => (),
+    }
+    let name = match flow {
+        Flow::Continue => "continue",
+        Flow::Exception => "exception",
+        Flow::Quit => "quit",
+    };
+    emit(json!({ "event": "done", "id": id, "flow": name }));
+    flow
+}
+
+/// Reads commands from stdin until the session ends.
+pub fn run(dbg: &mut Debugger, cpu: &mut Neander, device: &Captured) {
+    for line in std::io::stdin().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cmd: Value = match serde_json::from_str(&line) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                emit(json!({ "event": "error", "id": null, "message": e.to_string() }));
+                continue;
+            }
+        };
+        let id = cmd.get("id").cloned().unwrap_or(Value::Null);
+        let dir = cmd
+            .get("directive")
+            .and_then(Value::as_str)
+            .ok_or("missing directive")
+            .and_then(debugger::parse_directive);
+        match dir {
+            Ok(dir) => {
+                crate::crash::set_file(dbg.file.as_deref());
+                crate::crash::set_machine(cpu);
+                crate::crash::record_directive(cmd["directive"].as_str().unwrap_or_default());
+                if execute(dbg, cpu, device, &id, dir) != Flow::Continue {
+                    break;
+                }
+            }
+            Err(e) => emit(json!({ "event": "error", "id": id, "message": e })),
+        }
+    }
+}
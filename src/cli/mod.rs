@@ -1,22 +1,699 @@
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 use clap::Parser;
 
 mod args;
+mod lsp;
+mod mi;
 mod repl;
 mod run;
+mod test;
 use args::*;
 
-use crate::cpu::Neander;
+use crate::ansi;
+use crate::bench::{self, Strategy};
+use crate::cesar::Cesar;
+use crate::conformance;
+use crate::cosim::{self, CoSim};
+use crate::cpu::{ExecResult, Neander};
+use crate::crash;
+use crate::fuzz;
+use crate::inspect;
+use crate::lint;
+use crate::logisim;
+use crate::memfile;
+use crate::numfmt::{self, NumberFormat};
+use crate::pack::{self, Pack};
+use crate::pin;
+use crate::printout::Printout;
+use crate::quiz::Quiz;
+use crate::render;
+use crate::spec::{self, Spec};
+use crate::stats::Report;
 
 pub fn cli() -> std::process::ExitCode {
     let args = args::CliArgs::parse();
+    numfmt::set(NumberFormat::from_env());
+    ansi::set_enabled(match args.color {
+        ColorChoice::Auto => {
+            std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+        }
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    });
+    // fuzzing panics on purpose to report them
+    if !matches!(args.command, Commands::Fuzz { .. }) {
+        crash::install();
+    }
     match args.command {
-        Commands::Run { file } => run::run_file(&file),
-        Commands::Load { file } => repl::run_repl(&file),
-        Commands::ISA => {
-            crate::cpu::instr::print_instr_table();
+        Commands::Run {
+            batch: Some(dir),
+            opts,
+            ..
+        } => run::run_batch(&dir, &opts),
+        Commands::Run {
+            file: Some(file),
+            opts,
+            ..
+        } => run::run_file(&file, &opts),
+        Commands::Run { .. } => unreachable!("clap requires a file or a directory"),
+        Commands::Load { file, opts } => repl::run_repl(&file, &opts),
+        #[cfg(feature = "gui")]
+        Commands::Gui {
+            file,
+            exercise,
+            partner,
+            pack,
+        } => {
+            let mut cpu = Neander::new();
+            if let Some(file) = &file {
+                if let Err(e) = cpu.load_file(file) {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            let exercise = match exercise
+                .as_deref()
+                .map(crate::exercise::Exercise::load)
+                .transpose()
+            {
+                Ok(exercise) => exercise,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let pack = match pack.as_deref().map(Pack::open).transpose() {
+                Ok(pack) => pack,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            if let Err(e) = crate::ui::run_ui(cpu, file, exercise, partner, pack) {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
+        }
+        Commands::Export { file, output } => {
+            let mut cpu = Neander::new();
+            if let Err(e) = cpu.load_file(&file) {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+            let image = logisim::write_image(cpu.memory());
+            match output {
+                Some(path) => {
+                    if let Err(e) = fs::write(path, image) {
+                        eprintln!("error: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+                None => print!("{image}"),
+            }
+            ExitCode::SUCCESS
+        }
+        Commands::Render {
+            file,
+            at_step,
+            output,
+        } => render(&file, at_step, output.as_deref()),
+        Commands::Check { file, format } => check(&file, format),
+        Commands::ExplainByte { file, addr } => explain_byte(&file, &addr),
+        Commands::Fmt {
+            file,
+            width,
+            write,
+            check,
+        } => {
+            let source = match fs::read_to_string(&file) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let formatted = match memfile::format_memfile(&source, width) {
+                Ok(formatted) => formatted,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            if check {
+                if formatted != source {
+                    eprintln!("{} is not formatted", file.display());
+                    return ExitCode::FAILURE;
+                }
+            } else if write {
+                if let Err(e) = fs::write(&file, formatted) {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            } else {
+                print!("{formatted}");
+            }
+            ExitCode::SUCCESS
+        }
+        Commands::BuildPack { dir, output } => {
+            match Pack::open(&dir).and_then(|pack| pack.write(&output)) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Commands::InstallPack { file, dir } => install_pack(&file, dir),
+        Commands::SelfCheck { pack } => self_check(pack.as_deref()),
+        Commands::Test {
+            file,
+            builtin,
+            watch: true,
+        } => test::watch_spec(&file, builtin.as_deref()),
+        Commands::Test { file, builtin, .. } => test::run_spec(&file, builtin.as_deref()),
+        Commands::Report {
+            path,
+            output,
+            spec,
+            builtin,
+        } if path.is_file() => {
+            let spec = match (spec, builtin) {
+                (Some(spec), _) => Spec::load(&spec).map(Some),
+                (_, Some(name)) => Spec::builtin(&name, &path).map(Some),
+                _ => Ok(None),
+            };
+            match spec {
+                Ok(spec) => program_report(&path, output.as_deref(), spec.as_ref()),
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Commands::Report { path, .. } => match Report::from_dir(&path) {
+            Ok(report) => {
+                print!("{report}");
+                for path in &report.skipped {
+                    eprintln!("warning: skipped {}", path.display());
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        },
+        Commands::Isa { format } => {
+            match format {
+                IsaFormat::Text => crate::cpu::instr::print_instr_table(),
+                _ => print!("{}", isa_table(format)),
+            }
+            ExitCode::SUCCESS
+        }
+        Commands::Quiz { count, seed } => {
+            print!("{}", Quiz::new(count, seed.unwrap_or_else(time_seed)));
             ExitCode::SUCCESS
         }
+        Commands::Conformance {
+            file,
+            trace,
+            max_steps,
+        } => conformance(&file, trace.as_deref(), max_steps),
+        Commands::Fuzz {
+            programs,
+            seed,
+            max_steps,
+        } => {
+            let seed = seed.unwrap_or_else(time_seed);
+            println!("fuzzing from seed {seed}");
+            let report = fuzz::fuzz(seed, programs, max_steps);
+            print!("{report}");
+            if report.violations.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Commands::Cesar { file, max_steps } => cesar(&file, max_steps),
+        Commands::Cosim {
+            a,
+            b,
+            mailbox,
+            max_steps,
+        } => cosim(&a, &b, &mailbox, max_steps),
+        Commands::Bench {
+            internal: true,
+            runs,
+            ..
+        } => {
+            print!("{}", bench_table(runs));
+            ExitCode::SUCCESS
+        }
+        Commands::Bench { steps, .. } => {
+            let steps = steps.saturating_mul(1_000_000);
+            let time = bench::throughput(steps);
+            let rate = steps as f64 / time.as_secs_f64().max(1e-9);
+            println!(
+                "{} instructions in {time:.2?}, {:.1} million instructions/s",
+                numfmt::get().count(steps),
+                rate / 1e6
+            );
+            ExitCode::SUCCESS
+        }
+        Commands::Lsp => lsp::run(),
+    }
+}
+
+fn cesar(file: &Path, max_steps: u64) -> ExitCode {
+    let mut cpu = Cesar::new();
+    let loaded = fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| memfile::parse_memfile(cpu.memory_mut(), &s).map_err(|e| e.to_string()));
+    if let Err(e) = loaded {
+        eprintln!("error: {}: {e}", file.display());
+        return ExitCode::FAILURE;
+    }
+    let res = cpu.run_with_limit(max_steps);
+    println!("{cpu}");
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn cosim(a: &Path, b: &Path, mailbox: &str, max_steps: u64) -> ExitCode {
+    let mailbox = match spec::parse_range(mailbox) {
+        Ok(mailbox) => mailbox,
+        Err(e) => {
+            eprintln!("error: mailbox: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut machines = Vec::new();
+    for file in [a, b] {
+        let mut cpu = Neander::new();
+        if let Err(e) = cpu.load_file(file) {
+            eprintln!("error: {}: {e}", file.display());
+            return ExitCode::FAILURE;
+        }
+        machines.push(cpu);
+    }
+    let b_cpu = machines.pop().unwrap();
+    let a_cpu = machines.pop().unwrap();
+    let mut sim = CoSim::new(a_cpu, b_cpu, mailbox.clone());
+    let res = sim.run_with_limit(max_steps);
+    for (i, file) in [a, b].iter().enumerate() {
+        let cpu = &sim.machines[i];
+        println!(
+            "machine {} ({}), {} steps:",
+            cosim::name(i),
+            file.display(),
+            sim.steps()[i]
+        );
+        println!("{cpu}");
+    }
+    println!("mailbox:");
+    let mailbox = (*mailbox.start() as usize, *mailbox.end() as usize);
+    print!(
+        "{}",
+        sim.machines[0].mem_range_string(mailbox.0, mailbox.1, false)
+    );
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Times every workload of `neander::bench` with every strategy.
+fn bench_table(runs: u32) -> String {
+    let mut table = format!(
+        "{:<16}{:<13}{:>12}{:>14}\n",
+        "workload", "strategy", "time", "instr/s"
+    );
+    let workloads = bench::workloads();
+    for workload in &workloads {
+        for strategy in Strategy::ALL {
+            let Some((time, instructions)) = workload.measure(strategy, runs) else {
+                continue;
+            };
+            let rate = instructions as f64 / time.as_secs_f64().max(1e-9);
+            table.push_str(&format!(
+                "{:<16}{:<13}{:>12}{:>14.0}\n",
+                workload.name,
+                strategy.name(),
+                format!("{time:.2?}"),
+                rate
+            ));
+        }
+    }
+    table.push('\n');
+    for workload in &workloads {
+        table.push_str(&format!("{}: {}\n", workload.name, workload.description));
+    }
+    table
+}
+
+/// Whether to color the output, see `--color`.
+fn color() -> bool {
+    ansi::enabled()
+}
+
+/// Prints the lints of the program at `file`, see `crate::lint`,
+/// and the bytes it writes twice.
+fn check(file: &Path, format: CheckFormat) -> ExitCode {
+    let mut cpu = Neander::new();
+    let image = match cpu.load_file_image(file) {
+        Ok((image, _, _)) => image,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // (kind, address, line, message)
+    let mut warnings: Vec<_> = lint::check(cpu.memory())
+        .into_iter()
+        .map(|lint| {
+            let line = lint.addr().and_then(|a| image.origin(a as usize)?.line);
+            (lint.name(), lint.addr(), line, lint.to_string())
+        })
+        .collect();
+    for overlap in image.overlaps() {
+        let message = format!(
+            "{:02X}: overwrites the byte from {}",
+            overlap.addr, overlap.first
+        );
+        let addr = Some(overlap.addr as u8);
+        warnings.push(("overlap", addr, overlap.second.line, message));
+    }
+    match format {
+        CheckFormat::Text => {
+            for (_, _, line, message) in &warnings {
+                match line {
+                    Some(n) => println!("{}:{n}: warning: {message}", file.display()),
+                    None => println!("{}: warning: {message}", file.display()),
+                }
+            }
+            if !warnings.is_empty() {
+                println!("{} warnings", warnings.len());
+            }
+        }
+        CheckFormat::Json => {
+            let warnings: Vec<_> = warnings
+                .iter()
+                .map(|(kind, addr, line, message)| {
+                    serde_json::json!({
+                        "kind": kind,
+                        "addr": addr,
+                        "line": line,
+                        "message": message,
+                    })
+                })
+                .collect();
+            println!("{:#}", serde_json::Value::from(warnings));
+        }
+    }
+    match warnings.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+/// Prints what's known about the byte at `addr` of the program
+/// at `file`, see `crate::inspect`.
+fn explain_byte(file: &Path, addr: &str) -> ExitCode {
+    let mut cpu = Neander::new();
+    let (image, symbols, source_map) = match cpu.load_file_image(file) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some(addr) = symbols.get(addr).or(spec::parse_addr(addr).ok()) else {
+        eprintln!("error: invalid address or unknown label: {addr}");
+        return ExitCode::FAILURE;
+    };
+    for line in inspect::describe_byte(cpu.memory(), addr, &symbols, &source_map, &image) {
+        println!("{line}");
+    }
+    ExitCode::SUCCESS
+}
+
+/// Installs the pack at `file` into `dir`, or the packs directory.
+fn install_pack(file: &Path, dir: Option<PathBuf>) -> ExitCode {
+    let Some(dir) = dir.or_else(pack::packs_dir) else {
+        eprintln!("error: no directory to install into, set --dir or NEANDER_PACKS");
+        return ExitCode::FAILURE;
+    };
+    let installed = Pack::open(file).and_then(|pack| Ok((pack.install(&dir)?, pack)));
+    match installed {
+        Ok((path, pack)) => {
+            if let Some(warning) = pack.version_warning() {
+                eprintln!("warning: {warning}");
+            }
+            let m = &pack.manifest;
+            println!("installed {} to {}", m.title, path.display());
+            println!(
+                "{} examples, {} exercises, {} specs, {} tutorials",
+                m.examples.len(),
+                m.exercises.len(),
+                m.specs.len(),
+                m.tutorials.len()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the version of the simulator and checks it against the
+/// versions pinned, see `crate::pin`.
+fn self_check(pack: Option<&Path>) -> ExitCode {
+    println!("neander {}", pin::VERSION);
+    // (what pinned it, the version pinned)
+    let mut pins = Vec::new();
+    let project = std::env::current_dir()
+        .ok()
+        .and_then(|dir| pin::find_project(&dir));
+    if let Some(file) = project {
+        match pin::project_pin(&file) {
+            Ok(Some(version)) => pins.push((file.display().to_string(), version)),
+            Ok(None) => println!("{} pins no version", file.display()),
+            Err(e) => {
+                eprintln!("error: {}: {e}", file.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(path) = pack {
+        match Pack::open(path) {
+            Ok(pack) => match pack.manifest.neander {
+                Some(version) => pins.push((pack.manifest.title, version)),
+                None => println!("{} pins no version", pack.manifest.title),
+            },
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if pins.is_empty() {
+        println!("no version pinned");
+    }
+    let mut matched = true;
+    for (by, version) in pins {
+        // the pins were checked when read
+        match pin::check(&version, &by).ok().flatten() {
+            Some(warning) => {
+                eprintln!("warning: {warning}");
+                matched = false;
+            }
+            None => println!("{by} pins neander {version}: ok"),
+        }
+    }
+    match matched {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+/// The instruction table as JSON, CSV or Markdown, with the
+/// operands, flag effects and description of every instruction.
+fn isa_table(format: IsaFormat) -> String {
+    use crate::cpu::instr::{self, INSTRUCTIONS};
+    let rows = INSTRUCTIONS.iter().map(|&(opcode, name)| {
+        let operands = instr::has_operand(opcode) as u8;
+        let flags = if instr::sets_flags(opcode) { "NZ" } else { "" };
+        let description = instr::describe(opcode).unwrap_or_default();
+        (opcode, name, operands, flags, description)
+    });
+    match format {
+        IsaFormat::Json => {
+            let table: Vec<_> = rows
+                .map(|(opcode, name, operands, flags, description)| {
+                    serde_json::json!({
+                        "opcode": opcode,
+                        "mnemonic": name,
+                        "operands": operands,
+                        "flags": flags.chars().map(String::from).collect::<Vec<_>>(),
+                        "description": description,
+                    })
+                })
+                .collect();
+            format!("{:#}\n", serde_json::Value::from(table))
+        }
+        IsaFormat::Csv => {
+            let mut out = "opcode,mnemonic,operands,flags,description\n".to_string();
+            for (opcode, name, operands, flags, description) in rows {
+                out += &format!("0x{opcode:02X},{name},{operands},{flags},\"{description}\"\n");
+            }
+            out
+        }
+        _ => {
+            let mut out = "| Opcode | Mnemonic | Operands | Flags | Description |\n\
+                           |--------|----------|----------|-------|-------------|\n"
+                .to_string();
+            for (opcode, name, operands, flags, description) in rows {
+                out += &format!(
+                    "| `0x{opcode:02X}` | {name} | {operands} | {flags} | {description} |\n"
+                );
+            }
+            out
+        }
+    }
+}
+
+/// Replays the reference trace at `trace` on the program,
+/// or prints the trace of the program if there's none.
+fn conformance(file: &Path, trace: Option<&Path>, max_steps: u64) -> ExitCode {
+    let mut cpu = Neander::new();
+    if let Err(e) = cpu.load_file(file) {
+        eprintln!("error: {e}");
+        return ExitCode::FAILURE;
+    }
+    let Some(trace) = trace else {
+        println!("{}", conformance::trace_header());
+        for _ in 0..max_steps {
+            let res = cpu.step();
+            println!("{}", conformance::trace_line(&cpu, res));
+            if matches!(res, ExecResult::Halted | ExecResult::Exception(_)) {
+                break;
+            }
+        }
+        return ExitCode::SUCCESS;
+    };
+    let steps = match fs::read_to_string(trace).map_err(|e| e.to_string()) {
+        Ok(text) => conformance::parse_trace(&text),
+        Err(e) => Err(e),
+    };
+    let steps = match steps {
+        Ok(steps) => steps,
+        Err(e) => {
+            eprintln!("error: {}: {e}", trace.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let divergences = conformance::replay(&mut cpu, &steps);
+    for d in &divergences {
+        println!("{d}");
+    }
+    println!(
+        "{} steps replayed, {} divergences",
+        steps.len(),
+        divergences.len()
+    );
+    if divergences.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// A seed from the current time.
+fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Writes the report of the program at `file`, see `crate::printout`.
+fn program_report(file: &Path, output: Option<&Path>, spec: Option<&Spec>) -> ExitCode {
+    let printout = match Printout::new(file, spec) {
+        Ok(printout) => printout,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some(output) = output else {
+        print!("{}", printout.to_text());
+        return ExitCode::SUCCESS;
+    };
+    let bytes = match output.extension().and_then(|e| e.to_str()) {
+        Some("html") => printout.to_html().into_bytes(),
+        Some("pdf") => printout.to_pdf(),
+        _ => {
+            eprintln!("error: reports are written as .html or .pdf");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = fs::write(output, bytes) {
+        eprintln!("error: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Steps the program `at_step` times and draws it.
+fn render(file: &Path, at_step: Option<u64>, output: Option<&Path>) -> ExitCode {
+    if output.is_some_and(|o| o.extension().is_some_and(|e| e != "svg")) {
+        eprintln!("error: only SVG images can be rendered");
+        return ExitCode::FAILURE;
+    }
+    let mut cpu = Neander::new();
+    if let Err(e) = cpu.load_file(file) {
+        eprintln!("error: {e}");
+        return ExitCode::FAILURE;
+    }
+    let mut steps = 0;
+    let mut stop = None;
+    while at_step.is_none_or(|n| steps < n) {
+        steps += 1;
+        match cpu.step() {
+            ExecResult::Halted => stop = Some("halted".to_string()),
+            ExecResult::Exception(e) => stop = Some(e.to_string()),
+            _ => continue,
+        }
+        break;
+    }
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let title = match stop {
+        Some(stop) => format!("{name}, step {steps}: {stop}"),
+        None => format!("{name}, step {steps}"),
+    };
+    let svg = render::render_svg(&cpu, &title);
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, svg) {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{svg}"),
     }
+    ExitCode::SUCCESS
 }
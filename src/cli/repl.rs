@@ -1,303 +1,253 @@
-use std::fs;
-use std::{path::Path, process::ExitCode};
+use std::path::Path;
+use std::process::ExitCode;
 
-use crate::cpu::{ExecResult, Neander};
-use crate::memfile;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use serde_json::Value;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Directive {
-    Step,
-    StepN(u32),
-    BreakPoint(u8),
-    Clear(u8),
-    Continue,
-    PrintCpu,
-    PrintMemAddr(u8),
-    PrintMemRange(u8, u8),
-    Help,
-    Quit,
-}
+use super::args::LoadOptions;
+use super::mi;
+use crate::clock::Clock;
+use crate::cpu::Neander;
+use crate::crash;
+use crate::debugger::{self, breakpoints, Debugger, Directive, Flow};
+use crate::exercise::Exercise;
+use crate::io::{CharOutput, OUTPUT_ADDR};
+use crate::stats::Session;
 
-pub fn run_repl(file: &Path) -> ExitCode {
+pub fn run_repl(file: &Path, opts: &LoadOptions) -> ExitCode {
     let mut cpu = Neander::new();
-    let source = match fs::read_to_string(file) {
-        Ok(s) => s,
+    let (symbols, source_map) = match cpu.load_file_mapped(file) {
+        Ok(loaded) => loaded,
         Err(e) => {
             eprintln!("error: {e}");
             return ExitCode::FAILURE;
         }
     };
-    if let Err(e) = memfile::parse_memfile(cpu.memory_mut(), &source) {
-        eprintln!("error: {e}");
-        return ExitCode::FAILURE;
-    }
-    let mut buf = String::new();
-    let mut bps = [false; 256];
-    let mut last_dir = None;
-    loop {
-        // read directive
-        buf.clear();
-        let dir = match std::io::stdin().read_line(&mut buf).unwrap() {
-            // if 1 byte was read, it was a newline. Repeat last directive
-            1 => match last_dir {
-                Some(d) => d,
-                None => continue,
-            },
-            // if EOF, quit session
-            0 => break,
-            // else, parse the line
-            _ => match parser::parse_directive(&buf) {
-                Ok(d) => d,
-                Err(e) => {
-                    println!("{e}");
-                    continue;
-                }
-            },
-        };
-        last_dir = Some(dir);
-        match dir {
-            Directive::Quit => break,
-            Directive::Help => print_help(),
-            Directive::BreakPoint(x) => {
-                if bps[x as usize] {
-                    println!("breakpoint already set at {x}");
-                } else {
-                    bps[x as usize] = true;
-                    println!("breakpoint set at {x}");
-                }
-            }
-            Directive::Clear(x) => {
-                if !bps[x as usize] {
-                    println!("no breakpoint at {x}");
-                } else {
-                    bps[x as usize] = false;
-                    println!("cleared breakpoint at {x}");
-                }
-            }
-            Directive::PrintCpu => {
-                println!("{cpu}");
+    let mut dbg = Debugger::new(Some(file.to_path_buf()), symbols);
+    dbg.source_map = source_map;
+    // stdin holds the directives, so there's no keyboard
+    let device = mi::Captured::default();
+    if opts.mi {
+        dbg.bus.attach(OUTPUT_ADDR, CharOutput(device.clone()));
+    } else {
+        dbg.bus.attach(OUTPUT_ADDR, CharOutput(std::io::stdout()));
+    }
+    dbg.bus.set_clock(Clock::new(opts.seed));
+    dbg.session = Session::new("load", Some(file));
+    dbg.color = !opts.mi && super::color();
+    // executes directives given by the options, before the session
+    let exec = |dbg: &mut Debugger, cpu: &mut Neander, dir: Directive| {
+        if opts.mi {
+            return mi::execute(dbg, cpu, &device, &Value::Null, dir);
+        }
+        let mut out = String::new();
+        let flow = dbg
+            .execute(cpu, dir, &mut out)
+            .expect("writing to a String");
+        print!("{out}");
+        flow
+    };
+
+    let bp_file = opts
+        .breakpoints
+        .clone()
+        .unwrap_or_else(|| breakpoints::default_path(file));
+    match breakpoints::load(&bp_file, file) {
+        Ok(points) if !points.is_empty() => {
+            for &a in &points.breakpoints {
+                dbg.breakpoints[a as usize] = true;
             }
-            Directive::PrintMemAddr(a) => {
-                println!("{0} | {0:X} | {0:b}", cpu.memory()[a as usize]);
+            for &a in &points.watchpoints {
+                dbg.watchpoints[a as usize] = true;
             }
-            Directive::PrintMemRange(a, b) => {
-                cpu.print_mem_range(a, b);
+            let text = format!(
+                "restored {} breakpoints and {} watchpoints from {}\n",
+                points.breakpoints.len(),
+                points.watchpoints.len(),
+                bp_file.display()
+            );
+            match opts.mi {
+                true => mi::output(&Value::Null, &text),
+                false => print!("{text}"),
             }
-            Directive::Step => match cpu.step() {
-                ExecResult::Halted => println!("end of program reached"),
-                ExecResult::Normal => println!("{cpu}"),
-                ExecResult::MemWrite { addr, value } => println!("{cpu}\nmem[{addr}] <- {value}"),
-                ExecResult::Exception(e) => {
-                    println!("exception: {e}");
-                    break;
-                }
-            },
-            Directive::StepN(n) => {
-                for _ in 0..n {
-                    match cpu.step() {
-                        ExecResult::Halted => {
-                            println!("end of program reached");
-                            break;
-                        }
-                        ExecResult::MemWrite { addr, value } => {
-                            println!("mem[{addr}] <- {value}");
-                            if bps[cpu.pc() as usize] {
-                                println!("breakpoint reached");
-                                break;
-                            }
-                        }
-                        ExecResult::Normal => {
-                            if bps[cpu.pc() as usize] {
-                                println!("breakpoint reached");
-                                break;
-                            }
-                        }
-                        ExecResult::Exception(e) => {
-                            println!("exception: {e}");
-                            break;
-                        }
-                    }
-                }
+        }
+        Ok(_) => (),
+        Err(e) => eprintln!("warning: {e}"),
+    }
+    if let Some(path) = &opts.exercise {
+        match Exercise::load(path) {
+            Ok(ex) => dbg.set_exercise(&mut cpu, Some(ex)),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
             }
-            Directive::Continue => loop {
-                match cpu.step() {
-                    ExecResult::Halted => {
-                        println!("end of program reached");
-                        break;
-                    }
-                    ExecResult::MemWrite { addr, value } => {
-                        println!("mem[{addr}] <- {value}");
-                        if bps[cpu.pc() as usize] {
-                            println!("breakpoint reached");
-                            break;
-                        }
-                    }
-                    ExecResult::Normal => {
-                        if bps[cpu.pc() as usize] {
-                            println!("breakpoint reached");
-                            break;
-                        }
-                    }
-                    ExecResult::Exception(e) => {
-                        println!("exception: {e}");
-                        break;
-                    }
-                }
-            },
         }
+        exec(&mut dbg, &mut cpu, Directive::Goal);
     }
-    ExitCode::SUCCESS
-}
-fn print_help() {
-    println!(
-        "valid directives:
-         - help, h: display this help
-         - step, s: execute the next instruction
-         - (step, s) n: execute the next n instructions
-         - (breakpoint, b) i: set a breakpoint at instruction i
-         - (clear, cl) i: clear a breakpoint at instruction i
-         - continue, c: continue execution until next breakpoint
-         - cpu, show, print: print CPU content
-         - mem: print all memory
-         - mem (addr, start.., ..end, start..end): print memory in address or supplied range
-         - quit, q: quit session"
-    )
-}
-
-mod parser {
-    use std::str::FromStr;
-
-    use super::Directive;
-    use nom::branch::alt;
-    use nom::bytes::complete::tag;
-    use nom::bytes::complete::take_while1;
-    use nom::character::complete::digit1;
-    use nom::combinator::eof;
-    use nom::combinator::map_res;
-    use nom::combinator::opt;
-    use nom::sequence::{pair, preceded};
-    use nom::{IResult, Parser};
-
-    pub fn parse_directive(input: &str) -> Result<Directive, &'static str> {
-        match directive(input) {
-            Ok((rest, dir)) if rest.is_empty() => Ok(dir),
-            _ => Err("Invalid directive. For valid directives, type `help`"),
-        }
+    let mut flow = Flow::Continue;
+    if let Some(path) = &opts.script {
+        flow = exec(&mut dbg, &mut cpu, Directive::Source(path.clone()));
     }
-
-    fn directive(input: &str) -> IResult<&str, Directive> {
-        alt((quit, cont, step, mem, cpu, breakpoint, clear, help)).parse(input.trim())
+    if flow == Flow::Continue {
+        if opts.mi {
+            mi::run(&mut dbg, &mut cpu, &device);
+        } else {
+            interactive(&mut dbg, &mut cpu, &file.with_file_name(HISTORY_FILE));
+        }
     }
-
-    fn help(input: &str) -> IResult<&str, Directive> {
-        word("h")
-            .or(word("help"))
-            .map(|_| Directive::Help)
-            .parse(input)
+    // saved for the program loaded last, which the breakpoints refer to
+    let program = dbg.file.clone().unwrap_or_else(|| file.to_path_buf());
+    let points = breakpoints::Points {
+        breakpoints: dbg.breakpoint_list(),
+        watchpoints: dbg.watchpoint_list(),
+    };
+    if let Err(e) = breakpoints::save(&bp_file, &program, &points) {
+        eprintln!("warning: could not save breakpoints: {e}");
     }
+    dbg.session.save_if_enabled();
+    ExitCode::SUCCESS
+}
 
-    fn mem(input: &str) -> IResult<&str, Directive> {
-        let end_range = preceded(tag(".."), uint::<u8>).map(|x| Directive::PrintMemRange(0, x));
-        let range =
-            pair(uint::<u8>, opt(preceded(tag(".."), opt(uint::<u8>)))).map(|(a, n)| match n {
-                None => Directive::PrintMemAddr(a),
-                Some(None) => Directive::PrintMemRange(a, 255),
-                Some(Some(b)) => Directive::PrintMemRange(a, b),
-            });
+/// Where the REPL keeps the directives typed, next to the program
+/// like the breakpoints.
+const HISTORY_FILE: &str = ".neander_history";
 
-        let range = end_range.or(range);
-        let mem = word("mem").map(|_| Directive::PrintMemRange(0, 255));
-        let mem_range = pair(word("mem"), range).map(|(_, d)| d);
-        mem_range.or(mem).parse(input)
-    }
-    fn quit(input: &str) -> IResult<&str, Directive> {
-        word("quit")
-            .or(word("q"))
-            .map(|_| Directive::Quit)
-            .parse(input)
-    }
-    fn cpu(input: &str) -> IResult<&str, Directive> {
-        word("cpu").map(|_| Directive::PrintCpu).parse(input)
-    }
-    fn cont(input: &str) -> IResult<&str, Directive> {
-        word("continue")
-            .or(word("c"))
-            .map(|_| Directive::Continue)
-            .parse(input)
-    }
-    //fn parse_directive(input: &str) -> Result<Directive, &str> {}
-    fn breakpoint(input: &str) -> IResult<&str, Directive> {
-        let bp = word("breakpoint").or(word("b")).or(word("bp"));
-        let pc = uint::<u8>;
-        pair(bp, pc)
-            .map(|(_, x)| Directive::BreakPoint(x))
-            .parse(input)
-    }
-    fn clear(input: &str) -> IResult<&str, Directive> {
-        let bp = word("clear").or(word("cl"));
-        let pc = uint::<u8>;
-        pair(bp, pc).map(|(_, x)| Directive::Clear(x)).parse(input)
-    }
-    fn step(input: &str) -> IResult<&str, Directive> {
-        let step_n = pair(word("step").or(word("s")), uint).map(|(_, n)| Directive::StepN(n));
-        let step = word("step").or(word("s")).map(|_| Directive::Step);
-        alt((step_n, step))(input)
-    }
-    fn uint<T: FromStr>(input: &str) -> IResult<&str, T> {
-        map_res(digit1, str::parse)(input)
-    }
-    fn space(input: &str) -> IResult<&str, ()> {
-        take_while1(|c: char| c.is_whitespace())
-            .map(|_| ())
-            .parse(input)
-    }
-    fn word(word: &str) -> impl Parser<&str, (), nom::error::Error<&str>> {
-        use nom::sequence::terminated;
-        terminated(tag(word), space.or(eof.map(|_| ()))).map(|_| ())
+/// Completes directive names and aliases, and labels in their arguments.
+struct Completion {
+    aliases: Vec<String>,
+    labels: Vec<String>,
+}
+impl Completer for Completion {
+    type Candidate = String;
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.aliases, &self.labels))
     }
+}
+impl Hinter for Completion {
+    type Hint = String;
+}
+impl Highlighter for Completion {}
+impl Validator for Completion {}
+impl Helper for Completion {}
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        #[test]
-        fn parse_step() {
-            assert_eq!(step("step 10"), Ok(("", Directive::StepN(10))));
-            assert_eq!(step("step"), Ok(("", Directive::Step)));
+/// The start of the word before `pos` and the names it may be
+/// completed to: directives and aliases for the first word of a
+/// directive, labels for the others.
+fn complete(line: &str, pos: usize, aliases: &[String], labels: &[String]) -> (usize, Vec<String>) {
+    let start = line[..pos].rfind([' ', ';']).map_or(0, |i| i + 1);
+    let word = &line[start..pos];
+    let directive = line[..start].rsplit(';').next().unwrap_or_default();
+    let names: Vec<&str> = match directive.trim().is_empty() {
+        true => debugger::DIRECTIVES
+            .iter()
+            .copied()
+            .chain(aliases.iter().map(String::as_str))
+            .collect(),
+        false => labels.iter().map(String::as_str).collect(),
+    };
+    let matches = names
+        .into_iter()
+        .filter(|name| name.starts_with(word))
+        .map(str::to_string)
+        .collect();
+    (start, matches)
+}
+
+/// Reads lines of directives with a line editor until the session
+/// ends. An empty line repeats the last line.
+fn interactive(dbg: &mut Debugger, cpu: &mut Neander, history: &Path) {
+    let config = Config::builder()
+        .auto_add_history(false)
+        .history_ignore_dups(true)
+        .and_then(|c| c.max_history_size(1000))
+        .map(|c| c.completion_type(CompletionType::List).build());
+    let editor = config.and_then(Editor::<Completion, DefaultHistory>::with_config);
+    let mut editor = match editor {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return;
         }
-        #[test]
-        fn parse_breakpoint() {
-            assert_eq!(
-                breakpoint("breakpoint 10"),
-                Ok(("", Directive::BreakPoint(10)))
-            );
-            assert!(breakpoint("breakpoint -1").is_err());
-            assert!(breakpoint("breakpoint").is_err());
+    };
+    // there's no history yet the first time
+    let _ = editor.load_history(history);
+    let mut out = String::new();
+    let mut last_line: Option<String> = None;
+    loop {
+        let labels = dbg.symbols.iter().map(|(name, _)| name.to_string());
+        editor.set_helper(Some(Completion {
+            aliases: dbg.aliases.keys().cloned().collect(),
+            labels: labels.collect(),
+        }));
+        let prompt = format!("neander {:02X}> ", cpu.pc());
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            // Ctrl-C drops the line being typed
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("error: {e}");
+                break;
+            }
+        };
+        let line = match line.trim() {
+            "" => match &last_line {
+                Some(line) => line.clone(),
+                None => continue,
+            },
+            _ => {
+                let _ = editor.add_history_entry(line.as_str());
+                line
+            }
+        };
+        // an invalid line isn't repeated
+        let valid = dbg.parse_line(&line).is_ok();
+        crash::set_file(dbg.file.as_deref());
+        crash::set_machine(cpu);
+        crash::record_directive(&line);
+        out.clear();
+        let flow = dbg
+            .execute_line(cpu, &line, &mut out)
+            .expect("writing to a String");
+        print!("{out}");
+        if valid {
+            last_line = Some(line);
         }
-        #[test]
-        fn parse_mem() {
-            assert_eq!(mem("mem"), Ok(("", Directive::PrintMemRange(0, 255))));
-            assert_eq!(mem("mem 10.."), Ok(("", Directive::PrintMemRange(10, 255))));
-            assert_eq!(mem("mem ..100"), Ok(("", Directive::PrintMemRange(0, 100))));
-            assert_eq!(mem("mem 10"), Ok(("", Directive::PrintMemAddr(10))));
+        if flow != Flow::Continue {
+            break;
         }
+    }
+    if let Err(e) = editor.save_history(history) {
+        eprintln!("warning: could not save the history: {e}");
+    }
+}
 
-        #[test]
-        fn parse_word() {
-            assert_eq!(word("abc").parse("abc"), Ok(("", ())));
-            assert_eq!(word("abcd").parse("abcd  1"), Ok(("1", ())));
-            assert!(word("abc").parse("abcdef").is_err());
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        #[test]
-        fn test_directive() {
-            assert_eq!(parse_directive("c"), Ok(Directive::Continue));
-            assert_eq!(parse_directive("continue"), Ok(Directive::Continue));
-            assert_eq!(parse_directive("h"), Ok(Directive::Help));
-            assert_eq!(parse_directive("help"), Ok(Directive::Help));
-            assert_eq!(parse_directive("cpu"), Ok(Directive::PrintCpu));
-            assert_eq!(parse_directive("mem"), Ok(Directive::PrintMemRange(0, 255)));
-            assert!(parse_directive("c a").is_err());
-            assert!(parse_directive("continue 1").is_err());
-        }
+    #[test]
+    fn test_complete() {
+        let labels = vec!["loop".to_string(), "limit".to_string(), "x".to_string()];
+        let aliases = vec!["stop".to_string()];
+        let complete = |line: &str, pos| complete(line, pos, &aliases, &labels);
+        assert_eq!(complete("st", 2), (0, vec!["step".into(), "stop".into()]));
+        let (start, names) = complete("b l", 3);
+        assert_eq!((start, names), (2, vec!["loop".into(), "limit".into()]));
+        assert_eq!(complete("print x + l", 10).1.len(), 3);
+        assert_eq!(complete("s;cp", 4), (2, vec!["cpu".into()]));
+        assert_eq!(complete("s; b x", 6).1, ["x"]);
+        assert!(complete("zz", 2).1.is_empty());
     }
 }
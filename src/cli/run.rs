@@ -1,26 +1,496 @@
-use crate::cpu::{Neander, NeanderException};
-use crate::memfile::*;
+use super::args::{OutputFormat, Render, RtlFormat, RunOptions};
+use crate::clock::Clock;
+use crate::coverage::Coverage;
+use crate::cpu::{fmt_dual, ExecResult, Neander, NeanderException};
+use crate::data;
+use crate::explain::Explainer;
+use crate::io::{Bus, CharOutput, Keyboard, KEYBOARD_ADDR, OUTPUT_ADDR};
+use crate::micro::RtlTrace;
+use crate::numfmt::{self, NumberFormat};
+use crate::predecode::Predecoded;
+use crate::profile::Profile;
+use crate::render;
+use crate::smc::SmcWatch;
+use crate::stats::Session;
+use crate::symbols::SymbolTable;
+use crate::timeline::Timeline;
+use crate::trace::Trace;
+use crate::traps::Traps;
+use std::collections::VecDeque;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::path::Path;
 use std::process::ExitCode;
 
-pub fn run_file(file: &Path) -> ExitCode {
+/// How many of the last instructions are kept to explain exceptions.
+const TRAIL_LEN: usize = 64;
+
+pub fn run_file(file: &Path, opts: &RunOptions) -> ExitCode {
+    if opts.deterministic {
+        numfmt::set(NumberFormat::PLAIN);
+    }
     let mut cpu = Neander::new();
-    let source = match fs::read_to_string(file) {
-        Ok(s) => s,
+    let symbols = match cpu.load_file_symbols(file) {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let data_out = match &opts.data_out {
+        Some(args) => match crate::spec::parse_range(&args[0]) {
+            Ok(range) => Some((range, Path::new(&args[1]))),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    if let Some(path) = &opts.data_in {
+        let res = data::read(path).and_then(|b| data::load(cpu.memory_mut(), opts.data_at, &b));
+        if let Err(e) = res {
+            eprintln!("error: {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    crate::crash::set_file(Some(file));
+    crate::crash::set_machine(&cpu);
+    let initial = cpu.memory().to_vec();
+    // the last instructions executed, for explaining exceptions
+    let mut trail = VecDeque::new();
+    let mut coverage = Coverage::new();
+    let mut timeline = opts.timeline.as_ref().map(|_| Timeline::new());
+    let mut profile = opts.profile.then(Profile::new);
+    let mut smc = opts.warn_smc.then(SmcWatch::new);
+    let mut traps = match opts.traps.as_deref().map(Traps::load).transpose() {
+        Ok(traps) => traps,
         Err(e) => {
             eprintln!("error: {e}");
             return ExitCode::FAILURE;
         }
     };
-    if let Err(e) = parse_memfile(cpu.memory_mut(), &source) {
+    let max_steps = opts.max_steps.unwrap_or(u64::MAX);
+    let mut session = Session::new("run", Some(file));
+    let res = match (&opts.trace, &opts.rtl_trace) {
+        (_, Some(path)) => {
+            let initial = cpu.clone();
+            let mut log = RtlTrace::new();
+            let res = cpu.run_uops_traced_with_limit(&mut log, max_steps);
+            session.steps = log.entries().iter().filter(|e| e.t == 0).count() as u64;
+            let out = match opts.rtl_format {
+                RtlFormat::Csv => log.to_csv(),
+                RtlFormat::Vcd => log.to_vcd(&initial),
+            };
+            if let Err(e) = fs::write(path, out) {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+            res
+        }
+        (Some(out), None) => {
+            let mut log = Trace::new();
+            let res = cpu.run_traced_with_limit(&mut log, max_steps);
+            session.steps = log.len() as u64;
+            let start = log.len().saturating_sub(TRAIL_LEN);
+            trail.extend(log.entries()[start..].iter().map(|e| e.pc));
+            match out {
+                Some(path) => {
+                    if let Err(e) = fs::write(path, log.to_string()) {
+                        eprintln!("error: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+                None => print!("{log}"),
+            }
+            res
+        }
+        (None, None) => {
+            let mut bus = Bus::new();
+            bus.set_clock(Clock::new(opts.seed));
+            if opts.io {
+                bus.attach(OUTPUT_ADDR, CharOutput(std::io::stdout()));
+                bus.attach(KEYBOARD_ADDR, Keyboard(std::io::stdin()));
+            }
+            // without devices, instructions can be decoded only once
+            let mut cache = Predecoded::new();
+            let mut res = Err(NeanderException::StepLimitExceeded(max_steps));
+            while session.steps < max_steps {
+                if opts.explain {
+                    if trail.len() == TRAIL_LEN {
+                        trail.pop_front();
+                    }
+                    trail.push_back(cpu.pc());
+                }
+                let step = match (bus.is_empty(), opts.coverage) {
+                    _ if timeline.is_some()
+                        || traps.is_some()
+                        || profile.is_some()
+                        || smc.is_some() =>
+                    {
+                        let coverage = opts.coverage.then_some(&mut coverage);
+                        let watches = (&mut profile, (&mut smc, coverage));
+                        let mut hooks = (&mut timeline, (&mut traps, watches));
+                        cpu.step_bus_hooked(&mut bus, &mut hooks)
+                    }
+                    (_, true) => cpu.step_hooked(&mut coverage),
+                    (true, false) => cpu.step_predecoded(&mut cache),
+                    (false, false) => cpu.step_bus(&mut bus),
+                };
+                if traps.as_ref().is_some_and(|t| t.tripped().is_some()) {
+                    res = Ok(());
+                    break;
+                }
+                match step {
+                    ExecResult::Halted => {
+                        session.steps += 1;
+                        res = Ok(());
+                        break;
+                    }
+                    ExecResult::Exception(e) => {
+                        res = Err(e);
+                        break;
+                    }
+                    _ => session.steps += 1,
+                }
+            }
+            if opts.io {
+                println!();
+            }
+            res
+        }
+    };
+    let tripped = traps.as_ref().and_then(|t| t.tripped());
+    match res {
+        Ok(()) => session.halted = tripped.is_none(),
+        Err(e) => session.exception(e),
+    }
+    if !opts.deterministic {
+        session.save_if_enabled();
+    }
+    if let Some((range, path)) = data_out {
+        let bytes = &cpu.memory()[*range.start() as usize..=*range.end() as usize];
+        if let Err(e) = data::write(path, bytes) {
+            eprintln!("error: {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    if let (Some(path), Some(timeline)) = (&opts.timeline, &timeline) {
+        let out = match path.extension().is_some_and(|e| e == "csv") {
+            true => timeline.to_csv(),
+            false => timeline.to_svg(&file.display().to_string()),
+        };
+        if let Err(e) = fs::write(path, out) {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+        if timeline.is_truncated() {
+            eprintln!("warning: the timeline only has the first accesses of the run");
+        }
+    }
+    if opts.diff {
+        print!("{}", mem_diff(&initial, cpu.memory(), &symbols));
+        println!("{cpu}");
+    } else if let Err(e) = print_machine(&cpu, file, opts) {
         eprintln!("error: {e}");
         return ExitCode::FAILURE;
     }
-    if let Err(e) = cpu.run() {
+    if opts.coverage {
+        print!(
+            "{}",
+            coverage.report(cpu.memory(), &symbols, super::color())
+        );
+    }
+    for warning in smc.as_mut().map(SmcWatch::take).unwrap_or_default() {
+        eprintln!("warning: {warning}");
+    }
+    if let Some(profile) = &profile {
+        print!("{}", profile.report(cpu.memory(), &symbols, super::color()));
+    }
+    if let Err(e) = res {
         eprintln!("exception: {e}");
+        if opts.explain {
+            let trail = trail.make_contiguous();
+            eprint!(
+                "{}",
+                Explainer::new(cpu.memory(), &symbols, trail).explain(e)
+            );
+        }
+    }
+    if let Some(tripped) = tripped {
+        eprintln!("trap: {tripped}");
+        return ExitCode::FAILURE;
+    }
+    match res {
+        Err(NeanderException::StepLimitExceeded(_)) => ExitCode::FAILURE,
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// How a program of a batch ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BatchRow {
+    name: String,
+    /// "halted", or why the program didn't
+    end: String,
+    steps: u64,
+    acc: i8,
+    pc: u8,
+}
+impl BatchRow {
+    fn halted(&self) -> bool {
+        self.end == "halted"
+    }
+}
+
+/// Runs the program at `path` in a fresh machine for at most
+/// `max_steps`, with `data` loaded at its address.
+fn batch_row(path: &Path, max_steps: u64, data: Option<(u8, &[u8])>) -> BatchRow {
+    let mut cpu = Neander::new();
+    let mut steps = 0;
+    let loaded = cpu.load_file(path).and_then(|()| match data {
+        Some((start, bytes)) => data::load(cpu.memory_mut(), start, bytes),
+        None => Ok(()),
+    });
+    let end = match loaded {
+        Err(e) => format!("error: {}", e.lines().next().unwrap_or_default()),
+        Ok(()) => loop {
+            if steps == max_steps {
+                break format!("no halt in {max_steps} steps");
+            }
+            match cpu.step() {
+                ExecResult::Halted => {
+                    steps += 1;
+                    break "halted".to_string();
+                }
+                ExecResult::Exception(e) => break format!("exception: {e}"),
+                _ => steps += 1,
+            }
+        },
+    };
+    BatchRow {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into(),
+        end,
+        steps,
+        acc: cpu.acc(),
+        pc: cpu.pc(),
+    }
+}
+
+/// Runs every program in `dir`, in name order, printing how each
+/// ended as a table, or as JSON with `--output-format json`.
+/// Fails if any program doesn't halt.
+pub fn run_batch(dir: &Path, opts: &RunOptions) -> ExitCode {
+    if opts.deterministic {
+        numfmt::set(NumberFormat::PLAIN);
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("error: {}: {e}", dir.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && !p
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .starts_with('.')
+        })
+        .collect();
+    files.sort();
+    let max_steps = opts
+        .max_steps
+        .unwrap_or_else(crate::spec::default_max_steps);
+    let data = match opts.data_in.as_deref().map(data::read).transpose() {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("error: {}: {e}", opts.data_in.as_ref().unwrap().display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let data = data.as_deref().map(|bytes| (opts.data_at, bytes));
+    let rows: Vec<_> = files
+        .iter()
+        .map(|f| batch_row(f, max_steps, data))
+        .collect();
+    if opts.output_format == OutputFormat::Json {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                serde_json::json!({
+                    "file": r.name,
+                    "halted": r.halted(),
+                    "end": r.end,
+                    "steps": r.steps,
+                    "acc": r.acc,
+                    "pc": r.pc,
+                })
+            })
+            .collect();
+        println!("{:#}", serde_json::Value::from(rows));
+    } else {
+        print!("{}", batch_table(&rows));
+    }
+    match rows.iter().all(BatchRow::halted) {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+fn batch_table(rows: &[BatchRow]) -> String {
+    let width = rows.iter().map(|r| r.name.len()).max().unwrap_or(0).max(4);
+    let mut out = format!(
+        "{:<width$}  {:>4}  {:>4}  {:>7}  END\n",
+        "FILE", "AC", "PC", "STEPS"
+    );
+    for r in rows {
+        out.push_str(&format!(
+            "{:<width$}  {:>4}  {:>4}  {:>7}  {}\n",
+            r.name,
+            r.acc,
+            format!("{:02X}", r.pc),
+            r.steps,
+            r.end
+        ));
+    }
+    let halted = rows.iter().filter(|r| r.halted()).count();
+    out.push_str(&format!(
+        "\n{} programs, {halted} halted, {} didn't\n",
+        rows.len(),
+        rows.len() - halted
+    ));
+    out
+}
+
+/// Prints the machine after a run in the chosen format,
+/// or draws it in the chosen style.
+fn print_machine(cpu: &Neander, file: &Path, opts: &RunOptions) -> std::io::Result<()> {
+    let mut out = std::io::stdout().lock();
+    if let Some(style) = opts.render {
+        let drawing = match style {
+            Render::Ascii => render::render_ascii(cpu, super::color()),
+            Render::Svg => render::render_svg(cpu, &file.display().to_string()),
+        };
+        write!(out, "{drawing}")?;
+        return out.flush();
+    }
+    match opts.output_format {
+        OutputFormat::Pretty => {
+            cpu.print_mem();
+            println!("{cpu}");
+        }
+        OutputFormat::Hex => write!(out, "{}", hexdump(cpu.memory()))?,
+        OutputFormat::Raw => out.write_all(cpu.memory())?,
+        OutputFormat::Json => {
+            let json = serde_json::json!({
+                "pc": cpu.pc(),
+                "acc": cpu.acc(),
+                "zero": cpu.status_zero(),
+                "negative": cpu.status_negative(),
+                "memory": cpu.memory(),
+            });
+            writeln!(out, "{json}")?;
+        }
+    }
+    out.flush()
+}
+
+/// The cells that differ between `before` and `after`, one per line
+/// as `addr: old -> new` with their decimal values and labels.
+fn mem_diff(before: &[u8], after: &[u8], symbols: &SymbolTable) -> String {
+    let mut out = String::new();
+    let changed = before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b);
+    for (addr, (&old, &new)) in changed {
+        let label = symbols
+            .name_at(addr as u8)
+            .map_or(String::new(), |l| format!(" {l}"));
+        out.push_str(&format!(
+            "{addr:02X}{label}: {} -> {}\n",
+            fmt_dual(old),
+            fmt_dual(new)
+        ));
+    }
+    if out.is_empty() {
+        out.push_str("no memory cell changed\n");
+    }
+    out
+}
+
+/// Formats `mem` with 16 bytes per line, each line starting with its address.
+fn hexdump(mem: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, row) in mem.chunks(16).enumerate() {
+        let bytes: Vec<_> = row.iter().map(|b| format!("{b:02x}")).collect();
+        out.push_str(&format!("{:02x}: {}\n", i * 16, bytes.join(" ")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let mut mem = [0; 32];
+        mem[17] = 0xAB;
+        assert_eq!(
+            hexdump(&mem),
+            "00: 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n\
+             10: 00 ab 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n"
+        );
+    }
+
+    #[test]
+    fn test_mem_diff() {
+        let before = [0, 5, 200, 7];
+        let after = [0, 6, 200, 0xF0];
+        let mut symbols = SymbolTable::new();
+        symbols.insert("sum", 3);
+        assert_eq!(
+            mem_diff(&before, &after, &symbols),
+            "01: 05 (5) -> 06 (6)\n03 sum: 07 (7) -> F0 (-16 / 240)\n"
+        );
+        assert_eq!(
+            mem_diff(&before, &before, &symbols),
+            "no memory cell changed\n"
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let name = format!("neander-test_batch-{}", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.asm"), "lda x\nadd x\nhlt\norg 128\nx: 0").unwrap();
+        fs::write(dir.join("b.asm"), "jmp 0").unwrap();
+        fs::write(dir.join("c.asm"), "lda").unwrap();
+        let rows: Vec<_> = ["a.asm", "b.asm", "c.asm"]
+            .iter()
+            .map(|f| batch_row(&dir.join(f), 100, Some((0x80, &[3]))))
+            .collect();
+        assert_eq!(
+            batch_table(&rows),
+            "FILE     AC    PC    STEPS  END\n\
+             a.asm     6    05        3  halted\n\
+             b.asm     0    00      100  no halt in 100 steps\n\
+             c.asm     0    00        0  error: line 1: LDA needs an address\n\
+             \n3 programs, 1 halted, 2 didn't\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
     }
-    cpu.print_mem();
-    println!("{cpu}");
-    ExitCode::SUCCESS
 }
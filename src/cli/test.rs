@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use crate::cpu::Neander;
+use crate::spec::Spec;
+
+/// How often watched files are checked for changes.
+const POLL: Duration = Duration::from_millis(500);
+
+/// The name of a case, and its failures as lines.
+/// Passed cases have no lines.
+type Outcome = (String, Vec<String>);
+
+/// Loads the spec at `path`, or the built-in spec `builtin`
+/// on the program at `path`, and the program it tests.
+fn load(path: &Path, builtin: Option<&str>) -> Result<(Spec, Neander), String> {
+    let spec = match builtin {
+        Some(name) => Spec::builtin(name, path)?,
+        None => Spec::load(path)?,
+    };
+    let mut program = Neander::new();
+    if let Err(e) = program.load_file(&spec.program) {
+        return Err(format!("{}: {e}", spec.program.display()));
+    }
+    Ok((spec, program))
+}
+
+/// Runs every case, for comparing runs.
+fn run_cases(spec: &Spec, program: &Neander) -> Vec<Outcome> {
+    let outcome = |case| match spec.run_case(program, case) {
+        Ok(failures) => failures.iter().map(|f| f.to_string()).collect(),
+        Err(e) => vec![format!("error: {e}")],
+    };
+    spec.cases
+        .iter()
+        .map(|case| (case.name.clone(), outcome(case)))
+        .collect()
+}
+
+/// Runs every case in the spec at `path`, or in the built-in spec
+/// `builtin` on the program at `path`, printing the failed checks.
+/// Fails if any case fails.
+pub fn run_spec(path: &Path, builtin: Option<&str>) -> ExitCode {
+    let (spec, program) = match load(path, builtin) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(description) = &spec.description {
+        println!("{}\n", description.trim());
+    }
+    let mut passed = 0;
+    for case in &spec.cases {
+        match spec.run_case(&program, case) {
+            Ok(failures) if failures.is_empty() => {
+                passed += 1;
+                println!("test {} ... ok", case.name);
+            }
+            Ok(failures) => {
+                println!("test {} ... FAILED", case.name);
+                for f in failures {
+                    println!("    {f}");
+                }
+            }
+            Err(e) => println!("test {} ... ERROR\n    {e}", case.name),
+        }
+    }
+    let failed = spec.cases.len() - passed;
+    println!(
+        "\n{} tests, {passed} passed, {failed} failed",
+        spec.cases.len()
+    );
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs the spec like `run_spec`, then again whenever the spec
+/// or the program changes, printing the cases that started or
+/// stopped failing since the last run. Runs until interrupted.
+pub fn watch_spec(path: &Path, builtin: Option<&str>) -> ExitCode {
+    let _ = run_spec(path, builtin);
+    let mut last = load(path, builtin)
+        .ok()
+        .map(|(spec, program)| run_cases(&spec, &program));
+    let mut files = watched(path, builtin);
+    let mut stamps = modified(&files);
+    println!("\nwatching for changes, press Ctrl+C to stop");
+    let mut run = 1;
+    loop {
+        std::thread::sleep(POLL);
+        let now = modified(&files);
+        if now == stamps {
+            continue;
+        }
+        run += 1;
+        println!("\n[run {run}]");
+        match load(path, builtin) {
+            Ok((spec, program)) => {
+                let outcomes = run_cases(&spec, &program);
+                print!("{}", delta(last.as_deref(), &outcomes));
+                last = Some(outcomes);
+            }
+            Err(e) => println!("error: {e}"),
+        }
+        // the spec may point to another program now
+        files = watched(path, builtin);
+        stamps = modified(&files);
+    }
+}
+
+/// The spec and the program it tests.
+fn watched(path: &Path, builtin: Option<&str>) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    if builtin.is_none() {
+        if let Ok(spec) = Spec::load(path) {
+            files.push(spec.program);
+        }
+    }
+    files
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|f| f.metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// The cases that were fixed or broken since `last`, with the
+/// failures of the broken ones, and a summary with the change
+/// in passed cases.
+fn delta(last: Option<&[Outcome]>, now: &[Outcome]) -> String {
+    let passed_before = |name: &str| {
+        last.and_then(|l| l.iter().find(|(n, _)| n == name))
+            .map(|(_, f)| f.is_empty())
+    };
+    let mut out = String::new();
+    let mut still = Vec::new();
+    for (name, failures) in now {
+        match (passed_before(name), failures.is_empty()) {
+            (Some(false), true) => out.push_str(&format!("  fixed: {name}\n")),
+            (Some(true) | None, false) => {
+                out.push_str(&format!("  broken: {name}\n"));
+                for f in failures {
+                    out.push_str(&format!("    {f}\n"));
+                }
+            }
+            (Some(false), false) => still.push(name.as_str()),
+            _ => (),
+        }
+    }
+    if !still.is_empty() {
+        out.push_str(&format!("  still failing: {}\n", still.join(", ")));
+    }
+    let passed = now.iter().filter(|(_, f)| f.is_empty()).count();
+    let before = last.map_or(0, |l| l.iter().filter(|(_, f)| f.is_empty()).count());
+    let change = passed as i64 - before as i64;
+    out.push_str(&format!(
+        "{} tests, {passed} passed, {} failed ({change:+})\n",
+        now.len(),
+        now.len() - passed
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta() {
+        let case = |name: &str, failures: &[&str]| {
+            let failures = failures.iter().map(|f| f.to_string()).collect();
+            (name.to_string(), failures)
+        };
+        let last = [case("a", &["x"]), case("b", &[]), case("c", &["y"])];
+        let now = [case("a", &[]), case("b", &["z"]), case("c", &["y"])];
+        assert_eq!(
+            delta(Some(&last), &now),
+            "  fixed: a\n  broken: b\n    z\n  still failing: c\n\
+             3 tests, 1 passed, 2 failed (+0)\n"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Differential testing against another Neander implementation.
+//! A reference trace has a line per executed instruction with the
+//! state after it, as `key=value` pairs in hexadecimal:
+//!
+//! ```text
+//! # after LDA 80
+//! pc=02 ac=05 n=0 z=0
+//! pc=04 ac=05 n=0 z=0 mem[81]=05
+//! ```
+//!
+//! Lines may leave out any value, which is then not checked, and
+//! `#` starts a comment. Traces written by this simulator start
+//! with a `# neander-trace 1` comment giving their version. The program is replayed one instruction per
+//! line, flagging every value that differs from the trace.
+use std::fmt;
+
+use crate::cpu::{ExecResult, Neander};
+use crate::formats::TRACE;
+
+/// The state after a step, as far as the trace records it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expected {
+    /// Line of the trace, from 1
+    pub line: usize,
+    pub pc: Option<u8>,
+    pub acc: Option<u8>,
+    pub n: Option<bool>,
+    pub z: Option<bool>,
+    /// (address, value)
+    pub memory: Vec<(u8, u8)>,
+}
+
+/// A value that differs from the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The instruction, counting from 1
+    pub step: usize,
+    pub line: usize,
+    pub message: String,
+}
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {} (line {}): {}",
+            self.step, self.line, self.message
+        )
+    }
+}
+
+fn parse_hex(value: &str, line: usize) -> Result<u8, String> {
+    u8::from_str_radix(value, 16).map_err(|_| format!("line {line}: invalid value: {value}"))
+}
+
+/// Parses a reference trace, skipping blank and comment lines.
+pub fn parse_trace(text: &str) -> Result<Vec<Expected>, String> {
+    let mut steps = Vec::new();
+    for (i, text) in text.lines().enumerate() {
+        let line = i + 1;
+        let (text, comment) = text.split_once('#').unwrap_or((text, ""));
+        if let Some(version) = TRACE.parse_header(comment) {
+            version.map_err(|e| format!("line {line}: {e}"))?;
+        }
+        if text.trim().is_empty() {
+            continue;
+        }
+        let mut step = Expected {
+            line,
+            ..Default::default()
+        };
+        for pair in text.split_whitespace() {
+            let Some((key, value)) = pair.split_once('=') else {
+                return Err(format!("line {line}: expected key=value, got {pair}"));
+            };
+            let value = parse_hex(value, line)?;
+            let flag = |value| match value {
+                0 | 1 => Ok(value == 1),
+                _ => Err(format!("line {line}: flags are 0 or 1")),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "pc" => step.pc = Some(value),
+                "ac" => step.acc = Some(value),
+                "n" => step.n = Some(flag(value)?),
+                "z" => step.z = Some(flag(value)?),
+                key => {
+                    let addr = key
+                        .strip_prefix("mem[")
+                        .and_then(|k| k.strip_suffix(']'))
+                        .ok_or_else(|| format!("line {line}: unknown key: {key}"))?;
+                    step.memory.push((parse_hex(addr, line)?, value));
+                }
+            }
+        }
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+/// The comment starting a trace, with its version.
+pub fn trace_header() -> String {
+    format!("# {}", TRACE.header())
+}
+
+/// A trace line of the state of `cpu` after a step ending with `res`.
+pub fn trace_line(cpu: &Neander, res: ExecResult) -> String {
+    let mut line = format!(
+        "pc={:02X} ac={:02X} n={} z={}",
+        cpu.pc(),
+        cpu.acc() as u8,
+        cpu.status_negative() as u8,
+        cpu.status_zero() as u8
+    );
+    if let ExecResult::MemWrite { addr, value } = res {
+        line.push_str(&format!(" mem[{addr:02X}]={:02X}", value as u8));
+    }
+    line
+}
+
+/// Replays `trace` on `cpu`, one instruction per step, returning
+/// every divergence. After a divergent step, `cpu` takes the values
+/// of the trace, so later steps are compared on their own.
+pub fn replay(cpu: &mut Neander, trace: &[Expected]) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    for (i, expected) in trace.iter().enumerate() {
+        let res = cpu.step();
+        let mut diverge = |message| {
+            divergences.push(Divergence {
+                step: i + 1,
+                line: expected.line,
+                message,
+            })
+        };
+        let mut check = |what: String, want: Option<u8>, got: u8| match want {
+            Some(want) if want != got => {
+                diverge(format!("{what}: expected {want:02X}, got {got:02X}"));
+                want
+            }
+            _ => got,
+        };
+        if let ExecResult::Exception(e) = res {
+            // the reference went on, so there is nothing to compare
+            diverge(format!("stopped: {e}"));
+            break;
+        }
+        let mut regs = cpu.registers();
+        regs.pc = check("PC".to_string(), expected.pc, regs.pc);
+        regs.acc = check("AC".to_string(), expected.acc, regs.acc as u8) as i8;
+        let n = check(
+            "N".to_string(),
+            expected.n.map(u8::from),
+            cpu.status_negative() as u8,
+        );
+        let z = check(
+            "Z".to_string(),
+            expected.z.map(u8::from),
+            cpu.status_zero() as u8,
+        );
+        regs.status = (regs.status & !3) | (n << 1) | z;
+        cpu.set_registers(regs);
+        for &(addr, value) in &expected.memory {
+            let got = cpu.ram(addr);
+            cpu.set_ram(addr, check(format!("mem[{addr:02X}]"), Some(value), got));
+        }
+        let left = trace.len() - i - 1;
+        if res == ExecResult::Halted && left > 0 {
+            diverge(format!("halted with {left} steps of the trace left"));
+            break;
+        }
+    }
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_conformance() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 0x80, STA, 0x81, NOT, HLT]);
+        cpu.set_ram(0x80, 5);
+
+        // our own trace replays without divergences
+        let mut lines = Vec::new();
+        let mut run = cpu.clone();
+        loop {
+            let res = run.step();
+            lines.push(trace_line(&run, res));
+            if res == ExecResult::Halted {
+                break;
+            }
+        }
+        assert_eq!(lines[1], "pc=04 ac=05 n=0 z=0 mem[81]=05");
+        let trace = format!("{}\n{}", trace_header(), lines.join("\n"));
+        let trace = parse_trace(&trace).unwrap();
+        assert_eq!(replay(&mut cpu.clone(), &trace), []);
+
+        // a reference whose STA sets Z, and whose NOT does nothing
+        let reference = "# STA updates the flags\n\
+                         pc=02 ac=05\n\
+                         pc=04 n=0 z=1 mem[81]=05\n\
+                         pc=05 ac=05\n";
+        let trace = parse_trace(reference).unwrap();
+        assert_eq!(trace[1].memory, [(0x81, 5)]);
+        let divergences = replay(&mut cpu.clone(), &trace);
+        let text: Vec<_> = divergences.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "step 2 (line 3): Z: expected 01, got 00",
+                "step 3 (line 4): AC: expected 05, got FA",
+            ]
+        );
+
+        assert!(parse_trace("pc=02 q=1").is_err());
+        assert!(parse_trace("pc=zz").is_err());
+        assert!(parse_trace("n=2").is_err());
+        assert!(parse_trace("# neander-trace 2\npc=01").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Co-simulation of two machines sharing a mailbox: a range of
+//! addresses both of them see, for producer/consumer and handshake
+//! exercises. The machines take turns executing one instruction,
+//! and a store into the mailbox shows up in the other machine
+//! before its next instruction.
+//!
+//! ```text
+//! ; producer            ; consumer
+//! wait: LDA 0xF0        wait: LDA 0xF0
+//!       JZ  put               JZ  wait
+//!       JMP wait              LDA 0xF1
+//! put:  ...                   ...
+//! ```
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::cpu::{ExecResult, Neander, NeanderException};
+
+/// The mailbox used when none is given.
+pub const DEFAULT_MAILBOX: RangeInclusive<u8> = 0xF0..=0xFF;
+
+/// Why a co-simulation stopped with an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoSimError {
+    /// A machine raised an exception, with its index
+    Exception(usize, NeanderException),
+    /// The machines ran for the given number of instructions, together,
+    /// without both halting. Often a deadlock, both waiting on the other.
+    StepLimitExceeded(u64),
+}
+impl fmt::Display for CoSimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exception(i, e) => write!(f, "machine {}: {e}", name(*i)),
+            Self::StepLimitExceeded(n) => write!(
+                f,
+                "the machines did not both halt after {n} instructions, \
+                 maybe each is waiting on the other"
+            ),
+        }
+    }
+}
+
+/// The name of the machine at `index`, A or B.
+pub fn name(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+/// Two machines stepped in alternation.
+#[derive(Debug, Clone)]
+pub struct CoSim {
+    pub machines: [Neander; 2],
+    mailbox: RangeInclusive<u8>,
+    /// The machine that executes the next instruction
+    next: usize,
+    /// How each machine stopped, if it did
+    stopped: [Option<ExecResult>; 2],
+    steps: [u64; 2],
+}
+
+impl CoSim {
+    /// Starts with the mailbox of `a` copied into `b`.
+    pub fn new(a: Neander, mut b: Neander, mailbox: RangeInclusive<u8>) -> Self {
+        for addr in mailbox.clone() {
+            b.set_ram(addr, a.ram(addr));
+        }
+        Self {
+            machines: [a, b],
+            mailbox,
+            next: 0,
+            stopped: [None, None],
+            steps: [0, 0],
+        }
+    }
+    pub fn mailbox(&self) -> RangeInclusive<u8> {
+        self.mailbox.clone()
+    }
+    /// Instructions executed by each machine.
+    pub fn steps(&self) -> [u64; 2] {
+        self.steps
+    }
+    /// How the machine at `index` stopped, if it did.
+    pub fn stopped(&self, index: usize) -> Option<ExecResult> {
+        self.stopped[index]
+    }
+    /// Whether neither machine can run anymore.
+    pub fn is_done(&self) -> bool {
+        self.stopped.iter().all(Option::is_some)
+    }
+
+    /// Executes an instruction of the next machine that can run,
+    /// returning its index and the result. None if both stopped.
+    pub fn step(&mut self) -> Option<(usize, ExecResult)> {
+        let i = (0..2)
+            .map(|k| (self.next + k) % 2)
+            .find(|&i| self.stopped[i].is_none())?;
+        self.next = 1 - i;
+        let res = self.machines[i].step();
+        self.steps[i] += 1;
+        match res {
+            ExecResult::MemWrite { addr, value } if self.mailbox.contains(&(addr as u8)) => {
+                self.machines[1 - i].set_ram(addr as u8, value as u8);
+            }
+            ExecResult::Halted | ExecResult::Exception(_) => self.stopped[i] = Some(res),
+            _ => (),
+        }
+        Some((i, res))
+    }
+
+    /// Runs until both machines halt, failing on the first exception
+    /// or after `max_steps` instructions of both machines together.
+    pub fn run_with_limit(&mut self, max_steps: u64) -> Result<(), CoSimError> {
+        for _ in 0..max_steps {
+            match self.step() {
+                None => return Ok(()),
+                Some((i, ExecResult::Exception(e))) => return Err(CoSimError::Exception(i, e)),
+                Some(_) => (),
+            }
+        }
+        match self.is_done() {
+            true => Ok(()),
+            false => Err(CoSimError::StepLimitExceeded(max_steps)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_cosim() {
+        // A sends 3, 2, 1 through 0xF1, raising the flag at 0xF0
+        // and waiting for B to lower it. B sums them into 0x80.
+        let mut a = Neander::new();
+        #[rustfmt::skip]
+        a.set_ram_slice(0, &[
+            LDA, 0xF0, JZ, 0x06, JMP, 0x00, // wait for the flag to be down
+            LDA, 0x81, JZ, 0x1A, STA, 0xF1, // send the count
+            ADD, 0x82, STA, 0x81,           // count down
+            LDA, 0x83, STA, 0xF0, JMP, 0x00, // raise the flag
+        ]);
+        a.set_ram(0x1A, HLT);
+        a.set_ram_slice(0x81, &[3, 0xFF, 1]);
+        let mut b = Neander::new();
+        #[rustfmt::skip]
+        b.set_ram_slice(0, &[
+            LDA, 0xF0, JZ, 0x00,             // wait for the flag to be up
+            LDA, 0x80, ADD, 0xF1, STA, 0x80, // sum the value
+            LDA, 0xF1, ADD, 0x85, STA, 0x86, // keep the value - 1
+            LDA, 0x84, STA, 0xF0,            // lower the flag
+            LDA, 0x86, JZ, 0x1A, JMP, 0x00, HLT,
+        ]);
+        b.set_ram(0x85, 0xFF);
+        let mut sim = CoSim::new(a, b, DEFAULT_MAILBOX);
+        sim.run_with_limit(10_000).unwrap();
+        assert_eq!(sim.machines[1].ram(0x80), 6);
+        assert!(sim.is_done());
+
+        // two machines waiting on each other never halt
+        let mut wait = Neander::new();
+        wait.set_ram_slice(0, &[LDA, 0xF0, JZ, 0x00, HLT]);
+        let mut sim = CoSim::new(wait.clone(), wait, DEFAULT_MAILBOX);
+        assert_eq!(
+            sim.run_with_limit(100),
+            Err(CoSimError::StepLimitExceeded(100))
+        );
+        assert_eq!(sim.steps(), [50, 50]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Which addresses a program executed as instructions and which
+//! it read or wrote as data, and which ways its branches went.
+use std::fmt::Write;
+
+use crate::ansi;
+use crate::cpu::instr::*;
+use crate::cpu::{ExecResult, Neander};
+use crate::disasm;
+use crate::hooks::Hooks;
+use crate::symbols::SymbolTable;
+
+/// Counts of the conditional jump at an address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// The use of every address, collected as `Hooks`.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// Addresses of executed opcodes
+    pub executed: [bool; 256],
+    /// Operands of executed instructions
+    pub operand: [bool; 256],
+    pub read: [bool; 256],
+    pub written: [bool; 256],
+    pub branches: [Branch; 256],
+    /// The opcode being executed
+    opcode: u8,
+}
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            executed: [false; 256],
+            operand: [false; 256],
+            read: [false; 256],
+            written: [false; 256],
+            branches: [Branch::default(); 256],
+            opcode: NOP,
+        }
+    }
+}
+
+impl Hooks for Coverage {
+    fn on_fetch(&mut self, _cpu: &Neander, pc: u8, opcode: u8) {
+        self.executed[pc as usize] = true;
+        if has_operand(opcode) && pc != 255 {
+            self.operand[pc as usize + 1] = true;
+        }
+        self.opcode = opcode;
+    }
+    fn on_mem_read(&mut self, addr: u8, _value: u8) {
+        self.read[addr as usize] = true;
+    }
+    fn on_mem_write(&mut self, addr: u8, _old: u8, _new: u8) {
+        self.written[addr as usize] = true;
+    }
+    fn on_exec(&mut self, cpu: &Neander, pc: u8, res: ExecResult) {
+        let jumped = matches!(self.opcode, JN | JZ) && !matches!(res, ExecResult::Exception(_));
+        if jumped && self.executed[pc as usize] {
+            let branch = &mut self.branches[pc as usize];
+            if cpu.pc() == pc.wrapping_add(2) {
+                branch.not_taken += 1;
+            } else {
+                branch.taken += 1;
+            }
+        }
+        self.opcode = NOP;
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The map symbol of `addr`, and its ANSI color.
+    fn symbol(&self, addr: usize) -> (char, &'static str) {
+        let branch = self.branches[addr];
+        let partial = (branch.taken == 0) != (branch.not_taken == 0);
+        match (self.read[addr], self.written[addr]) {
+            _ if self.executed[addr] && partial => ('?', "33"),
+            _ if self.executed[addr] => ('X', "32"),
+            _ if self.operand[addr] => ('-', "32"),
+            (true, true) => ('B', "36"),
+            (true, false) => ('R', "36"),
+            (false, true) => ('W', "36"),
+            (false, false) => ('.', ansi::DIM),
+        }
+    }
+
+    /// A map of memory with a symbol for every address, followed by
+    /// the conditional jumps that only went one way.
+    pub fn report(&self, mem: &[u8], symbols: &SymbolTable, color: bool) -> String {
+        let count = |cells: &[bool; 256]| cells.iter().filter(|&&b| b).count();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} instructions executed, {} addresses read, {} written",
+            count(&self.executed),
+            count(&self.read),
+            count(&self.written)
+        );
+        let _ = write!(out, "  ");
+        for col in 0..16 {
+            let _ = write!(out, " {col:X}");
+        }
+        for addr in 0..256 {
+            if addr % 16 == 0 {
+                let _ = write!(out, "\n{:X}_", addr / 16);
+            }
+            let (symbol, code) = self.symbol(addr);
+            let _ = write!(out, " {}", ansi::paint(code, symbol, color));
+        }
+        let _ = writeln!(
+            out,
+            "\nX executed, - operand, ? branch taken one way only, \
+             R read, W written, B both, . unused"
+        );
+        for addr in 0..=255u8 {
+            let branch = self.branches[addr as usize];
+            let missing = match (branch.taken, branch.not_taken) {
+                (0, 0) => continue,
+                (0, _) => "never jumped",
+                (_, 0) => "never fell through",
+                _ => continue,
+            };
+            let line = disasm::decode_labeled(mem, addr, symbols);
+            let _ = writeln!(out, "{addr:02X}: {} {missing}", line.text);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        let mut cpu = Neander::new();
+        // counts x up to 0, then stores it in y
+        cpu.set_ram_slice(0, &[LDA, 128, ADD, 129, STA, 128, JN, 0, STA, 130, HLT]);
+        cpu.set_ram_slice(128, &[0xFE, 1]);
+        let mut cov = Coverage::new();
+        while cpu.step_hooked(&mut cov) != ExecResult::Halted {}
+        assert!(cov.executed[6] && cov.operand[7] && !cov.executed[7]);
+        assert_eq!(
+            cov.branches[6],
+            Branch {
+                taken: 1,
+                not_taken: 1
+            }
+        );
+        assert!(cov.read[128] && cov.written[128] && cov.read[129] && !cov.written[129]);
+        assert!(cov.written[130] && !cov.read[130]);
+
+        let report = cov.report(cpu.memory(), &SymbolTable::new(), false);
+        assert!(report.starts_with("6 instructions executed, 2 addresses read, 2 written"));
+        assert!(report.contains("\n0_ X - X - X - X - X - X . . . . ."));
+        assert!(report.contains("\n8_ B R W . "));
+        assert!(!report.contains("never"));
+
+        // without the second pass, the JN never falls through
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[JN, 0]);
+        cpu.set_acc(-1);
+        let mut cov = Coverage::new();
+        cpu.step_hooked(&mut cov);
+        let report = cov.report(cpu.memory(), &SymbolTable::new(), false);
+        assert!(report.contains("\n0_ ? - ."));
+        assert!(report.contains("00: JN 0x00 never fell through"));
+    }
+}
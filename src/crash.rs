@@ -0,0 +1,179 @@
+//! Diagnostic bundles for bug reports. The CLI and the GUI keep the
+//! context of the session up to date: the program loaded, the machine
+//! and the recent directives. When the simulator panics, the user is
+//! offered a zip of that context, with the panic and version info,
+//! to attach to an issue. Without a terminal to ask on, as when the
+//! GUI is started from a desktop, the zip is written to the temporary
+//! directory right away.
+use std::collections::VecDeque;
+use std::io::{BufRead, IsTerminal, Write};
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::cpu::Neander;
+
+/// How many directives the bundle keeps.
+const DIRECTIVES_LEN: usize = 50;
+
+/// What the session was doing, for the bundle.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub file: Option<PathBuf>,
+    /// The machine, as a binary state
+    pub state: Option<Vec<u8>>,
+    /// The last directives executed, oldest first
+    pub directives: VecDeque<String>,
+}
+
+static CONTEXT: Mutex<Option<Context>> = Mutex::new(None);
+
+/// Updates the context, if the crash reporter is installed.
+fn update(f: impl FnOnce(&mut Context)) {
+    // a panic while the lock was held must not stop the session
+    let mut context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(context) = context.as_mut() {
+        f(context);
+    }
+}
+pub fn set_file(file: Option<&Path>) {
+    update(|c| c.file = file.map(Path::to_path_buf));
+}
+pub fn set_machine(cpu: &Neander) {
+    update(|c| c.state = Some(cpu.state_bytes()));
+}
+pub fn record_directive(directive: &str) {
+    update(|c| {
+        if c.directives.len() == DIRECTIVES_LEN {
+            c.directives.pop_front();
+        }
+        c.directives.push_back(directive.trim_end().to_string());
+    });
+}
+
+/// Writes `files` as a zip archive at `path`.
+pub fn write(path: &Path, files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    for (name, contents) in files {
+        zip.start_file(name.as_str(), SimpleFileOptions::default())
+            .map_err(|e| e.to_string())?;
+        zip.write_all(contents).map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The files of the bundle of a panic, described by `message`.
+pub fn bundle(context: &Context, message: &str) -> Vec<(String, Vec<u8>)> {
+    let args: Vec<_> = std::env::args().collect();
+    let report = format!(
+        "neander {} on {} {}\ncommand: {}\n\n{message}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        args.join(" "),
+    );
+    let mut files = vec![("report.txt".to_string(), report.into_bytes())];
+    if let Some(file) = &context.file {
+        if let Ok(source) = std::fs::read(file) {
+            let name = file
+                .file_name()
+                .map_or("program".into(), |n| n.to_string_lossy());
+            files.push((format!("program/{name}"), source));
+        }
+    }
+    if let Some(state) = &context.state {
+        files.push(("state.bin".to_string(), state.clone()));
+    }
+    if !context.directives.is_empty() {
+        let directives: Vec<_> = context.directives.iter().map(String::as_str).collect();
+        files.push((
+            "directives.txt".to_string(),
+            format!("{}\n", directives.join("\n")).into_bytes(),
+        ));
+    }
+    files
+}
+
+/// Asks on the terminal whether to write the bundle, and writes it.
+fn offer(info: &PanicHookInfo) {
+    let context = match CONTEXT.try_lock() {
+        Ok(context) => context.clone().unwrap_or_default(),
+        Err(_) => Context::default(),
+    };
+    eprintln!("\nneander crashed. This is a bug in neander, not in your program.");
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    if interactive {
+        eprint!("Write a diagnostic bundle to attach to a bug report? [y/N] ");
+        let mut answer = String::new();
+        let _ = std::io::stdin().lock().read_line(&mut answer);
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            return;
+        }
+    }
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    let message = format!("{info}\n\nbacktrace:\n{backtrace}");
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let name = format!("neander-crash-{time}.zip");
+    let path = match interactive {
+        true => PathBuf::from(name),
+        false => std::env::temp_dir().join(name),
+    };
+    match write(&path, &bundle(&context, &message)) {
+        Ok(()) => eprintln!(
+            "Wrote {}. Please attach it to an issue at {}/issues",
+            path.display(),
+            env!("CARGO_PKG_REPOSITORY"),
+        ),
+        Err(e) => eprintln!("could not write the bundle: {e}"),
+    }
+}
+
+/// Starts recording the context, and offers a bundle on panics
+/// after printing them as usual.
+pub fn install() {
+    *CONTEXT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Context::default());
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+        offer(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_bundle() {
+        let mut context = Context {
+            state: Some(Neander::new().state_bytes()),
+            ..Default::default()
+        };
+        context.directives.push_back("step".to_string());
+        let files = bundle(&context, "panicked at cpu.rs");
+        let names: Vec<_> = files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["report.txt", "state.bin", "directives.txt"]);
+        assert!(String::from_utf8_lossy(&files[0].1).contains("panicked at cpu.rs"));
+
+        let path = std::env::temp_dir().join(format!("neander-crash-{}.zip", std::process::id()));
+        write(&path, &files).unwrap();
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut state = Vec::new();
+        archive
+            .by_name("state.bin")
+            .unwrap()
+            .read_to_end(&mut state)
+            .unwrap();
+        assert_eq!(state, files[1].1);
+        assert_eq!(archive.len(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Data files programs are run with, so they can be tested on
+//! datasets without editing their memory files. Files ending in
+//! `.csv` hold values in decimal or hexadecimal (0x..), separated by
+//! commas or lines, and other files hold raw bytes.
+use std::path::Path;
+
+use crate::memfile;
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+}
+
+/// Reads the bytes of a data file.
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    if !is_csv(path) {
+        return std::fs::read(path).map_err(|e| e.to_string());
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        for value in line.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let byte = memfile::parse_byte(value)
+                .map_err(|_| format!("line {}: invalid value: {value}", i + 1))?;
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Writes `bytes` to a data file, one unsigned value
+/// per line if it's a CSV file.
+pub fn write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let out = match is_csv(path) {
+        true => bytes
+            .iter()
+            .map(|b| format!("{b}\n"))
+            .collect::<String>()
+            .into_bytes(),
+        false => bytes.to_vec(),
+    };
+    std::fs::write(path, out).map_err(|e| e.to_string())
+}
+
+/// Copies `bytes` into `mem` from `start`, failing if they don't fit.
+pub fn load(mem: &mut [u8], start: u8, bytes: &[u8]) -> Result<(), String> {
+    let end = start as usize + bytes.len();
+    if end > mem.len() {
+        return Err(format!(
+            "{} bytes of data don't fit in memory from {start:#04X}",
+            bytes.len()
+        ));
+    }
+    mem[start as usize..end].copy_from_slice(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data() {
+        let dir = std::env::temp_dir();
+        let csv = dir.join("neander-data.csv");
+        std::fs::write(&csv, "1, 2,0x10\n-1\n\n255,\n").unwrap();
+        let bytes = read(&csv).unwrap();
+        assert_eq!(bytes, [1, 2, 16, 255, 255]);
+        write(&csv, &bytes[..3]).unwrap();
+        assert_eq!(std::fs::read_to_string(&csv).unwrap(), "1\n2\n16\n");
+        let bin = dir.join("neander-data.bin");
+        write(&bin, &bytes).unwrap();
+        assert_eq!(read(&bin).unwrap(), bytes);
+
+        let mut mem = [0; 256];
+        load(&mut mem, 0x80, &bytes).unwrap();
+        assert_eq!(mem[0x80..0x85], bytes);
+        assert!(load(&mut mem, 0xFE, &bytes).is_err());
+        std::fs::write(&csv, "1,x").unwrap();
+        assert_eq!(read(&csv).unwrap_err(), "line 1: invalid value: x");
+    }
+}
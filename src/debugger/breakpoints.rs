@@ -0,0 +1,157 @@
+//! Breakpoints and watchpoints kept between sessions. A breakpoints
+//! file has a header, then one line per program, with its file name,
+//! the addresses of its breakpoints and those it watches, after a `w`:
+//!
+//! ```text
+//! neander-breakpoints 2
+//! sum.mem: 4 10 w128
+//! ```
+//!
+//! Files from before the header or watchpoints were added are read
+//! the same, and get the new header when next saved.
+use std::path::{Path, PathBuf};
+
+use crate::formats::BREAKPOINTS;
+
+/// The breakpoints file used when none is given,
+/// in the directory of the program.
+pub const DEFAULT_FILE: &str = ".neander_bps";
+
+/// Returns the default breakpoints file of `program`.
+pub fn default_path(program: &Path) -> PathBuf {
+    program.with_file_name(DEFAULT_FILE)
+}
+
+fn key(program: &Path) -> String {
+    program
+        .file_name()
+        .map_or_else(|| program.to_string_lossy(), |n| n.to_string_lossy())
+        .into_owned()
+}
+
+/// The breakpoints and watched addresses of a program, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Points {
+    pub breakpoints: Vec<u8>,
+    pub watchpoints: Vec<u8>,
+}
+impl Points {
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty() && self.watchpoints.is_empty()
+    }
+}
+
+/// Returns the points saved for `program` in `source`.
+pub fn parse(source: &str, program: &Path) -> Result<Points, String> {
+    let key = key(program);
+    for (i, line) in source.lines().enumerate() {
+        if let Some(version) = BREAKPOINTS.parse_header(line) {
+            version?;
+            continue;
+        }
+        let Some((name, addrs)) = line.rsplit_once(':') else {
+            continue;
+        };
+        if name.trim() != key {
+            continue;
+        }
+        let mut points = Points::default();
+        for a in addrs.split_whitespace() {
+            let (list, addr) = match a.strip_prefix('w') {
+                Some(addr) => (&mut points.watchpoints, addr),
+                None => (&mut points.breakpoints, a),
+            };
+            let addr = addr
+                .parse()
+                .map_err(|_| format!("line {}: invalid address: {a}", i + 1))?;
+            list.push(addr);
+        }
+        return Ok(points);
+    }
+    Ok(Points::default())
+}
+
+/// Replaces the line of `program` in `source` with `points`,
+/// removing it if there are none.
+pub fn update(source: &str, program: &Path, points: &Points) -> String {
+    let key = key(program);
+    let mut out = format!("{}\n", BREAKPOINTS.header());
+    let lines = source
+        .lines()
+        .filter(|line| BREAKPOINTS.parse_header(line).is_none())
+        .filter(|line| line.rsplit_once(':').is_none_or(|(n, _)| n.trim() != key));
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+    if !points.is_empty() {
+        let breakpoints = points.breakpoints.iter().map(u8::to_string);
+        let watchpoints = points.watchpoints.iter().map(|a| format!("w{a}"));
+        let addrs: Vec<_> = breakpoints.chain(watchpoints).collect();
+        out.push_str(&format!("{key}: {}\n", addrs.join(" ")));
+    }
+    out
+}
+
+/// Reads the points of `program` from the file at `path`.
+/// A missing file has none.
+pub fn load(path: &Path, program: &Path) -> Result<Points, String> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => parse(&source, program),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Points::default()),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+/// Writes the points of `program` into the file at `path`,
+/// keeping those of other programs.
+pub fn save(path: &Path, program: &Path, points: &Points) -> Result<(), String> {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    let updated = update(&source, program, points);
+    if updated == source {
+        return Ok(());
+    }
+    std::fs::write(path, updated).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(breakpoints: &[u8], watchpoints: &[u8]) -> Points {
+        Points {
+            breakpoints: breakpoints.to_vec(),
+            watchpoints: watchpoints.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let sum = Path::new("dir/sum.mem");
+        let source = update("", sum, &points(&[4, 10], &[128]));
+        assert_eq!(source, "neander-breakpoints 2\nsum.mem: 4 10 w128\n");
+        let source = update(&source, Path::new("mul.mem"), &points(&[2], &[]));
+        assert_eq!(parse(&source, sum), Ok(points(&[4, 10], &[128])));
+        assert_eq!(parse(&source, Path::new("mul.mem")), Ok(points(&[2], &[])));
+        assert_eq!(parse(&source, Path::new("x.mem")), Ok(Points::default()));
+        let source = update(&source, sum, &Points::default());
+        assert_eq!(source, "neander-breakpoints 2\nmul.mem: 2\n");
+        assert!(parse("sum.mem: 300", sum).is_err());
+        assert!(parse("sum.mem: wx", sum).is_err());
+        // files without a header or of version 1 are migrated
+        assert_eq!(parse("sum.mem: 4", sum), Ok(points(&[4], &[])));
+        assert_eq!(
+            parse("neander-breakpoints 1\nsum.mem: 4", sum),
+            Ok(points(&[4], &[]))
+        );
+        assert_eq!(
+            update("sum.mem: 4\n", Path::new("mul.mem"), &points(&[2], &[])),
+            "neander-breakpoints 2\nsum.mem: 4\nmul.mem: 2\n"
+        );
+        assert!(parse("neander-breakpoints 3\nsum.mem: 4", sum).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Arithmetic expressions of the `print` directive.
+use crate::cpu::Neander;
+use crate::numfmt::NumberFormat;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// An expression over registers, memory cells, labels and literals.
+/// The accumulator and memory cells are read as signed bytes,
+/// labels as the address they name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(i64),
+    Acc,
+    Pc,
+    Label(String),
+    Mem(Box<Expr>),
+    Neg(Box<Expr>),
+    Bin(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, cpu: &Neander, symbols: &SymbolTable) -> Result<i64, String> {
+        Ok(match self {
+            Expr::Num(x) => *x,
+            Expr::Acc => cpu.acc() as i64,
+            Expr::Pc => cpu.pc() as i64,
+            Expr::Label(name) => symbols
+                .get(name)
+                .ok_or_else(|| format!("unknown label: {name}"))?
+                as i64,
+            Expr::Mem(addr) => {
+                let addr = addr.eval(cpu, symbols)?;
+                let addr = u8::try_from(addr).map_err(|_| format!("mem[{addr}] out of range"))?;
+                cpu.ram(addr) as i8 as i64
+            }
+            Expr::Neg(x) => x.eval(cpu, symbols)?.wrapping_neg(),
+            Expr::Bin(op, a, b) => {
+                let (a, b) = (a.eval(cpu, symbols)?, b.eval(cpu, symbols)?);
+                match op {
+                    Op::Add => a.wrapping_add(b),
+                    Op::Sub => a.wrapping_sub(b),
+                    Op::Mul => a.wrapping_mul(b),
+                    Op::Div | Op::Rem if b == 0 => return Err("division by zero".to_string()),
+                    Op::Div => a.wrapping_div(b),
+                    Op::Rem => a.wrapping_rem(b),
+                }
+            }
+        })
+    }
+}
+
+/// Writes `x` in decimal, hexadecimal and binary.
+pub(crate) fn fmt_value(x: i64, format: NumberFormat) -> String {
+    let sign = if x < 0 { "-" } else { "" };
+    let abs = x.unsigned_abs();
+    let dec = format.decimal(&x.to_string());
+    let bin = format.binary(&format!("{abs:b}"));
+    format!("{dec} = {sign}0x{abs:X} = {sign}0b{bin}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::parse_directive;
+    use crate::debugger::Directive;
+
+    fn eval(cpu: &Neander, symbols: &SymbolTable, src: &str) -> Result<i64, String> {
+        match parse_directive(&format!("print {src}")) {
+            Ok(Directive::Print(expr)) => expr.eval(cpu, symbols),
+            other => panic!("{src}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut cpu = Neander::new();
+        let mut symbols = SymbolTable::new();
+        assert!(symbols.insert("x", 130));
+        cpu.set_ram(128, 100);
+        cpu.set_ram(129, 0xFE);
+        cpu.set_ram(130, 3);
+        cpu.set_acc(-5);
+        cpu.set_pc(7);
+        assert_eq!(eval(&cpu, &symbols, "mem[128] + mem[129]"), Ok(98));
+        assert_eq!(eval(&cpu, &symbols, "acc*2 - -pc"), Ok(-3));
+        assert_eq!(eval(&cpu, &symbols, "(1 + 2) * 0x10 % 7"), Ok(6));
+        assert_eq!(eval(&cpu, &symbols, "mem[x] + x"), Ok(133));
+        assert_eq!(eval(&cpu, &symbols, "mem[ mem[130] + 125 ]"), Ok(100));
+        assert!(eval(&cpu, &symbols, "mem[256]").is_err());
+        assert!(eval(&cpu, &symbols, "1 / (pc - 7)").is_err());
+        assert!(eval(&cpu, &symbols, "y").is_err());
+        assert!(parse_directive("print 1 +").is_err());
+        assert_eq!(parse_directive("print"), Ok(Directive::PrintCpu));
+        assert_eq!(fmt_value(-10, NumberFormat::PLAIN), "-10 = -0xA = -0b1010");
+        let grouped = NumberFormat {
+            group_binary: true,
+            ..NumberFormat::PLAIN
+        };
+        assert_eq!(fmt_value(100, grouped), "100 = 0x64 = 0b110_0100");
+    }
+}
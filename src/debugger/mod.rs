@@ -0,0 +1,1286 @@
+//! The debugger behind the REPL and the GUI console.
+//! Directives are parsed by `parse_directive` and executed
+//! on a CPU by a `Debugger`, which writes their output as text.
+//! Lines may hold many directives separated by `;`, and aliases
+//! the user defines, which `execute_line` expands.
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ansi;
+use crate::clock::Clock;
+use crate::coverage::Coverage;
+use crate::cpu::{self, ExecResult, Neander, NeanderException};
+use crate::disasm::{self, DisasmLine};
+use crate::exercise::Exercise;
+use crate::explain::Explainer;
+use crate::history::History;
+use crate::hooks::Hooks;
+use crate::io::Bus;
+use crate::narrate;
+use crate::numfmt::{self, NumberFormat};
+use crate::render;
+use crate::smc::SmcWatch;
+use crate::srcmap::SourceMap;
+use crate::stats::Session;
+use crate::symbols::SymbolTable;
+use crate::traps::{Traps, Tripped};
+
+pub mod breakpoints;
+mod expr;
+pub use expr::{Expr, Op};
+mod parser;
+pub use parser::{parse_directive, DIRECTIVES};
+
+/// An address, given by number or by label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Addr {
+    Num(u8),
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    Step,
+    StepN(u32),
+    MicroStep,
+    /// Runs the next fetch, operand or execute phase
+    PhaseStep,
+    Undo,
+    SetAcc(i8),
+    SetPc(Addr),
+    SetMem(Addr, u8),
+    Load(PathBuf),
+    Reload,
+    /// Executes the directives of a file, one per line
+    Source(PathBuf),
+    BreakPoint(Addr),
+    Clear(Addr),
+    /// Stops runs when the program stores to the address
+    Watch(Addr),
+    Unwatch(Addr),
+    Continue,
+    PrintCpu,
+    /// Draws the registers and memory with box characters
+    View,
+    /// Explains the exception of the last step
+    Explain,
+    /// Prints which addresses were executed and which were
+    /// used as data since the program was loaded
+    Coverage,
+    /// Evaluates an expression, printing it in three bases
+    Print(expr::Expr),
+    PrintMemAddr(Addr),
+    PrintMemRange(Addr, Addr),
+    /// Whether dumps show the decimal values of bytes
+    DualDisplay(bool),
+    /// Turns on or off describing every step in plain language
+    Narrate(bool),
+    /// Turns on or off grouping binary digits
+    GroupDigits(bool),
+    /// Turns on or off warning of self-modifying code
+    WarnSmc(bool),
+    List,
+    ListAt(Addr),
+    ListRange(Addr, Addr),
+    Symbols,
+    /// Checks the program against the exercise
+    Goal,
+    /// Lists part of the debugging state
+    Info(Info),
+    /// Keeps a copy of the machine under a name
+    Save(String),
+    Restore(String),
+    /// Writes the machine state to a file, see `Neander::save_state`
+    SaveFile(PathBuf),
+    RestoreFile(PathBuf),
+    /// Defines a name for the directives of a text
+    Alias(String, String),
+    Unalias(String),
+    /// Lists the aliases
+    Aliases,
+    Help,
+    Quit,
+}
+
+/// The debugging state `info` lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Info {
+    Breakpoints,
+    Watchpoints,
+    /// The statistics of the session
+    Stats,
+    /// The file, labels and exercise
+    Program,
+}
+
+/// What the session should do after a directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    /// The CPU raised an exception while stepping
+    Exception,
+    Quit,
+}
+
+/// Why the last directive that ran the CPU stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// After the steps it was asked for
+    Step,
+    Halted,
+    Breakpoint,
+    /// The program stored to a watched address
+    Watchpoint,
+    /// A trap of the exercise was raised
+    Trap,
+    Exception(NeanderException),
+}
+impl Stop {
+    fn of(res: ExecResult) -> Self {
+        match res {
+            ExecResult::Halted => Stop::Halted,
+            ExecResult::Exception(e) => Stop::Exception(e),
+            _ => Stop::Step,
+        }
+    }
+}
+
+/// How many steps can be undone.
+const HISTORY_LEN: usize = 1000;
+/// How many instructions `list` shows before and after an address.
+const LIST_BEFORE: usize = 4;
+const LIST_AFTER: usize = 8;
+/// How deeply scripts may source other scripts.
+const MAX_SOURCE_DEPTH: usize = 8;
+/// How deeply aliases may use other aliases.
+const MAX_ALIAS_DEPTH: usize = 16;
+
+pub const HELP: &str = "valid directives:
+ - help, h: display this help
+ - step, s: execute the next instruction
+ - (step, s) n: execute the next n instructions
+ - microstep, ms: execute the next micro-operation of the current instruction
+ - ustep, us: execute the next phase of the current instruction:
+   fetch opcode, fetch operand or execute
+ - back, undo: revert the last step, micro-step or phase
+ - (breakpoint, b) i: set a breakpoint at instruction i
+ - (clear, cl) i: clear a breakpoint at instruction i
+ - watch addr, unwatch addr: stop runs when the program stores to addr,
+   or stop watching it
+ - set acc v, set pc v, set mem addr v: change a register or memory cell.
+   values may be decimal, negative or hexadecimal (0x..)
+ - continue, c: continue execution until next breakpoint
+ - cpu, show, print: print CPU content
+ - view: draw the registers and memory in a grid, marking the PC with >
+ - explain: explain the exception raised by the last step, and its likely cause
+ - coverage: map the addresses executed and those read or written since the
+   program was loaded, and list the branches that only went one way
+ - (print, show) expr: evaluate an expression and print it in decimal, hexadecimal
+   and binary. expressions may use + - * / %, parentheses, literals, labels,
+   acc, pc and mem[addr], e.g. `print mem[128] + mem[129]`.
+   acc and memory cells are read as signed bytes
+ - mem: print all memory
+ - mem (addr, start.., ..end, start..end): print memory in address or supplied range
+ - narrate on, narrate off: describe every step in a sentence, like
+   \"Fetched LDA 0x80: loaded 10 from 0x80 into the accumulator\"
+ - display dual, display plain: show bytes in memory dumps along with their
+   signed and unsigned decimal values, or only in hexadecimal
+ - group on, group off: split binary numbers in groups of 4 digits, like 0101_0101
+ - smc on, smc off: warn when an instruction runs from an address the program
+   stored to since it was loaded, usually a store over the program by mistake
+ - list, l: disassemble the instructions around the PC.
+   the PC is marked with `->` and breakpoints with `*`
+ - list (addr, start..end): disassemble around an address or in a range
+ - symbols, syms: list the labels of the memory file.
+   labels may be used anywhere an address is expected
+ - info breakpoints, info watchpoints, info stats, info program: list the
+   breakpoints or watchpoints set, the statistics of the session, or the
+   file, labels and exercise of the program
+ - goal: check the program against the cases of the exercise.
+   the goal is also reported whenever the number of passing cases changes
+ - save name, restore name: keep a snapshot of the machine, or go back to it
+ - save-file path, restore-file path: write the machine state to a file, or read it.
+   files ending in .json are written as JSON, others in binary
+ - load path: reset the CPU and load another memory file, keeping breakpoints
+   and watchpoints
+ - reload: reset the CPU and load the current memory file again
+ - source path: execute the directives in a file, one per line.
+   empty lines and lines starting with # are skipped
+ - alias name text: make `name` run the directives of `text`, followed by
+   the words after it, e.g. `alias sc s; cpu`. `alias` lists the aliases
+   and `unalias name` removes one
+ - several directives may be given on a line, separated by `;`,
+   e.g. `s 10; cpu; mem 128..140`
+ - quit, q: quit session";
+
+/// The state of a debugging session, apart from the CPU itself.
+pub struct Debugger {
+    /// The file the program was loaded from
+    pub file: Option<PathBuf>,
+    pub symbols: SymbolTable,
+    /// The source lines of the program, if it was assembled
+    pub source_map: SourceMap,
+    pub breakpoints: [bool; 256],
+    /// Addresses whose stores stop runs
+    pub watchpoints: [bool; 256],
+    /// Whether dumps show the decimal values of bytes
+    pub dual: bool,
+    /// How numbers are printed, see `crate::numfmt`
+    pub format: NumberFormat,
+    /// Whether the output has ANSI colors, see `crate::ansi`
+    pub color: bool,
+    /// Whether steps are described in plain language
+    pub narrate: bool,
+    /// The devices programs run with
+    pub bus: Bus,
+    /// The instructions after which `c` and `s n` give up, for
+    /// frontends that can't wait for a program that never halts
+    pub max_steps: Option<u64>,
+    /// Statistics of the session, see `crate::stats`
+    pub session: Session,
+    /// The text of every alias, by name
+    pub aliases: BTreeMap<String, String>,
+    exercise: Option<Exercise>,
+    /// How many cases passed when the goal was last reported
+    reported: Option<usize>,
+    /// The machine and the clock of the devices, by name
+    snapshots: BTreeMap<String, (Neander, Clock)>,
+    history: History,
+    coverage: Coverage,
+    /// Watches for self-modifying code, if `smc on`
+    smc: Option<SmcWatch>,
+    /// The traps of the exercise, checked on steps
+    traps: Option<Traps>,
+    /// How many `source` directives are being executed
+    sourcing: usize,
+    stop: Option<Stop>,
+    /// The exception of the last step, if it raised one
+    fault: Option<NeanderException>,
+}
+impl Debugger {
+    pub fn new(file: Option<PathBuf>, symbols: SymbolTable) -> Self {
+        Self {
+            file,
+            symbols,
+            source_map: SourceMap::default(),
+            breakpoints: [false; 256],
+            watchpoints: [false; 256],
+            dual: false,
+            format: numfmt::get(),
+            color: false,
+            narrate: false,
+            bus: Bus::new(),
+            max_steps: None,
+            session: Session::default(),
+            aliases: BTreeMap::new(),
+            exercise: None,
+            reported: None,
+            snapshots: BTreeMap::new(),
+            history: History::new(HISTORY_LEN),
+            coverage: Coverage::new(),
+            smc: None,
+            traps: None,
+            sourcing: 0,
+            stop: None,
+            fault: None,
+        }
+    }
+
+    /// Returns why the CPU last stopped, if it ran
+    /// since the last call.
+    pub fn take_stop(&mut self) -> Option<Stop> {
+        self.stop.take()
+    }
+
+    /// Returns the addresses with breakpoints, in order.
+    pub fn breakpoint_list(&self) -> Vec<u8> {
+        (0..=255)
+            .filter(|&a| self.breakpoints[a as usize])
+            .collect()
+    }
+    /// Returns the watched addresses, in order.
+    pub fn watchpoint_list(&self) -> Vec<u8> {
+        (0..=255)
+            .filter(|&a| self.watchpoints[a as usize])
+            .collect()
+    }
+
+    pub fn exercise(&self) -> Option<&Exercise> {
+        self.exercise.as_ref()
+    }
+    /// Starts `exercise`, writing its data into `cpu`.
+    pub fn set_exercise(&mut self, cpu: &mut Neander, exercise: Option<Exercise>) {
+        if let Some(ex) = &exercise {
+            ex.apply(cpu);
+        }
+        self.traps = exercise.as_ref().map(|ex| Traps::new(ex.traps.clone()));
+        self.exercise = exercise;
+        self.reported = None;
+    }
+
+    /// Executes the directives of `line`, stopping when one ends
+    /// the session. None is executed if any is invalid.
+    pub fn execute_line(
+        &mut self,
+        cpu: &mut Neander,
+        line: &str,
+        out: &mut impl Write,
+    ) -> Result<Flow, fmt::Error> {
+        self.execute_line_hooked(cpu, line, &mut (), out)
+    }
+    /// Same as `execute_line`, calling `hooks` on every step
+    /// along with those of the debugger.
+    pub fn execute_line_hooked(
+        &mut self,
+        cpu: &mut Neander,
+        line: &str,
+        hooks: &mut impl Hooks,
+        out: &mut impl Write,
+    ) -> Result<Flow, fmt::Error> {
+        let dirs = match self.parse_line(line) {
+            Ok(dirs) => dirs,
+            Err(e) => {
+                writeln!(out, "{e}")?;
+                return Ok(Flow::Continue);
+            }
+        };
+        for dir in dirs {
+            let flow = self.execute_hooked(cpu, dir, hooks, out)?;
+            if flow != Flow::Continue {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+    /// Parses the directives of `line`, separated by `;`, with
+    /// aliases replaced by their text.
+    pub fn parse_line(&self, line: &str) -> Result<Vec<Directive>, String> {
+        let mut texts = Vec::new();
+        self.expand(line, 0, &mut texts)?;
+        texts
+            .iter()
+            .map(|t| parse_directive(t).map_err(str::to_string))
+            .collect()
+    }
+    fn expand(&self, line: &str, depth: usize, texts: &mut Vec<String>) -> Result<(), String> {
+        for part in parser::split_line(line) {
+            let (name, args) = part.split_once(' ').unwrap_or((part, ""));
+            match self.aliases.get(name) {
+                Some(_) if depth == MAX_ALIAS_DEPTH => {
+                    return Err(format!(
+                        "alias {name} nests too deeply, does it use itself?"
+                    ));
+                }
+                Some(text) => {
+                    let text = format!("{text} {args}");
+                    self.expand(&text, depth + 1, texts)?;
+                }
+                None => texts.push(part.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes `dir` on `cpu`, writing its output to `out`.
+    /// With an exercise, reports the goal if it changed.
+    pub fn execute(
+        &mut self,
+        cpu: &mut Neander,
+        dir: Directive,
+        out: &mut impl Write,
+    ) -> Result<Flow, fmt::Error> {
+        self.execute_hooked(cpu, dir, &mut (), out)
+    }
+    /// Same as `execute`, calling `hooks` on every step.
+    pub fn execute_hooked(
+        &mut self,
+        cpu: &mut Neander,
+        dir: Directive,
+        hooks: &mut impl Hooks,
+        out: &mut impl Write,
+    ) -> Result<Flow, fmt::Error> {
+        let flow = self.directive(cpu, dir, hooks, out)?;
+        if let Some(ex) = &self.exercise {
+            let goal = ex.check(cpu);
+            if self.reported != Some(goal.passed()) {
+                writeln!(out, "{goal}")?;
+                self.reported = Some(goal.passed());
+            }
+        }
+        Ok(flow)
+    }
+
+    fn directive(
+        &mut self,
+        cpu: &mut Neander,
+        dir: Directive,
+        hooks: &mut impl Hooks,
+        out: &mut impl Write,
+    ) -> Result<Flow, fmt::Error> {
+        match dir {
+            Directive::Quit => return Ok(Flow::Quit),
+            Directive::Help => writeln!(out, "{HELP}")?,
+            Directive::Alias(name, text) => {
+                if parser::is_directive(&name) {
+                    writeln!(out, "{name} is a directive, it can't be an alias")?;
+                } else {
+                    writeln!(out, "alias {name}: {text}")?;
+                    self.aliases.insert(name, text);
+                }
+            }
+            Directive::Unalias(name) => {
+                if self.aliases.remove(&name).is_none() {
+                    writeln!(out, "no alias named {name}")?;
+                }
+            }
+            Directive::Aliases => {
+                if self.aliases.is_empty() {
+                    writeln!(out, "no aliases defined")?;
+                }
+                for (name, text) in &self.aliases {
+                    writeln!(out, "{name}: {text}")?;
+                }
+            }
+            Directive::BreakPoint(x) => {
+                let Some(x) = self.resolve(&x, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                if self.breakpoints[x as usize] {
+                    writeln!(out, "breakpoint already set at {x}")?;
+                } else {
+                    self.breakpoints[x as usize] = true;
+                    writeln!(out, "breakpoint set at {x}")?;
+                }
+            }
+            Directive::Clear(x) => {
+                let Some(x) = self.resolve(&x, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                if !self.breakpoints[x as usize] {
+                    writeln!(out, "no breakpoint at {x}")?;
+                } else {
+                    self.breakpoints[x as usize] = false;
+                    writeln!(out, "cleared breakpoint at {x}")?;
+                }
+            }
+            Directive::Watch(x) => {
+                let Some(x) = self.resolve(&x, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                if self.watchpoints[x as usize] {
+                    writeln!(out, "already watching {x}")?;
+                } else {
+                    self.watchpoints[x as usize] = true;
+                    writeln!(out, "watching {x}")?;
+                }
+            }
+            Directive::Unwatch(x) => {
+                let Some(x) = self.resolve(&x, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                if !self.watchpoints[x as usize] {
+                    writeln!(out, "not watching {x}")?;
+                } else {
+                    self.watchpoints[x as usize] = false;
+                    writeln!(out, "stopped watching {x}")?;
+                }
+            }
+            Directive::PrintCpu => writeln!(out, "{cpu}")?,
+            Directive::View => write!(out, "{}", render::render_ascii(cpu, self.color))?,
+            Directive::Coverage => {
+                let report = self
+                    .coverage
+                    .report(cpu.memory(), &self.symbols, self.color);
+                write!(out, "{report}")?
+            }
+            Directive::Explain => match self.fault {
+                Some(e) => {
+                    let trail = self.history.trail();
+                    let explainer = Explainer::new(cpu.memory(), &self.symbols, &trail);
+                    write!(out, "{}", explainer.explain(e))?
+                }
+                None => writeln!(out, "no exception to explain")?,
+            },
+            Directive::Print(expr) => match expr.eval(cpu, &self.symbols) {
+                Ok(x) => writeln!(out, "{}", expr::fmt_value(x, self.format))?,
+                Err(e) => writeln!(out, "error: {e}")?,
+            },
+            Directive::PrintMemAddr(a) => {
+                let Some(a) = self.resolve(&a, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                let val = cpu.memory()[a as usize];
+                let bin = |val: u8| self.format.binary(&format!("{val:b}"));
+                if self.dual {
+                    writeln!(out, "{} | {}", cpu::fmt_dual(val), bin(val))?;
+                } else {
+                    writeln!(out, "{val} | {val:X} | {}", bin(val))?;
+                }
+            }
+            Directive::PrintMemRange(a, b) => {
+                let Some((a, b)) = self.resolve_range(&a, &b, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                write!(
+                    out,
+                    "{}",
+                    cpu.mem_range_string(a.into(), b.into(), self.dual)
+                )?;
+            }
+            Directive::Narrate(on) => {
+                self.narrate = on;
+                let state = if on { "on" } else { "off" };
+                writeln!(out, "narration {state}")?;
+            }
+            Directive::GroupDigits(on) => {
+                self.format.group_binary = on;
+                let state = if on { "on" } else { "off" };
+                writeln!(out, "grouping binary digits {state}")?;
+            }
+            Directive::WarnSmc(on) => {
+                // stores before it was turned on aren't known
+                self.smc = on.then(|| self.smc.take().unwrap_or_default());
+                let state = if on { "on" } else { "off" };
+                writeln!(out, "self-modifying code warnings {state}")?;
+            }
+            Directive::DualDisplay(dual) => {
+                self.dual = dual;
+                if dual {
+                    writeln!(out, "showing signed and unsigned values")?;
+                } else {
+                    writeln!(out, "showing plain values")?;
+                }
+            }
+            Directive::List => {
+                let lines = disasm::window(
+                    cpu.memory(),
+                    cpu.pc(),
+                    LIST_BEFORE,
+                    LIST_AFTER,
+                    &self.symbols,
+                );
+                self.listing(cpu, &lines, out)?;
+            }
+            Directive::ListAt(a) => {
+                let Some(a) = self.resolve(&a, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                let lines = disasm::window(cpu.memory(), a, LIST_BEFORE, LIST_AFTER, &self.symbols);
+                self.listing(cpu, &lines, out)?;
+            }
+            Directive::ListRange(a, b) => {
+                let Some((a, b)) = self.resolve_range(&a, &b, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                let lines = disasm::disassemble_labeled(cpu.memory(), a, b, &self.symbols);
+                self.listing(cpu, &lines, out)?;
+            }
+            Directive::Symbols => {
+                if self.symbols.is_empty() {
+                    writeln!(out, "no labels defined")?;
+                }
+                for (name, addr) in self.symbols.iter() {
+                    writeln!(out, "{addr:3} | {addr:02X} | {name}")?;
+                }
+            }
+            Directive::Step => {
+                let before = cpu.registers();
+                let res = self.step(cpu, hooks, out)?;
+                let regs = ansi::registers(cpu, Some(&before), self.color);
+                match res {
+                    ExecResult::Halted => writeln!(out, "end of program reached")?,
+                    ExecResult::Normal => {
+                        writeln!(out, "{regs}")?;
+                        self.at_line(cpu, out)?;
+                    }
+                    ExecResult::MemWrite { addr, value } => {
+                        writeln!(out, "{regs}\nmem[{addr}] <- {value}")?;
+                        self.at_line(cpu, out)?;
+                    }
+                    ExecResult::Exception(e) => {
+                        writeln!(out, "exception: {e}")?;
+                        return Ok(Flow::Exception);
+                    }
+                }
+            }
+            Directive::MicroStep => {
+                let mut hooks = (
+                    &mut self.coverage,
+                    (&mut self.smc, (&mut self.traps, hooks)),
+                );
+                let (op, res) = self
+                    .history
+                    .step_uop_bus_hooked(cpu, &mut self.bus, &mut hooks);
+                self.record(res, !cpu.mid_instruction());
+                writeln!(out, "{op}")?;
+                self.report(out)?;
+                return micro_result(cpu, res, out);
+            }
+            Directive::PhaseStep => {
+                let mut hooks = (
+                    &mut self.coverage,
+                    (&mut self.smc, (&mut self.traps, hooks)),
+                );
+                let (phase, res) =
+                    self.history
+                        .micro_step_bus_hooked(cpu, &mut self.bus, &mut hooks);
+                self.record(res, !cpu.mid_instruction());
+                writeln!(out, "{phase}")?;
+                self.report(out)?;
+                return micro_result(cpu, res, out);
+            }
+            Directive::Undo => {
+                self.fault = None;
+                if self.history.undo(cpu) {
+                    writeln!(out, "{cpu}")?;
+                } else {
+                    writeln!(out, "nothing to undo")?;
+                }
+            }
+            Directive::SetAcc(v) => {
+                cpu.set_acc(v);
+                writeln!(out, "{cpu}")?;
+            }
+            Directive::SetPc(v) => {
+                let Some(v) = self.resolve(&v, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                cpu.set_pc(v);
+                writeln!(out, "{cpu}")?;
+            }
+            Directive::SetMem(addr, v) => {
+                let Some(addr) = self.resolve(&addr, out)? else {
+                    return Ok(Flow::Continue);
+                };
+                if self.exercise.as_ref().is_some_and(|ex| ex.is_locked(addr)) {
+                    writeln!(out, "mem[{addr}] is locked by the exercise")?;
+                } else {
+                    cpu.set_ram(addr, v);
+                    writeln!(out, "mem[{addr}] <- {}", v as i8)?;
+                }
+            }
+            Directive::Goal => match &self.exercise {
+                Some(ex) => {
+                    let goal = ex.check(cpu);
+                    writeln!(out, "{}", ex.title)?;
+                    if !ex.text.is_empty() {
+                        writeln!(out, "{}", ex.text)?;
+                    }
+                    writeln!(out, "{goal}")?;
+                    for (name, failures) in &goal.failed {
+                        writeln!(out, "case {name} failed:")?;
+                        for f in failures {
+                            writeln!(out, "    {f}")?;
+                        }
+                    }
+                    self.reported = Some(goal.passed());
+                }
+                None => writeln!(out, "no exercise loaded")?,
+            },
+            Directive::Info(info) => self.info(cpu, info, out)?,
+            Directive::Save(name) => {
+                writeln!(out, "saved snapshot {name}")?;
+                self.snapshots.insert(name, (cpu.clone(), self.bus.clock()));
+            }
+            Directive::Restore(name) => match self.snapshots.get(&name) {
+                Some((snapshot, clock)) => {
+                    self.history.clear();
+                    self.fault = None;
+                    *cpu = snapshot.clone();
+                    self.bus.set_clock(*clock);
+                    writeln!(out, "{cpu}")?;
+                }
+                None => writeln!(out, "no snapshot named {name}")?,
+            },
+            Directive::SaveFile(path) => match cpu.save_state(&path) {
+                Ok(()) => writeln!(out, "saved state to {}", path.display())?,
+                Err(e) => writeln!(out, "error: {e}")?,
+            },
+            Directive::RestoreFile(path) => match Neander::load_state(&path) {
+                Ok(new) => {
+                    self.history.clear();
+                    self.fault = None;
+                    *cpu = new;
+                    writeln!(out, "{cpu}")?;
+                }
+                Err(e) => writeln!(out, "error: {e}")?,
+            },
+            Directive::Load(path) => self.load(cpu, path, out)?,
+            Directive::Source(path) => {
+                if self.sourcing == MAX_SOURCE_DEPTH {
+                    writeln!(out, "error: scripts nested too deeply")?;
+                    return Ok(Flow::Continue);
+                }
+                self.sourcing += 1;
+                let flow = self.source(cpu, &path, hooks, out);
+                self.sourcing -= 1;
+                return flow;
+            }
+            Directive::Reload => match self.file.clone() {
+                Some(file) => self.load(cpu, file, out)?,
+                None => writeln!(out, "no file to reload")?,
+            },
+            Directive::StepN(n) => self.run(cpu, Some(n), hooks, out)?,
+            Directive::Continue => self.run(cpu, None, hooks, out)?,
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Executes the lines of directives in the file at `path`, echoing them.
+    /// Stops at the first invalid line, or when a directive
+    /// ends the session.
+    fn source(
+        &mut self,
+        cpu: &mut Neander,
+        path: &Path,
+        hooks: &mut impl Hooks,
+        out: &mut impl Write,
+    ) -> Result<Flow, fmt::Error> {
+        let script = match std::fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                writeln!(out, "error: {}: {e}", path.display())?;
+                return Ok(Flow::Continue);
+            }
+        };
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            writeln!(out, "> {line}")?;
+            let dirs = match self.parse_line(line) {
+                Ok(dirs) => dirs,
+                Err(e) => {
+                    writeln!(out, "{}:{}: {e}", path.display(), i + 1)?;
+                    return Ok(Flow::Continue);
+                }
+            };
+            for dir in dirs {
+                let flow = self.execute_hooked(cpu, dir, hooks, out)?;
+                if flow != Flow::Continue {
+                    return Ok(flow);
+                }
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Executes an instruction, recording it in the history and
+    /// the session, and narrating it if narration is on.
+    fn step(
+        &mut self,
+        cpu: &mut Neander,
+        hooks: &mut impl Hooks,
+        out: &mut impl Write,
+    ) -> Result<ExecResult, fmt::Error> {
+        let before = self.narrate.then(|| cpu.clone());
+        let mut hooks = (
+            &mut self.coverage,
+            (&mut self.smc, (&mut self.traps, hooks)),
+        );
+        let res = self.history.step_bus_hooked(cpu, &mut self.bus, &mut hooks);
+        self.record(res, true);
+        self.report(out)?;
+        if let Some(before) = before {
+            writeln!(
+                out,
+                "{}",
+                narrate::narrate(&before, cpu, res, &self.symbols)
+            )?;
+        }
+        Ok(res)
+    }
+    /// Writes the self-modifying code warnings and the trap raised
+    /// by the last step. A trap is forgotten once reported, so the
+    /// next steps can raise it again.
+    fn report(&mut self, out: &mut impl Write) -> fmt::Result {
+        for warning in self.smc.as_mut().map(SmcWatch::take).unwrap_or_default() {
+            let text = ansi::paint(ansi::BREAKPOINT, "warning", self.color);
+            writeln!(out, "{text}: {warning}")?;
+        }
+        if let Some(tripped) = self.take_tripped() {
+            self.stop = Some(Stop::Trap);
+            let text = ansi::paint(ansi::BREAKPOINT, "trap", self.color);
+            writeln!(out, "{text}: {tripped}")?;
+        }
+        Ok(())
+    }
+    fn take_tripped(&mut self) -> Option<Tripped> {
+        let traps = self.traps.as_mut()?;
+        let tripped = traps.tripped().cloned();
+        if tripped.is_some() {
+            traps.reset();
+        }
+        tripped
+    }
+    /// Records the result of a step, or of a micro-step
+    /// that may have finished an instruction.
+    fn record(&mut self, res: ExecResult, finished: bool) {
+        self.session.record(res, finished);
+        self.stop = Some(Stop::of(res));
+        self.fault = match res {
+            ExecResult::Exception(e) => Some(e),
+            _ => None,
+        };
+    }
+
+    /// Steps `n` times, or until the program ends if `None`,
+    /// stopping at breakpoints, watchpoints and traps. Traps on steps count from here.
+    fn run(
+        &mut self,
+        cpu: &mut Neander,
+        n: Option<u32>,
+        hooks: &mut impl Hooks,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        if let Some(traps) = &mut self.traps {
+            traps.reset();
+        }
+        let mut steps = 0;
+        while n.is_none_or(|n| steps < n) {
+            steps += 1;
+            let res = self.step(cpu, hooks, out)?;
+            if self.stop == Some(Stop::Trap) {
+                return self.at_line(cpu, out);
+            }
+            match res {
+                ExecResult::Halted => {
+                    return writeln!(out, "end of program reached");
+                }
+                ExecResult::MemWrite { addr, value } => {
+                    writeln!(out, "mem[{addr}] <- {value}")?;
+                    if self.watchpoints[addr as usize] {
+                        self.stop = Some(Stop::Watchpoint);
+                        let text = ansi::paint(ansi::BREAKPOINT, "watchpoint hit", self.color);
+                        writeln!(out, "{text}")?;
+                        return self.at_line(cpu, out);
+                    }
+                }
+                ExecResult::Normal => (),
+                ExecResult::Exception(e) => {
+                    return writeln!(out, "exception: {e}");
+                }
+            }
+            if self.breakpoints[cpu.pc() as usize] {
+                self.stop = Some(Stop::Breakpoint);
+                let text = ansi::paint(ansi::BREAKPOINT, "breakpoint reached", self.color);
+                writeln!(out, "{text}")?;
+                return self.at_line(cpu, out);
+            }
+            if self.max_steps == Some(steps.into()) && n.is_none_or(|n| n > steps) {
+                let e = NeanderException::StepLimitExceeded(steps.into());
+                self.stop = Some(Stop::Exception(e));
+                writeln!(out, "exception: {e}")?;
+                return self.at_line(cpu, out);
+            }
+        }
+        self.at_line(cpu, out)
+    }
+    /// Writes the source line of the next instruction,
+    /// if the program was assembled.
+    fn at_line(&self, cpu: &Neander, out: &mut impl Write) -> fmt::Result {
+        match self.source_map.describe(cpu.pc()) {
+            Some(line) => writeln!(out, "at {line}"),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces `cpu` with a fresh one loaded from `file`.
+    /// On error, `cpu` is left untouched.
+    fn load(&mut self, cpu: &mut Neander, file: PathBuf, out: &mut impl Write) -> fmt::Result {
+        let mut new = Neander::new();
+        match new.load_file_mapped(&file) {
+            Ok((symbols, source_map)) => {
+                if let Some(ex) = &self.exercise {
+                    ex.apply(&mut new);
+                }
+                *cpu = new;
+                writeln!(out, "loaded {}", file.display())?;
+                self.file = Some(file);
+                self.symbols = symbols;
+                self.source_map = source_map;
+                self.coverage.clear();
+                if let Some(smc) = &mut self.smc {
+                    smc.clear();
+                }
+                if let Some(traps) = &mut self.traps {
+                    traps.reset();
+                }
+                self.history.clear();
+                self.fault = None;
+                let mut clock = self.bus.clock();
+                clock.reset();
+                self.bus.set_clock(clock);
+            }
+            Err(e) => writeln!(out, "error: {e}")?,
+        }
+        Ok(())
+    }
+
+    /// Lists part of the debugging state.
+    fn info(&self, cpu: &Neander, info: Info, out: &mut impl Write) -> fmt::Result {
+        match info {
+            Info::Breakpoints => {
+                let list = self.breakpoint_list();
+                if list.is_empty() {
+                    return writeln!(out, "no breakpoints set");
+                }
+                for addr in list {
+                    let name = self.symbols.name_at(addr).unwrap_or("");
+                    writeln!(out, "{addr:3} | {addr:02X} | {name}")?;
+                }
+            }
+            Info::Watchpoints => {
+                let list = self.watchpoint_list();
+                if list.is_empty() {
+                    return writeln!(out, "no watchpoints set");
+                }
+                for addr in list {
+                    let name = self.symbols.name_at(addr).unwrap_or("");
+                    writeln!(out, "{addr:3} | {addr:02X} | {name}")?;
+                }
+            }
+            Info::Stats => {
+                let s = &self.session;
+                let steps = self.format.count(s.steps);
+                writeln!(out, "instructions executed: {steps}")?;
+                writeln!(out, "halted: {}", if s.halted { "yes" } else { "no" })?;
+                if s.exceptions.is_empty() {
+                    writeln!(out, "exceptions: none")?;
+                } else {
+                    writeln!(out, "exceptions:")?;
+                    for (e, n) in &s.exceptions {
+                        writeln!(out, "    {n} x {e}")?;
+                    }
+                }
+                writeln!(out, "steps to undo: {}", self.history.len())?;
+                let names: Vec<_> = self.snapshots.keys().map(String::as_str).collect();
+                match names.is_empty() {
+                    true => writeln!(out, "snapshots: none")?,
+                    false => writeln!(out, "snapshots: {}", names.join(", "))?,
+                }
+            }
+            Info::Program => {
+                match &self.file {
+                    Some(file) => writeln!(out, "file: {}", file.display())?,
+                    None => writeln!(out, "file: none")?,
+                }
+                writeln!(out, "labels: {}", self.symbols.len())?;
+                match &self.exercise {
+                    Some(ex) => writeln!(out, "exercise: {}, {}", ex.title, ex.check(cpu))?,
+                    None => writeln!(out, "exercise: none")?,
+                }
+                writeln!(out, "{cpu}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up the address of `addr`, reporting unknown labels.
+    fn resolve(&self, addr: &Addr, out: &mut impl Write) -> Result<Option<u8>, fmt::Error> {
+        match addr {
+            Addr::Num(x) => Ok(Some(*x)),
+            Addr::Label(name) => {
+                let x = self.symbols.get(name);
+                if x.is_none() {
+                    writeln!(out, "unknown label: {name}")?;
+                }
+                Ok(x)
+            }
+        }
+    }
+
+    /// Looks up the addresses of a range, reporting unknown
+    /// labels and ranges that end before they start.
+    fn resolve_range(
+        &self,
+        start: &Addr,
+        end: &Addr,
+        out: &mut impl Write,
+    ) -> Result<Option<(u8, u8)>, fmt::Error> {
+        let (Some(a), Some(b)) = (self.resolve(start, out)?, self.resolve(end, out)?) else {
+            return Ok(None);
+        };
+        if a > b {
+            writeln!(out, "error: the range {a}..{b} ends before it starts")?;
+            return Ok(None);
+        }
+        Ok(Some((a, b)))
+    }
+
+    /// Writes disassembled lines, marking the PC with `->`
+    /// and breakpoints with `*`.
+    fn listing(&self, cpu: &Neander, lines: &[DisasmLine], out: &mut impl Write) -> fmt::Result {
+        for line in lines {
+            if let Some(name) = self.symbols.name_at(line.addr) {
+                writeln!(out, "{}:", ansi::paint(ansi::LABEL, name, self.color))?;
+            }
+            let pc = if line.addr == cpu.pc() { "->" } else { "  " };
+            let bp = if self.breakpoints[line.addr as usize] {
+                ansi::paint(ansi::BREAKPOINT, '*', self.color)
+            } else {
+                " ".to_string()
+            };
+            let bytes: Vec<_> = line.bytes.iter().map(|b| format!("{b:02X}")).collect();
+            writeln!(
+                out,
+                "{pc}{bp} {0:02X} ({0:03}): {1:<5}  {2}",
+                line.addr,
+                bytes.join(" "),
+                ansi::instruction(&line.text, self.color)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the datapath after a micro-step or phase, and its result.
+fn micro_result(cpu: &Neander, res: ExecResult, out: &mut impl Write) -> Result<Flow, fmt::Error> {
+    writeln!(
+        out,
+        "MAR: {:02X} | MDR: {:02X} | IR: {:02X}",
+        cpu.mar(),
+        cpu.mdr(),
+        cpu.ir()
+    )?;
+    match res {
+        ExecResult::Halted => writeln!(out, "end of program reached")?,
+        ExecResult::Normal => writeln!(out, "{cpu}")?,
+        ExecResult::MemWrite { addr, value } => writeln!(out, "{cpu}\nmem[{addr}] <- {value}")?,
+        ExecResult::Exception(e) => {
+            writeln!(out, "exception: {e}")?;
+            return Ok(Flow::Exception);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_execute() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, ADD, 128, ADD, 128, HLT]);
+        cpu.set_ram(128, 2);
+        let mut symbols = SymbolTable::new();
+        symbols.insert("last", 4);
+        let mut dbg = Debugger::new(None, symbols);
+        let mut out = String::new();
+        let mut exec = |cpu: &mut Neander, dir: &str| {
+            let dir = parse_directive(dir).unwrap();
+            dbg.execute(cpu, dir, &mut out).unwrap()
+        };
+        assert_eq!(exec(&mut cpu, "b last"), Flow::Continue);
+        exec(&mut cpu, "c");
+        assert_eq!((cpu.pc(), cpu.acc()), (4, 4));
+        exec(&mut cpu, "undo");
+        assert_eq!(cpu.pc(), 2);
+        exec(&mut cpu, "b nowhere");
+        exec(&mut cpu, "mem 200..10");
+        exec(&mut cpu, "list 200..10");
+        assert_eq!(exec(&mut cpu, "q"), Flow::Quit);
+        assert_eq!(
+            out,
+            "breakpoint set at 4\nbreakpoint reached\nSTATE:\nAC: 2 | 0x2 | 0b10\n\
+             PC: 2 | 0x2 | 0b10\nN: 0, Z: 0\nunknown label: nowhere\n\
+             error: the range 200..10 ends before it starts\n\
+             error: the range 200..10 ends before it starts\n"
+        );
+
+        // c and s n give up after max_steps
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[JMP, 0]);
+        let mut dbg = Debugger::new(None, SymbolTable::new());
+        dbg.max_steps = Some(5);
+        let mut out = String::new();
+        dbg.execute_line(&mut cpu, "c", &mut out).unwrap();
+        assert_eq!(out, "exception: did not halt after 5 instructions\n");
+        out.clear();
+        dbg.execute_line(&mut cpu, "s 5; s 8", &mut out).unwrap();
+        assert_eq!(out, "exception: did not halt after 5 instructions\n");
+
+        // c stops after a store to a watched address
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, STA, 130, HLT]);
+        cpu.set_ram(128, 3);
+        let mut dbg = Debugger::new(None, SymbolTable::new());
+        let mut out = String::new();
+        dbg.execute_line(&mut cpu, "info watchpoints; watch 130; c", &mut out)
+            .unwrap();
+        assert_eq!((cpu.pc(), dbg.take_stop()), (6, Some(Stop::Watchpoint)));
+        assert_eq!(
+            out,
+            "no watchpoints set\nwatching 130\nmem[129] <- 3\nmem[130] <- 3\nwatchpoint hit\n"
+        );
+        out.clear();
+        dbg.execute_line(
+            &mut cpu,
+            "info watchpoints; unwatch 130; unwatch 130",
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(out, "130 | 82 | \nstopped watching 130\nnot watching 130\n");
+    }
+
+    #[test]
+    fn test_lines_and_aliases() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, ADD, 128, ADD, 128, HLT]);
+        cpu.set_ram(128, 2);
+        let mut dbg = Debugger::new(None, SymbolTable::new());
+        let mut out = String::new();
+        let mut exec = |cpu: &mut Neander, line: &str| {
+            out.clear();
+            let flow = dbg.execute_line(cpu, line, &mut out).unwrap();
+            (flow, out.clone())
+        };
+        exec(&mut cpu, "s; set acc 7; print acc");
+        assert_eq!((cpu.pc(), cpu.acc()), (2, 7));
+        let (_, text) = exec(&mut cpu, "alias go s; print acc +");
+        assert_eq!(text, "alias go: s; print acc +\n");
+        let (_, text) = exec(&mut cpu, "go 1");
+        assert!(text.ends_with("10 = 0xA = 0b1010\n"), "{text}");
+        // nothing runs if a directive is invalid
+        let (_, text) = exec(&mut cpu, "s; nonsense");
+        assert_eq!(cpu.pc(), 4);
+        assert!(text.starts_with("Invalid directive"));
+        exec(&mut cpu, "alias loop loop");
+        assert!(exec(&mut cpu, "loop").1.contains("nests too deeply"));
+        assert_eq!(
+            exec(&mut cpu, "alias s c").1,
+            "s is a directive, it can't be an alias\n"
+        );
+        exec(&mut cpu, "unalias loop");
+        assert_eq!(exec(&mut cpu, "alias").1, "go: s; print acc +\n");
+        assert_eq!(exec(&mut cpu, "go 0; q; s").0, Flow::Quit);
+        assert_eq!(cpu.pc(), 6);
+
+        // hooks given with a line see its steps and micro-steps
+        let mut profile = crate::profile::Profile::new();
+        cpu.set_pc(0);
+        dbg.execute_line_hooked(&mut cpu, "s; ms; s 2", &mut profile, &mut String::new())
+            .unwrap();
+        assert_eq!(profile.counts[..6], [1, 0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_source_lines() {
+        let mut cpu = Neander::new();
+        let source = "start: lda x\n  add x ; twice\n  hlt\nx: 3";
+        let (symbols, map) = crate::asm::assemble_mapped(cpu.memory_mut(), source).unwrap();
+        let mut dbg = Debugger::new(None, symbols);
+        dbg.source_map = map;
+        let mut out = String::new();
+        dbg.execute(&mut cpu, parse_directive("b 4").unwrap(), &mut out)
+            .unwrap();
+        out.clear();
+        dbg.execute(&mut cpu, Directive::Continue, &mut out)
+            .unwrap();
+        assert_eq!(out, "breakpoint reached\nat line 3: hlt\n");
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, HLT]);
+        cpu.set_ram(128, 7);
+        let mut dbg = Debugger::new(None, SymbolTable::new());
+        let mut out = String::new();
+        let mut exec = |cpu: &mut Neander, dir: &str| {
+            let dir = parse_directive(dir).unwrap();
+            dbg.execute(cpu, dir, &mut out).unwrap();
+        };
+        exec(&mut cpu, "save start");
+        exec(&mut cpu, "c");
+        assert_eq!(cpu.ram(129), 7);
+        exec(&mut cpu, "restore start");
+        assert_eq!((cpu.pc(), cpu.ram(129)), (0, 0));
+        exec(&mut cpu, "restore nothing");
+        assert!(out.ends_with("no snapshot named nothing\n"));
+        let mut out = String::new();
+        dbg.execute(&mut cpu, Directive::Info(Info::Stats), &mut out)
+            .unwrap();
+        assert_eq!(
+            out,
+            "instructions executed: 3\nhalted: yes\nexceptions: none\n\
+             steps to undo: 0\nsnapshots: start\n"
+        );
+        // the clock of the devices went back too
+        assert_eq!(dbg.bus.clock().ticks, 0);
+    }
+
+    #[test]
+    fn test_source() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, HLT]);
+        cpu.set_ram(128, 7);
+        let path = std::env::temp_dir().join(format!("neander-script-{}", std::process::id()));
+        let script = "# demo\n\nb 2\nc\nset acc 1\nbogus\nset acc 2\n";
+        std::fs::write(&path, script).unwrap();
+        let mut dbg = Debugger::new(None, SymbolTable::new());
+        let mut out = String::new();
+        let flow = dbg.execute(&mut cpu, Directive::Source(path.clone()), &mut out);
+        assert_eq!(flow, Ok(Flow::Continue));
+        assert_eq!((cpu.pc(), cpu.acc()), (2, 1));
+        assert!(out.starts_with("> b 2\n"));
+        assert!(out.contains(":6: Invalid directive"));
+        // a script sourcing itself stops at the depth limit
+        std::fs::write(&path, format!("source {}", path.display())).unwrap();
+        out.clear();
+        dbg.execute(&mut cpu, Directive::Source(path.clone()), &mut out)
+            .unwrap();
+        assert!(out.ends_with("error: scripts nested too deeply\n"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_exercise() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, HLT]);
+        let exercise = Exercise::from_toml(
+            "title = \"t\"\ndata = { 128 = 3 }\n\
+             [[case]]\nname = \"c\"\nexpect = { acc = 4 }",
+        )
+        .unwrap();
+        let mut dbg = Debugger::new(None, SymbolTable::new());
+        dbg.set_exercise(&mut cpu, Some(exercise));
+        assert_eq!(cpu.ram(128), 3);
+        let mut out = String::new();
+        let mut exec = |cpu: &mut Neander, dir: &str| {
+            let dir = parse_directive(dir).unwrap();
+            dbg.execute(cpu, dir, &mut out).unwrap();
+        };
+        exec(&mut cpu, "set mem 128 4");
+        exec(&mut cpu, "set mem 1 129");
+        exec(&mut cpu, "set mem 129 4");
+        exec(&mut cpu, "set mem 130 4");
+        assert_eq!(
+            out,
+            "mem[128] is locked by the exercise\ngoal not met: 0 of 1 cases pass\n\
+             mem[1] <- -127\nmem[129] <- 4\ngoal met: 1 of 1 cases pass\nmem[130] <- 4\n"
+        );
+
+        // its traps stop runs and micro-steps
+        let exercise = Exercise::from_toml(
+            "title = \"t\"\n[[case]]\nname = \"c\"\nexpect = { acc = 4 }\n\
+             [[trap]]\nread = \"128\"",
+        )
+        .unwrap();
+        cpu.set_ram_slice(0, &[LDA, 128, NOP, HLT]);
+        dbg.set_exercise(&mut cpu, Some(exercise));
+        let mut out = String::new();
+        dbg.execute_line(&mut cpu, "c", &mut out).unwrap();
+        assert!(out.contains("trap: read from 80 (instruction at 00, step 0)\n"));
+        assert_eq!((cpu.pc(), dbg.take_stop()), (2, Some(Stop::Trap)));
+        dbg.execute_line(&mut cpu, "set pc 0; us; us", &mut out)
+            .unwrap();
+        assert_eq!(out.matches("trap:").count(), 1);
+        dbg.execute_line(&mut cpu, "us", &mut out).unwrap();
+        assert_eq!(out.matches("trap:").count(), 2);
+    }
+}
@@ -0,0 +1,562 @@
+//! The nom parser of the directive language.
+use std::str::FromStr;
+
+use super::expr::{Expr, Op};
+use super::{Addr, Directive, Info};
+use crate::symbols;
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while1;
+use nom::character::complete::{char, digit1, hex_digit1, multispace0, one_of};
+use nom::combinator::eof;
+use nom::combinator::map_res;
+use nom::combinator::opt;
+use nom::combinator::recognize;
+use nom::combinator::rest;
+use nom::combinator::verify;
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::{IResult, Parser};
+use std::path::PathBuf;
+
+pub fn parse_directive(input: &str) -> Result<Directive, &'static str> {
+    match directive(input) {
+        Ok(("", dir)) => Ok(dir),
+        _ => Err("Invalid directive. For valid directives, type `help`"),
+    }
+}
+
+/// The names of the directives, without their short forms,
+/// for completion.
+pub const DIRECTIVES: &[&str] = &[
+    "alias",
+    "back",
+    "breakpoint",
+    "clear",
+    "continue",
+    "coverage",
+    "cpu",
+    "display",
+    "explain",
+    "goal",
+    "group",
+    "help",
+    "info",
+    "list",
+    "load",
+    "mem",
+    "microstep",
+    "narrate",
+    "print",
+    "quit",
+    "reload",
+    "restore",
+    "restore-file",
+    "save",
+    "save-file",
+    "set",
+    "show",
+    "smc",
+    "source",
+    "step",
+    "symbols",
+    "unalias",
+    "undo",
+    "unwatch",
+    "ustep",
+    "view",
+    "watch",
+];
+
+/// The short forms of directives.
+const SHORT_FORMS: &[&str] = &["b", "bp", "c", "cl", "h", "l", "ms", "q", "s", "syms", "us"];
+
+/// Whether `name` is a directive, in long or short form.
+pub fn is_directive(name: &str) -> bool {
+    DIRECTIVES.contains(&name) || SHORT_FORMS.contains(&name)
+}
+
+/// Splits a line into its directives, separated by `;`. An alias
+/// takes the rest of the line, so its text may hold more directives.
+pub fn split_line(line: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        if word("alias").parse(rest).is_ok() {
+            parts.push(rest);
+            break;
+        }
+        let (part, next) = rest.split_once(';').unwrap_or((rest, ""));
+        parts.push(part.trim());
+        rest = next.trim();
+    }
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn directive(input: &str) -> IResult<&str, Directive> {
+    alt((
+        quit, cont, step, microstep, phase_step, undo, set, load, reload, mem, list, inspect,
+        breakpoint, clear, syms, goal, display, snapshot, print, help, alias,
+    ))
+    .parse(input.trim())
+}
+
+fn help(input: &str) -> IResult<&str, Directive> {
+    word("h")
+        .or(word("help"))
+        .map(|_| Directive::Help)
+        .parse(input)
+}
+
+fn mem(input: &str) -> IResult<&str, Directive> {
+    let end_range = preceded(tag(".."), addr).map(|x| Directive::PrintMemRange(Addr::Num(0), x));
+    let range = pair(addr, opt(preceded(tag(".."), opt(addr)))).map(|(a, n)| match n {
+        None => Directive::PrintMemAddr(a),
+        Some(None) => Directive::PrintMemRange(a, Addr::Num(255)),
+        Some(Some(b)) => Directive::PrintMemRange(a, b),
+    });
+
+    let range = end_range.or(range);
+    let mem = word("mem").map(|_| Directive::PrintMemRange(Addr::Num(0), Addr::Num(255)));
+    let mem_range = pair(word("mem"), range).map(|(_, d)| d);
+    mem_range.or(mem).parse(input)
+}
+fn list(input: &str) -> IResult<&str, Directive> {
+    let range = pair(addr, opt(preceded(tag(".."), addr))).map(|(a, b)| match b {
+        None => Directive::ListAt(a),
+        Some(b) => Directive::ListRange(a, b),
+    });
+    let list = word("list").or(word("l"));
+    pair(list, opt(range))
+        .map(|(_, d)| d.unwrap_or(Directive::List))
+        .parse(input)
+}
+fn quit(input: &str) -> IResult<&str, Directive> {
+    word("quit")
+        .or(word("q"))
+        .map(|_| Directive::Quit)
+        .parse(input)
+}
+/// Directives that only show the machine.
+fn inspect(input: &str) -> IResult<&str, Directive> {
+    let view = word("view").map(|_| Directive::View);
+    let explain = word("explain").map(|_| Directive::Explain);
+    let coverage = word("coverage").map(|_| Directive::Coverage);
+    let topic = alt((
+        word("breakpoints").map(|_| Info::Breakpoints),
+        word("watchpoints").map(|_| Info::Watchpoints),
+        word("stats").map(|_| Info::Stats),
+        word("program").map(|_| Info::Program),
+    ));
+    let info = preceded(word("info"), topic).map(Directive::Info);
+    word("cpu")
+        .map(|_| Directive::PrintCpu)
+        .or(view)
+        .or(explain)
+        .or(coverage)
+        .or(info)
+        .parse(input)
+}
+fn cont(input: &str) -> IResult<&str, Directive> {
+    word("continue")
+        .or(word("c"))
+        .map(|_| Directive::Continue)
+        .parse(input)
+}
+//fn parse_directive(input: &str) -> Result<Directive, &str> {}
+fn breakpoint(input: &str) -> IResult<&str, Directive> {
+    let bp = word("breakpoint").or(word("b")).or(word("bp"));
+    let pc = addr;
+    pair(bp, pc)
+        .map(|(_, x)| Directive::BreakPoint(x))
+        .parse(input)
+}
+fn clear(input: &str) -> IResult<&str, Directive> {
+    let bp = word("clear").or(word("cl"));
+    let pc = addr;
+    let watch = pair(word("watch"), addr).map(|(_, x)| Directive::Watch(x));
+    let unwatch = pair(word("unwatch"), addr).map(|(_, x)| Directive::Unwatch(x));
+    pair(bp, pc)
+        .map(|(_, x)| Directive::Clear(x))
+        .or(watch)
+        .or(unwatch)
+        .parse(input)
+}
+fn step(input: &str) -> IResult<&str, Directive> {
+    let step_n = pair(word("step").or(word("s")), uint).map(|(_, n)| Directive::StepN(n));
+    let step = word("step").or(word("s")).map(|_| Directive::Step);
+    alt((step_n, step))(input)
+}
+fn goal(input: &str) -> IResult<&str, Directive> {
+    word("goal").map(|_| Directive::Goal).parse(input)
+}
+fn syms(input: &str) -> IResult<&str, Directive> {
+    word("symbols")
+        .or(word("syms"))
+        .map(|_| Directive::Symbols)
+        .parse(input)
+}
+fn microstep(input: &str) -> IResult<&str, Directive> {
+    word("microstep")
+        .or(word("ms"))
+        .map(|_| Directive::MicroStep)
+        .parse(input)
+}
+fn phase_step(input: &str) -> IResult<&str, Directive> {
+    word("ustep")
+        .or(word("us"))
+        .map(|_| Directive::PhaseStep)
+        .parse(input)
+}
+fn undo(input: &str) -> IResult<&str, Directive> {
+    word("back")
+        .or(word("undo"))
+        .map(|_| Directive::Undo)
+        .parse(input)
+}
+fn alias(input: &str) -> IResult<&str, Directive> {
+    let name = || take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    let text = verify(rest, |t: &str| !t.is_empty());
+    alt((
+        preceded(word("alias"), pair(terminated(name(), space), text))
+            .map(|(n, t): (&str, &str)| Directive::Alias(n.to_string(), t.to_string())),
+        word("alias").map(|_| Directive::Aliases),
+        preceded(word("unalias"), name()).map(|n: &str| Directive::Unalias(n.to_string())),
+    ))
+    .parse(input)
+}
+fn set(input: &str) -> IResult<&str, Directive> {
+    let acc = preceded(word("acc"), byte).map(|v| Directive::SetAcc(v as i8));
+    let byte_addr = || byte.map(Addr::Num).or(label);
+    let pc = preceded(word("pc"), byte_addr()).map(Directive::SetPc);
+    let mem = preceded(word("mem"), pair(terminated(byte_addr(), space), byte))
+        .map(|(addr, v)| Directive::SetMem(addr, v));
+    preceded(word("set"), alt((acc, pc, mem))).parse(input)
+}
+/// A byte in decimal, negative decimal or hexadecimal.
+fn byte(input: &str) -> IResult<&str, u8> {
+    let hex = preceded(
+        tag("0x"),
+        map_res(hex_digit1, |x| u8::from_str_radix(x, 16)),
+    );
+    let neg = map_res(recognize(pair(tag("-"), digit1)), |x: &str| {
+        x.parse::<i8>().map(|x| x as u8)
+    });
+    alt((hex, neg, uint::<u8>)).parse(input)
+}
+/// An address in decimal or a label.
+fn addr(input: &str) -> IResult<&str, Addr> {
+    uint::<u8>.map(Addr::Num).or(label).parse(input)
+}
+fn label(input: &str) -> IResult<&str, Addr> {
+    let name = take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    verify(name, |n: &str| symbols::is_label(n))
+        .map(|n: &str| Addr::Label(n.to_string()))
+        .parse(input)
+}
+fn load(input: &str) -> IResult<&str, Directive> {
+    let path = || verify(rest, |p: &str| !p.is_empty()).map(PathBuf::from);
+    alt((
+        preceded(word("load"), path()).map(Directive::Load),
+        preceded(word("source"), path()).map(Directive::Source),
+    ))
+    .parse(input)
+}
+fn snapshot(input: &str) -> IResult<&str, Directive> {
+    let name = || take_while1(|c: char| !c.is_whitespace()).map(str::to_string);
+    let path = || verify(rest, |p: &str| !p.is_empty()).map(PathBuf::from);
+    alt((
+        preceded(word("save"), name()).map(Directive::Save),
+        preceded(word("restore"), name()).map(Directive::Restore),
+        preceded(word("save-file"), path()).map(Directive::SaveFile),
+        preceded(word("restore-file"), path()).map(Directive::RestoreFile),
+    ))
+    .parse(input)
+}
+fn print(input: &str) -> IResult<&str, Directive> {
+    let print = word("print").or(word("show"));
+    pair(print, opt(expr))
+        .map(|(_, e)| e.map_or(Directive::PrintCpu, Directive::Print))
+        .parse(input)
+}
+/// Sums and differences of terms.
+fn expr(input: &str) -> IResult<&str, Expr> {
+    let op = one_of("+-").map(|c| if c == '+' { Op::Add } else { Op::Sub });
+    binary(input, op, term)
+}
+/// Products, quotients and remainders of factors.
+fn term(input: &str) -> IResult<&str, Expr> {
+    let op = one_of("*/%").map(|c| match c {
+        '*' => Op::Mul,
+        '/' => Op::Div,
+        _ => Op::Rem,
+    });
+    binary(input, op, factor)
+}
+/// Folds `operand (op operand)*` to the left.
+fn binary<'a>(
+    input: &'a str,
+    op: impl Parser<&'a str, Op, nom::error::Error<&'a str>>,
+    operand: fn(&'a str) -> IResult<&'a str, Expr>,
+) -> IResult<&'a str, Expr> {
+    let (input, first) = operand(input)?;
+    let (input, rest) = many0(pair(padded(op), operand)).parse(input)?;
+    let expr = rest
+        .into_iter()
+        .fold(first, |a, (op, b)| Expr::Bin(op, Box::new(a), Box::new(b)));
+    Ok((input, expr))
+}
+fn factor(input: &str) -> IResult<&str, Expr> {
+    let neg = preceded(padded(char('-')), factor).map(|x| Expr::Neg(Box::new(x)));
+    let paren = delimited(padded(char('(')), expr, padded(char(')')));
+    let num = alt((
+        preceded(
+            tag("0x"),
+            map_res(hex_digit1, |x| i64::from_str_radix(x, 16)),
+        ),
+        uint::<i64>,
+    ))
+    .map(Expr::Num);
+    let mem = preceded(
+        pair(tag("mem"), padded(char('['))),
+        terminated(expr, padded(char(']'))),
+    )
+    .map(|x| Expr::Mem(Box::new(x)));
+    let name = take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    let name = verify(name, |n: &str| symbols::is_label(n)).map(|n: &str| match n {
+        "acc" => Expr::Acc,
+        "pc" => Expr::Pc,
+        _ => Expr::Label(n.to_string()),
+    });
+    padded(alt((neg, paren, num, mem, name))).parse(input)
+}
+fn padded<'a, O>(
+    p: impl Parser<&'a str, O, nom::error::Error<&'a str>>,
+) -> impl Parser<&'a str, O, nom::error::Error<&'a str>> {
+    delimited(multispace0, p, multispace0)
+}
+fn display(input: &str) -> IResult<&str, Directive> {
+    let mode = word("dual").map(|_| true).or(word("plain").map(|_| false));
+    let on = || word("on").map(|_| true).or(word("off").map(|_| false));
+    let narrate = preceded(word("narrate"), on()).map(Directive::Narrate);
+    let group = preceded(word("group"), on()).map(Directive::GroupDigits);
+    let smc = preceded(word("smc"), on()).map(Directive::WarnSmc);
+    preceded(word("display"), mode)
+        .map(Directive::DualDisplay)
+        .or(narrate)
+        .or(group)
+        .or(smc)
+        .parse(input)
+}
+fn reload(input: &str) -> IResult<&str, Directive> {
+    word("reload").map(|_| Directive::Reload).parse(input)
+}
+fn uint<T: FromStr>(input: &str) -> IResult<&str, T> {
+    map_res(digit1, str::parse)(input)
+}
+fn space(input: &str) -> IResult<&str, ()> {
+    take_while1(|c: char| c.is_whitespace())
+        .map(|_| ())
+        .parse(input)
+}
+fn word(word: &str) -> impl Parser<&str, (), nom::error::Error<&str>> {
+    terminated(tag(word), space.or(eof.map(|_| ()))).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_alias() {
+        assert_eq!(
+            parse_directive("alias go s 10; cpu"),
+            Ok(Directive::Alias("go".into(), "s 10; cpu".into()))
+        );
+        assert_eq!(parse_directive("alias"), Ok(Directive::Aliases));
+        assert_eq!(
+            parse_directive("unalias go"),
+            Ok(Directive::Unalias("go".into()))
+        );
+        assert!(parse_directive("alias go").is_err());
+        assert_eq!(
+            split_line("s 10; cpu;; mem 128..140"),
+            ["s 10", "cpu", "mem 128..140"]
+        );
+        assert_eq!(split_line("s; alias x c; q"), ["s", "alias x c; q"]);
+        assert!(split_line(" ").is_empty());
+    }
+    #[test]
+    fn parse_step() {
+        assert_eq!(step("step 10"), Ok(("", Directive::StepN(10))));
+        assert_eq!(step("step"), Ok(("", Directive::Step)));
+    }
+    #[test]
+    fn parse_breakpoint() {
+        assert_eq!(
+            breakpoint("breakpoint 10"),
+            Ok(("", Directive::BreakPoint(Addr::Num(10))))
+        );
+        assert_eq!(
+            breakpoint("b loop_start"),
+            Ok(("", Directive::BreakPoint(Addr::Label("loop_start".into()))))
+        );
+        assert!(breakpoint("breakpoint -1").is_err());
+        assert!(parse_directive("breakpoint 1abc").is_err());
+        assert!(breakpoint("breakpoint").is_err());
+    }
+    #[test]
+    fn parse_mem() {
+        assert_eq!(
+            mem("mem"),
+            Ok(("", Directive::PrintMemRange(Addr::Num(0), Addr::Num(255))))
+        );
+        assert_eq!(
+            mem("mem 10.."),
+            Ok(("", Directive::PrintMemRange(Addr::Num(10), Addr::Num(255))))
+        );
+        assert_eq!(
+            mem("mem ..100"),
+            Ok(("", Directive::PrintMemRange(Addr::Num(0), Addr::Num(100))))
+        );
+        assert_eq!(
+            mem("mem 10"),
+            Ok(("", Directive::PrintMemAddr(Addr::Num(10))))
+        );
+        assert_eq!(
+            mem("mem counter"),
+            Ok(("", Directive::PrintMemAddr(Addr::Label("counter".into()))))
+        );
+        assert_eq!(
+            mem("mem start..end"),
+            Ok((
+                "",
+                Directive::PrintMemRange(Addr::Label("start".into()), Addr::Label("end".into()))
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_list() {
+        assert_eq!(parse_directive("list"), Ok(Directive::List));
+        assert_eq!(
+            parse_directive("l 10"),
+            Ok(Directive::ListAt(Addr::Num(10)))
+        );
+        assert_eq!(
+            parse_directive("list 10..loop"),
+            Ok(Directive::ListRange(
+                Addr::Num(10),
+                Addr::Label("loop".into())
+            ))
+        );
+        assert!(parse_directive("list 10..").is_err());
+    }
+
+    #[test]
+    fn parse_set() {
+        assert_eq!(set("set acc -3"), Ok(("", Directive::SetAcc(-3))));
+        assert_eq!(
+            set("set pc 0x1F"),
+            Ok(("", Directive::SetPc(Addr::Num(31))))
+        );
+        assert_eq!(
+            set("set mem 128 200"),
+            Ok(("", Directive::SetMem(Addr::Num(128), 200)))
+        );
+        assert_eq!(
+            set("set mem x 0x10"),
+            Ok(("", Directive::SetMem(Addr::Label("x".into()), 16)))
+        );
+        assert!(set("set mem 128").is_err());
+        assert!(set("set acc 256").is_err());
+        assert!(set("set acc -129").is_err());
+    }
+
+    #[test]
+    fn parse_load() {
+        assert_eq!(
+            parse_directive("load  dir/my prog.mem \n"),
+            Ok(Directive::Load(PathBuf::from("dir/my prog.mem")))
+        );
+        assert_eq!(parse_directive("reload"), Ok(Directive::Reload));
+        assert!(parse_directive("load").is_err());
+        assert_eq!(
+            parse_directive("source demo.txt"),
+            Ok(Directive::Source(PathBuf::from("demo.txt")))
+        );
+    }
+
+    #[test]
+    fn parse_word() {
+        assert_eq!(word("abc").parse("abc"), Ok(("", ())));
+        assert_eq!(word("abcd").parse("abcd  1"), Ok(("1", ())));
+        assert!(word("abc").parse("abcdef").is_err());
+    }
+
+    #[test]
+    fn test_directive() {
+        assert_eq!(parse_directive("c"), Ok(Directive::Continue));
+        assert_eq!(parse_directive("continue"), Ok(Directive::Continue));
+        assert_eq!(parse_directive("h"), Ok(Directive::Help));
+        assert_eq!(parse_directive("help"), Ok(Directive::Help));
+        assert_eq!(parse_directive("cpu"), Ok(Directive::PrintCpu));
+        assert_eq!(parse_directive("view"), Ok(Directive::View));
+        assert_eq!(parse_directive("explain"), Ok(Directive::Explain));
+        assert_eq!(parse_directive("coverage"), Ok(Directive::Coverage));
+        assert_eq!(parse_directive("narrate on"), Ok(Directive::Narrate(true)));
+        assert_eq!(
+            parse_directive("group on"),
+            Ok(Directive::GroupDigits(true))
+        );
+        assert_eq!(
+            parse_directive("narrate off"),
+            Ok(Directive::Narrate(false))
+        );
+        assert_eq!(parse_directive("smc on"), Ok(Directive::WarnSmc(true)));
+        assert_eq!(parse_directive("syms"), Ok(Directive::Symbols));
+        assert_eq!(parse_directive("goal"), Ok(Directive::Goal));
+        assert_eq!(
+            parse_directive("info breakpoints"),
+            Ok(Directive::Info(Info::Breakpoints))
+        );
+        assert!(parse_directive("info").is_err());
+        assert_eq!(
+            parse_directive("watch x"),
+            Ok(Directive::Watch(Addr::Label("x".into())))
+        );
+        assert_eq!(
+            parse_directive("unwatch 128"),
+            Ok(Directive::Unwatch(Addr::Num(128)))
+        );
+        assert!(parse_directive("watch").is_err());
+        assert_eq!(
+            parse_directive("save before-loop"),
+            Ok(Directive::Save("before-loop".to_string()))
+        );
+        assert_eq!(
+            parse_directive("restore-file my state.json"),
+            Ok(Directive::RestoreFile(PathBuf::from("my state.json")))
+        );
+        assert!(parse_directive("save a b").is_err());
+        assert_eq!(
+            parse_directive("display dual"),
+            Ok(Directive::DualDisplay(true))
+        );
+        assert_eq!(
+            parse_directive("display plain"),
+            Ok(Directive::DualDisplay(false))
+        );
+        assert_eq!(parse_directive("ms"), Ok(Directive::MicroStep));
+        assert_eq!(parse_directive("back"), Ok(Directive::Undo));
+        assert_eq!(parse_directive("undo"), Ok(Directive::Undo));
+        assert_eq!(parse_directive("microstep"), Ok(Directive::MicroStep));
+        assert_eq!(parse_directive("ustep"), Ok(Directive::PhaseStep));
+        assert_eq!(
+            parse_directive("mem"),
+            Ok(Directive::PrintMemRange(Addr::Num(0), Addr::Num(255)))
+        );
+        assert!(parse_directive("c a").is_err());
+        assert!(parse_directive("continue 1").is_err());
+    }
+}
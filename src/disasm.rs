@@ -0,0 +1,145 @@
+use crate::cpu::instr;
+use crate::symbols::SymbolTable;
+
+/// A decoded instruction, or a data byte that
+/// doesn't correspond to any instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisasmLine {
+    pub addr: u8,
+    /// The bytes taken by the instruction
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+/// Decodes the instruction at `addr`. Instructions missing
+/// their argument at the end of memory are shown as data.
+pub fn decode(mem: &[u8], addr: u8) -> DisasmLine {
+    decode_labeled(mem, addr, &SymbolTable::new())
+}
+
+/// Same as `decode`, showing operands by their label when they have one.
+pub fn decode_labeled(mem: &[u8], addr: u8, symbols: &SymbolTable) -> DisasmLine {
+    let opcode = mem[addr as usize];
+    match instr::name(opcode) {
+        Some(name) if instr::has_operand(opcode) && addr != 255 => {
+            let arg = mem[addr as usize + 1];
+            let text = match symbols.name_at(arg) {
+                Some(label) => format!("{name} {label}"),
+                None => format!("{name} 0x{arg:02X}"),
+            };
+            DisasmLine {
+                addr,
+                bytes: vec![opcode, arg],
+                text,
+            }
+        }
+        Some(name) if !instr::has_operand(opcode) => DisasmLine {
+            addr,
+            bytes: vec![opcode],
+            text: name.to_string(),
+        },
+        _ => DisasmLine {
+            addr,
+            bytes: vec![opcode],
+            text: format!("0x{opcode:02X}"),
+        },
+    }
+}
+
+/// Disassembles memory from `start` to `end`, inclusive.
+/// The last instruction may read its argument past `end`.
+pub fn disassemble(mem: &[u8], start: u8, end: u8) -> Vec<DisasmLine> {
+    disassemble_labeled(mem, start, end, &SymbolTable::new())
+}
+
+/// Same as `disassemble`, showing operands by their label when they have one.
+pub fn disassemble_labeled(
+    mem: &[u8],
+    start: u8,
+    end: u8,
+    symbols: &SymbolTable,
+) -> Vec<DisasmLine> {
+    let mut lines = Vec::new();
+    let mut addr = start as usize;
+    while addr <= end as usize {
+        let line = decode_labeled(mem, addr as u8, symbols);
+        addr += line.bytes.len();
+        lines.push(line);
+    }
+    lines
+}
+
+/// Disassembles up to `before` instructions preceding `addr` and
+/// `after` instructions starting at it. The preceding instructions
+/// are found by decoding from address 0, so an instruction that
+/// would run over `addr` is shown as data.
+pub fn window(
+    mem: &[u8],
+    addr: u8,
+    before: usize,
+    after: usize,
+    symbols: &SymbolTable,
+) -> Vec<DisasmLine> {
+    let mut lines = Vec::new();
+    let mut cur = 0;
+    while cur < addr as usize {
+        let mut line = decode_labeled(mem, cur as u8, symbols);
+        if cur + line.bytes.len() > addr as usize {
+            line.text = format!("0x{:02X}", line.bytes[0]);
+            line.bytes.truncate(1);
+        }
+        cur += line.bytes.len();
+        lines.push(line);
+    }
+    let skip = lines.len().saturating_sub(before);
+    lines.drain(..skip);
+    let mut cur = addr as usize;
+    for _ in 0..after {
+        if cur > 255 {
+            break;
+        }
+        let line = decode_labeled(mem, cur as u8, symbols);
+        cur += line.bytes.len();
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_disassemble() {
+        let mut mem = [0_u8; 256];
+        mem[..6].copy_from_slice(&[LDA, 128, NOT, 0x33, JMP, 0]);
+        mem[255] = ADD;
+        let lines = disassemble(&mem, 0, 4);
+        let text: Vec<_> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, ["LDA 0x80", "NOT", "0x33", "JMP 0x00"]);
+        assert_eq!(lines[3].addr, 4);
+        assert_eq!(decode(&mem, 255).text, "0x30");
+
+        let mut symbols = SymbolTable::new();
+        symbols.insert("x", 128);
+        let lines = disassemble_labeled(&mem, 0, 4, &symbols);
+        assert_eq!(lines[0].text, "LDA x");
+        assert_eq!(lines[3].text, "JMP 0x00");
+    }
+
+    #[test]
+    fn test_window() {
+        let mut mem = [0_u8; 256];
+        mem[..7].copy_from_slice(&[LDA, 128, NOT, ADD, 129, STA, 130]);
+        let symbols = SymbolTable::new();
+        let addrs = |lines: Vec<DisasmLine>| lines.iter().map(|l| l.addr).collect::<Vec<_>>();
+        assert_eq!(addrs(window(&mem, 3, 1, 2, &symbols)), [2, 3, 5]);
+        assert_eq!(addrs(window(&mem, 0, 3, 1, &symbols)), [0]);
+        // jumping into the middle of an instruction
+        let lines = window(&mem, 4, 2, 1, &symbols);
+        assert_eq!(addrs(lines.clone()), [2, 3, 4]);
+        assert_eq!(lines[1].text, "0x30");
+        assert_eq!(addrs(window(&mem, 255, 0, 5, &symbols)), [255]);
+    }
+}
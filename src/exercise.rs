@@ -0,0 +1,217 @@
+//! Exercises: an instructor provides the data of a program and the
+//! outputs it should produce, and the student writes the program.
+//! Exercises are written in TOML or JSON, with the cases of a spec:
+//!
+//! ```toml
+//! title = "Sum"
+//! text = "Store the sum of the cells 128 and 129 in 130."
+//! data = { 128 = 2, 129 = 3 }
+//! locked = ["0x80..0x82"]
+//!
+//! [[case]]
+//! name = "negative"
+//! memory = { 128 = -4, 129 = 1 }
+//! expect = { memory = { 130 = -3 } }
+//! ```
+//!
+//! The `data` cells and the `locked` addresses or ranges can't be edited,
+//! and `trap` entries are enforced like in specs.
+//! The goal is checked by running every case on a copy of the memory,
+//! with the data restored and the registers reset.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cpu::Neander;
+use crate::spec::{self, Case, Failure};
+use crate::traps::Trap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExerciseFile {
+    title: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    data: BTreeMap<String, i16>,
+    #[serde(default)]
+    locked: Vec<String>,
+    #[serde(default = "spec::default_max_steps")]
+    max_steps: u64,
+    #[serde(rename = "case", default)]
+    cases: Vec<Case>,
+    #[serde(rename = "trap", default)]
+    traps: Vec<Trap>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exercise {
+    pub title: String,
+    pub text: String,
+    /// The provided cells, by address
+    pub data: Vec<(u8, u8)>,
+    pub locked: [bool; 256],
+    pub max_steps: u64,
+    pub cases: Vec<Case>,
+    /// Rules checked while the program runs, in the cases and the GUI
+    pub traps: Vec<Trap>,
+}
+
+/// The result of checking a program against an exercise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Goal {
+    pub cases: usize,
+    /// The names and failed checks of every failed case
+    pub failed: Vec<(String, Vec<Failure>)>,
+}
+impl Goal {
+    pub fn passed(&self) -> usize {
+        self.cases - self.failed.len()
+    }
+    pub fn met(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.met() {
+            write!(
+                f,
+                "goal met: {} of {} cases pass",
+                self.passed(),
+                self.cases
+            )
+        } else {
+            write!(
+                f,
+                "goal not met: {} of {} cases pass",
+                self.passed(),
+                self.cases
+            )
+        }
+    }
+}
+
+impl Exercise {
+    /// Reads an exercise, as JSON if the file ends in `.json` and TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        if path.extension().is_some_and(|e| e == "json") {
+            Self::from_json(&source)
+        } else {
+            Self::from_toml(&source)
+        }
+    }
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        Self::from_file(toml::from_str(source).map_err(|e| e.to_string())?)
+    }
+    pub fn from_json(source: &str) -> Result<Self, String> {
+        Self::from_file(serde_json::from_str(source).map_err(|e| e.to_string())?)
+    }
+    fn from_file(file: ExerciseFile) -> Result<Self, String> {
+        let mut locked = [false; 256];
+        let mut data = Vec::new();
+        for (addr, value) in &file.data {
+            let addr = spec::parse_addr(addr)?;
+            data.push((addr, spec::byte(*value)?));
+            locked[addr as usize] = true;
+        }
+        for range in &file.locked {
+            let range = spec::parse_range(range)?;
+            locked[*range.start() as usize..=*range.end() as usize].fill(true);
+        }
+        for case in &file.cases {
+            for addr in case.memory.keys().chain(case.expect.memory.keys()) {
+                spec::parse_addr(addr)?;
+            }
+        }
+        Ok(Self {
+            title: file.title,
+            text: file.text,
+            data,
+            locked,
+            max_steps: file.max_steps,
+            cases: file.cases,
+            traps: file.traps,
+        })
+    }
+
+    pub fn is_locked(&self, addr: u8) -> bool {
+        self.locked[addr as usize]
+    }
+    /// Returns the first locked cell that differs between `old` and `new`.
+    pub fn locked_change(&self, old: &[u8], new: &[u8]) -> Option<u8> {
+        (0..=255).find(|&a| self.is_locked(a) && old[a as usize] != new[a as usize])
+    }
+
+    /// Writes the provided data into `cpu`'s memory.
+    pub fn apply(&self, cpu: &mut Neander) {
+        for &(addr, value) in &self.data {
+            cpu.set_ram(addr, value);
+        }
+    }
+
+    /// Checks the program in `cpu`'s memory against every case.
+    pub fn check(&self, cpu: &Neander) -> Goal {
+        let mut program = Neander::new();
+        program.memory_mut().copy_from_slice(cpu.memory());
+        self.apply(&mut program);
+        let mut failed = Vec::new();
+        for case in &self.cases {
+            // addresses were checked when parsing
+            let failures =
+                spec::run_case(&program, case, self.max_steps, &self.traps).unwrap_or_default();
+            if !failures.is_empty() {
+                failed.push((case.name.clone(), failures));
+            }
+        }
+        Goal {
+            cases: self.cases.len(),
+            failed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_exercise() {
+        let exercise = Exercise::from_toml(
+            r#"
+            title = "Sum"
+            data = { 128 = 2, 129 = 3 }
+            locked = ["0x82", "200..202"]
+            [[case]]
+            name = "data"
+            expect = { memory = { 130 = 5 } }
+            [[case]]
+            name = "negative"
+            memory = { 128 = -4 }
+            expect = { acc = -1 }
+            "#,
+        )
+        .unwrap();
+        assert!(exercise.is_locked(128) && exercise.is_locked(130) && exercise.is_locked(202));
+        assert!(!exercise.is_locked(131) && !exercise.is_locked(203));
+        let mut new = [0; 256];
+        assert_eq!(exercise.locked_change(&[0; 256], &new), None);
+        new[201] = 1;
+        assert_eq!(exercise.locked_change(&[0; 256], &new), Some(201));
+
+        let mut cpu = Neander::new();
+        let goal = exercise.check(&cpu);
+        assert_eq!((goal.passed(), goal.cases), (0, 2));
+        cpu.set_ram_slice(0, &[LDA, 128, ADD, 129, STA, 130, HLT]);
+        let goal = exercise.check(&cpu);
+        assert!(goal.met());
+        assert_eq!(goal.to_string(), "goal met: 2 of 2 cases pass");
+        assert_eq!(cpu.ram(128), 0);
+
+        assert!(Exercise::from_toml("title = \"t\"\nlocked = [\"3..1\"]").is_err());
+    }
+}
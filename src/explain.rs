@@ -0,0 +1,270 @@
+//! Explanations of exceptions for students: the byte the CPU
+//! stopped at, how it decoded it and what likely went wrong.
+use std::fmt::Write;
+
+use crate::cpu::instr::{self, *};
+use crate::cpu::NeanderException;
+use crate::disasm;
+use crate::symbols::SymbolTable;
+
+/// Explains exceptions in the program in `mem`.
+pub struct Explainer<'a> {
+    mem: &'a [u8],
+    symbols: &'a SymbolTable,
+    /// The addresses of the last instructions executed, oldest
+    /// first, ending with the one that raised the exception
+    trail: &'a [u8],
+}
+
+impl<'a> Explainer<'a> {
+    pub fn new(mem: &'a [u8], symbols: &'a SymbolTable, trail: &'a [u8]) -> Self {
+        Self {
+            mem,
+            symbols,
+            trail,
+        }
+    }
+
+    /// Returns an explanation of `e`, one paragraph per line:
+    /// what happened, how the byte decoded and the likely cause.
+    pub fn explain(&self, e: NeanderException) -> String {
+        let mut out = String::new();
+        let at = self.trail.last().copied();
+        let what = match (e, at) {
+            (NeanderException::InvalidInstruction(byte), Some(at)) => format!(
+                "the CPU fetched the byte {byte:02X} at {} as an instruction, but it's no opcode",
+                self.addr(at)
+            ),
+            (NeanderException::MissingArgument, _) => format!(
+                "the {} at FF needs an address in the next byte, but FF is the last byte of memory",
+                instr::name(self.mem[255]).unwrap_or("instruction")
+            ),
+            (NeanderException::EndOfProgram, _) => {
+                "the PC went past FF, the last byte of memory, without reaching a HLT".to_string()
+            }
+            (NeanderException::StepLimitExceeded(n), _) => {
+                format!("the program ran {n} instructions without reaching a HLT")
+            }
+            _ => e.to_string(),
+        };
+        let _ = writeln!(out, "what happened: {what}");
+        if let NeanderException::InvalidInstruction(byte) = e {
+            let _ = writeln!(out, "how it decoded: {}", decoding(byte));
+        }
+        if let Some(cause) = self.cause(e) {
+            let _ = writeln!(out, "likely cause: {cause}");
+        }
+        out
+    }
+
+    /// An address, with its label if it has one.
+    fn addr(&self, addr: u8) -> String {
+        match self.symbols.name_at(addr) {
+            Some(label) => format!("{addr:02X} ({label})"),
+            None => format!("{addr:02X}"),
+        }
+    }
+    fn instruction(&self, addr: u8) -> String {
+        let line = disasm::decode_labeled(self.mem, addr, self.symbols);
+        format!("`{}` at {}", line.text, self.addr(addr))
+    }
+
+    /// Whether the executed instructions read or write `addr`,
+    /// or it has a label no instruction was executed from.
+    fn is_data(&self, addr: u8) -> bool {
+        let accessed = self.trail.iter().any(|&pc| {
+            matches!(self.mem[pc as usize], STA | LDA | ADD | OR | AND)
+                && pc != 255
+                && self.mem[pc as usize + 1] == addr
+        });
+        let labeled = self.symbols.name_at(addr).is_some();
+        accessed || (labeled && !self.trail[..self.trail.len() - 1].contains(&addr))
+    }
+
+    /// The last loop executed, as the address it jumps back
+    /// to and the address of the jump.
+    fn last_loop(&self) -> Option<(u8, u8)> {
+        self.trail
+            .windows(2)
+            .rev()
+            .find(|w| w[1] <= w[0] && matches!(self.mem[w[0] as usize], JMP | JN | JZ))
+            .map(|w| (w[1], w[0]))
+    }
+
+    /// Whether the instruction at `from` went on to the next one, `to`.
+    fn fell_through(&self, from: u8, to: u8) -> bool {
+        let len = if has_operand(self.mem[from as usize]) {
+            2
+        } else {
+            1
+        };
+        from.wrapping_add(len) == to
+    }
+    /// How the PC got from the instruction at `from` to `to`.
+    fn arrival(&self, from: u8, to: u8) -> String {
+        if self.fell_through(from, to) {
+            format!(
+                "{} doesn't jump, so the CPU went on to the next byte",
+                self.instruction(from)
+            )
+        } else {
+            format!("{} jumped there", self.instruction(from))
+        }
+    }
+
+    fn cause(&self, e: NeanderException) -> Option<String> {
+        match e {
+            NeanderException::InvalidInstruction(_) | NeanderException::MissingArgument => {
+                let (&at, before) = self.trail.split_last()?;
+                let Some(&from) = before.last() else {
+                    return Some(format!("the program starts at {}", self.addr(at)));
+                };
+                let mut cause = self.arrival(from, at);
+                if self.is_data(at) {
+                    cause = format!("the PC walked into your data at {}: {cause}", self.addr(at));
+                }
+                let fell = self.fell_through(from, at);
+                match self.last_loop() {
+                    Some((start, jump)) if fell && jump < at => {
+                        let _ = write!(
+                            cause,
+                            ". When the loop at {} stops jumping back from {}, \
+                             the code after it runs on: it lacks a JMP or HLT",
+                            self.addr(start),
+                            self.addr(jump)
+                        );
+                    }
+                    _ if fell => cause.push_str(". The code before it may lack a JMP or HLT"),
+                    _ => cause.push_str(". Check the target of that jump"),
+                }
+                Some(cause)
+            }
+            NeanderException::EndOfProgram => {
+                // zeros decode as NOP, so programs without a HLT
+                // usually run through empty memory to the end
+                let last = self
+                    .trail
+                    .iter()
+                    .rev()
+                    .find(|&&pc| self.mem[pc as usize] != NOP)?;
+                Some(format!(
+                    "after {} the CPU ran through bytes 00, which decode as NOP: \
+                     the program lacks a HLT after it",
+                    self.instruction(*last)
+                ))
+            }
+            NeanderException::StepLimitExceeded(_) => {
+                let (start, jump) = self.last_loop()?;
+                // the jump that should leave the loop
+                let exit = match self.mem[jump as usize] {
+                    JMP => self.trail.iter().copied().find(|&pc| {
+                        (start..jump).contains(&pc) && matches!(self.mem[pc as usize], JN | JZ)
+                    }),
+                    _ => Some(jump),
+                };
+                let how = match exit {
+                    Some(exit) => {
+                        let flag = if self.mem[exit as usize] == JN {
+                            "N"
+                        } else {
+                            "Z"
+                        };
+                        format!(
+                            "never leaves through {}: check what sets the {flag} flag before it",
+                            self.instruction(exit)
+                        )
+                    }
+                    None => {
+                        "jumps back unconditionally, so it needs a JN or JZ to leave".to_string()
+                    }
+                };
+                Some(format!(
+                    "the program is stuck in the loop from {} to {}, which {how}",
+                    self.addr(start),
+                    self.instruction(jump)
+                ))
+            }
+        }
+    }
+}
+
+/// How the CPU decodes a byte that's no opcode.
+fn decoding(byte: u8) -> String {
+    let opcodes: Vec<_> = INSTRUCTIONS
+        .iter()
+        .map(|(op, name)| format!("{op:02X} {name}"))
+        .collect();
+    let mut text = format!(
+        "only whole bytes are opcodes ({}), so {byte:02X} matches none of them",
+        opcodes.join(", ")
+    );
+    let high = byte & 0xF0;
+    if let Some(name) = instr::name(high).filter(|_| high != NOP) {
+        let _ = write!(
+            text,
+            ". Its high half is the one of {name}; if you meant {name}, \
+             the low half must be 0"
+        );
+        if has_operand(high) {
+            text.push_str(" and the address goes in the next byte");
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Neander;
+
+    /// Runs `mem` until an exception, returning it with the trail.
+    fn fault(cpu: &mut Neander, max_steps: usize) -> (NeanderException, Vec<u8>) {
+        let mut trail = Vec::new();
+        for _ in 0..max_steps {
+            trail.push(cpu.pc());
+            if let crate::cpu::ExecResult::Exception(e) = cpu.step() {
+                return (e, trail);
+            }
+        }
+        (NeanderException::StepLimitExceeded(max_steps as u64), trail)
+    }
+
+    #[test]
+    fn test_explain() {
+        // a loop counting up to 0, running into its data when done
+        let mut symbols = SymbolTable::new();
+        symbols.insert("one", 8);
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 9, ADD, 8, STA, 9, JN, 0, 1, 0xFD]);
+        let (e, trail) = fault(&mut cpu, 100);
+        assert_eq!(e, NeanderException::InvalidInstruction(1));
+        let text = Explainer::new(cpu.memory(), &symbols, &trail).explain(e);
+        assert!(text.contains("byte 01 at 08 (one)"), "{text}");
+        assert!(text.contains("walked into your data at 08 (one)"), "{text}");
+        assert!(text.contains("`JN 0x00` at 06 doesn't jump"), "{text}");
+        assert!(
+            text.contains("loop at 00 stops jumping back from 06"),
+            "{text}"
+        );
+
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 8, 0x21, 8]);
+        let (e, trail) = fault(&mut cpu, 100);
+        let text = Explainer::new(cpu.memory(), &SymbolTable::new(), &trail).explain(e);
+        assert!(text.contains("if you meant LDA"), "{text}");
+
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[NOT]);
+        let (e, trail) = fault(&mut cpu, 1000);
+        assert_eq!(e, NeanderException::EndOfProgram);
+        let text = Explainer::new(cpu.memory(), &SymbolTable::new(), &trail).explain(e);
+        assert!(text.contains("after `NOT` at 00"), "{text}");
+
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[NOP, JMP, 0]);
+        let (e, trail) = fault(&mut cpu, 100);
+        let text = Explainer::new(cpu.memory(), &SymbolTable::new(), &trail).explain(e);
+        assert!(text.contains("loop from 00 to `JMP 0x00` at 01"), "{text}");
+        assert!(text.contains("needs a JN or JZ"), "{text}");
+    }
+}
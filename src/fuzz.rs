@@ -0,0 +1,235 @@
+//! Fuzz testing of the simulator itself. Random valid programs run
+//! with a step limit while every instruction is checked against the
+//! semantics of the ISA, and the micro-stepped and predecoded
+//! interpreters must end each run in the same state.
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::cpu::instr::{self, *};
+use crate::cpu::{ExecResult, Neander};
+use crate::rng::Rng;
+
+/// Generated code stays below this address, with the data above it.
+const CODE_END: u8 = 0x80;
+/// The most instructions of a generated program, before its HLT.
+const MAX_INSTRUCTIONS: u64 = 40;
+
+/// A broken invariant, or a panic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Seed of the program, to generate it again
+    pub seed: u64,
+    /// Instructions executed before the violation
+    pub step: u64,
+    pub message: String,
+}
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "program {}, step {}: {}",
+            self.seed, self.step, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub programs: u64,
+    /// Programs that halted within the step limit
+    pub halted: u64,
+    pub violations: Vec<Violation>,
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for v in &self.violations {
+            writeln!(f, "{v}")?;
+        }
+        writeln!(
+            f,
+            "{} programs, {} halted, {} violations",
+            self.programs,
+            self.halted,
+            self.violations.len()
+        )
+    }
+}
+
+/// Generates the program of `seed`: instructions on whole bytes below
+/// `CODE_END`, ending in a HLT, that jump only to instructions
+/// and access only the data above them.
+pub fn program(seed: u64) -> Neander {
+    let mut rng = Rng::new(seed);
+    let mut cpu = Neander::new();
+    // the instructions first, to know where jumps may land
+    let mut code = Vec::new();
+    let mut addr = 0;
+    for _ in 0..=rng.below(MAX_INSTRUCTIONS) {
+        let (opcode, _) = rng.pick(&INSTRUCTIONS);
+        code.push((addr, opcode));
+        addr += if has_operand(opcode) { 2 } else { 1 };
+    }
+    code.push((addr, HLT));
+    for &(addr, opcode) in &code {
+        cpu.set_ram(addr, opcode);
+        if has_operand(opcode) {
+            let arg = match opcode {
+                JMP | JN | JZ => rng.pick(&code).0,
+                _ => CODE_END + rng.below(128) as u8,
+            };
+            cpu.set_ram(addr + 1, arg);
+        }
+    }
+    for addr in CODE_END..=255 {
+        cpu.set_ram(addr, rng.byte());
+    }
+    cpu
+}
+
+/// Checks the step from `before` to `after`, which ended with `res`.
+fn check_step(before: &Neander, after: &Neander, res: ExecResult) -> Result<(), String> {
+    let pc = before.pc();
+    let opcode = before.ram(pc);
+    let name = instr::name(opcode).unwrap_or("???");
+    let arg = before.ram(pc.wrapping_add(1));
+    if let ExecResult::Exception(e) = res {
+        return Err(format!("{e} at {pc:02X}, in a valid program"));
+    }
+    let (acc, flags) = (after.acc(), after.status() & 3);
+    if sets_flags(opcode) {
+        if after.status_zero() != (acc == 0) || after.status_negative() != (acc < 0) {
+            return Err(format!("{name} left flags {flags:02b} with ACC {acc}"));
+        }
+    } else if (acc, flags) != (before.acc(), before.status() & 3) {
+        return Err(format!("{name} changed ACC or the flags"));
+    }
+    let next = pc.wrapping_add(if has_operand(opcode) { 2 } else { 1 });
+    let targets = match opcode {
+        JMP => vec![arg],
+        JN | JZ => vec![arg, next],
+        _ => vec![next],
+    };
+    if !targets.contains(&after.pc()) {
+        return Err(format!(
+            "{name} at {pc:02X} moved the PC to {:02X}",
+            after.pc()
+        ));
+    }
+    for addr in 0..=255 {
+        let (old, new) = (before.ram(addr), after.ram(addr));
+        if old == new {
+            continue;
+        }
+        if opcode != STA || addr != arg {
+            return Err(format!("{name} at {pc:02X} wrote {addr:02X}"));
+        }
+        if new != before.acc() as u8 {
+            return Err(format!("STA stored {new:02X}, not ACC"));
+        }
+    }
+    Ok(())
+}
+
+/// Whether two machines have the same memory and visible registers.
+fn same_state(a: &Neander, b: &Neander) -> bool {
+    (a.pc(), a.acc(), a.status(), a.memory()) == (b.pc(), b.acc(), b.status(), b.memory())
+}
+
+/// Runs the program of `seed`, returning whether it halted.
+fn run(seed: u64, max_steps: u64) -> Result<bool, Violation> {
+    let start = program(seed);
+    let violation = |step, message| Violation {
+        seed,
+        step,
+        message,
+    };
+    let mut cpu = start.clone();
+    let mut steps = 0;
+    let halted = loop {
+        if steps == max_steps {
+            break false;
+        }
+        let before = cpu.clone();
+        let res = cpu.step();
+        steps += 1;
+        check_step(&before, &cpu, res).map_err(|m| violation(steps, m))?;
+        if res == ExecResult::Halted {
+            break true;
+        }
+    };
+
+    let mut micro = start.clone();
+    for _ in 0..steps {
+        micro.step_uop();
+        while micro.mid_instruction() {
+            micro.step_uop();
+        }
+    }
+    if !same_state(&cpu, &micro) {
+        return Err(violation(steps, "micro-steps ended elsewhere".to_string()));
+    }
+    let mut predecoded = start;
+    let _ = predecoded.run_predecoded(max_steps);
+    if !same_state(&cpu, &predecoded) {
+        return Err(violation(
+            steps,
+            "the predecoded run ended elsewhere".to_string(),
+        ));
+    }
+    Ok(halted)
+}
+
+/// Runs `programs` programs, from the one of `seed` on, running
+/// each for at most `max_steps` instructions.
+pub fn fuzz(seed: u64, programs: u64, max_steps: u64) -> Report {
+    let mut report = Report::default();
+    for seed in (0..programs).map(|i| seed.wrapping_add(i)) {
+        report.programs += 1;
+        match panic::catch_unwind(AssertUnwindSafe(|| run(seed, max_steps))) {
+            Ok(Ok(halted)) => report.halted += halted as u64,
+            Ok(Err(v)) => report.violations.push(v),
+            Err(payload) => {
+                let message = match payload.downcast_ref::<&str>() {
+                    Some(s) => s.to_string(),
+                    None => payload
+                        .downcast_ref::<String>()
+                        .cloned()
+                        .unwrap_or_default(),
+                };
+                report.violations.push(Violation {
+                    seed,
+                    step: 0,
+                    message: format!("panicked: {message}"),
+                });
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz() {
+        let cpu = program(5);
+        assert_eq!(cpu.memory(), program(5).memory());
+        assert_ne!(cpu.memory(), program(6).memory());
+
+        let report = fuzz(0, 300, 200);
+        assert_eq!(report.violations, []);
+        assert!(report.halted > 0 && report.halted < 300, "{report}");
+
+        // a machine whose ADD forgets the flags
+        let mut before = Neander::new();
+        before.set_ram_slice(0, &[ADD, 0x80]);
+        before.set_ram(0x80, 0xFF);
+        let mut after = before.clone();
+        let mut regs = after.registers();
+        (regs.acc, regs.pc) = (-1, 2);
+        after.set_registers(regs);
+        let res = check_step(&before, &after, ExecResult::Normal);
+        assert_eq!(res, Err("ADD left flags 00 with ACC -1".to_string()));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+
+use crate::cpu::{ExecResult, Neander, Registers};
+use crate::hooks::Hooks;
+use crate::io::Bus;
+use crate::micro::{MicroOp, Phase};
+
+/// The changes made by a single step: the registers before
+/// it and the previous value of the memory cell it wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Delta {
+    regs: Registers,
+    /// (address, previous value)
+    write: Option<(u8, u8)>,
+}
+
+/// A bounded history of steps, that can be undone in reverse order.
+/// Once full, the oldest steps are forgotten. The whole capacity
+/// is allocated up front, so stepping with a history doesn't allocate.
+#[derive(Debug, Clone)]
+pub struct History {
+    deltas: VecDeque<Delta>,
+    capacity: usize,
+}
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            deltas: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+    }
+
+    /// Executes the next instruction of `cpu`, recording its changes.
+    pub fn step(&mut self, cpu: &mut Neander) -> ExecResult {
+        self.step_bus(cpu, &mut Bus::new())
+    }
+    /// Executes the next micro-operation of `cpu`, recording its changes.
+    pub fn step_uop(&mut self, cpu: &mut Neander) -> (MicroOp, ExecResult) {
+        self.step_uop_bus(cpu, &mut Bus::new())
+    }
+    /// Same as `step`, with the devices of `bus`.
+    /// Bytes read from devices and the ticks of its clock are not undone.
+    pub fn step_bus(&mut self, cpu: &mut Neander, bus: &mut Bus) -> ExecResult {
+        self.step_bus_hooked(cpu, bus, &mut ())
+    }
+    /// Same as `step_bus`, calling `hooks` like `Neander::step_hooked`.
+    pub fn step_bus_hooked(
+        &mut self,
+        cpu: &mut Neander,
+        bus: &mut Bus,
+        hooks: &mut impl Hooks,
+    ) -> ExecResult {
+        let regs = cpu.registers();
+        let mut write = OldByte(None);
+        let res = cpu.step_bus_hooked(bus, &mut (&mut write, hooks));
+        self.record(regs, write.0);
+        res
+    }
+    /// Same as `step_uop`, with the devices of `bus`.
+    pub fn step_uop_bus(&mut self, cpu: &mut Neander, bus: &mut Bus) -> (MicroOp, ExecResult) {
+        self.step_uop_bus_hooked(cpu, bus, &mut ())
+    }
+    /// Same as `step_uop_bus`, calling `hooks` like `Neander::step_uop_hooked`.
+    pub fn step_uop_bus_hooked(
+        &mut self,
+        cpu: &mut Neander,
+        bus: &mut Bus,
+        hooks: &mut impl Hooks,
+    ) -> (MicroOp, ExecResult) {
+        let regs = cpu.registers();
+        let mut write = OldByte(None);
+        let (op, res) = cpu.step_uop_bus_hooked(bus, &mut (&mut write, hooks));
+        self.record(regs, write.0);
+        (op, res)
+    }
+    /// Same as `Neander::micro_step`, with the devices of `bus`.
+    /// The whole phase is undone at once.
+    pub fn micro_step_bus(&mut self, cpu: &mut Neander, bus: &mut Bus) -> (Phase, ExecResult) {
+        self.micro_step_bus_hooked(cpu, bus, &mut ())
+    }
+    /// Same as `micro_step_bus`, calling `hooks` like `Neander::step_uop_hooked`.
+    pub fn micro_step_bus_hooked(
+        &mut self,
+        cpu: &mut Neander,
+        bus: &mut Bus,
+        hooks: &mut impl Hooks,
+    ) -> (Phase, ExecResult) {
+        let regs = cpu.registers();
+        let mut write = OldByte(None);
+        let (phase, res) = cpu.micro_step_bus_hooked(bus, &mut (&mut write, hooks));
+        self.record(regs, write.0);
+        (phase, res)
+    }
+    /// Reverts the last recorded step. Returns false if
+    /// there is nothing to undo.
+    pub fn undo(&mut self, cpu: &mut Neander) -> bool {
+        let Some(delta) = self.deltas.pop_back() else {
+            return false;
+        };
+        cpu.set_registers(delta.regs);
+        if let Some((addr, value)) = delta.write {
+            cpu.set_ram(addr, value);
+        }
+        true
+    }
+    /// Undoes steps until `len` are left, returning how many were undone.
+    pub fn undo_to(&mut self, cpu: &mut Neander, len: usize) -> usize {
+        let mut undone = 0;
+        while self.len() > len && self.undo(cpu) {
+            undone += 1;
+        }
+        undone
+    }
+
+    /// The registers before each recorded step, oldest first.
+    pub fn snapshots(&self) -> impl ExactSizeIterator<Item = Registers> + '_ {
+        self.deltas.iter().map(|d| d.regs)
+    }
+    /// The addresses of the instructions started by the recorded
+    /// steps, oldest first.
+    pub fn trail(&self) -> Vec<u8> {
+        self.deltas
+            .iter()
+            .filter(|d| d.regs.uop == 0)
+            .map(|d| d.regs.pc)
+            .collect()
+    }
+
+    fn record(&mut self, regs: Registers, write: Option<(u8, u8)>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        self.deltas.push_back(Delta { regs, write });
+    }
+}
+
+/// Keeps the address and the previous value of the byte a step
+/// writes, so steps don't copy the whole memory to undo it.
+struct OldByte(Option<(u8, u8)>);
+impl Hooks for OldByte {
+    fn on_mem_write(&mut self, addr: u8, old: u8, _new: u8) {
+        self.0 = Some((addr, old));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_undo() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, NOT, HLT]);
+        cpu.set_ram_slice(128, &[5, 9]);
+        let start = cpu.clone();
+        let mut hist = History::new(10);
+        while hist.step(&mut cpu) != ExecResult::Halted {}
+        hist.step_uop(&mut cpu);
+        assert_eq!(cpu.ram(129), 5);
+        assert_eq!(hist.len(), 5);
+        assert_eq!(hist.trail(), [0, 2, 4, 5, 6]);
+        assert_eq!(hist.snapshots().nth(2).unwrap().pc, 4);
+        assert_eq!(hist.undo_to(&mut cpu, 2), 3);
+        assert_eq!((cpu.pc(), cpu.ram(129)), (4, 5));
+        assert_eq!(hist.undo_to(&mut cpu, 1), 1);
+        assert_eq!((cpu.pc(), cpu.ram(129)), (2, 9));
+        while hist.undo(&mut cpu) {}
+        assert_eq!(cpu.registers(), start.registers());
+        assert_eq!(cpu.memory(), start.memory());
+
+        // stores made by micro-steps are undone too
+        cpu.set_pc(2);
+        for _ in 0..3 {
+            hist.micro_step_bus(&mut cpu, &mut Bus::new());
+        }
+        assert_eq!((cpu.pc(), cpu.ram(129)), (4, 0));
+        assert_eq!(hist.undo_to(&mut cpu, 0), 3);
+        assert_eq!(cpu.ram(129), 9);
+    }
+    #[test]
+    fn test_bounded() {
+        let mut cpu = Neander::new();
+        let mut hist = History::new(2);
+        for _ in 0..5 {
+            hist.step(&mut cpu);
+        }
+        assert_eq!(hist.len(), 2);
+        assert!(hist.undo(&mut cpu));
+        assert!(hist.undo(&mut cpu));
+        assert!(!hist.undo(&mut cpu));
+        assert_eq!(cpu.pc(), 3);
+    }
+}
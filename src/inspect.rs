@@ -0,0 +1,107 @@
+//! Everything known about one byte of memory: its address and value
+//! in every base, how it decodes, whether the program reaches it, its
+//! label and the source it came from. Used by the `explain-byte`
+//! command and the tooltips of the GUI memory grid.
+use crate::cpu::instr;
+use crate::disasm;
+use crate::image::MemoryImage;
+use crate::lint::{self, Reach};
+use crate::numfmt::NumberBase;
+use crate::srcmap::SourceMap;
+use crate::symbols::SymbolTable;
+
+/// What's known about the byte at `addr` of `mem`, one fact per
+/// line. `image` is the program as it was loaded, before it ran.
+pub fn describe_byte(
+    mem: &[u8],
+    addr: u8,
+    symbols: &SymbolTable,
+    source_map: &SourceMap,
+    image: &MemoryImage,
+) -> Vec<String> {
+    let value = mem[addr as usize];
+    let mut lines = vec![format!(
+        "Address {addr:02X} | {addr} | {}",
+        NumberBase::Bin.fmt(addr)
+    )];
+    if let Some(name) = symbols.name_at(addr) {
+        lines.push(format!("Label {name}"));
+    }
+    lines.push(format!(
+        "Value {value:02X} | {value} unsigned | {} signed | {}",
+        value as i8,
+        NumberBase::Bin.fmt(value)
+    ));
+    lines.push(match instr::name(value) {
+        Some(_) => {
+            let line = disasm::decode_labeled(mem, addr, symbols);
+            format!("As an instruction: {}", line.text)
+        }
+        None => "No instruction has this opcode".to_string(),
+    });
+    lines.push(reached(&lint::reach(mem), addr));
+    if let Some(line) = source_map.describe(addr) {
+        lines.push(format!("Assembled from {line}"));
+    }
+    if let Some(origin) = image.origin(addr as usize) {
+        let mut text = origin.describe();
+        text[..1].make_ascii_uppercase();
+        if image.bytes()[addr as usize] != value {
+            text.push_str(", and was written since");
+        }
+        lines.push(text);
+    }
+    lines
+}
+
+/// How the paths from address 0 use the byte at `addr`.
+fn reached(reach: &Reach, addr: u8) -> String {
+    let i = addr as usize;
+    let uses: Vec<_> = [
+        (reach.opcodes[i], "executed as an instruction"),
+        (reach.operands[i], "the address of an instruction"),
+        (reach.data[i], "read or written as data"),
+    ]
+    .into_iter()
+    .filter_map(|(used, what)| used.then_some(what))
+    .collect();
+    match uses.is_empty() {
+        true => "No path from 00 reaches this byte".to_string(),
+        false => format!("Reached from 00: {}", uses.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn test_describe_byte() {
+        let source = "lda x\nhlt\nx: db 0x33\ny: db 0xF0\n";
+        let (image, symbols, map) = asm::assemble_image(256, source, None).unwrap();
+        let mut mem = image.bytes().to_vec();
+        mem[3] = 0x34;
+        let lines = describe_byte(&mem, 3, &symbols, &map, &image);
+        assert_eq!(
+            lines,
+            [
+                "Address 03 | 3 | 00000011",
+                "Label x",
+                "Value 34 | 52 unsigned | 52 signed | 00110100",
+                "No instruction has this opcode",
+                "Reached from 00: read or written as data",
+                "Assembled from line 3: x: db 0x33",
+                "This byte came from line 3, in the block at 00, and was written since",
+            ]
+        );
+        let lines = describe_byte(&mem, 4, &symbols, &map, &image);
+        assert_eq!(lines[3], "As an instruction: HLT");
+        assert_eq!(lines[4], "No path from 00 reaches this byte");
+        let lines = describe_byte(&mem, 0, &symbols, &map, &image);
+        assert_eq!(lines[2], "As an instruction: LDA x");
+        assert_eq!(lines[3], "Reached from 00: executed as an instruction");
+        let lines = describe_byte(&mem, 1, &symbols, &map, &image);
+        assert_eq!(lines[3], "Reached from 00: the address of an instruction");
+    }
+}
@@ -1,4 +1,49 @@
+pub use neander_core::{
+    asm, clock, cpu, formats, hooks, image, io, logisim, memfile, micro, predecode, srcmap,
+    symbols, trace,
+};
+
+pub mod ansi;
+pub mod bench;
+pub mod cesar;
+#[cfg(all(feature = "cli", not(target_arch = "wasm32")))]
 pub mod cli;
-pub mod cpu;
-pub mod memfile;
-//pub mod ui;
+pub mod conformance;
+pub mod cosim;
+pub mod coverage;
+#[cfg(feature = "zip")]
+pub mod crash;
+pub mod data;
+#[cfg(feature = "debugger")]
+pub mod debugger;
+pub mod disasm;
+#[cfg(feature = "serde")]
+pub mod exercise;
+pub mod explain;
+pub mod fuzz;
+pub mod history;
+pub mod inspect;
+pub mod lint;
+pub mod narrate;
+pub mod numfmt;
+#[cfg(feature = "zip")]
+pub mod pack;
+#[cfg(feature = "serde")]
+pub mod pin;
+pub mod prelude;
+#[cfg(feature = "serde")]
+pub mod printout;
+pub mod profile;
+pub mod quiz;
+pub mod render;
+pub mod rng;
+pub mod smc;
+#[cfg(feature = "serde")]
+pub mod spec;
+#[cfg(feature = "serde")]
+pub mod stats;
+pub mod timeline;
+#[cfg(feature = "serde")]
+pub mod traps;
+#[cfg(feature = "gui")]
+pub mod ui;
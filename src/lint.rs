@@ -0,0 +1,263 @@
+//! A static check of programs for likely mistakes, before running
+//! them. The instructions reachable from address 0 are found by
+//! following every branch both ways; the bytes they load, add or
+//! store are the data of the program.
+use std::fmt;
+
+use crate::cpu::instr::{self, *};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// A jump to a byte read or written as data, or that is no opcode
+    JumpToData { at: u8, target: u8 },
+    /// Execution falls through into a byte that is no opcode
+    RunsIntoData { at: u8 },
+    /// An instruction taking an address at FF, the last byte
+    MissingOperand { at: u8 },
+    /// A HLT that no path from address 0 reaches
+    UnreachableHalt { at: u8 },
+    /// No path from address 0 reaches a HLT
+    NoHalt,
+    /// A store into the address of a reachable instruction
+    SelfModifying { at: u8, target: u8 },
+    /// A store over the opcode of a reachable instruction
+    WritesCode { at: u8, target: u8 },
+}
+
+impl Lint {
+    /// A short name of the kind of lint, for machine-readable output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::JumpToData { .. } => "jump-to-data",
+            Lint::RunsIntoData { .. } => "runs-into-data",
+            Lint::MissingOperand { .. } => "missing-operand",
+            Lint::UnreachableHalt { .. } => "unreachable-halt",
+            Lint::NoHalt => "no-halt",
+            Lint::SelfModifying { .. } => "self-modifying",
+            Lint::WritesCode { .. } => "writes-code",
+        }
+    }
+    /// The address of the byte with the mistake, if there is one.
+    pub fn addr(&self) -> Option<u8> {
+        match *self {
+            Lint::JumpToData { at, .. }
+            | Lint::RunsIntoData { at }
+            | Lint::MissingOperand { at }
+            | Lint::UnreachableHalt { at }
+            | Lint::SelfModifying { at, .. }
+            | Lint::WritesCode { at, .. } => Some(at),
+            Lint::NoHalt => None,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::JumpToData { at, target } => {
+                write!(f, "{at:02X}: jump to {target:02X}, which holds data")
+            }
+            Lint::RunsIntoData { at } => write!(
+                f,
+                "{at:02X}: execution runs into data, maybe a HLT is missing before it"
+            ),
+            Lint::MissingOperand { at } => write!(
+                f,
+                "{at:02X}: the instruction needs an address, but it's the last byte of memory"
+            ),
+            Lint::UnreachableHalt { at } => write!(f, "{at:02X}: this HLT is never reached"),
+            Lint::NoHalt => write!(f, "no HLT is reached from address 00"),
+            Lint::SelfModifying { at, target } => write!(
+                f,
+                "{at:02X}: self-modifying code, stores into the operand at {target:02X}"
+            ),
+            Lint::WritesCode { at, target } => {
+                write!(f, "{at:02X}: stores over the instruction at {target:02X}")
+            }
+        }
+    }
+}
+
+/// How the instructions reachable from address 0 use each byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reach {
+    pub opcodes: [bool; 256],
+    pub operands: [bool; 256],
+    /// Bytes loaded, added or stored by an instruction
+    pub data: [bool; 256],
+}
+
+/// Finds the bytes used by the program in `mem`.
+pub fn reach(mem: &[u8]) -> Reach {
+    walk(mem).reach
+}
+
+/// The reachable bytes, with what the walk found along the way.
+struct Walk {
+    reach: Reach,
+    lints: Vec<Lint>,
+    jumps: Vec<(u8, u8)>,
+    stores: Vec<(u8, u8)>,
+    halts: bool,
+}
+
+/// Follows every branch from address 0 both ways.
+fn walk(mem: &[u8]) -> Walk {
+    // the opcodes and operands of reachable instructions
+    let mut opcodes = [false; 256];
+    let mut operands = [false; 256];
+    let mut data = [false; 256];
+    let mut lints = Vec::new();
+    let mut jumps = Vec::new();
+    let mut stores = Vec::new();
+    let mut halts = false;
+    let mut queue = vec![0_u8];
+    while let Some(at) = queue.pop() {
+        if opcodes[at as usize] {
+            continue;
+        }
+        let opcode = mem[at as usize];
+        if instr::name(opcode).is_none() {
+            lints.push(Lint::RunsIntoData { at });
+            continue;
+        }
+        opcodes[at as usize] = true;
+        if opcode == HLT {
+            halts = true;
+            continue;
+        }
+        if !instr::has_operand(opcode) {
+            queue.extend(at.checked_add(1));
+            continue;
+        }
+        if at == 255 {
+            lints.push(Lint::MissingOperand { at });
+            continue;
+        }
+        operands[at as usize + 1] = true;
+        let target = mem[at as usize + 1];
+        let next = at.checked_add(2);
+        match opcode {
+            JMP => jumps.push((at, target)),
+            JN | JZ => {
+                jumps.push((at, target));
+                queue.extend(next);
+            }
+            STA => {
+                data[target as usize] = true;
+                stores.push((at, target));
+                queue.extend(next);
+            }
+            _ => {
+                data[target as usize] = true;
+                queue.extend(next);
+            }
+        }
+        if matches!(opcode, JMP | JN | JZ) && instr::name(mem[target as usize]).is_some() {
+            queue.push(target);
+        }
+    }
+    Walk {
+        reach: Reach {
+            opcodes,
+            operands,
+            data,
+        },
+        lints,
+        jumps,
+        stores,
+        halts,
+    }
+}
+
+/// Checks the program in `mem`, returning its lints by address.
+pub fn check(mem: &[u8]) -> Vec<Lint> {
+    let Walk {
+        reach: Reach {
+            opcodes,
+            operands,
+            data,
+        },
+        mut lints,
+        jumps,
+        stores,
+        halts,
+    } = walk(mem);
+    for (at, target) in jumps {
+        let invalid = instr::name(mem[target as usize]).is_none();
+        if invalid || (data[target as usize] && !opcodes[target as usize]) {
+            lints.push(Lint::JumpToData { at, target });
+        }
+    }
+    for (at, target) in stores {
+        if opcodes[target as usize] {
+            lints.push(Lint::WritesCode { at, target });
+        } else if operands[target as usize] {
+            lints.push(Lint::SelfModifying { at, target });
+        }
+    }
+    for at in 0..=255 {
+        let i = at as usize;
+        let used = opcodes[i] || operands[i] || data[i];
+        if mem[i] == HLT && !used {
+            lints.push(Lint::UnreachableHalt { at });
+        }
+    }
+    if !halts {
+        lints.push(Lint::NoHalt);
+    }
+    lints.sort_by_key(|l| l.addr().map_or(256, u16::from));
+    lints.dedup();
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut mem = [0; 256];
+        assert_eq!(check(&mem), [Lint::NoHalt]);
+
+        // a program using the operand at 03 as a pointer
+        #[rustfmt::skip]
+        mem[..12].copy_from_slice(&[
+            LDA, 0x80, ADD, 0x81, STA, 0x80, // 00
+            STA, 0x03,                       // 06 self-modifying
+            JZ, 0x80,                        // 08 jumps to data
+            HLT, HLT,                        // 0B is never reached
+        ]);
+        mem[0x80] = 0x77;
+        assert_eq!(
+            check(&mem),
+            [
+                Lint::SelfModifying { at: 6, target: 3 },
+                Lint::JumpToData {
+                    at: 8,
+                    target: 0x80
+                },
+                Lint::UnreachableHalt { at: 0x0B },
+            ]
+        );
+        mem[6] = STA;
+        mem[7] = 0x02;
+        mem[0x0A] = 0x77;
+        assert_eq!(
+            check(&mem),
+            [
+                Lint::WritesCode { at: 6, target: 2 },
+                Lint::JumpToData {
+                    at: 8,
+                    target: 0x80
+                },
+                Lint::RunsIntoData { at: 0x0A },
+                Lint::UnreachableHalt { at: 0x0B },
+                Lint::NoHalt,
+            ]
+        );
+        mem[0xFF] = LDA;
+        mem[..2].copy_from_slice(&[JMP, 0xFF]);
+        assert!(check(&mem).contains(&Lint::MissingOperand { at: 0xFF }));
+    }
+}
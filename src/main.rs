@@ -1,4 +1,28 @@
-use neander::cli::cli;
+#[cfg(all(feature = "cli", not(target_arch = "wasm32")))]
 fn main() -> std::process::ExitCode {
-    cli()
+    neander::cli::cli()
+}
+
+#[cfg(all(feature = "gui", target_arch = "wasm32"))]
+fn main() {
+    neander::ui::run_web("neander");
+}
+
+/// Without the command line, the GUI starts with an empty machine.
+#[cfg(all(feature = "gui", not(feature = "cli"), not(target_arch = "wasm32")))]
+fn main() -> std::process::ExitCode {
+    let cpu = neander::cpu::Neander::new();
+    match neander::ui::run_ui(cpu, None, None, None, None) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(any(all(feature = "cli", not(target_arch = "wasm32")), feature = "gui")))]
+fn main() -> std::process::ExitCode {
+    eprintln!("neander was built without a user interface, enable the `cli` or `gui` feature");
+    std::process::ExitCode::FAILURE
 }
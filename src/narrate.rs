@@ -0,0 +1,153 @@
+//! Plain-language sentences describing what a step did,
+//! for students stepping through their first programs, and
+//! describing the whole machine for those using screen readers.
+use crate::cpu::instr::*;
+use crate::cpu::{ExecResult, Neander};
+use crate::disasm;
+use crate::symbols::SymbolTable;
+
+fn flag(set: bool) -> &'static str {
+    if set {
+        "set"
+    } else {
+        "cleared"
+    }
+}
+
+/// Describes the step from `before` to `after`, which ended with `res`,
+/// like "Fetched LDA 0x80: loaded 10 into the accumulator; Z cleared, N cleared".
+pub fn narrate(
+    before: &Neander,
+    after: &Neander,
+    res: ExecResult,
+    symbols: &SymbolTable,
+) -> String {
+    if before.mid_instruction() {
+        return "Finished the instruction started by micro-steps".to_string();
+    }
+    if let ExecResult::Exception(e) = res {
+        return format!("Stopped at {:02X}: {e}", before.pc());
+    }
+    let pc = before.pc();
+    let opcode = before.ram(pc);
+    let line = disasm::decode_labeled(before.memory(), pc, symbols);
+    let arg = before.ram(pc.wrapping_add(1));
+    let addr = match symbols.name_at(arg) {
+        Some(label) => format!("{label} (0x{arg:02X})"),
+        None => format!("0x{arg:02X}"),
+    };
+    let value = before.ram(arg) as i8;
+    let acc = after.acc();
+    let effect = match opcode {
+        NOP => "did nothing".to_string(),
+        STA => format!("stored {} from the accumulator at {addr}", before.acc()),
+        LDA => format!("loaded {value} from {addr} into the accumulator"),
+        ADD => format!("added {value} from {addr} to the accumulator, giving {acc}"),
+        OR => format!("ORed the accumulator with {value} from {addr}, giving {acc}"),
+        AND => format!("ANDed the accumulator with {value} from {addr}, giving {acc}"),
+        NOT => format!("inverted the bits of the accumulator, giving {acc}"),
+        JMP => format!("jumped to {addr}"),
+        JN | JZ => {
+            let (name, set) = match opcode {
+                JN => ("N", before.status_negative()),
+                _ => ("Z", before.status_zero()),
+            };
+            match set {
+                true => format!("{name} is set, so jumped to {addr}"),
+                false => format!("{name} is clear, so went on to 0x{:02X}", after.pc()),
+            }
+        }
+        _ => "halted the program".to_string(),
+    };
+    let mut text = format!("Fetched {}: {effect}", line.text);
+    if matches!(opcode, LDA | ADD | OR | AND | NOT) {
+        text.push_str(&format!(
+            "; Z {}, N {}",
+            flag(after.status_zero()),
+            flag(after.status_negative())
+        ));
+    }
+    text
+}
+
+fn on(set: bool) -> &'static str {
+    if set {
+        "set"
+    } else {
+        "clear"
+    }
+}
+
+/// Describes the registers and the next instruction, like "The program
+/// counter is 0x02, at ADD x. The accumulator is 10. Z is clear and N is clear."
+pub fn describe(cpu: &Neander, symbols: &SymbolTable) -> String {
+    let pc = cpu.pc();
+    let line = disasm::decode_labeled(cpu.memory(), pc, symbols);
+    let mut text = format!("The program counter is 0x{pc:02X} ({pc})");
+    if let Some(label) = symbols.name_at(pc) {
+        text.push_str(&format!(", labeled {label}"));
+    }
+    text.push_str(&format!(", at {}. ", line.text));
+    if cpu.mid_instruction() {
+        text.push_str(&format!(
+            "The instruction is in the middle of its {} phase. ",
+            cpu.phase()
+        ));
+    }
+    let acc = cpu.acc();
+    text.push_str(&format!(
+        "The accumulator is {acc} (0x{:02X}). Z is {} and N is {}.",
+        acc as u8,
+        on(cpu.status_zero()),
+        on(cpu.status_negative())
+    ));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Narrates the next step of `cpu`.
+    fn step(cpu: &mut Neander, symbols: &SymbolTable) -> String {
+        let before = cpu.clone();
+        let res = cpu.step();
+        narrate(&before, cpu, res, symbols)
+    }
+
+    #[test]
+    fn test_narrate() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert("x", 128);
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, ADD, 129, JZ, 0, STA, 130, HLT, 0x70]);
+        cpu.set_ram_slice(128, &[10, 0xF6]);
+        assert_eq!(
+            step(&mut cpu, &symbols),
+            "Fetched LDA x: loaded 10 from x (0x80) into the accumulator; Z cleared, N cleared"
+        );
+        assert_eq!(
+            step(&mut cpu, &symbols),
+            "Fetched ADD 0x81: added -10 from 0x81 to the accumulator, giving 0; Z set, N cleared"
+        );
+        assert_eq!(
+            step(&mut cpu, &symbols),
+            "Fetched JZ 0x00: Z is set, so jumped to 0x00"
+        );
+        assert_eq!(
+            describe(&cpu, &symbols),
+            "The program counter is 0x00 (0), at LDA x. \
+             The accumulator is 0 (0x00). Z is set and N is clear."
+        );
+        cpu.set_pc(6);
+        assert_eq!(
+            step(&mut cpu, &symbols),
+            "Fetched STA 0x82: stored 0 from the accumulator at 0x82"
+        );
+        assert_eq!(step(&mut cpu, &symbols), "Fetched HLT: halted the program");
+        assert_eq!(
+            step(&mut cpu, &symbols),
+            "Stopped at 09: invalid instruction: 70"
+        );
+    }
+}
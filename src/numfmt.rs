@@ -0,0 +1,178 @@
+//! How numbers are written across the simulator: the base bytes are
+//! shown in, and how long strings of digits are split to be read,
+//! like `0101_0101` or `1.000.000`. The CLI sets the format from the
+//! locale when it starts, the GUI from its settings, and everything
+//! printing numbers reads it with `get`.
+use std::fmt::{Binary, Display, UpperHex};
+use std::sync::RwLock;
+
+/// What base the UI is shown in.
+/// Used to format numbers in its respective
+/// base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumberBase {
+    Dec,
+    Hex,
+    Bin,
+}
+impl NumberBase {
+    pub fn fmt(self, val: impl Binary + UpperHex + Display) -> String {
+        match self {
+            Self::Dec => get().decimal(&format!("{val:03}")),
+            Self::Bin => get().binary(&format!("{val:08b}")),
+            Self::Hex => format!("{val:02X}"),
+        }
+    }
+    /// Same as `fmt`, followed by the decimal values of the byte,
+    /// signed and unsigned if they differ.
+    pub fn fmt_dual(self, val: u8) -> String {
+        if val < 128 {
+            format!("{} ({val})", self.fmt(val))
+        } else {
+            format!("{} ({} / {val})", self.fmt(val), val as i8)
+        }
+    }
+    /// Formats `val` with `fmt`, or with `fmt_dual` if `dual` is set.
+    pub fn fmt_byte(self, val: u8, dual: bool) -> String {
+        if dual {
+            self.fmt_dual(val)
+        } else {
+            self.fmt(val)
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dec => "dec",
+            Self::Hex => "hex",
+            Self::Bin => "bin",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dec" => Some(Self::Dec),
+            "hex" => Some(Self::Hex),
+            "bin" => Some(Self::Bin),
+            _ => None,
+        }
+    }
+}
+
+/// How digits are grouped. The default leaves them as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct NumberFormat {
+    /// Splits binary numbers in groups of 4 digits
+    pub group_binary: bool,
+    /// Between the groups of binary digits
+    pub separator: char,
+    /// Between the thousands of decimal numbers
+    pub thousands: Option<char>,
+}
+impl NumberFormat {
+    pub const PLAIN: Self = Self {
+        group_binary: false,
+        separator: '_',
+        thousands: None,
+    };
+
+    /// The plain format, with the thousands separator of the locale
+    /// in `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|l| !l.is_empty())
+            .unwrap_or_default();
+        Self {
+            thousands: thousands_separator(&locale),
+            ..Self::PLAIN
+        }
+    }
+    /// Groups a string of binary digits from the right.
+    pub fn binary(self, digits: &str) -> String {
+        match self.group_binary {
+            true => group(digits, 4, self.separator),
+            false => digits.to_string(),
+        }
+    }
+    /// Groups the thousands of a string of decimal digits,
+    /// which may start with a sign.
+    pub fn decimal(self, digits: &str) -> String {
+        let Some(sep) = self.thousands else {
+            return digits.to_string();
+        };
+        match digits.strip_prefix('-') {
+            Some(abs) => format!("-{}", group(abs, 3, sep)),
+            None => group(digits, 3, sep),
+        }
+    }
+    /// A count, like of steps, with its thousands grouped.
+    pub fn count(self, n: u64) -> String {
+        self.decimal(&n.to_string())
+    }
+}
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::PLAIN
+    }
+}
+
+/// The thousands separator of a locale like `pt_BR.UTF-8`.
+/// The C locale has none.
+fn thousands_separator(locale: &str) -> Option<char> {
+    let lang = locale.split(['_', '.', '@']).next().unwrap_or_default();
+    match lang {
+        "" | "C" | "POSIX" => None,
+        "pt" | "de" | "es" | "it" | "nl" | "da" | "id" | "tr" | "el" | "ro" => Some('.'),
+        "fr" | "ru" | "uk" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "hu" => Some(' '),
+        _ => Some(','),
+    }
+}
+
+fn group(digits: &str, size: usize, sep: char) -> String {
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(size) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+static FORMAT: RwLock<NumberFormat> = RwLock::new(NumberFormat::PLAIN);
+
+/// The format numbers are written in.
+pub fn get() -> NumberFormat {
+    *FORMAT.read().unwrap_or_else(|e| e.into_inner())
+}
+pub fn set(format: NumberFormat) {
+    *FORMAT.write().unwrap_or_else(|e| e.into_inner()) = format;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_format() {
+        let plain = NumberFormat::PLAIN;
+        assert_eq!(plain.binary("01010101"), "01010101");
+        assert_eq!(plain.count(1_000_000), "1000000");
+        let grouped = NumberFormat {
+            group_binary: true,
+            thousands: thousands_separator("pt_BR.UTF-8"),
+            ..plain
+        };
+        assert_eq!(grouped.binary("01010101"), "0101_0101");
+        assert_eq!(grouped.binary("101"), "101");
+        assert_eq!(grouped.binary("110101"), "11_0101");
+        assert_eq!(grouped.count(1_000_000), "1.000.000");
+        assert_eq!(grouped.decimal("-1234"), "-1.234");
+        assert_eq!(grouped.decimal("255"), "255");
+        assert_eq!(thousands_separator("en_US.UTF-8"), Some(','));
+        assert_eq!(thousands_separator("C"), None);
+    }
+}
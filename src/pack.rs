@@ -0,0 +1,319 @@
+//! Course packs: a whole course setup in one `.neanderpack` file, a
+//! zip archive with a `manifest.toml` listing what it holds:
+//!
+//! ```toml
+//! name = "arq1"
+//! title = "Computer Architecture I"
+//! neander = "0.1"
+//! examples = ["examples/sum.asm"]
+//! exercises = ["exercises/sum.toml"]
+//! specs = ["specs/sum.toml"]
+//! tutorials = ["tutorials/intro.toml"]
+//!
+//! [profile]
+//! base = "hex"
+//! dual = true
+//! group_binary = true
+//! ```
+//!
+//! Paths are relative to the root of the archive. `neander` is the
+//! version of the simulator the course runs, see `crate::pin`. The
+//! profile sets how the GUI shows numbers while the pack is open.
+//! Packs are built from a directory with `neander build-pack`,
+//! installed into a directory with `neander install-pack` and
+//! opened in the GUI.
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::exercise::Exercise;
+use crate::image::{self, MemoryImage};
+use crate::numfmt::NumberBase;
+use crate::pin;
+use crate::spec::Spec;
+use crate::srcmap::SourceMap;
+use crate::symbols::SymbolTable;
+
+pub const MANIFEST: &str = "manifest.toml";
+/// The variable with the directory packs are installed into
+pub const PACKS_DIR_VAR: &str = "NEANDER_PACKS";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The directory the pack is installed into
+    pub name: String,
+    pub title: String,
+    /// The version of the simulator pinned, see `crate::pin`
+    pub neander: Option<String>,
+    /// Programs, as assembly sources, memory files or Logisim images
+    #[serde(default)]
+    pub examples: Vec<String>,
+    #[serde(default)]
+    pub exercises: Vec<String>,
+    /// Specs for `neander test`, with their programs in the pack
+    #[serde(default)]
+    pub specs: Vec<String>,
+    #[serde(default)]
+    pub tutorials: Vec<String>,
+    #[serde(default)]
+    pub profile: Profile,
+}
+
+/// How an instructor wants numbers shown. Settings not given are
+/// left as the student has them.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// `dec`, `hex` or `bin`
+    pub base: Option<String>,
+    pub dual: Option<bool>,
+    pub group_binary: Option<bool>,
+}
+impl Profile {
+    pub fn base(&self) -> Option<NumberBase> {
+        NumberBase::from_name(self.base.as_deref()?)
+    }
+}
+
+/// A pack read into memory, with its files checked.
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub manifest: Manifest,
+    /// The contents of every file, by path
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Pack {
+    /// Reads a `.neanderpack` file, or a directory with a manifest
+    /// like the ones packs are built from and installed into.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if path.is_dir() {
+            return Self::from_dir(path);
+        }
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::from_zip(&bytes)
+    }
+    pub fn from_zip(bytes: &[u8]) -> Result<Self, String> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let mut files = BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+            if file.is_dir() {
+                continue;
+            }
+            // rejects absolute paths and paths out of the archive
+            let Some(path) = file.enclosed_name() else {
+                return Err(format!("invalid path in the pack: {}", file.name()));
+            };
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).map_err(|e| e.to_string())?;
+            files.insert(key(&path), contents);
+        }
+        Self::from_files(files)
+    }
+    /// Reads the files of a pack from `dir`, skipping hidden ones.
+    pub fn from_dir(dir: &Path) -> Result<Self, String> {
+        let mut files = BTreeMap::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(next) = dirs.pop() {
+            for entry in std::fs::read_dir(&next).map_err(|e| e.to_string())? {
+                let path = entry.map_err(|e| e.to_string())?.path();
+                if path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+                {
+                    continue;
+                }
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let contents = std::fs::read(&path).map_err(|e| e.to_string())?;
+                    let relative = path.strip_prefix(dir).map_err(|e| e.to_string())?;
+                    files.insert(key(relative), contents);
+                }
+            }
+        }
+        Self::from_files(files)
+    }
+    fn from_files(files: BTreeMap<String, Vec<u8>>) -> Result<Self, String> {
+        let manifest = files
+            .get(MANIFEST)
+            .ok_or_else(|| format!("the pack has no {MANIFEST}"))?;
+        let manifest = std::str::from_utf8(manifest).map_err(|e| e.to_string())?;
+        let manifest: Manifest = toml::from_str(manifest).map_err(|e| e.to_string())?;
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if manifest.name.is_empty() || !manifest.name.chars().all(valid) {
+            return Err(format!(
+                "invalid pack name: {:?}, use letters, digits, - and _",
+                manifest.name
+            ));
+        }
+        if let Some(pin) = &manifest.neander {
+            pin::matches(pin, pin::VERSION)?;
+        }
+        if manifest.profile.base.is_some() && manifest.profile.base().is_none() {
+            return Err("the base of the profile must be dec, hex or bin".to_string());
+        }
+        let pack = Self { manifest, files };
+        pack.check()?;
+        Ok(pack)
+    }
+    /// Checks that every file of the manifest is in the pack and loads.
+    fn check(&self) -> Result<(), String> {
+        let in_file = |path: &str, e: String| format!("{path}: {e}");
+        for path in &self.manifest.examples {
+            self.example(path, 256).map_err(|e| in_file(path, e))?;
+        }
+        for path in &self.manifest.exercises {
+            self.exercise(path).map_err(|e| in_file(path, e))?;
+        }
+        for path in &self.manifest.specs {
+            let spec = self.spec(path).map_err(|e| in_file(path, e))?;
+            let program = Path::new(path).with_file_name(&spec.program);
+            if !self.files.contains_key(&key(&program)) {
+                return Err(in_file(path, "the program isn't in the pack".to_string()));
+            }
+        }
+        for path in &self.manifest.tutorials {
+            let text = self.text(path)?;
+            toml::from_str::<toml::Table>(text).map_err(|e| in_file(path, e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// The text of the file at `path`.
+    pub fn text(&self, path: &str) -> Result<&str, String> {
+        let bytes = self
+            .files
+            .get(path)
+            .ok_or_else(|| format!("{path} isn't in the pack"))?;
+        std::str::from_utf8(bytes).map_err(|e| format!("{path}: {e}"))
+    }
+    /// Loads the example at `path` into an image of `len` bytes.
+    pub fn example(
+        &self,
+        path: &str,
+        len: usize,
+    ) -> Result<(MemoryImage, SymbolTable, SourceMap), String> {
+        image::parse(path, self.text(path)?, len)
+    }
+    pub fn exercise(&self, path: &str) -> Result<Exercise, String> {
+        match path.ends_with(".json") {
+            true => Exercise::from_json(self.text(path)?),
+            false => Exercise::from_toml(self.text(path)?),
+        }
+    }
+    pub fn spec(&self, path: &str) -> Result<Spec, String> {
+        match path.ends_with(".json") {
+            true => Spec::from_json(self.text(path)?),
+            false => Spec::from_toml(self.text(path)?),
+        }
+    }
+
+    /// A warning if the pack pins another version of the simulator.
+    pub fn version_warning(&self) -> Option<String> {
+        let pin = self.manifest.neander.as_deref()?;
+        // checked when the pack was read
+        pin::check(pin, &self.manifest.title).ok().flatten()
+    }
+
+    /// Writes the pack as a `.neanderpack` file.
+    pub fn write(&self, out: &Path) -> Result<(), String> {
+        let file = std::fs::File::create(out).map_err(|e| e.to_string())?;
+        let mut zip = ZipWriter::new(file);
+        for (path, contents) in &self.files {
+            zip.start_file(path.as_str(), SimpleFileOptions::default())
+                .map_err(|e| e.to_string())?;
+            zip.write_all(contents).map_err(|e| e.to_string())?;
+        }
+        zip.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    /// Writes the files of the pack into a directory named after
+    /// it in `dir`, replacing an older version, and returns it.
+    pub fn install(&self, dir: &Path) -> Result<PathBuf, String> {
+        let root = dir.join(&self.manifest.name);
+        if root.exists() {
+            std::fs::remove_dir_all(&root).map_err(|e| e.to_string())?;
+        }
+        for (path, contents) in &self.files {
+            let path = root.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, contents).map_err(|e| e.to_string())?;
+        }
+        Ok(root)
+    }
+}
+
+/// A path in the pack, with `/` between its parts.
+fn key(path: &Path) -> String {
+    let parts: Vec<_> = path.iter().map(|p| p.to_string_lossy()).collect();
+    parts.join("/")
+}
+
+/// Where packs are installed: `$NEANDER_PACKS`, or `.neander/packs`
+/// in the home directory.
+pub fn packs_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|d| !d.is_empty());
+    var(PACKS_DIR_VAR)
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(var("HOME")?).join(".neander/packs")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let name = format!("neander-test_pack-{}", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        let source = dir.join("source");
+        std::fs::create_dir_all(source.join("ex")).unwrap();
+        let write = |path: &str, text: &str| std::fs::write(source.join(path), text).unwrap();
+        write(
+            MANIFEST,
+            "name = \"arq1\"\ntitle = \"Arq I\"\nexamples = [\"ex/sum.asm\"]\n\
+             exercises = [\"ex/sum.toml\"]\nspecs = [\"ex/spec.toml\"]\n\
+             [profile]\nbase = \"hex\"\n",
+        );
+        write("ex/sum.asm", "lda x\nhlt\nx: db 5\n");
+        write(
+            "ex/sum.toml",
+            "title = \"Sum\"\nlocked = [\"0x80..0x82\"]\n",
+        );
+        write(
+            "ex/spec.toml",
+            "program = \"sum.asm\"\n[[case]]\nname = \"a\"\n",
+        );
+        write(".hidden", "");
+
+        let pack = Pack::open(&source).unwrap();
+        assert_eq!(pack.manifest.profile.base(), Some(NumberBase::Hex));
+        let file = dir.join("arq1.neanderpack");
+        pack.write(&file).unwrap();
+        let pack = Pack::open(&file).unwrap();
+        assert_eq!(pack.files.len(), 4);
+        let (image, symbols, _) = pack.example("ex/sum.asm", 256).unwrap();
+        assert_eq!(image.bytes()[..4], [0x20, 3, 0xF0, 5]);
+        assert_eq!(symbols.get("x"), Some(3));
+        assert!(pack.exercise("ex/sum.toml").unwrap().locked[0x81]);
+
+        let installed = pack.install(&dir.join("packs")).unwrap();
+        assert_eq!(installed, dir.join("packs/arq1"));
+        assert!(installed.join("ex/spec.toml").exists());
+
+        write("ex/spec.toml", "program = \"missing.asm\"\ncase = []\n");
+        let e = Pack::open(&source).unwrap_err();
+        assert_eq!(e, "ex/spec.toml: the program isn't in the pack");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! Version pins, so graders and students can check that they run
+//! the same simulator. A course pins a version in the `neander` key
+//! of a `neander.toml` in its directory, or of a course pack:
+//!
+//! ```toml
+//! neander = "0.1"
+//! ```
+//!
+//! A pin with fewer parts matches every version starting with them,
+//! so `0.1` matches 0.1.0 and 0.1.7. Nothing is ever downloaded,
+//! `neander self-check` only warns of a mismatch.
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// The version of this simulator.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const PROJECT_FILE: &str = "neander.toml";
+
+/// Whether `version` is the version pinned by `pin`.
+pub fn matches(pin: &str, version: &str) -> Result<bool, String> {
+    let parts: Vec<_> = pin.trim().split('.').collect();
+    let valid = |p: &&str| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit());
+    if parts.len() > 3 || !parts.iter().all(valid) {
+        return Err(format!(
+            "invalid version pin: {pin:?}, expected a version like 0.1 or 0.1.2"
+        ));
+    }
+    let version: Vec<_> = version.split(['.', '-', '+']).collect();
+    Ok(version.len() >= parts.len() && parts.iter().zip(&version).all(|(p, v)| p == v))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProjectFile {
+    neander: Option<String>,
+}
+
+/// The `neander.toml` in `dir` or the closest of its parents.
+pub fn find_project(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_FILE))
+        .find(|f| f.is_file())
+}
+
+/// The version pinned by the project file at `path`, if any.
+pub fn project_pin(path: &Path) -> Result<Option<String>, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let file: ProjectFile = toml::from_str(&source).map_err(|e| e.to_string())?;
+    if let Some(pin) = &file.neander {
+        matches(pin, VERSION)?;
+    }
+    Ok(file.neander)
+}
+
+/// A warning if this simulator isn't the version `pin` pins,
+/// `by` being what pinned it.
+pub fn check(pin: &str, by: &str) -> Result<Option<String>, String> {
+    Ok((!matches(pin, VERSION)?)
+        .then(|| format!("{by} pins neander {pin}, but this is neander {VERSION}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin() {
+        assert_eq!(matches("0.1", "0.1.7"), Ok(true));
+        assert_eq!(matches("0.1.7", "0.1.7"), Ok(true));
+        assert_eq!(matches("0", "0.1.7"), Ok(true));
+        assert_eq!(matches("0.1.7", "0.1.70"), Ok(false));
+        assert_eq!(matches("0.2", "0.1.7"), Ok(false));
+        assert_eq!(matches("0.1.0", "0.1.0-beta"), Ok(true));
+        assert!(matches("0.1.x", VERSION).is_err());
+        assert!(matches("", VERSION).is_err());
+        assert_eq!(check(VERSION, "the pack"), Ok(None));
+        assert_eq!(
+            check("999", "neander.toml").unwrap().unwrap(),
+            format!("neander.toml pins neander 999, but this is neander {VERSION}")
+        );
+
+        let root = std::env::temp_dir().join(format!("neander-test_pin-{}", std::process::id()));
+        let dir = root.join("course/week1");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.parent().unwrap().join(PROJECT_FILE);
+        std::fs::write(&file, "neander = \"0.1\"\n").unwrap();
+        assert_eq!(find_project(&dir), Some(file.clone()));
+        assert_eq!(project_pin(&file), Ok(Some("0.1".to_string())));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
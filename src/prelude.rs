@@ -0,0 +1,31 @@
+//! The types most programs built on the crate need:
+//!
+//! ```
+//! use neander::prelude::*;
+//!
+//! let mut cpu = Neander::new();
+//! cpu.set_ram_slice(0, &[instr::LDA, 128, instr::HLT]);
+//! cpu.set_ram(128, 7);
+//! assert_eq!(cpu.run(), Ok(()));
+//! assert_eq!(cpu.acc(), 7);
+//! ```
+//!
+//! Items are only added here in minor releases, and only
+//! removed or changed in incompatible ways in major ones.
+//! Modules not re-exported here, such as `debugger`, `ui` and
+//! `cli`, serve the `neander` binary and may change in any release.
+//!
+//! The machine, the assembler and the loaders come from the
+//! `neander-core` crate, re-exported here under the same paths. Tools
+//! that only embed the simulator can depend on it alone, without
+//! egui, clap or nom, and on `no_std` targets with `alloc`.
+pub use crate::asm::{assemble, AsmError};
+pub use crate::clock::Clock;
+pub use crate::cpu::instr;
+pub use crate::cpu::{ExecResult, LoadError, Neander, NeanderException, Registers};
+pub use crate::history::History;
+pub use crate::hooks::Hooks;
+pub use crate::io::{Bus, CharOutput, Device, Keyboard};
+pub use crate::memfile::{parse_memfile, parse_memfile_symbols, MemfileError};
+pub use crate::micro::{MicroOp, Phase};
+pub use crate::symbols::SymbolTable;
@@ -0,0 +1,345 @@
+//! Printable reports of a program, for lab submissions: the source
+//! with line numbers, the assembled listing, the labels, a
+//! disassembly, the results of a spec and the state the program
+//! ends in. Written as text, as an HTML page or as a PDF document.
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::cpu::{ExecResult, Neander};
+use crate::disasm;
+use crate::spec::{self, Spec};
+
+/// A titled block of preformatted lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Printout {
+    pub title: String,
+    pub sections: Vec<Section>,
+}
+
+impl Printout {
+    /// Builds the report of the program at `path`, running
+    /// the cases of `spec` on it if given.
+    pub fn new(path: &Path, spec: Option<&Spec>) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut cpu = Neander::new();
+        let (image, symbols, map) = cpu.load_file_image(path)?;
+        let mut sections = Vec::new();
+
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>4}  {line}", i + 1))
+            .collect();
+        sections.push(Section::new("Source", lines));
+
+        let mut lines = Vec::new();
+        if map.is_empty() {
+            lines.push("Only assembly sources (.asm) have a listing.".to_string());
+        }
+        // addresses grouped by the line they came from, in order
+        let mut groups: Vec<(usize, Vec<u8>)> = Vec::new();
+        for addr in 0..=255 {
+            let Some(line) = map.line(addr) else {
+                continue;
+            };
+            match groups.last_mut() {
+                Some((l, addrs)) if *l == line && addrs.last() == Some(&(addr - 1)) => {
+                    addrs.push(addr)
+                }
+                _ => groups.push((line, vec![addr])),
+            }
+        }
+        for (line, addrs) in groups {
+            let bytes: Vec<_> = addrs
+                .iter()
+                .map(|&a| format!("{:02X}", cpu.ram(a)))
+                .collect();
+            lines.push(format!(
+                "{:02X}  {:<12}{:>4}  {}",
+                addrs[0],
+                bytes.join(" "),
+                line,
+                map.text(addrs[0]).unwrap_or_default()
+            ));
+        }
+        sections.push(Section::new("Listing", lines));
+
+        let mut lines: Vec<_> = symbols
+            .iter()
+            .map(|(name, addr)| format!("{name:<16} {addr:02X} ({addr})"))
+            .collect();
+        if lines.is_empty() {
+            lines.push("No labels.".to_string());
+        }
+        sections.push(Section::new("Symbols", lines));
+
+        let end = (0..=255).rev().find(|&a| cpu.ram(a) != 0).unwrap_or(0);
+        let lines = disasm::disassemble_labeled(cpu.memory(), 0, end, &symbols)
+            .into_iter()
+            .map(|line| {
+                let label = symbols.name_at(line.addr).unwrap_or_default();
+                let label = if label.is_empty() {
+                    String::new()
+                } else {
+                    format!("{label}:")
+                };
+                match image.origin(line.addr as usize) {
+                    Some(origin) => {
+                        format!("{:02X}  {label:<12} {:<14} {origin}", line.addr, line.text)
+                    }
+                    None => format!("{:02X}  {label:<12} {}", line.addr, line.text),
+                }
+            })
+            .collect();
+        sections.push(Section::new("Disassembly", lines));
+
+        if let Some(spec) = spec {
+            sections.push(Section::new("Tests", test_lines(spec, &cpu)));
+        }
+        sections.push(Section::new("Final state", final_state(cpu)));
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        Ok(Self {
+            title: format!("Report of {name}"),
+            sections,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{}\n", self.title);
+        for section in &self.sections {
+            let _ = write!(out, "\n{}\n\n", section.title);
+            for line in &section.lines {
+                let _ = writeln!(out, "{line}");
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{0}</title>\n<style>\n\
+             body {{ font-family: sans-serif; max-width: 50em; margin: auto; }}\n\
+             pre {{ background: #f4f4f4; padding: 0.5em; }}\n\
+             @media print {{ h2 {{ break-after: avoid; }} }}\n\
+             </style>\n</head>\n<body>\n<h1>{0}</h1>",
+            escape(&self.title)
+        );
+        for section in &self.sections {
+            let _ = writeln!(out, "<h2>{}</h2>\n<pre>", escape(&section.title));
+            for line in &section.lines {
+                let _ = writeln!(out, "{}", escape(line));
+            }
+            let _ = writeln!(out, "</pre>");
+        }
+        let _ = writeln!(out, "</body>\n</html>");
+        out
+    }
+
+    /// A PDF document of A4 pages in Courier.
+    /// Characters outside ASCII are written as `?`.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut lines = vec![(true, self.title.clone())];
+        for section in &self.sections {
+            lines.push((false, String::new()));
+            lines.push((true, section.title.clone()));
+            for line in &section.lines {
+                let mut rest = line.as_str();
+                while rest.chars().count() > PDF_COLUMNS {
+                    let split = rest.char_indices().nth(PDF_COLUMNS).unwrap().0;
+                    lines.push((false, rest[..split].to_string()));
+                    rest = &rest[split..];
+                }
+                lines.push((false, rest.to_string()));
+            }
+        }
+        let pages: Vec<_> = lines.chunks(PDF_LINES).map(pdf_page).collect();
+
+        // objects 1 and 2 are the catalog and the page tree, 3 and 4
+        // the fonts, then each page is followed by its contents
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..pages.len())
+                    .map(|i| format!("{} 0 R", 5 + 2 * i))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                pages.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier-Bold >>".to_string(),
+        ];
+        for (i, content) in pages.into_iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                6 + 2 * i
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{content}\nendstream",
+                content.len()
+            ));
+        }
+        let mut pdf = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            let _ = write!(pdf, "{} 0 obj\n{object}\nendobj\n", i + 1);
+        }
+        let xref = pdf.len();
+        let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(pdf, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            pdf,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        );
+        pdf.into_bytes()
+    }
+}
+
+impl Section {
+    fn new(title: &str, lines: Vec<String>) -> Self {
+        Self {
+            title: title.to_string(),
+            lines,
+        }
+    }
+}
+
+/// Lines per PDF page, and characters per line, in 9 point Courier.
+const PDF_LINES: usize = 68;
+const PDF_COLUMNS: usize = 95;
+
+/// The content stream of a page of (bold, text) lines.
+fn pdf_page(lines: &[(bool, String)]) -> String {
+    let mut out = String::from("BT\n11 TL\n40 800 Td\n");
+    for (bold, text) in lines {
+        let font = if *bold { "F2" } else { "F1" };
+        let text: String = text
+            .chars()
+            .map(|c| match c {
+                '(' | ')' | '\\' => format!("\\{c}"),
+                ' '..='~' => c.to_string(),
+                '\t' => "    ".to_string(),
+                _ => "?".to_string(),
+            })
+            .collect();
+        let _ = writeln!(out, "/{font} 9 Tf\n({text}) Tj T*");
+    }
+    out.push_str("ET");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The results of the cases of `spec` on `program`.
+fn test_lines(spec: &Spec, program: &Neander) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut passed = 0;
+    for case in &spec.cases {
+        match spec.run_case(program, case) {
+            Ok(failures) if failures.is_empty() => {
+                passed += 1;
+                lines.push(format!("test {} ... ok", case.name));
+            }
+            Ok(failures) => {
+                lines.push(format!("test {} ... FAILED", case.name));
+                lines.extend(failures.iter().map(|f| format!("    {f}")));
+            }
+            Err(e) => {
+                lines.push(format!("test {} ... ERROR", case.name));
+                lines.push(format!("    {e}"));
+            }
+        }
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "{} tests, {passed} passed, {} failed",
+        spec.cases.len(),
+        spec.cases.len() - passed
+    ));
+    lines
+}
+
+/// Runs `cpu` until it stops, describing how it stopped,
+/// its registers and the memory rows that aren't zero.
+fn final_state(mut cpu: Neander) -> Vec<String> {
+    let max_steps = spec::default_max_steps();
+    let mut steps = 0;
+    let stop = loop {
+        if steps == max_steps {
+            break format!("still running after {max_steps} steps");
+        }
+        steps += 1;
+        match cpu.step() {
+            ExecResult::Halted => break format!("halted after {steps} steps"),
+            ExecResult::Exception(e) => break format!("exception after {steps} steps: {e}"),
+            _ => (),
+        }
+    };
+    let mut lines = vec![
+        stop,
+        format!(
+            "PC {:02X}  AC {} ({:02X})  N {}  Z {}",
+            cpu.pc(),
+            cpu.acc(),
+            cpu.acc() as u8,
+            cpu.status_negative() as u8,
+            cpu.status_zero() as u8
+        ),
+        String::new(),
+    ];
+    let rows = cpu.memory().chunks(16).enumerate();
+    for (row, bytes) in rows.filter(|(_, row)| row.iter().any(|&b| b != 0)) {
+        let bytes: Vec<_> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+        lines.push(format!("{:02X}: {}", row * 16, bytes.join(" ")));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_printout() {
+        let name = format!("neander-test_printout-{}", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("neander-printout.asm");
+        std::fs::write(
+            &path,
+            "start: lda x\n  add x\n  sta x\n  hlt\norg 128\nx: 3\n",
+        )
+        .unwrap();
+        let printout = Printout::new(&path, None).unwrap();
+        let text = printout.to_text();
+        assert!(text.contains("   2    add x"));
+        assert!(text.contains("02  30 80          2  add x"));
+        assert!(text.contains("x                80 (128)"));
+        assert!(text.contains("04               STA x          neander-printout.asm:3"));
+        assert!(text.contains("halted after 4 steps"));
+        assert!(text.contains("80: 06 00"));
+        assert!(printout.to_html().contains("<h2>Disassembly</h2>"));
+        let pdf = printout.to_pdf();
+        assert!(pdf.starts_with(b"%PDF-1.4") && pdf.ends_with(b"%%EOF\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+//! How many times each address was executed as an instruction, to
+//! find the hot loops of a program. Printed as a table by
+//! `neander run --profile` and shown over the GUI memory grid.
+use std::fmt::Write;
+
+use crate::ansi;
+use crate::cpu::Neander;
+use crate::disasm;
+use crate::hooks::Hooks;
+use crate::symbols::SymbolTable;
+
+/// The executions of every address, collected as `Hooks`.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub counts: [u64; 256],
+}
+impl Default for Profile {
+    fn default() -> Self {
+        Self { counts: [0; 256] }
+    }
+}
+
+impl Hooks for Profile {
+    fn on_fetch(&mut self, _cpu: &Neander, pc: u8, _opcode: u8) {
+        self.counts[pc as usize] += 1;
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+    /// The instructions executed.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+    /// How hot `addr` is, from 0 for never executed to 1 for the
+    /// hottest address. Logarithmic, so a loop run a hundred times
+    /// doesn't make the code around it look cold.
+    pub fn heat(&self, addr: u8) -> f32 {
+        let max = self.counts.iter().max().copied().unwrap_or(0);
+        match self.counts[addr as usize] {
+            0 => 0.,
+            count => ((count as f32).ln_1p() / (max as f32).ln_1p()).max(0.05),
+        }
+    }
+
+    /// The executed addresses, hottest first, with their counts,
+    /// share of the run and instructions.
+    pub fn report(&self, mem: &[u8], symbols: &SymbolTable, color: bool) -> String {
+        let total = self.total();
+        let mut addrs: Vec<u8> = (0..=255).filter(|&a| self.counts[a as usize] > 0).collect();
+        addrs.sort_by_key(|&a| std::cmp::Reverse(self.counts[a as usize]));
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{total} instructions executed at {} addresses",
+            addrs.len()
+        );
+        let _ = writeln!(out, "{:>10}  {:>6}  ADDR  INSTRUCTION", "COUNT", "SHARE");
+        for addr in addrs {
+            let count = self.counts[addr as usize];
+            let share = count as f64 * 100. / total as f64;
+            let line = disasm::decode_labeled(mem, addr, symbols);
+            let label = match symbols.name_at(addr) {
+                Some(name) => ansi::paint(ansi::LABEL, format!("{name}: "), color),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                out,
+                "{count:>10}  {share:>5.1}%  {addr:02X}    {label}{}",
+                ansi::instruction(&line.text, color)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn test_profile() {
+        let source = "loop: lda x\nadd one\nsta x\njn loop\nhlt\nx: db -3\none: db 1\n";
+        let mut cpu = Neander::new();
+        let symbols = asm::assemble(cpu.memory_mut(), source).unwrap();
+        let mut profile = Profile::new();
+        while cpu.step_hooked(&mut profile) != crate::cpu::ExecResult::Halted {}
+        assert_eq!(profile.counts[0], 3);
+        assert_eq!(profile.counts[8], 1);
+        assert_eq!(profile.total(), 13);
+        assert_eq!(profile.heat(0), 1.);
+        assert_eq!(profile.heat(9), 0.);
+        assert!(profile.heat(8) < profile.heat(0));
+
+        let report = profile.report(cpu.memory(), &symbols, false);
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines[0], "13 instructions executed at 5 addresses");
+        assert_eq!(lines[1], "     COUNT   SHARE  ADDR  INSTRUCTION");
+        assert_eq!(lines[2], "         3   23.1%  00    loop: LDA x");
+        assert_eq!(lines[6], "         1    7.7%  08    HLT");
+    }
+}
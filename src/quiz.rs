@@ -0,0 +1,156 @@
+//! Random questions on the encoding of instructions, with an answer
+//! key, for instructors building problem sets.
+use std::fmt;
+
+use crate::cpu::instr::{self, *};
+use crate::cpu::{fmt_dual, Neander};
+use crate::rng::Rng;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub prompt: String,
+    pub answer: String,
+}
+
+/// An instruction in assembly, like "LDA 0x80".
+fn mnemonic(opcode: u8, arg: u8) -> String {
+    let name = instr::name(opcode).unwrap_or("???");
+    match has_operand(opcode) {
+        true => format!("{name} 0x{arg:02X}"),
+        false => name.to_string(),
+    }
+}
+fn bytes(opcode: u8, arg: u8) -> String {
+    match has_operand(opcode) {
+        true => format!("{opcode:02X} {arg:02X}"),
+        false => format!("{opcode:02X}"),
+    }
+}
+
+fn encode(rng: &mut Rng) -> Question {
+    let (opcode, _) = rng.pick(&INSTRUCTIONS);
+    let arg = rng.byte();
+    Question {
+        prompt: format!(
+            "Encode `{}` as bytes, in hexadecimal.",
+            mnemonic(opcode, arg)
+        ),
+        answer: bytes(opcode, arg),
+    }
+}
+
+fn decode(rng: &mut Rng) -> Question {
+    let (opcode, _) = rng.pick(&INSTRUCTIONS);
+    let arg = rng.byte();
+    Question {
+        prompt: format!("Decode the bytes `{}`.", bytes(opcode, arg)),
+        answer: mnemonic(opcode, arg),
+    }
+}
+
+/// Three instructions working on the data at 0x80 to 0x82,
+/// answered by running them.
+fn accumulator(rng: &mut Rng) -> Question {
+    let mut cpu = Neander::new();
+    let data: Vec<u8> = (0..3).map(|_| rng.byte()).collect();
+    cpu.set_ram_slice(0x80, &data);
+    let mut program = vec![(LDA, 0x80 + rng.below(3) as u8)];
+    for _ in 0..2 {
+        let opcode = rng.pick(&[ADD, OR, AND, NOT]);
+        program.push((opcode, 0x80 + rng.below(3) as u8));
+    }
+    let mut addr = 0;
+    for &(opcode, arg) in &program {
+        cpu.set_ram_slice(addr, &[opcode, arg]);
+        addr += if has_operand(opcode) { 2 } else { 1 };
+    }
+    for _ in &program {
+        cpu.step();
+    }
+    let memory: Vec<_> = data
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| format!("{:02X}: {}", 0x80 + i, fmt_dual(v)))
+        .collect();
+    let code: Vec<_> = program
+        .iter()
+        .map(|&(opcode, arg)| format!("`{}`", mnemonic(opcode, arg)))
+        .collect();
+    Question {
+        prompt: format!(
+            "Memory holds {}. What does the accumulator hold after {}?",
+            memory.join(", "),
+            code.join(", ")
+        ),
+        answer: fmt_dual(cpu.acc() as u8),
+    }
+}
+
+/// Generates `count` questions on encoding, decoding or
+/// evaluating instructions, picking each kind at random.
+pub fn generate(count: usize, seed: u64) -> Vec<Question> {
+    let mut rng = Rng::new(seed);
+    let kinds: [fn(&mut Rng) -> Question; 3] = [encode, decode, accumulator];
+    (0..count).map(|_| rng.pick(&kinds)(&mut rng)).collect()
+}
+
+/// A quiz, printed as its numbered questions followed by the answer key.
+pub struct Quiz {
+    pub seed: u64,
+    pub questions: Vec<Question>,
+}
+
+impl Quiz {
+    pub fn new(count: usize, seed: u64) -> Self {
+        Self {
+            seed,
+            questions: generate(count, seed),
+        }
+    }
+}
+
+impl fmt::Display for Quiz {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Neander quiz (seed {})\n", self.seed)?;
+        for (i, q) in self.questions.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, q.prompt)?;
+        }
+        writeln!(f, "\nAnswer key\n")?;
+        for (i, q) in self.questions.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, q.answer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiz() {
+        assert_eq!(generate(20, 7), generate(20, 7));
+        assert_ne!(generate(20, 7), generate(20, 8));
+        let questions = generate(60, 1);
+        for q in &questions {
+            if let Some(code) = q.prompt.strip_prefix("Encode `") {
+                let code = code.split('`').next().unwrap();
+                let mut mem = [0; 256];
+                crate::asm::assemble(&mut mem, code).unwrap();
+                let len = q.answer.split(' ').count();
+                let expected: Vec<_> = mem[..len].iter().map(|b| format!("{b:02X}")).collect();
+                assert_eq!(q.answer, expected.join(" "), "{}", q.prompt);
+            }
+        }
+        assert!(questions.iter().any(|q| q.prompt.starts_with("Decode")));
+
+        let mut rng = Rng::new(0);
+        let q = accumulator(&mut rng);
+        assert!(q.prompt.starts_with("Memory holds 80: "), "{}", q.prompt);
+        assert!(q.prompt.contains("`LDA 0x8"), "{}", q.prompt);
+
+        let quiz = Quiz::new(2, 3).to_string();
+        assert!(quiz.starts_with("Neander quiz (seed 3)\n\n1. "), "{quiz}");
+        assert!(quiz.contains("\nAnswer key\n\n1. "), "{quiz}");
+    }
+}
@@ -0,0 +1,178 @@
+//! Renders the registers and memory of a machine, as an SVG image
+//! for handouts and feedback, or as text for terminals.
+use std::fmt::Write;
+
+use crate::ansi;
+use crate::cpu::{fmt_dual, Neander};
+
+const CELL: (u32, u32) = (30, 22);
+/// Space for the row and column headers of the memory.
+const HEADER: u32 = 34;
+const MARGIN: u32 = 16;
+/// Height of the title and registers above the memory.
+const TOP: u32 = 78;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Returns an SVG image of `cpu`, with `title` on top.
+/// The cell the program counter points to is highlighted.
+pub fn render_svg(cpu: &Neander, title: &str) -> String {
+    let width = 2 * MARGIN + HEADER + 16 * CELL.0;
+    let height = TOP + HEADER + 16 * CELL.1 + MARGIN;
+    let mut svg = String::new();
+    // writing to a String never fails
+    let mut line = |s: String| {
+        let _ = writeln!(svg, "{s}");
+    };
+    line(format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="12">"#
+    ));
+    line(format!(
+        r#"<rect width="{width}" height="{height}" fill="white"/>"#
+    ));
+    line(format!(
+        r#"<text x="{MARGIN}" y="24" font-size="15" font-weight="bold">{}</text>"#,
+        escape(title)
+    ));
+    let flags = format!(
+        "N={} Z={}",
+        cpu.status_negative() as u8,
+        cpu.status_zero() as u8
+    );
+    let registers = [
+        format!("PC: {}", fmt_dual(cpu.pc())),
+        format!("AC: {}", fmt_dual(cpu.acc() as u8)),
+        flags,
+    ];
+    for (i, reg) in registers.iter().enumerate() {
+        line(format!(
+            r#"<text x="{}" y="52">{reg}</text>"#,
+            MARGIN + i as u32 * 170
+        ));
+    }
+    if cpu.status_end_of_prog() {
+        line(format!(
+            r#"<text x="{MARGIN}" y="68" fill="firebrick">end of program</text>"#
+        ));
+    }
+
+    let (x0, y0) = (MARGIN + HEADER, TOP + HEADER);
+    for i in 0..16 {
+        line(format!(
+            r##"<text x="{}" y="{}" text-anchor="middle" fill="#888">_{i:X}</text>"##,
+            x0 + i * CELL.0 + CELL.0 / 2,
+            y0 - 8
+        ));
+        line(format!(
+            r##"<text x="{}" y="{}" text-anchor="end" fill="#888">{i:X}_</text>"##,
+            x0 - 8,
+            y0 + i * CELL.1 + 15
+        ));
+    }
+    for (addr, &byte) in cpu.memory().iter().enumerate() {
+        let (row, col) = (addr as u32 / 16, addr as u32 % 16);
+        let (x, y) = (x0 + col * CELL.0, y0 + row * CELL.1);
+        let (fill, stroke) = if addr == cpu.pc() as usize {
+            ("#ffd27f", "#cc7a00")
+        } else if byte != 0 {
+            ("#eef3fb", "#bbb")
+        } else {
+            ("white", "#ddd")
+        };
+        line(format!(
+            r#"<rect x="{x}" y="{y}" width="{}" height="{}" fill="{fill}" stroke="{stroke}"/>"#,
+            CELL.0, CELL.1
+        ));
+        line(format!(
+            r#"<text x="{}" y="{}" text-anchor="middle">{byte:02X}</text>"#,
+            x + CELL.0 / 2,
+            y + 15
+        ));
+    }
+    line("</svg>".to_string());
+    svg
+}
+
+/// Returns a drawing of `cpu` with box characters, a panel with the
+/// registers above a memory grid. The PC is marked with `>`, and
+/// with `color` it's also highlighted and zeros are dimmed.
+pub fn render_ascii(cpu: &Neander, color: bool) -> String {
+    // 16 cells of 3 characters and a space at the end
+    const INNER: usize = 16 * 3 + 1;
+    let paint = |code: &str, text: String| ansi::paint(code, text, color);
+    let mut out = String::new();
+    let bar = "─".repeat(INNER);
+    let mut panel = format!(
+        " PC {:<12} AC {:<12} N {}  Z {}",
+        fmt_dual(cpu.pc()),
+        fmt_dual(cpu.acc() as u8),
+        cpu.status_negative() as u8,
+        cpu.status_zero() as u8
+    );
+    if cpu.status_end_of_prog() {
+        panel.push_str("  END");
+    }
+    let _ = writeln!(out, "   ┌{bar}┐");
+    let _ = writeln!(out, "   │{panel:<INNER$}│");
+    let _ = writeln!(out, "   ├{bar}┤");
+    let _ = write!(out, "   │");
+    for col in 0..16 {
+        let _ = write!(out, " _{col:X}");
+    }
+    let _ = writeln!(out, " │");
+    for (row, bytes) in cpu.memory().chunks(16).enumerate() {
+        let _ = write!(out, "{row:X}_ │");
+        for (col, &byte) in bytes.iter().enumerate() {
+            let cell = match row * 16 + col == cpu.pc() as usize {
+                true => paint(ansi::HIGHLIGHT, format!(">{byte:02X}")),
+                false if byte == 0 => paint(ansi::DIM, format!(" {byte:02X}")),
+                false => format!(" {byte:02X}"),
+            };
+            out.push_str(&cell);
+        }
+        let _ = writeln!(out, " │");
+    }
+    let _ = writeln!(out, "   └{bar}┘");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_svg() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[0x20, 0x80, 0xF0]);
+        cpu.set_pc(2);
+        let svg = render_svg(&cpu, "a < b");
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("a &lt; b"));
+        assert!(svg.contains(">F0</text>"));
+        assert!(svg.contains("PC: 02 (2)"));
+        // the cell of the PC
+        assert_eq!(svg.matches("#ffd27f").count(), 1);
+        assert_eq!(svg.matches("<rect").count(), 257);
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[0x20, 0x80, 0xF0]);
+        cpu.set_pc(2);
+        let text = render_ascii(&cpu, false);
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2 + 2 + 16 + 1);
+        // every line of the box has the same width
+        assert!(lines.iter().all(|l| l.chars().count() == 54));
+        assert!(lines[1].contains("PC 02 (2)"));
+        assert!(lines[4].starts_with("0_ │ 20 80>F0 00"));
+        assert!(!text.contains('\x1b'));
+        assert!(render_ascii(&cpu, true).contains("\x1b[7m>F0"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Warnings for self-modifying code: instructions executed from an
+//! address the same run stored to. Students mostly do it by mistake,
+//! storing a result over their own program, so `neander run
+//! --warn-smc` and the `smc on` directive of the debugger point it
+//! out. Stores to the operand of an instruction aren't warned about,
+//! since that's how Neander programs walk through arrays.
+use std::fmt;
+
+use crate::cpu::instr;
+use crate::cpu::Neander;
+use crate::hooks::Hooks;
+
+/// An instruction executed from an address stored to before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmcWarning {
+    pub addr: u8,
+    pub opcode: u8,
+    /// The address of the STA that stored to `addr`
+    pub writer: u8,
+}
+impl fmt::Display for SmcWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = instr::name(self.opcode).unwrap_or("an invalid instruction");
+        write!(
+            f,
+            "{:02X}: executed {name} after the STA at {:02X} stored to it, \
+             is the program writing over its code?",
+            self.addr, self.writer
+        )
+    }
+}
+
+/// Watches a run for self-modifying code, warning once per address.
+#[derive(Debug, Clone)]
+pub struct SmcWatch {
+    /// The STA that last stored to each address
+    writers: [Option<u8>; 256],
+    warned: [bool; 256],
+    /// The address of the instruction being executed
+    pc: u8,
+    warnings: Vec<SmcWarning>,
+}
+impl Default for SmcWatch {
+    fn default() -> Self {
+        Self {
+            writers: [None; 256],
+            warned: [false; 256],
+            pc: 0,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Hooks for SmcWatch {
+    fn on_fetch(&mut self, _cpu: &Neander, pc: u8, opcode: u8) {
+        self.pc = pc;
+        if let Some(writer) = self.writers[pc as usize] {
+            if !self.warned[pc as usize] {
+                self.warned[pc as usize] = true;
+                self.warnings.push(SmcWarning {
+                    addr: pc,
+                    opcode,
+                    writer,
+                });
+            }
+        }
+    }
+    fn on_mem_write(&mut self, addr: u8, _old: u8, _new: u8) {
+        self.writers[addr as usize] = Some(self.pc);
+    }
+}
+
+impl SmcWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+    /// The warnings raised since the last call.
+    pub fn take(&mut self) -> Vec<SmcWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_smc() {
+        let mut cpu = Neander::new();
+        // stores to the operand of the LDA at 7, then a HLT over the NOP at 9
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 8, STA, 9, NOP, LDA, 0, NOP]);
+        cpu.set_ram(128, HLT);
+        let mut smc = SmcWatch::new();
+        while cpu.step_hooked(&mut smc) != crate::cpu::ExecResult::Halted {}
+        let warnings = smc.take();
+        assert_eq!(
+            warnings,
+            [SmcWarning {
+                addr: 9,
+                opcode: HLT,
+                writer: 4
+            }]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "09: executed HLT after the STA at 04 stored to it, \
+             is the program writing over its code?"
+        );
+
+        // warned once per address
+        cpu.set_pc(9);
+        cpu.step_hooked(&mut smc);
+        assert!(smc.take().is_empty());
+    }
+}
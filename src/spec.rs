@@ -0,0 +1,329 @@
+//! Test specs, checking the result of a program against
+//! expected values. Specs are written in TOML or JSON:
+//!
+//! ```toml
+//! program = "sum.mem"
+//! max_steps = 1000
+//!
+//! [[case]]
+//! name = "small numbers"
+//! memory = { 128 = 2, 129 = 3 }
+//! expect = { acc = 5, memory = { 130 = 5 } }
+//! ```
+//!
+//! The program path is relative to the spec file. Each case loads
+//! the program, presets the memory cells, runs it to HLT and checks
+//! the expected values. Addresses may be decimal or hexadecimal (0x..),
+//! values are bytes from -128 to 255. Cases also fail if they raise
+//! one of the `trap` entries, described in `traps`.
+//!
+//! The specs of the classic assignments are built in, with a
+//! description of where the program finds its input and leaves
+//! its results instead of a program.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::cpu::{ExecResult, Neander, NeanderException};
+use crate::traps::{Trap, Traps};
+
+pub fn default_max_steps() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    /// Empty in the built-in specs
+    #[serde(default)]
+    pub program: PathBuf,
+    /// What the program must do, in the built-in specs
+    pub description: Option<String>,
+    /// Instructions a case may execute before it fails
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u64,
+    #[serde(rename = "case")]
+    pub cases: Vec<Case>,
+    #[serde(rename = "trap", default)]
+    pub traps: Vec<Trap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    pub name: String,
+    /// Memory cells set before running
+    #[serde(default)]
+    pub memory: BTreeMap<String, i16>,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    #[serde(default)]
+    pub memory: BTreeMap<String, i16>,
+    pub acc: Option<i16>,
+    pub pc: Option<u8>,
+}
+
+/// Why a case failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    Mismatch {
+        what: String,
+        expected: u8,
+        got: u8,
+    },
+    Exception(String),
+    StepLimit(u64),
+    /// A trap was raised, described by the message
+    Trap(String),
+}
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Mismatch {
+                what,
+                expected,
+                got,
+            } => write!(
+                f,
+                "{what}: expected {} (0x{expected:02X}), got {} (0x{got:02X})",
+                *expected as i8, *got as i8
+            ),
+            Failure::Exception(e) => write!(f, "exception: {e}"),
+            Failure::StepLimit(n) => write!(f, "did not halt after {n} steps"),
+            Failure::Trap(t) => write!(f, "trap: {t}"),
+        }
+    }
+}
+
+/// The specs of the classic assignments, by name.
+pub const BUILTIN: [(&str, &str); 4] = [
+    ("sum", include_str!("../assignments/sum.toml")),
+    ("maximum", include_str!("../assignments/maximum.toml")),
+    ("multiply", include_str!("../assignments/multiply.toml")),
+    ("divide", include_str!("../assignments/divide.toml")),
+];
+
+impl Spec {
+    /// Returns the built-in spec called `name`, testing `program`.
+    pub fn builtin(name: &str, program: &Path) -> Result<Self, String> {
+        let Some((_, source)) = BUILTIN.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<_> = BUILTIN.iter().map(|(n, _)| *n).collect();
+            return Err(format!(
+                "no built-in spec called {name}, try one of: {}",
+                names.join(", ")
+            ));
+        };
+        let mut spec = Self::from_toml(source)?;
+        spec.program = program.to_path_buf();
+        Ok(spec)
+    }
+    /// Reads a spec, as JSON if the file ends in `.json` and TOML otherwise.
+    /// The program path is made relative to the spec's directory.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut spec = if path.extension().is_some_and(|e| e == "json") {
+            Self::from_json(&source)?
+        } else {
+            Self::from_toml(&source)?
+        };
+        if spec.program.as_os_str().is_empty() {
+            return Err("the spec has no program".to_string());
+        }
+        if let Some(dir) = path.parent() {
+            spec.program = dir.join(&spec.program);
+        }
+        Ok(spec)
+    }
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        toml::from_str(source).map_err(|e| e.to_string())
+    }
+    pub fn from_json(source: &str) -> Result<Self, String> {
+        serde_json::from_str(source).map_err(|e| e.to_string())
+    }
+
+    /// Runs `case` on a CPU loaded with `program`, returning every failed check.
+    pub fn run_case(&self, program: &Neander, case: &Case) -> Result<Vec<Failure>, String> {
+        run_case(program, case, self.max_steps, &self.traps)
+    }
+}
+
+/// Runs `case` on a CPU loaded with `program`, failing if it
+/// executes more than `max_steps` instructions or raises a trap.
+pub fn run_case(
+    program: &Neander,
+    case: &Case,
+    max_steps: u64,
+    traps: &[Trap],
+) -> Result<Vec<Failure>, String> {
+    let mut cpu = program.clone();
+    for (addr, value) in &case.memory {
+        cpu.set_ram(parse_addr(addr)?, byte(*value)?);
+    }
+    let res = if traps.is_empty() {
+        cpu.run_predecoded(max_steps)
+    } else {
+        let mut traps = Traps::new(traps.to_vec());
+        let mut res = Err(NeanderException::StepLimitExceeded(max_steps));
+        for _ in 0..max_steps {
+            let step = cpu.step_hooked(&mut traps);
+            if let Some(t) = traps.tripped() {
+                return Ok(vec![Failure::Trap(t.to_string())]);
+            }
+            match step {
+                ExecResult::Halted => res = Ok(()),
+                ExecResult::Exception(e) => res = Err(e),
+                _ => continue,
+            }
+            break;
+        }
+        res
+    };
+    match res {
+        Ok(()) => (),
+        Err(NeanderException::StepLimitExceeded(n)) => return Ok(vec![Failure::StepLimit(n)]),
+        Err(e) => return Ok(vec![Failure::Exception(e.to_string())]),
+    }
+    let mut failures = Vec::new();
+    let mut check = |what: String, expected: u8, got: u8| {
+        if expected != got {
+            failures.push(Failure::Mismatch {
+                what,
+                expected,
+                got,
+            });
+        }
+    };
+    if let Some(acc) = case.expect.acc {
+        check("ACC".to_string(), byte(acc)?, cpu.acc() as u8);
+    }
+    if let Some(pc) = case.expect.pc {
+        check("PC".to_string(), pc, cpu.pc());
+    }
+    for (addr, value) in &case.expect.memory {
+        let a = parse_addr(addr)?;
+        check(format!("mem[{a}]"), byte(*value)?, cpu.ram(a));
+    }
+    Ok(failures)
+}
+
+/// Parses an address in decimal or hexadecimal (0x..).
+pub(crate) fn parse_addr(addr: &str) -> Result<u8, String> {
+    let res = match addr.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => addr.parse(),
+    };
+    res.map_err(|_| format!("invalid address: {addr}"))
+}
+
+/// Parses an address, or a range of them as `start..end`, inclusive.
+pub(crate) fn parse_range(range: &str) -> Result<RangeInclusive<u8>, String> {
+    let (start, end) = match range.split_once("..") {
+        Some((a, b)) => (parse_addr(a.trim())?, parse_addr(b.trim())?),
+        None => (parse_addr(range.trim())?, parse_addr(range.trim())?),
+    };
+    if start > end {
+        return Err(format!("invalid range: {range}"));
+    }
+    Ok(start..=end)
+}
+
+/// Converts a value from -128 to 255 into a byte.
+pub(crate) fn byte(value: i16) -> Result<u8, String> {
+    match value {
+        -128..=255 => Ok(value as u8),
+        _ => Err(format!("value out of range: {value}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_spec() {
+        let spec = Spec::from_toml(
+            r#"
+            program = "sum.mem"
+            max_steps = 100
+            [[case]]
+            name = "ok"
+            memory = { 128 = 2, 0x81 = -3 }
+            expect = { acc = -1, memory = { 130 = 255 } }
+            [[case]]
+            name = "wrong"
+            expect = { acc = 1, pc = 7 }
+            "#,
+        )
+        .unwrap();
+        let json = r#"{"program": "sum.mem", "case": [{"name": "ok"}]}"#;
+        assert_eq!(Spec::from_json(json).unwrap().max_steps, 10_000);
+        assert!(Spec::from_toml("program = \"a\"\nwhat = 1\ncase = []").is_err());
+
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, ADD, 129, STA, 130, HLT]);
+        assert_eq!(spec.run_case(&cpu, &spec.cases[0]), Ok(vec![]));
+        let failures = spec.run_case(&cpu, &spec.cases[1]).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].to_string(),
+            "ACC: expected 1 (0x01), got 0 (0x00)"
+        );
+
+        cpu.set_ram_slice(0, &[JMP, 0]);
+        let failures = spec.run_case(&cpu, &spec.cases[0]).unwrap();
+        assert_eq!(failures, [Failure::StepLimit(100)]);
+
+        assert!(Spec::builtin("square", Path::new("p.mem")).is_err());
+    }
+
+    /// Runs the built-in spec `name` on the program `source`.
+    fn check_builtin(name: &str, source: &str) {
+        let spec = Spec::builtin(name, Path::new("p.mem")).unwrap();
+        assert!(spec.description.is_some());
+        let mut cpu = Neander::new();
+        crate::asm::assemble(cpu.memory_mut(), source).unwrap();
+        for case in &spec.cases {
+            assert_eq!(
+                spec.run_case(&cpu, case),
+                Ok(vec![]),
+                "{name}: {}",
+                case.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin() {
+        check_builtin(
+            "sum",
+            "lda zero sta 0x80 lda first sta p
+             loop: lda 0x81 jz end add minus1 sta 0x81
+             lda 0x80 0x30 p: 0 sta 0x80
+             lda p add one sta p jmp loop
+             end: hlt zero: 0 one: 1 minus1: 0xFF first: 0x82",
+        );
+        check_builtin(
+            "multiply",
+            "lda zero sta 0x82
+             loop: lda 0x81 jz end add minus1 sta 0x81
+             lda 0x82 add 0x80 sta 0x82 jmp loop
+             end: hlt zero: 0 minus1: 0xFF",
+        );
+        check_builtin(
+            "divide",
+            "lda zero sta 0x82 lda 0x81 not add one sta neg lda 0x80
+             loop: sta 0x83 add neg jn end sta rest
+             lda 0x82 add one sta 0x82 lda rest jmp loop
+             end: hlt zero: 0 one: 1 neg: 0 rest: 0",
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Local usage statistics, for instructors. Nothing is recorded unless
+//! `NEANDER_STATS_DIR` is set: then every `run` and `load` session
+//! writes a small JSON file to that directory, and `neander report`
+//! summarizes a directory of them. Nothing is ever sent anywhere.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{ExecResult, NeanderException};
+use crate::numfmt;
+
+/// The environment variable enabling statistics.
+pub const STATS_DIR_VAR: &str = "NEANDER_STATS_DIR";
+
+/// Sessions saved by this process, keeping file names unique.
+static SAVED: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the directory sessions are recorded to, if enabled.
+pub fn stats_dir() -> Option<PathBuf> {
+    std::env::var_os(STATS_DIR_VAR)
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+}
+
+/// What happened in a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The subcommand, like `run` or `load`
+    pub command: String,
+    /// File name of the program
+    pub program: Option<String>,
+    /// Instructions executed
+    pub steps: u64,
+    pub halted: bool,
+    /// How many times each exception was raised
+    #[serde(default)]
+    pub exceptions: BTreeMap<String, u64>,
+}
+impl Session {
+    pub fn new(command: &str, program: Option<&Path>) -> Self {
+        Self {
+            command: command.to_string(),
+            program: program
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned()),
+            ..Self::default()
+        }
+    }
+    /// Records the result of an instruction, or of a micro-operation
+    /// if `completed` is false.
+    pub fn record(&mut self, res: ExecResult, completed: bool) {
+        match res {
+            ExecResult::Halted => self.halted = true,
+            ExecResult::Exception(e) => return self.exception(e),
+            _ => (),
+        }
+        if completed {
+            self.steps += 1;
+        }
+    }
+    pub fn exception(&mut self, e: NeanderException) {
+        *self.exceptions.entry(e.to_string()).or_default() += 1;
+    }
+    /// Writes the session to a new file in `dir`.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let seq = SAVED.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("session-{time}-{}-{seq}.json", std::process::id()));
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+    /// Saves the session if statistics are enabled, warning on errors.
+    pub fn save_if_enabled(&self) {
+        if let Some(dir) = stats_dir() {
+            if let Err(e) = self.save(&dir) {
+                eprintln!("warning: could not save statistics: {e}");
+            }
+        }
+    }
+}
+
+/// Totals for a single program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramStats {
+    pub sessions: u64,
+    pub steps: u64,
+    pub halted: u64,
+    pub exceptions: u64,
+}
+
+/// A summary of many sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub sessions: u64,
+    /// Sessions by program, `-` for sessions without one
+    pub programs: BTreeMap<String, ProgramStats>,
+    pub exceptions: BTreeMap<String, u64>,
+    /// Files that couldn't be read
+    pub skipped: Vec<PathBuf>,
+}
+impl Report {
+    pub fn add(&mut self, session: &Session) {
+        self.sessions += 1;
+        let name = session.program.clone().unwrap_or_else(|| "-".to_string());
+        let program = self.programs.entry(name).or_default();
+        program.sessions += 1;
+        program.steps += session.steps;
+        program.halted += session.halted as u64;
+        for (e, n) in &session.exceptions {
+            program.exceptions += n;
+            *self.exceptions.entry(e.clone()).or_default() += n;
+        }
+    }
+    /// Summarizes every `.json` session file in `dir`.
+    pub fn from_dir(dir: &Path) -> Result<Self, String> {
+        let mut report = Self::default();
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        for path in paths {
+            let session = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|s| serde_json::from_str::<Session>(&s).ok());
+            match session {
+                Some(session) => report.add(&session),
+                None => report.skipped.push(path),
+            }
+        }
+        Ok(report)
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: u64 = self.programs.values().map(|p| p.steps).sum();
+        let steps = numfmt::get().count(steps);
+        writeln!(f, "{} sessions, {steps} instructions", self.sessions)?;
+        if self.programs.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            f,
+            "\n{:<24} {:>8} {:>12} {:>7} {:>10}",
+            "program", "sessions", "instructions", "halted", "exceptions"
+        )?;
+        for (name, p) in &self.programs {
+            writeln!(
+                f,
+                "{name:<24} {:>8} {:>12} {:>7} {:>10}",
+                p.sessions, p.steps, p.halted, p.exceptions
+            )?;
+        }
+        if !self.exceptions.is_empty() {
+            writeln!(f, "\nexceptions:")?;
+            for (e, n) in &self.exceptions {
+                writeln!(f, "{n:>6}  {e}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut a = Session::new("run", Some(Path::new("dir/sum.mem")));
+        a.record(ExecResult::Normal, true);
+        a.record(ExecResult::Halted, true);
+        let mut b = Session::new("load", Some(Path::new("sum.mem")));
+        b.record(ExecResult::Normal, false);
+        b.record(ExecResult::Exception(NeanderException::EndOfProgram), true);
+        b.exception(NeanderException::EndOfProgram);
+        assert_eq!(b.steps, 0);
+
+        let dir = std::env::temp_dir().join(format!("neander-stats-{}", std::process::id()));
+        a.save(&dir).unwrap();
+        b.save(&dir).unwrap();
+        std::fs::write(dir.join("junk.json"), "{").unwrap();
+        let report = Report::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let report = report.unwrap();
+        assert_eq!(report.sessions, 2);
+        assert_eq!(report.skipped.len(), 1);
+        let sum = &report.programs["sum.mem"];
+        assert_eq!((sum.steps, sum.halted, sum.exceptions), (2, 1, 2));
+        assert_eq!(report.exceptions.len(), 1);
+    }
+}
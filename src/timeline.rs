@@ -0,0 +1,225 @@
+//! The memory accesses of every step, to plot with the step on the
+//! X axis and the address on the Y axis. Loops show up as repeated
+//! columns and arrays as diagonal lines.
+use std::fmt::Write;
+
+use crate::cpu::instr::has_operand;
+use crate::cpu::{ExecResult, Neander};
+use crate::hooks::Hooks;
+
+/// Accesses kept at most, so long runs don't take all memory.
+const MAX_ACCESSES: usize = 1 << 20;
+
+/// Size of the plot of `to_svg`, without its margins.
+const PLOT: (u32, u32) = (800, 512);
+const MARGIN: u32 = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// A byte of an instruction
+    Fetch,
+    Read,
+    Write,
+}
+impl AccessKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            AccessKind::Fetch => "fetch",
+            AccessKind::Read => "read",
+            AccessKind::Write => "write",
+        }
+    }
+    /// The color of the kind in plots.
+    pub fn color(self) -> &'static str {
+        match self {
+            AccessKind::Fetch => "silver",
+            AccessKind::Read => "steelblue",
+            AccessKind::Write => "firebrick",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    /// The instruction, counting from 0
+    pub step: u64,
+    pub addr: u8,
+    pub kind: AccessKind,
+}
+
+/// The accesses of a run, collected as `Hooks`.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    accesses: Vec<Access>,
+    /// Instructions executed so far
+    steps: u64,
+    /// Whether accesses were dropped after `MAX_ACCESSES`
+    truncated: bool,
+}
+
+impl Hooks for Timeline {
+    fn on_fetch(&mut self, _cpu: &Neander, pc: u8, opcode: u8) {
+        self.push(pc, AccessKind::Fetch);
+        if has_operand(opcode) && pc != 255 {
+            self.push(pc + 1, AccessKind::Fetch);
+        }
+    }
+    fn on_mem_read(&mut self, addr: u8, _value: u8) {
+        self.push(addr, AccessKind::Read);
+    }
+    fn on_mem_write(&mut self, addr: u8, _old: u8, _new: u8) {
+        self.push(addr, AccessKind::Write);
+    }
+    fn on_exec(&mut self, _cpu: &Neander, _pc: u8, _res: ExecResult) {
+        self.steps += 1;
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+    fn push(&mut self, addr: u8, kind: AccessKind) {
+        if self.accesses.len() == MAX_ACCESSES {
+            self.truncated = true;
+            return;
+        }
+        self.accesses.push(Access {
+            step: self.steps,
+            addr,
+            kind,
+        });
+    }
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// A `step,addr,kind` line per access, after a header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,addr,kind\n");
+        for a in &self.accesses {
+            let _ = writeln!(csv, "{},{},{}", a.step, a.addr, a.kind.name());
+        }
+        csv
+    }
+
+    /// Plots the accesses as an SVG image, with a dot per access.
+    pub fn to_svg(&self, title: &str) -> String {
+        let (width, height) = (PLOT.0 + 2 * MARGIN, PLOT.1 + 2 * MARGIN);
+        let steps = self.steps.max(1);
+        let dot_w = (PLOT.0 as f64 / steps as f64).clamp(1., 8.);
+        let dot_h = PLOT.1 as f64 / 256.;
+        let mut svg = String::new();
+        let mut line = |s: String| {
+            let _ = writeln!(svg, "{s}");
+        };
+        line(format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="12">"#
+        ));
+        line(format!(
+            r#"<rect width="{width}" height="{height}" fill="white"/>"#
+        ));
+        let title = title
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        line(format!(
+            r#"<text x="{MARGIN}" y="24" font-size="15" font-weight="bold">{title}</text>"#
+        ));
+        line(format!(
+            r#"<rect x="{MARGIN}" y="{MARGIN}" width="{}" height="{}" fill="none" stroke="gray"/>"#,
+            PLOT.0, PLOT.1
+        ));
+        for a in &self.accesses {
+            let x = MARGIN as f64 + a.step as f64 * PLOT.0 as f64 / steps as f64;
+            let y = MARGIN as f64 + a.addr as f64 * dot_h;
+            line(format!(
+                r#"<rect x="{x:.1}" y="{y:.1}" width="{dot_w:.1}" height="{dot_h:.1}" fill="{}"/>"#,
+                a.kind.color()
+            ));
+        }
+        let bottom = MARGIN + PLOT.1;
+        line(format!(
+            r#"<text x="{MARGIN}" y="{}">step 0</text>"#,
+            bottom + 16
+        ));
+        line(format!(
+            r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+            MARGIN + PLOT.0,
+            bottom + 16,
+            self.steps
+        ));
+        line(format!(
+            r#"<text x="{}" y="{}" text-anchor="end">00</text>"#,
+            MARGIN - 4,
+            MARGIN + 10
+        ));
+        line(format!(
+            r#"<text x="{}" y="{bottom}" text-anchor="end">FF</text>"#,
+            MARGIN - 4
+        ));
+        let kinds = [AccessKind::Fetch, AccessKind::Read, AccessKind::Write];
+        for (i, kind) in kinds.into_iter().enumerate() {
+            let x = MARGIN + PLOT.0 - 240 + i as u32 * 80;
+            line(format!(
+                r#"<rect x="{x}" y="14" width="10" height="10" fill="{}"/>"#,
+                kind.color()
+            ));
+            line(format!(
+                r#"<text x="{}" y="24">{}</text>"#,
+                x + 14,
+                kind.name()
+            ));
+        }
+        line("</svg>".to_string());
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_timeline() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, HLT]);
+        let mut timeline = Timeline::new();
+        while cpu.step_hooked(&mut timeline) != ExecResult::Halted {}
+        let accesses: Vec<_> = timeline
+            .accesses()
+            .iter()
+            .map(|a| (a.step, a.addr, a.kind))
+            .collect();
+        use AccessKind::*;
+        assert_eq!(
+            accesses,
+            [
+                (0, 0, Fetch),
+                (0, 1, Fetch),
+                (0, 128, Read),
+                (1, 2, Fetch),
+                (1, 3, Fetch),
+                (1, 129, Write),
+                (2, 4, Fetch),
+            ]
+        );
+        assert_eq!(timeline.steps(), 3);
+        let csv = timeline.to_csv();
+        assert!(csv.starts_with("step,addr,kind\n0,0,fetch\n"));
+        assert!(csv.contains("1,129,write\n"));
+        let svg = timeline.to_svg("a < b");
+        assert!(svg.contains("a &lt; b"));
+        assert_eq!(svg.matches("firebrick").count(), 2);
+    }
+}
@@ -0,0 +1,258 @@
+//! Teacher traps: rules an instructor sets on how a program may run,
+//! checked while it runs instead of only on its results. Traps are
+//! `trap` entries of specs and exercises, or files of their own:
+//!
+//! ```toml
+//! [[trap]]
+//! message = "the table is read-only"
+//! write = "0x00..0x0F"
+//!
+//! [[trap]]
+//! steps = 5000
+//! ```
+//!
+//! Each trap has one condition: `write`, `read` or `execute` an address
+//! or range, use an `instruction`, or run more than `steps` instructions.
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cpu::instr::INSTRUCTIONS;
+use crate::cpu::{ExecResult, Neander};
+use crate::hooks::Hooks;
+use crate::spec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Write(RangeInclusive<u8>),
+    Read(RangeInclusive<u8>),
+    /// Fetching an instruction in the range
+    Execute(RangeInclusive<u8>),
+    Instruction(u8),
+    /// Running more than this many instructions
+    Steps(u64),
+}
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = |r: &RangeInclusive<u8>| match r.start() == r.end() {
+            true => format!("{:02X}", r.start()),
+            false => format!("{:02X}..{:02X}", r.start(), r.end()),
+        };
+        match self {
+            Condition::Write(r) => write!(f, "wrote to {}", range(r)),
+            Condition::Read(r) => write!(f, "read from {}", range(r)),
+            Condition::Execute(r) => write!(f, "executed code at {}", range(r)),
+            Condition::Instruction(i) => {
+                let name = crate::cpu::instr::name(*i).unwrap_or("?");
+                write!(f, "used {name}")
+            }
+            Condition::Steps(n) => write!(f, "ran more than {n} instructions"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "TrapFile")]
+pub struct Trap {
+    /// Shown when the trap is raised, the condition if not given
+    pub message: String,
+    pub condition: Condition,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrapFile {
+    message: Option<String>,
+    write: Option<String>,
+    read: Option<String>,
+    execute: Option<String>,
+    instruction: Option<String>,
+    steps: Option<u64>,
+}
+
+impl TryFrom<TrapFile> for Trap {
+    type Error = String;
+    fn try_from(file: TrapFile) -> Result<Self, String> {
+        let mut conditions = Vec::new();
+        if let Some(r) = &file.write {
+            conditions.push(Condition::Write(spec::parse_range(r)?));
+        }
+        if let Some(r) = &file.read {
+            conditions.push(Condition::Read(spec::parse_range(r)?));
+        }
+        if let Some(r) = &file.execute {
+            conditions.push(Condition::Execute(spec::parse_range(r)?));
+        }
+        if let Some(name) = &file.instruction {
+            let Some(&(opcode, _)) = INSTRUCTIONS
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            else {
+                return Err(format!("unknown instruction: {name}"));
+            };
+            conditions.push(Condition::Instruction(opcode));
+        }
+        if let Some(n) = file.steps {
+            conditions.push(Condition::Steps(n));
+        }
+        if conditions.len() != 1 {
+            return Err(
+                "a trap needs one of write, read, execute, instruction or steps".to_string(),
+            );
+        }
+        let condition = conditions.remove(0);
+        Ok(Self {
+            message: file.message.unwrap_or_else(|| condition.to_string()),
+            condition,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrapsFile {
+    #[serde(rename = "trap", default)]
+    traps: Vec<Trap>,
+}
+
+/// A raised trap, and where the program was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tripped {
+    pub message: String,
+    /// The instruction that raised it
+    pub pc: u8,
+    /// Instructions executed before it
+    pub step: u64,
+}
+impl fmt::Display for Tripped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (instruction at {:02X}, step {})",
+            self.message, self.pc, self.step
+        )
+    }
+}
+
+/// Traps checked as `Hooks`. The first one raised is kept,
+/// and runners stop when `tripped` returns it.
+#[derive(Debug, Clone, Default)]
+pub struct Traps {
+    traps: Vec<Trap>,
+    steps: u64,
+    pc: u8,
+    tripped: Option<Tripped>,
+}
+
+impl Traps {
+    pub fn new(traps: Vec<Trap>) -> Self {
+        Self {
+            traps,
+            ..Self::default()
+        }
+    }
+    /// Reads a file of traps, as JSON if it ends in `.json` and TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let file: TrapsFile = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&source).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&source).map_err(|e| e.to_string())?
+        };
+        Ok(Self::new(file.traps))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.traps.is_empty()
+    }
+    pub fn tripped(&self) -> Option<&Tripped> {
+        self.tripped.as_ref()
+    }
+    /// Forgets the steps and the raised trap, for a new run.
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        self.tripped = None;
+    }
+    fn check(&mut self, hit: impl Fn(&Condition) -> bool) {
+        if self.tripped.is_some() {
+            return;
+        }
+        if let Some(trap) = self.traps.iter().find(|t| hit(&t.condition)) {
+            self.tripped = Some(Tripped {
+                message: trap.message.clone(),
+                pc: self.pc,
+                step: self.steps,
+            });
+        }
+    }
+}
+
+impl Hooks for Traps {
+    fn on_fetch(&mut self, _cpu: &Neander, pc: u8, opcode: u8) {
+        self.pc = pc;
+        self.check(|c| match c {
+            Condition::Execute(r) => r.contains(&pc),
+            Condition::Instruction(i) => *i == opcode,
+            _ => false,
+        });
+    }
+    fn on_mem_read(&mut self, addr: u8, _value: u8) {
+        self.check(|c| matches!(c, Condition::Read(r) if r.contains(&addr)));
+    }
+    fn on_mem_write(&mut self, addr: u8, _old: u8, _new: u8) {
+        self.check(|c| matches!(c, Condition::Write(r) if r.contains(&addr)));
+    }
+    fn on_exec(&mut self, _cpu: &Neander, _pc: u8, _res: ExecResult) {
+        self.steps += 1;
+        let steps = self.steps;
+        self.check(|c| matches!(c, Condition::Steps(n) if steps > *n));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_traps() {
+        let file: TrapsFile = toml::from_str(
+            r#"
+            [[trap]]
+            message = "the table is read-only"
+            write = "0x00..0x0F"
+            [[trap]]
+            instruction = "jn"
+            [[trap]]
+            steps = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.traps[1].message, "used JN");
+        let mut traps = Traps::new(file.traps);
+
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0x20, &[LDA, 0x80, STA, 0x08, HLT]);
+        cpu.set_pc(0x20);
+        while traps.tripped().is_none() && cpu.step_hooked(&mut traps) != ExecResult::Halted {}
+        let tripped = traps.tripped().unwrap();
+        assert_eq!((tripped.pc, tripped.step), (0x22, 1));
+        assert_eq!(
+            tripped.to_string(),
+            "the table is read-only (instruction at 22, step 1)"
+        );
+
+        traps.reset();
+        cpu.set_ram_slice(0x20, &[NOP, NOP, NOP, NOP, HLT]);
+        cpu.set_pc(0x20);
+        while traps.tripped().is_none() && cpu.step_hooked(&mut traps) != ExecResult::Halted {}
+        assert_eq!(
+            traps.tripped().unwrap().message,
+            "ran more than 3 instructions"
+        );
+
+        let two = toml::from_str::<TrapsFile>("[[trap]]\nread = \"1\"\nsteps = 2");
+        assert!(two.is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! Every action the GUI can perform. Menus, keyboard shortcuts
+//! and the command palette are all built from `ACTIONS`.
+use super::clipboard::{self, CopyFormat, PasteWindow};
+use super::controls;
+use super::files::{self, FileAction, FilePrompt};
+use super::settings::{self, Theme};
+use super::tutorial::{self, Tutorial};
+use super::utils::NumberBase;
+use super::UiState;
+use crate::numfmt::{self, NumberFormat};
+use egui::{Context, Key, KeyboardShortcut, Modifiers, Ui};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Open,
+    Reload,
+    ExportLogisim,
+    OpenWorkspace,
+    SaveWorkspace,
+    CopyHex,
+    CopyMemfile,
+    CopyAssembly,
+    Paste,
+    Find,
+    CommandPalette,
+    BaseDec,
+    BaseHex,
+    BaseBin,
+    DualDisplay,
+    GroupDigits,
+    Notifications,
+    Console,
+    Editor,
+    Datapath,
+    FrameTimes,
+    Writes,
+    Timeline,
+    Heatmap,
+    History,
+    CoSim,
+    Describe,
+    Step,
+    MicroStep,
+    PhaseStep,
+    Narrate,
+    Run,
+    Stop,
+    Tutorial,
+    OpenTutorial,
+    OpenExercise,
+    CloseExercise,
+    OpenPack,
+    SaveState,
+    LoadState,
+    ThemeDark,
+    ThemeLight,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+}
+
+/// The menu an action is listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Menu {
+    File,
+    Edit,
+    View,
+    Run,
+    Settings,
+    Help,
+}
+impl Menu {
+    pub const ALL: [Menu; 6] = [
+        Menu::File,
+        Menu::Edit,
+        Menu::View,
+        Menu::Run,
+        Menu::Settings,
+        Menu::Help,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Menu::File => "File",
+            Menu::Edit => "Edit",
+            Menu::View => "View",
+            Menu::Run => "Run",
+            Menu::Settings => "Settings",
+            Menu::Help => "Help",
+        }
+    }
+}
+
+pub struct ActionInfo {
+    pub action: Action,
+    pub name: &'static str,
+    pub menu: Menu,
+    pub shortcut: Option<KeyboardShortcut>,
+}
+
+const fn shortcut(modifiers: Modifiers, key: Key) -> Option<KeyboardShortcut> {
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+const fn info(
+    action: Action,
+    name: &'static str,
+    menu: Menu,
+    shortcut: Option<KeyboardShortcut>,
+) -> ActionInfo {
+    ActionInfo {
+        action,
+        name,
+        menu,
+        shortcut,
+    }
+}
+
+pub const ACTIONS: &[ActionInfo] = &[
+    info(
+        Action::Open,
+        "Open...",
+        Menu::File,
+        shortcut(Modifiers::COMMAND, Key::O),
+    ),
+    info(
+        Action::Reload,
+        "Reload",
+        Menu::File,
+        shortcut(Modifiers::COMMAND, Key::R),
+    ),
+    info(
+        Action::ExportLogisim,
+        "Export Logisim image...",
+        Menu::File,
+        None,
+    ),
+    info(Action::OpenWorkspace, "Open workspace...", Menu::File, None),
+    info(Action::SaveState, "Save machine state...", Menu::File, None),
+    info(Action::LoadState, "Load machine state...", Menu::File, None),
+    info(Action::OpenExercise, "Open exercise...", Menu::File, None),
+    info(Action::CloseExercise, "Close exercise", Menu::File, None),
+    info(Action::OpenPack, "Open course pack...", Menu::File, None),
+    info(Action::SaveWorkspace, "Save workspace...", Menu::File, None),
+    info(Action::CopyHex, "Copy as hex", Menu::Edit, None),
+    info(Action::CopyMemfile, "Copy as memfile", Menu::Edit, None),
+    info(Action::CopyAssembly, "Copy as assembly", Menu::Edit, None),
+    info(Action::Paste, "Paste...", Menu::Edit, None),
+    info(
+        Action::Find,
+        "Find",
+        Menu::Edit,
+        shortcut(Modifiers::COMMAND, Key::F),
+    ),
+    info(
+        Action::CommandPalette,
+        "Command palette",
+        Menu::View,
+        shortcut(Modifiers::COMMAND, Key::P),
+    ),
+    info(Action::Notifications, "Notifications", Menu::View, None),
+    info(
+        Action::Console,
+        "Console",
+        Menu::View,
+        shortcut(Modifiers::COMMAND, Key::Backtick),
+    ),
+    info(
+        Action::Editor,
+        "Editor",
+        Menu::View,
+        shortcut(Modifiers::COMMAND, Key::E),
+    ),
+    info(Action::Datapath, "Datapath", Menu::View, None),
+    info(Action::FrameTimes, "Frame times", Menu::View, None),
+    info(Action::Writes, "Recent writes", Menu::View, None),
+    info(Action::Timeline, "Access timeline", Menu::View, None),
+    info(Action::Heatmap, "Execution heatmap", Menu::View, None),
+    info(Action::History, "History", Menu::View, None),
+    info(Action::CoSim, "Co-simulation", Menu::Run, None),
+    info(
+        Action::Describe,
+        "Describe state",
+        Menu::View,
+        shortcut(Modifiers::COMMAND, Key::D),
+    ),
+    info(
+        Action::Step,
+        "Step",
+        Menu::Run,
+        shortcut(Modifiers::NONE, Key::F10),
+    ),
+    info(
+        Action::MicroStep,
+        "Micro step",
+        Menu::Run,
+        shortcut(Modifiers::NONE, Key::F11),
+    ),
+    info(Action::PhaseStep, "Micro step by phase", Menu::Run, None),
+    info(Action::Narrate, "Narrate steps", Menu::Run, None),
+    info(
+        Action::Run,
+        "Run",
+        Menu::Run,
+        shortcut(Modifiers::NONE, Key::F5),
+    ),
+    info(
+        Action::Stop,
+        "Stop",
+        Menu::Run,
+        shortcut(Modifiers::SHIFT, Key::F5),
+    ),
+    info(Action::BaseDec, "Decimal", Menu::Settings, None),
+    info(Action::BaseHex, "Hexadecimal", Menu::Settings, None),
+    info(Action::BaseBin, "Binary", Menu::Settings, None),
+    info(
+        Action::DualDisplay,
+        "Signed and unsigned values",
+        Menu::Settings,
+        None,
+    ),
+    info(
+        Action::GroupDigits,
+        "Group binary digits",
+        Menu::Settings,
+        None,
+    ),
+    info(Action::ThemeDark, "Dark theme", Menu::Settings, None),
+    info(Action::ThemeLight, "Light theme", Menu::Settings, None),
+    info(Action::ZoomIn, "Zoom in", Menu::Settings, None),
+    info(Action::ZoomOut, "Zoom out", Menu::Settings, None),
+    info(Action::ZoomReset, "Reset zoom", Menu::Settings, None),
+    info(Action::Tutorial, "Tutorial", Menu::Help, None),
+    info(Action::OpenTutorial, "Open tutorial...", Menu::Help, None),
+];
+
+/// Returns false if `action` can't be performed in the current state.
+pub fn enabled(action: Action, state: &UiState) -> bool {
+    match action {
+        Action::Reload => state.file.is_some(),
+        Action::Step | Action::MicroStep | Action::Run => state.run.is_none(),
+        Action::Stop => state.run.is_some(),
+        Action::CloseExercise => state.exercise.is_some(),
+        _ => true,
+    }
+}
+
+pub fn perform(ctx: &Context, state: &mut UiState, action: Action) {
+    if !enabled(action, state) {
+        return;
+    }
+    let (start, end) = state.selected_range();
+    let copy = |format| clipboard::copy_range(state.cpu.memory(), start, end, format);
+    match action {
+        Action::Open => state.file_prompt = Some(FilePrompt::new(FileAction::Open)),
+        Action::Reload => {
+            if let Some(file) = state.file.clone() {
+                files::open_file(ctx, state, file);
+            }
+        }
+        Action::ExportLogisim => {
+            state.file_prompt = Some(FilePrompt::new(FileAction::ExportLogisim))
+        }
+        Action::OpenWorkspace => {
+            state.file_prompt = Some(FilePrompt::new(FileAction::OpenWorkspace))
+        }
+        Action::SaveWorkspace => {
+            state.file_prompt = Some(FilePrompt::new(FileAction::SaveWorkspace))
+        }
+        Action::CopyHex => ctx.copy_text(copy(CopyFormat::Hex)),
+        Action::CopyMemfile => ctx.copy_text(copy(CopyFormat::Memfile)),
+        Action::CopyAssembly => ctx.copy_text(copy(CopyFormat::Assembly)),
+        Action::Paste => state.paste = Some(PasteWindow::new(start)),
+        Action::Find => state.search.show(),
+        Action::CommandPalette => state.palette.open(),
+        Action::BaseDec => state.base = NumberBase::Dec,
+        Action::BaseHex => state.base = NumberBase::Hex,
+        Action::BaseBin => state.base = NumberBase::Bin,
+        Action::DualDisplay => state.dual = !state.dual,
+        Action::GroupDigits => {
+            let format = numfmt::get();
+            numfmt::set(NumberFormat {
+                group_binary: !format.group_binary,
+                ..format
+            });
+        }
+        Action::Notifications => state.notes.show_history = !state.notes.show_history,
+        Action::Console => state.console.open = !state.console.open,
+        Action::Editor => state.editor.open = !state.editor.open,
+        Action::Datapath => state.datapath.open = !state.datapath.open,
+        Action::FrameTimes => state.frames.open = !state.frames.open,
+        Action::Writes => state.writes.open = !state.writes.open,
+        Action::Timeline => state.timeline.open = !state.timeline.open,
+        Action::Heatmap => state.heatmap = !state.heatmap,
+        Action::History => state.history.open = !state.history.open,
+        Action::CoSim => state.cosim.open = !state.cosim.open,
+        Action::Describe => state.describe = !state.describe,
+        Action::Step => controls::step(ctx, state),
+        Action::MicroStep => controls::micro_step(ctx, state),
+        Action::PhaseStep => state.phase_step = !state.phase_step,
+        Action::Narrate => {
+            state.narrate = !state.narrate;
+            state.narration.clear();
+        }
+        Action::Run => controls::start(ctx, state),
+        Action::Stop => controls::stop(ctx, state),
+        Action::Tutorial => {
+            let tutorial = Tutorial::parse(tutorial::BUILTIN).expect("valid builtin tutorial");
+            tutorial::start(state, tutorial);
+        }
+        Action::OpenTutorial => state.file_prompt = Some(FilePrompt::new(FileAction::OpenTutorial)),
+        Action::OpenExercise => state.file_prompt = Some(FilePrompt::new(FileAction::OpenExercise)),
+        Action::CloseExercise => state.exercise = None,
+        Action::OpenPack => state.file_prompt = Some(FilePrompt::new(FileAction::OpenPack)),
+        Action::ThemeDark => settings::set_theme(ctx, state, Theme::Dark),
+        Action::ThemeLight => settings::set_theme(ctx, state, Theme::Light),
+        Action::ZoomIn => settings::zoom(ctx, Some(1.)),
+        Action::ZoomOut => settings::zoom(ctx, Some(-1.)),
+        Action::ZoomReset => settings::zoom(ctx, None),
+        Action::SaveState => state.file_prompt = Some(FilePrompt::new(FileAction::SaveState)),
+        Action::LoadState => state.file_prompt = Some(FilePrompt::new(FileAction::LoadState)),
+    }
+}
+
+/// Performs the actions whose shortcuts were pressed.
+pub fn handle_shortcuts(ctx: &Context, state: &mut UiState) {
+    for info in ACTIONS {
+        if let Some(sc) = &info.shortcut {
+            if ctx.input_mut(|i| i.consume_shortcut(sc)) {
+                perform(ctx, state, info.action);
+            }
+        }
+    }
+}
+
+/// A button for `action`, showing its shortcut. Returns true if clicked.
+pub fn button(ui: &mut Ui, state: &mut UiState, info: &ActionInfo) -> bool {
+    let mut button = egui::Button::new(info.name);
+    if let Some(sc) = &info.shortcut {
+        button = button.shortcut_text(ui.ctx().format_shortcut(sc));
+    }
+    let clicked = ui
+        .add_enabled(enabled(info.action, state), button)
+        .clicked();
+    if clicked {
+        perform(ui.ctx(), state, info.action);
+    }
+    clicked
+}
+
+pub fn info_of(action: Action) -> &'static ActionInfo {
+    ACTIONS.iter().find(|i| i.action == action).unwrap()
+}
+
+pub fn menu_bar(ui: &mut Ui, state: &mut UiState) -> egui::Response {
+    egui::menu::bar(ui, |ui| {
+        for menu in Menu::ALL {
+            ui.menu_button(menu.name(), |ui| {
+                for info in ACTIONS.iter().filter(|i| i.menu == menu) {
+                    if button(ui, state, info) {
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+    })
+    .response
+}
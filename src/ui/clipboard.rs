@@ -0,0 +1,188 @@
+use super::UiState;
+use crate::disasm;
+use crate::memfile;
+
+/// Formats in which a memory range can be copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    /// Plain hexadecimal bytes, as in a hexdump
+    Hex,
+    /// A memfile snippet, placed with ORG
+    Memfile,
+    /// One disassembled instruction per line
+    Assembly,
+}
+
+/// Formats in which pasted text can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteFormat {
+    Hex,
+    Memfile,
+}
+
+/// Formats the memory range from `start` to `end`, inclusive.
+pub fn copy_range(mem: &[u8], start: u8, end: u8, format: CopyFormat) -> String {
+    let range = &mem[start as usize..=end as usize];
+    match format {
+        CopyFormat::Hex => range
+            .chunks(16)
+            .map(|line| {
+                line.iter()
+                    .map(|b| format!("{b:02X}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        CopyFormat::Memfile => {
+            let mut out = format!("org 0x{start:02X}\n");
+            for line in range.chunks(16) {
+                let bytes: Vec<_> = line.iter().map(|b| format!("0x{b:02X}")).collect();
+                out.push_str(&bytes.join(" "));
+                out.push('\n');
+            }
+            out
+        }
+        CopyFormat::Assembly => {
+            let mut out = format!("org 0x{start:02X}\n");
+            for line in disasm::disassemble(mem, start, end) {
+                out.push_str(&format!("{:12}; {:02X}\n", line.text, line.addr));
+            }
+            out
+        }
+    }
+}
+
+/// Writes `text` into memory, starting at `addr`.
+/// Memfile text may move the cursor with ORG.
+/// Memory is left untouched if the text is invalid.
+pub fn paste(mem: &mut [u8], text: &str, addr: u8, format: PasteFormat) -> Result<(), String> {
+    match format {
+        PasteFormat::Hex => {
+            let mut bytes = Vec::new();
+            for word in text.split_whitespace() {
+                let digits = word.strip_prefix("0x").unwrap_or(word);
+                let byte = u8::from_str_radix(digits, 16)
+                    .map_err(|_| format!("invalid hex byte: {word}"))?;
+                bytes.push(byte);
+            }
+            let start = addr as usize;
+            if start + bytes.len() > mem.len() {
+                return Err("pasted bytes don't fit in memory".to_string());
+            }
+            mem[start..start + bytes.len()].copy_from_slice(&bytes);
+            Ok(())
+        }
+        PasteFormat::Memfile => {
+            let mut scratch = mem.to_vec();
+            memfile::parse_memfile_at(&mut scratch, text, addr).map_err(|e| e.to_string())?;
+            mem.copy_from_slice(&scratch);
+            Ok(())
+        }
+    }
+}
+
+/// State of the paste window.
+pub struct PasteWindow {
+    addr: u8,
+    format: PasteFormat,
+    text: String,
+    error: Option<String>,
+}
+impl PasteWindow {
+    pub fn new(addr: u8) -> Self {
+        Self {
+            addr,
+            format: PasteFormat::Hex,
+            text: String::new(),
+            error: None,
+        }
+    }
+}
+
+/// Shows the paste window, if it's open.
+pub fn paste_window(ctx: &egui::Context, state: &mut UiState) {
+    let Some(win) = &mut state.paste else {
+        return;
+    };
+    let mut open = true;
+    let mut done = false;
+    egui::Window::new("Paste")
+        .open(&mut open)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Address:");
+                ui.add(egui::DragValue::new(&mut win.addr).hexadecimal(2, false, true));
+                ui.separator();
+                ui.radio_value(&mut win.format, PasteFormat::Hex, "Hex");
+                ui.radio_value(&mut win.format, PasteFormat::Memfile, "Memfile");
+            });
+            ui.add(
+                egui::TextEdit::multiline(&mut win.text)
+                    .code_editor()
+                    .hint_text("paste here"),
+            );
+            if let Some(e) = &win.error {
+                ui.colored_label(egui::Color32::RED, e);
+            }
+            if ui.button("Write").clicked() {
+                let mut mem = state.cpu.memory().to_vec();
+                let locked = state.exercise.as_ref().and_then(|active| {
+                    paste(&mut mem, &win.text, win.addr, win.format).ok()?;
+                    active.exercise.locked_change(state.cpu.memory(), &mem)
+                });
+                match locked {
+                    Some(addr) => {
+                        win.error = Some(format!("mem[{addr}] is locked by the exercise"))
+                    }
+                    None => match paste(state.cpu.memory_mut(), &win.text, win.addr, win.format) {
+                        Ok(()) => done = true,
+                        Err(e) => win.error = Some(e),
+                    },
+                }
+            }
+        });
+    if !open || done {
+        state.paste = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_range() {
+        let mut mem = [0_u8; 256];
+        mem[0x80..0x83].copy_from_slice(&[0x20, 0x90, 0x60]);
+        assert_eq!(copy_range(&mem, 0x80, 0x82, CopyFormat::Hex), "20 90 60");
+        assert_eq!(
+            copy_range(&mem, 0x80, 0x82, CopyFormat::Memfile),
+            "org 0x80\n0x20 0x90 0x60\n"
+        );
+        assert_eq!(
+            copy_range(&mem, 0x80, 0x82, CopyFormat::Assembly),
+            "org 0x80\nLDA 0x90    ; 80\nNOT         ; 82\n"
+        );
+    }
+    #[test]
+    fn test_paste() {
+        let mut mem = [0_u8; 256];
+        assert_eq!(
+            paste(&mut mem, "20 0x90\n60", 0x10, PasteFormat::Hex),
+            Ok(())
+        );
+        assert_eq!(&mem[0x10..0x13], [0x20, 0x90, 0x60]);
+        assert!(paste(&mut mem, "FF FF", 0xFF, PasteFormat::Hex).is_err());
+
+        let copied = copy_range(&mem, 0x10, 0x12, CopyFormat::Memfile);
+        let mut other = [0_u8; 256];
+        assert_eq!(paste(&mut other, &copied, 0, PasteFormat::Memfile), Ok(()));
+        assert_eq!(mem, other);
+        assert_eq!(paste(&mut other, "1 2", 0x40, PasteFormat::Memfile), Ok(()));
+        assert_eq!(&other[0x40..0x42], [1, 2]);
+        assert!(paste(&mut other, "1 x", 0x50, PasteFormat::Memfile).is_err());
+        assert_eq!(other[0x50], 0);
+    }
+}
@@ -0,0 +1,299 @@
+//! A console panel accepting the same directives as the REPL.
+//! `c` and `s n` run in the run loop of the GUI, like the Run button.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use super::controls;
+use super::tutorial::{self, Target};
+use super::UiState;
+use crate::debugger::{Debugger, Directive, Flow};
+use crate::image::MemoryImage;
+use crate::io::{Bus, CharOutput, Keyboard, KEYBOARD_ADDR, OUTPUT_ADDR};
+use crate::srcmap::SourceMap;
+use crate::symbols::SymbolTable;
+use egui::{Key, TextEdit, TextStyle};
+
+/// How many bytes of output the console keeps.
+const LOG_LIMIT: usize = 64 * 1024;
+/// The instructions a run of the debugger may execute in a frame.
+const MAX_STEPS: u64 = 1_000_000;
+
+/// Bytes passed between the GUI and a device.
+#[derive(Clone, Default)]
+pub struct Queue(Rc<RefCell<VecDeque<u8>>>);
+impl io::Write for Queue {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl io::Read for Queue {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+pub struct Console {
+    pub open: bool,
+    input: String,
+    log: String,
+    debugger: Debugger,
+    /// Where the bytes of the program came from
+    image: MemoryImage,
+    /// The last valid line, repeated by an empty one
+    last: Option<String>,
+    /// Written by the character output device
+    output: Queue,
+    /// Read by the keyboard device
+    keys: Queue,
+    keys_input: String,
+    /// Directives waiting for a run to stop
+    pending: VecDeque<Directive>,
+}
+impl Console {
+    pub fn new(file: Option<PathBuf>) -> Self {
+        let mut console = Self {
+            open: false,
+            input: String::new(),
+            log: String::new(),
+            debugger: Debugger::new(file, SymbolTable::new()),
+            image: MemoryImage::default(),
+            last: None,
+            output: Queue::default(),
+            keys: Queue::default(),
+            keys_input: String::new(),
+            pending: VecDeque::new(),
+        };
+        console.debugger.bus = console.bus();
+        // runs of sourced scripts happen in a single frame
+        console.debugger.max_steps = Some(MAX_STEPS);
+        console
+    }
+    /// Returns a bus with the devices shown in the console.
+    pub fn bus(&self) -> Bus {
+        let mut bus = Bus::new();
+        bus.attach(OUTPUT_ADDR, CharOutput(self.output.clone()));
+        bus.attach(KEYBOARD_ADDR, Keyboard(self.keys.clone()));
+        bus
+    }
+    /// Sets the file, labels and source lines of the program,
+    /// like after loading it with the `load` directive.
+    pub fn set_program(
+        &mut self,
+        file: Option<PathBuf>,
+        symbols: SymbolTable,
+        map: SourceMap,
+        image: MemoryImage,
+    ) {
+        self.debugger.file = file;
+        self.debugger.symbols = symbols;
+        self.debugger.source_map = map;
+        self.image = image;
+    }
+    /// Addresses where runs stop, set with the `b` directive, in
+    /// the disassembly or by right-clicking memory cells.
+    pub fn breakpoints(&self) -> &[bool; 256] {
+        &self.debugger.breakpoints
+    }
+    pub fn toggle_breakpoint(&mut self, addr: u8) {
+        let brk = &mut self.debugger.breakpoints[addr as usize];
+        *brk = !*brk;
+    }
+    pub fn set_breakpoints(&mut self, breakpoints: [bool; 256]) {
+        self.debugger.breakpoints = breakpoints;
+    }
+    /// Addresses whose stores stop runs, set with the `watch` directive.
+    pub fn watchpoints(&self) -> &[bool; 256] {
+        &self.debugger.watchpoints
+    }
+    /// The labels of the program.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.debugger.symbols
+    }
+    /// The source lines of the program, if it was assembled.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.debugger.source_map
+    }
+    pub fn image(&self) -> &MemoryImage {
+        &self.image
+    }
+    /// Opens the console with `text` in its input, to finish typing.
+    pub fn edit_line(&mut self, text: &str) {
+        self.open = true;
+        self.input = text.to_string();
+    }
+    /// Logs how a run started by the console ended. The directives
+    /// after it are dropped if it failed, like after an exception.
+    pub fn run_stopped(&mut self, text: &str, failed: bool) {
+        self.log.push_str(&format!("{text}\n"));
+        if failed {
+            self.pending.clear();
+        }
+    }
+}
+
+/// Queues the directives typed in the console. An empty line
+/// repeats the last line, like in the REPL.
+fn submit(ctx: &egui::Context, state: &mut UiState) {
+    let console = &mut state.console;
+    let line = std::mem::take(&mut console.input);
+    console.log.push_str(&format!("> {}\n", line.trim()));
+    let line = if line.trim().is_empty() {
+        console.last.clone()
+    } else {
+        Some(line)
+    };
+    if let Some(line) = line {
+        queue(console, line);
+    }
+    resume(ctx, state);
+}
+
+/// Runs `line` as if typed in the console, opening it.
+pub fn run_line(ctx: &egui::Context, state: &mut UiState, line: &str) {
+    let console = &mut state.console;
+    console.open = true;
+    console.log.push_str(&format!("> {}\n", line.trim()));
+    queue(console, line.to_string());
+    resume(ctx, state);
+}
+
+/// Parses `line`, queueing its directives or logging the error.
+fn queue(console: &mut Console, line: String) {
+    crate::crash::record_directive(&line);
+    match console.debugger.parse_line(&line) {
+        Ok(dirs) => {
+            console.pending.extend(dirs);
+            console.last = Some(line);
+        }
+        Err(e) => console.log.push_str(&format!("{e}\n")),
+    }
+}
+
+/// Executes the queued directives, unless a run is in progress.
+/// `c` and `s n` are handed to the run loop of the GUI, which runs
+/// them over many frames so a program that never halts can't freeze
+/// the window. The directives after them wait for the run to stop.
+fn resume(ctx: &egui::Context, state: &mut UiState) {
+    if state.run.is_some() || state.console.pending.is_empty() {
+        return;
+    }
+    let console = &mut state.console;
+    // the GUI may have opened another file since the last directive
+    if console.debugger.file != state.file {
+        console.debugger.file = state.file.clone();
+        console.debugger.symbols = SymbolTable::new();
+    }
+    let exercise = state.exercise.as_ref().map(|a| &a.exercise);
+    if console.debugger.exercise() != exercise {
+        let exercise = exercise.cloned();
+        console.debugger.set_exercise(&mut state.cpu, exercise);
+    }
+    while let Some(dir) = state.console.pending.pop_front() {
+        match dir {
+            Directive::Continue => controls::start_for_console(ctx, state, None),
+            Directive::StepN(n) => controls::start_for_console(ctx, state, Some(n.into())),
+            dir => execute(state, dir),
+        }
+        if state.run.is_some() {
+            break;
+        }
+    }
+    let console = &mut state.console;
+    if console.log.len() > LOG_LIMIT {
+        let cut = console.log.len() - LOG_LIMIT / 2;
+        let cut = console.log[cut..].find('\n').map_or(cut, |i| cut + i + 1);
+        console.log.drain(..cut);
+    }
+    ctx.request_repaint();
+}
+
+/// Executes `dir` with the hooks of the open panels.
+fn execute(state: &mut UiState, dir: Directive) {
+    let console = &mut state.console;
+    // the debugger checks the traps of the exercise itself
+    let timeline = state.timeline.open.then_some(&mut state.timeline.timeline);
+    let mut hooks = (&mut state.writes, (timeline, &mut state.profile));
+    let flow = console
+        .debugger
+        .execute_hooked(&mut state.cpu, dir, &mut hooks, &mut console.log)
+        .expect("writing to a String");
+    match flow {
+        Flow::Quit => {
+            console.open = false;
+            console.pending.clear();
+        }
+        Flow::Exception => console.pending.clear(),
+        Flow::Continue => (),
+    }
+    if console.debugger.file != state.file {
+        state.file = console.debugger.file.clone();
+        state.run = None;
+    }
+}
+
+/// Moves the characters printed by the program into the
+/// log, opening the console.
+fn take_output(console: &mut Console) {
+    let output: Vec<u8> = console.output.0.borrow_mut().drain(..).collect();
+    if !output.is_empty() {
+        console.log.extend(output.iter().map(|&b| b as char));
+        console.open = true;
+    }
+}
+
+/// Shows the console panel, if it's open.
+pub fn console(ctx: &egui::Context, state: &mut UiState) {
+    take_output(&mut state.console);
+    resume(ctx, state);
+    if !state.console.open {
+        return;
+    }
+    let mut submitted = false;
+    egui::TopBottomPanel::bottom("Console")
+        .resizable(true)
+        .show(ctx, |ui| {
+            let console = &mut state.console;
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .auto_shrink([false, true])
+                .max_height(200.)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(&console.log).monospace());
+                });
+            let field = ui.add(
+                TextEdit::singleline(&mut console.input)
+                    .font(TextStyle::Monospace)
+                    .hint_text("type a directive, or help")
+                    .desired_width(f32::INFINITY),
+            );
+            submitted = field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if submitted {
+                field.request_focus();
+            }
+            ui.horizontal(|ui| {
+                ui.label("Keyboard:");
+                let field = ui.add(
+                    TextEdit::singleline(&mut console.keys_input)
+                        .font(TextStyle::Monospace)
+                        .hint_text("text read from 0xFF"),
+                );
+                if field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    let text = std::mem::take(&mut console.keys_input);
+                    console.keys.0.borrow_mut().extend(text.bytes());
+                    field.request_focus();
+                }
+                ui.weak(format!("{} keys queued", console.keys.0.borrow().len()));
+            });
+            tutorial::outline(ui, state, Target::Console, ui.min_rect());
+        });
+    if submitted {
+        submit(ctx, state);
+    }
+}
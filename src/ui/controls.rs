@@ -0,0 +1,301 @@
+use super::actions::{self, Action};
+use super::datapath;
+use super::UiState;
+use crate::cpu::{ExecResult, Neander, Registers};
+use crate::history::History;
+use crate::hooks::Hooks;
+use crate::io::Bus;
+use crate::narrate;
+use crate::traps::Tripped;
+use egui::{Context, Ui};
+
+/// Instructions executed per frame while running,
+/// so that the UI stays responsive on long runs.
+const STEPS_PER_FRAME: u32 = 10_000;
+/// The clock speed of throttled runs until one is chosen, in Hz.
+pub const DEFAULT_HZ: u32 = 4;
+/// The fastest clock speed of throttled runs.
+pub const MAX_HZ: u32 = 1000;
+
+/// A continuous run in progress.
+pub struct Run {
+    pub steps: u64,
+    /// The time of the last frame of the run, in seconds
+    last: f64,
+    /// The instructions due, in throttled runs
+    due: f64,
+    /// The registers before the last instruction, in throttled runs
+    pub before: Option<Registers>,
+    /// The steps after which the run stops, for `s n` in the console
+    limit: Option<u64>,
+    /// Whether the run was started by the console, which logs how it ended
+    console: bool,
+}
+
+/// Step, Run and Stop buttons, and the micro step mode.
+pub fn controls(ui: &mut Ui, state: &mut UiState) -> egui::Response {
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            actions::button(ui, state, actions::info_of(Action::Step));
+            actions::button(ui, state, actions::info_of(Action::MicroStep));
+            if state.run.is_none() {
+                actions::button(ui, state, actions::info_of(Action::Run));
+            } else {
+                actions::button(ui, state, actions::info_of(Action::Stop));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.throttle, "Run at")
+                .on_hover_text("Runs a few instructions per second, to watch the registers change");
+            ui.add(
+                egui::DragValue::new(&mut state.clock_hz)
+                    .range(1..=MAX_HZ)
+                    .suffix(" Hz"),
+            );
+        });
+        ui.checkbox(&mut state.phase_step, "Micro step by phase")
+            .on_hover_text("Micro step runs a whole fetch, operand or execute phase");
+        if state.cpu.mid_instruction() {
+            ui.label(format!("next phase: {}", state.cpu.phase()));
+        }
+    })
+    .response
+}
+
+/// Executes a single instruction.
+pub fn step(ctx: &Context, state: &mut UiState) {
+    let ops = datapath::step_ops(&state.cpu);
+    state.datapath.animate(ctx, ops);
+    let before = state.narrate.then(|| state.cpu.clone());
+    let res = step_hooked(state);
+    if let Some(before) = before {
+        let symbols = &state.console.symbols();
+        state.narration = narrate::narrate(&before, &state.cpu, res, symbols);
+    }
+    if let Some(tripped) = take_tripped(state) {
+        state.notes.error(ctx, format!("trap: {tripped}"));
+        return;
+    }
+    match res {
+        ExecResult::Halted => state.notes.info(ctx, "end of program reached"),
+        ExecResult::Exception(e) => state.notes.error(ctx, format!("exception: {e}")),
+        _ => (),
+    }
+}
+
+/// Steps with the hooks of the open panels and
+/// the traps of the exercise, recording it in the history.
+fn step_hooked(state: &mut UiState) -> ExecResult {
+    hooked(state, |history, cpu, bus, mut hooks| {
+        history.step_bus_hooked(cpu, bus, &mut hooks)
+    })
+}
+
+/// Runs `step` on the machine with the hooks of the open panels
+/// and the traps of the exercise, for steps and micro-steps alike.
+fn hooked<R>(
+    state: &mut UiState,
+    step: impl FnOnce(&mut History, &mut Neander, &mut Bus, &mut dyn Hooks) -> R,
+) -> R {
+    let timeline = state.timeline.open.then_some(&mut state.timeline.timeline);
+    let traps = state.exercise.as_mut().map(|a| &mut a.traps);
+    let mut hooks = (&mut state.writes, (timeline, (traps, &mut state.profile)));
+    let history = &mut state.history.history;
+    step(history, &mut state.cpu, &mut state.bus, &mut hooks)
+}
+
+/// The trap the last step raised, if any, so the next steps
+/// can raise it again.
+fn take_tripped(state: &mut UiState) -> Option<Tripped> {
+    let traps = &mut state.exercise.as_mut()?.traps;
+    let tripped = traps.tripped().cloned();
+    if tripped.is_some() {
+        traps.reset();
+    }
+    tripped
+}
+
+/// A caption bar describing the last step, if narration is on.
+pub fn narration(ctx: &Context, state: &UiState) {
+    if !state.narrate {
+        return;
+    }
+    egui::TopBottomPanel::bottom("Narration").show(ctx, |ui| {
+        if state.narration.is_empty() {
+            ui.weak("Step to see what each instruction does");
+        } else {
+            ui.label(&state.narration);
+        }
+    });
+}
+
+/// Executes a single micro operation, or a whole phase
+/// of the instruction if `phase_step` is set.
+pub fn micro_step(ctx: &Context, state: &mut UiState) {
+    let res = if state.phase_step {
+        let from = state.cpu.micro_cycle();
+        let (phase, res) = hooked(state, |history, cpu, bus, mut hooks| {
+            history.micro_step_bus_hooked(cpu, bus, &mut hooks)
+        });
+        let ops = datapath::phase_ops(&state.cpu, from);
+        state.datapath.animate(ctx, ops);
+        state
+            .notes
+            .info(ctx, format!("{phase}, next: {}", state.cpu.phase()));
+        res
+    } else {
+        let (op, res) = hooked(state, |history, cpu, bus, mut hooks| {
+            history.step_uop_bus_hooked(cpu, bus, &mut hooks)
+        });
+        state.datapath.animate(ctx, vec![op]);
+        res
+    };
+    if let Some(tripped) = take_tripped(state) {
+        state.notes.error(ctx, format!("trap: {tripped}"));
+        return;
+    }
+    match res {
+        ExecResult::Halted => state.notes.info(ctx, "end of program reached"),
+        ExecResult::Exception(e) => state.notes.error(ctx, format!("exception: {e}")),
+        _ => (),
+    }
+}
+
+/// Starts a continuous run.
+/// Traps on steps count from here.
+pub fn start(ctx: &Context, state: &mut UiState) {
+    begin(ctx, state, None, false);
+}
+
+/// Starts a run for the `c` or `s n` directives of the console,
+/// stopping after `limit` steps if given.
+pub fn start_for_console(ctx: &Context, state: &mut UiState, limit: Option<u64>) {
+    begin(ctx, state, limit, true);
+}
+
+fn begin(ctx: &Context, state: &mut UiState, limit: Option<u64>, console: bool) {
+    if let Some(active) = &mut state.exercise {
+        active.traps.reset();
+    }
+    state.run = Some(Run {
+        steps: 0,
+        last: ctx.input(|i| i.time),
+        // the first instruction runs right away
+        due: 1.,
+        before: None,
+        limit,
+        console,
+    });
+}
+
+/// Stops the current run.
+pub fn stop(ctx: &Context, state: &mut UiState) {
+    if let Some(run) = &state.run {
+        let text = format!("stopped after {} steps", run.steps);
+        finish(ctx, state, text, false);
+    }
+}
+
+/// Ends the current run, telling why with `text`, as an error if
+/// `failed`. The console is told too if the run came from it.
+fn finish(ctx: &Context, state: &mut UiState, text: String, failed: bool) {
+    let Some(run) = state.run.take() else {
+        return;
+    };
+    if run.console {
+        state.console.run_stopped(&text, failed);
+    }
+    match failed {
+        true => state.notes.error(ctx, text),
+        false => state.notes.info(ctx, text),
+    }
+}
+
+/// Advances the current run by up to `STEPS_PER_FRAME` instructions,
+/// or by the instructions due since the last frame if it's throttled.
+pub fn run_frame(ctx: &Context, state: &mut UiState) {
+    let Some(run) = &mut state.run else {
+        return;
+    };
+    let now = ctx.input(|i| i.time);
+    let budget = match state.throttle {
+        true => {
+            let due = run.due + (now - run.last) * state.clock_hz as f64;
+            run.due = due.min(STEPS_PER_FRAME as f64);
+            let budget = run.due.floor();
+            run.due -= budget;
+            budget as u32
+        }
+        false => STEPS_PER_FRAME,
+    };
+    run.last = now;
+    let mut steps = run.steps;
+    for _ in 0..budget {
+        steps += 1;
+        if state.throttle {
+            let ops = datapath::step_ops(&state.cpu);
+            state.datapath.animate(ctx, ops);
+            if let Some(run) = &mut state.run {
+                run.before = Some(state.cpu.registers());
+            }
+        }
+        let res = step_hooked(state);
+        if let Some(tripped) = take_tripped(state) {
+            return finish(ctx, state, format!("trap: {tripped}"), true);
+        }
+        match res {
+            ExecResult::Halted => {
+                let text = format!("halted after {steps} steps");
+                return finish(ctx, state, text, false);
+            }
+            ExecResult::Exception(e) => {
+                let text = format!("exception after {steps} steps: {e}");
+                return finish(ctx, state, text, true);
+            }
+            ExecResult::MemWrite { addr, .. } if state.console.watchpoints()[addr as usize] => {
+                let addr = state.base.fmt(addr);
+                let text = format!("watchpoint at {addr} hit after {steps} steps");
+                return finish(ctx, state, text, false);
+            }
+            _ => (),
+        }
+        let pc = state.cpu.pc();
+        if state.console.breakpoints()[pc as usize] {
+            let text = format!("breakpoint at {} after {steps} steps", state.base.fmt(pc));
+            return finish(ctx, state, text, false);
+        }
+        if state.run.as_ref().and_then(|r| r.limit) == Some(steps) {
+            return finish(ctx, state, format!("stepped {steps} instructions"), false);
+        }
+    }
+    let Some(run) = &mut state.run else {
+        return;
+    };
+    run.steps = steps;
+    match state.throttle {
+        true => {
+            let wait = (1. - run.due) / state.clock_hz as f64;
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(wait));
+        }
+        false => ctx.request_repaint(),
+    }
+}
+
+/// Progress of the current run, for the status bar.
+pub fn status(ui: &mut Ui, state: &UiState) {
+    match &state.run {
+        Some(run) => {
+            ui.spinner();
+            match state.throttle {
+                true => ui.label(format!(
+                    "running at {} Hz: {} steps",
+                    state.clock_hz, run.steps
+                )),
+                false => ui.label(format!("running: {} steps", run.steps)),
+            };
+        }
+        None => {
+            ui.label("idle");
+        }
+    }
+}
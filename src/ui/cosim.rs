@@ -0,0 +1,162 @@
+//! A window running the machine with a partner, side by side,
+//! sharing a mailbox. See `crate::cosim`.
+use std::path::Path;
+
+use egui::{Color32, RichText, Ui};
+
+use super::UiState;
+use crate::cosim::{self, CoSim};
+use crate::cpu::{ExecResult, Neander};
+use crate::spec;
+
+/// Instructions per frame while running, of both machines together.
+const STEPS_PER_FRAME: u32 = 1000;
+const MAILBOX_COLOR: Color32 = Color32::from_rgb(60, 90, 140);
+const PC_COLOR: Color32 = Color32::from_rgb(140, 110, 30);
+
+pub struct CoSimPanel {
+    pub open: bool,
+    /// The file of the partner machine, as typed
+    partner: String,
+    /// The mailbox range, as typed
+    mailbox: String,
+    sim: Option<CoSim>,
+    running: bool,
+}
+impl Default for CoSimPanel {
+    fn default() -> Self {
+        let mailbox = cosim::DEFAULT_MAILBOX;
+        Self {
+            open: false,
+            partner: String::new(),
+            mailbox: format!("0x{:02X}..0x{:02X}", mailbox.start(), mailbox.end()),
+            sim: None,
+            running: false,
+        }
+    }
+}
+
+/// Opens the window with `partner` as the second machine.
+pub fn open_with(state: &mut UiState, partner: &Path) -> Result<(), String> {
+    state.cosim.partner = partner.display().to_string();
+    state.cosim.open = true;
+    start(state)
+}
+
+/// Starts over with a copy of the machine and the partner file.
+fn start(state: &mut UiState) -> Result<(), String> {
+    let panel = &mut state.cosim;
+    let mailbox = spec::parse_range(&panel.mailbox)?;
+    let mut partner = Neander::new();
+    partner.load_file(Path::new(panel.partner.trim()))?;
+    panel.sim = Some(CoSim::new(state.cpu.clone(), partner, mailbox));
+    panel.running = false;
+    Ok(())
+}
+
+/// Shows the window, if it's open, and advances a run in progress.
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let mut open = state.cosim.open;
+    let mut restart = false;
+    egui::Window::new("Co-simulation")
+        .open(&mut open)
+        .default_width(720.)
+        .show(ctx, |ui| {
+            let panel = &mut state.cosim;
+            ui.horizontal(|ui| {
+                ui.label("Partner:");
+                ui.text_edit_singleline(&mut panel.partner);
+                ui.label("Mailbox:");
+                ui.add(egui::TextEdit::singleline(&mut panel.mailbox).desired_width(90.));
+                restart = ui
+                    .button("Start")
+                    .on_hover_text("Runs a copy of this machine with the partner")
+                    .clicked();
+            });
+            let Some(sim) = &mut panel.sim else {
+                ui.weak("Load a partner program to run it alongside this one");
+                return;
+            };
+            ui.horizontal(|ui| {
+                if ui.button("Step").clicked() {
+                    sim.step();
+                }
+                let run = if panel.running { "Stop" } else { "Run" };
+                if ui.button(run).clicked() {
+                    panel.running = !panel.running;
+                }
+                let reset = ui
+                    .button("Reset")
+                    .on_hover_text("Starts over from this machine and the partner file");
+                if reset.clicked() {
+                    restart = true;
+                }
+            });
+            if panel.running {
+                for _ in 0..STEPS_PER_FRAME {
+                    if sim.step().is_none() {
+                        panel.running = false;
+                        break;
+                    }
+                }
+                if sim.is_done() {
+                    panel.running = false;
+                }
+                ctx.request_repaint();
+            }
+            ui.columns(2, |cols| {
+                for (i, ui) in cols.iter_mut().enumerate() {
+                    machine(ui, sim, i);
+                }
+            });
+        });
+    state.cosim.open = open;
+    if restart {
+        if let Err(e) = start(state) {
+            state.notes.error(ctx, e);
+        }
+    }
+}
+
+/// The registers and memory of a machine, with the
+/// mailbox and the PC highlighted.
+fn machine(ui: &mut Ui, sim: &CoSim, index: usize) {
+    let cpu = &sim.machines[index];
+    let status = match sim.stopped(index) {
+        None => "running".to_string(),
+        Some(ExecResult::Exception(e)) => format!("exception: {e}"),
+        Some(_) => "halted".to_string(),
+    };
+    ui.heading(format!("Machine {}", cosim::name(index)));
+    ui.label(format!(
+        "PC: {:02X}  AC: {}  N: {}  Z: {}",
+        cpu.pc(),
+        cpu.acc(),
+        cpu.status_negative() as u8,
+        cpu.status_zero() as u8
+    ));
+    ui.weak(format!("{} steps, {status}", sim.steps()[index]));
+    let mailbox = sim.mailbox();
+    egui::Grid::new(("cosim", index))
+        .spacing([4., 2.])
+        .show(ui, |ui| {
+            for row in 0..16_u8 {
+                ui.label(
+                    RichText::new(format!("{:02X}", row * 16))
+                        .monospace()
+                        .weak(),
+                );
+                for col in 0..16_u8 {
+                    let addr = row * 16 + col;
+                    let mut text = RichText::new(format!("{:02X}", cpu.ram(addr))).monospace();
+                    if addr == cpu.pc() {
+                        text = text.background_color(PC_COLOR);
+                    } else if mailbox.contains(&addr) {
+                        text = text.background_color(MAILBOX_COLOR);
+                    }
+                    ui.label(text);
+                }
+                ui.end_row();
+            }
+        });
+}
@@ -0,0 +1,209 @@
+//! A diagram of the datapath, animating the register
+//! transfers of the last step.
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+
+use super::UiState;
+use crate::cpu::Neander;
+use crate::micro::{self, MicroOp};
+
+/// How long each micro-operation is highlighted, in seconds.
+const OP_TIME: f64 = 0.5;
+const SIZE: Vec2 = Vec2::new(380., 250.);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Pc,
+    Mar,
+    Mem,
+    Mdr,
+    Ir,
+    Alu,
+    Ac,
+}
+impl Unit {
+    const ALL: [Unit; 7] = [
+        Unit::Pc,
+        Unit::Mar,
+        Unit::Mem,
+        Unit::Mdr,
+        Unit::Ir,
+        Unit::Alu,
+        Unit::Ac,
+    ];
+    fn name(self) -> &'static str {
+        match self {
+            Unit::Pc => "PC",
+            Unit::Mar => "MAR",
+            Unit::Mem => "MEM",
+            Unit::Mdr => "MDR",
+            Unit::Ir => "IR",
+            Unit::Alu => "ALU",
+            Unit::Ac => "AC",
+        }
+    }
+    /// The box of the unit, relative to the top left of the diagram.
+    fn rect(self) -> Rect {
+        let (x, y) = match self {
+            Unit::Pc => (20., 20.),
+            Unit::Mar => (150., 20.),
+            Unit::Mem => (280., 20.),
+            Unit::Mdr => (150., 110.),
+            Unit::Ir => (20., 110.),
+            Unit::Alu => (150., 195.),
+            Unit::Ac => (280., 195.),
+        };
+        Rect::from_min_size(Pos2::new(x, y), Vec2::new(80., 40.))
+    }
+}
+
+/// The transfers of a micro-operation, as (from, to) pairs.
+fn transfers(op: MicroOp) -> &'static [(Unit, Unit)] {
+    use Unit::*;
+    match op {
+        MicroOp::MarFromPc => &[(Pc, Mar)],
+        MicroOp::ReadIncPc => &[(Mar, Mem), (Mem, Mdr), (Pc, Pc)],
+        MicroOp::IrFromMdr => &[(Mdr, Ir)],
+        MicroOp::MarFromMdr => &[(Mdr, Mar)],
+        MicroOp::Read => &[(Mar, Mem), (Mem, Mdr)],
+        MicroOp::MdrFromAcc => &[(Ac, Mdr)],
+        MicroOp::Write => &[(Mar, Mem), (Mdr, Mem)],
+        MicroOp::AccFromMdr | MicroOp::AccAdd | MicroOp::AccOr | MicroOp::AccAnd => {
+            &[(Mdr, Alu), (Alu, Ac)]
+        }
+        MicroOp::AccNot => &[(Ac, Alu), (Alu, Ac)],
+        MicroOp::PcFromMdr | MicroOp::PcFromMdrIfN | MicroOp::PcFromMdrIfZ => &[(Mdr, Pc)],
+        MicroOp::Halt => &[(Ir, Ir)],
+    }
+}
+
+#[derive(Default)]
+pub struct Datapath {
+    pub open: bool,
+    /// The micro-operations of the last step
+    ops: Vec<MicroOp>,
+    /// When the animation of `ops` started
+    start: f64,
+}
+impl Datapath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Starts animating the micro-operations of a step.
+    pub fn animate(&mut self, ctx: &egui::Context, ops: Vec<MicroOp>) {
+        self.ops = ops;
+        self.start = ctx.input(|i| i.time);
+    }
+}
+
+/// Returns the micro-operations the next `step` of `cpu` runs.
+pub fn step_ops(cpu: &Neander) -> Vec<MicroOp> {
+    let (program, from) = if cpu.mid_instruction() {
+        (micro::microprogram(cpu.ir()), cpu.micro_cycle() as usize)
+    } else {
+        (micro::microprogram(cpu.ram(cpu.pc())), 0)
+    };
+    program.unwrap_or(&micro::FETCH)[from..].to_vec()
+}
+
+/// Returns the micro-operations run from micro-cycle `from`
+/// until the current one, after a `micro_step` of `cpu`.
+pub fn phase_ops(cpu: &Neander, from: u8) -> Vec<MicroOp> {
+    let program = match micro::microprogram(cpu.ir()) {
+        Some(program) if from as usize >= micro::FETCH.len() => program,
+        _ => &micro::FETCH,
+    };
+    let to = match cpu.micro_cycle() as usize {
+        0 => program.len(),
+        n => n,
+    };
+    program[(from as usize).min(to)..to].to_vec()
+}
+
+fn diagram(ui: &mut Ui, state: &UiState) {
+    let dp = &state.datapath;
+    let now = ui.input(|i| i.time);
+    let current = ((now - dp.start) / OP_TIME) as usize;
+    let (resp, painter) = ui.allocate_painter(SIZE, Sense::hover());
+    resp.widget_info(|| {
+        egui::WidgetInfo::labeled(egui::WidgetType::Other, true, "datapath diagram")
+    });
+    let origin = resp.rect.min.to_vec2();
+    let visuals = ui.visuals();
+    let idle = Stroke::new(1., visuals.weak_text_color());
+    let active = Stroke::new(3., Color32::from_rgb(255, 170, 0));
+    let op = dp.ops.get(current.min(dp.ops.len().saturating_sub(1)));
+    let moves = op.map_or(&[][..], |&op| transfers(op));
+
+    let base = state.register_base();
+    for unit in Unit::ALL {
+        let rect = unit.rect().translate(origin);
+        let busy = moves.iter().any(|&(a, b)| a == unit || b == unit);
+        painter.rect_stroke(rect, 4., if busy { active } else { idle });
+        let value = match unit {
+            Unit::Pc => Some(state.cpu.pc()),
+            Unit::Mar => Some(state.cpu.mar()),
+            Unit::Mdr => Some(state.cpu.mdr()),
+            Unit::Ir => Some(state.cpu.ir()),
+            Unit::Ac => Some(state.cpu.acc() as u8),
+            Unit::Mem | Unit::Alu => None,
+        };
+        let text = match value {
+            Some(v) => format!("{}\n{}", unit.name(), base.fmt(v)),
+            None => unit.name().to_string(),
+        };
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            text,
+            FontId::monospace(13.),
+            visuals.text_color(),
+        );
+    }
+    for &(from, to) in moves {
+        let (a, b) = (from.rect().translate(origin), to.rect().translate(origin));
+        if from == to {
+            // the unit updates itself, like PC <- PC + 1
+            let top = a.center_top();
+            painter.circle_stroke(top - Vec2::new(0., 8.), 8., active);
+        } else {
+            let start = a.center() + (b.center() - a.center()).normalized() * 25.;
+            let end = b.center() - (b.center() - a.center()).normalized() * 25.;
+            painter.arrow(start, end - start, active);
+        }
+    }
+    if current < dp.ops.len() {
+        // the highlight only moves to the next operation
+        let next = dp.start + (current + 1) as f64 * OP_TIME;
+        ui.ctx()
+            .request_repaint_after(std::time::Duration::from_secs_f64(next - now));
+    }
+    ui.horizontal_wrapped(|ui| {
+        for (i, op) in dp.ops.iter().enumerate() {
+            let text = egui::RichText::new(op.to_string()).monospace();
+            let is_current = i == current.min(dp.ops.len() - 1);
+            ui.label(if is_current {
+                text.strong()
+            } else {
+                text.weak()
+            });
+        }
+    });
+}
+
+/// Shows the datapath window, if it's open.
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let mut open = state.datapath.open;
+    egui::Window::new("Datapath")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if state.datapath.ops.is_empty() {
+                ui.label("Step or micro step to see the register transfers");
+            }
+            diagram(ui, state);
+            if ui.button("Replay").clicked() {
+                state.datapath.start = ui.input(|i| i.time);
+            }
+        });
+    state.datapath.open = open;
+}
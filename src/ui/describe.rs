@@ -0,0 +1,52 @@
+//! A window describing the machine in sentences, for students
+//! using screen readers. The text is in a read-only text box,
+//! which screen readers can move through line by line.
+use super::UiState;
+use crate::narrate;
+
+/// The description of the machine, the selection and the run.
+pub fn description(state: &UiState) -> String {
+    let mut text = narrate::describe(&state.cpu, state.console.symbols());
+    let (start, end) = state.selected_range();
+    let values: Vec<_> = (start..=end)
+        .take(16)
+        .map(|a| (state.cpu.ram(a) as i8).to_string())
+        .collect();
+    let more = if end - start >= 16 { " and more" } else { "" };
+    if start == end {
+        text.push_str(&format!(
+            "\nSelected address {start}, holding {}.",
+            values[0]
+        ));
+    } else {
+        text.push_str(&format!(
+            "\nSelected addresses {start} to {end}, holding {}{more}.",
+            values.join(", ")
+        ));
+    }
+    if state.console.breakpoints()[state.cpu.pc() as usize] {
+        text.push_str("\nThere is a breakpoint at the program counter.");
+    }
+    match &state.run {
+        Some(run) => text.push_str(&format!("\nRunning, {} steps so far.", run.steps)),
+        None => text.push_str("\nNot running."),
+    }
+    if !state.narration.is_empty() {
+        text.push_str(&format!("\nLast step: {}", state.narration));
+    }
+    text
+}
+
+/// Shows the window, if it's open.
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let mut open = state.describe;
+    let text = description(state);
+    egui::Window::new("Description")
+        .open(&mut open)
+        .default_width(360.)
+        .show(ctx, |ui| {
+            ui.add(egui::TextEdit::multiline(&mut text.as_str()).desired_width(f32::INFINITY))
+                .on_hover_text("Updated after every step");
+        });
+    state.describe = open;
+}
@@ -0,0 +1,211 @@
+//! A source editor, assembling the program into memory. The source
+//! is checked as it's typed, underlining the tokens with errors and
+//! listing the labels defined.
+use std::sync::Arc;
+
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Color32, FontId, Key, KeyboardShortcut, Modifiers, Stroke, TextEdit, Ui};
+
+use super::UiState;
+use crate::asm::{AsmError, Assembler};
+use crate::cpu::Neander;
+use crate::symbols::SymbolTable;
+
+const ASSEMBLE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Enter);
+
+#[derive(Default)]
+pub struct Editor {
+    pub open: bool,
+    source: String,
+    errors: Vec<AsmError>,
+    /// The labels of the last source without errors
+    symbols: SymbolTable,
+    assembler: Assembler,
+}
+impl Editor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    pub fn set_source(&mut self, source: String) {
+        self.source = source;
+        self.check();
+    }
+    /// Assembles the source into scratch memory, updating the
+    /// errors and labels shown.
+    fn check(&mut self) {
+        let mut mem = [0; 256];
+        match self.assembler.assemble(&mut mem, &self.source) {
+            Ok((symbols, _)) => {
+                self.errors.clear();
+                self.symbols = symbols;
+            }
+            Err(errors) => self.errors = errors,
+        }
+    }
+}
+
+/// Lays out `source`, marking the lines with errors
+/// and underlining their tokens.
+fn layout(ui: &Ui, source: &str, errors: &[AsmError], wrap_width: f32) -> Arc<egui::Galley> {
+    let font = FontId::monospace(egui::TextStyle::Monospace.resolve(ui.style()).size);
+    let color = ui.visuals().text_color();
+    let mut job = LayoutJob::default();
+    for (i, line) in source.split_inclusive('\n').enumerate() {
+        let errors: Vec<_> = errors.iter().filter(|e| e.line == i + 1).collect();
+        let background = if errors.is_empty() {
+            Color32::TRANSPARENT
+        } else {
+            Color32::from_rgba_unmultiplied(255, 0, 0, 48)
+        };
+        let format = TextFormat {
+            font_id: font.clone(),
+            color,
+            background,
+            ..Default::default()
+        };
+        let underlined = TextFormat {
+            underline: Stroke::new(1.5, Color32::RED),
+            ..format.clone()
+        };
+        // the byte ranges of the tokens with errors, in order
+        let mut spans: Vec<_> = errors
+            .iter()
+            .filter_map(|e| {
+                let start = e.col.checked_sub(1).filter(|&s| line.is_char_boundary(s))?;
+                let len = line[start..]
+                    .find(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                    .unwrap_or(line.len() - start);
+                Some((start, start + len))
+            })
+            .collect();
+        spans.sort();
+        let mut at = 0;
+        for (start, end) in spans {
+            if start < at {
+                continue;
+            }
+            job.append(&line[at..start], 0., format.clone());
+            job.append(&line[start..end], 0., underlined.clone());
+            at = end;
+        }
+        job.append(&line[at..], 0., format);
+    }
+    job.wrap.max_width = wrap_width;
+    ui.fonts(|f| f.layout_job(job))
+}
+
+/// Assembles the source, loading it into a fresh CPU on success.
+fn assemble(ctx: &egui::Context, state: &mut UiState) {
+    let editor = &mut state.editor;
+    let mut cpu = Neander::new();
+    match editor
+        .assembler
+        .assemble_image(cpu.memory().len(), &editor.source)
+    {
+        Ok((image, symbols, map)) => {
+            image.commit(cpu.memory_mut());
+            editor.errors.clear();
+            editor.symbols = symbols.clone();
+            if let Some(active) = &state.exercise {
+                active.exercise.apply(&mut cpu);
+            }
+            state.cpu = cpu;
+            state.run = None;
+            state.history.history.clear();
+            state.file = None;
+            state.console.set_program(None, symbols, map, image);
+            state.notes.info(ctx, "assembled and loaded the program");
+        }
+        Err(errors) => {
+            let n = errors.len();
+            editor.errors = errors;
+            state.notes.error(ctx, format!("{n} errors in the program"));
+        }
+    }
+}
+
+/// Shows the editor window, if it's open.
+pub fn editor(ctx: &egui::Context, state: &mut UiState) {
+    if !state.editor.open {
+        return;
+    }
+    let mut open = true;
+    let mut submit = false;
+    let mut changed = false;
+    egui::Window::new("Editor")
+        .open(&mut open)
+        .default_size([360., 420.])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                submit = ui
+                    .button("Assemble & Load")
+                    .on_hover_text(ctx.format_shortcut(&ASSEMBLE))
+                    .clicked();
+                let file = state.file.clone();
+                if ui
+                    .add_enabled(file.is_some(), egui::Button::new("Open current file"))
+                    .clicked()
+                {
+                    if let Some(text) = file.and_then(|f| std::fs::read_to_string(f).ok()) {
+                        state.editor.set_source(text);
+                    }
+                }
+            });
+            let editor = &mut state.editor;
+            let errors = &editor.errors;
+            if !errors.is_empty() {
+                egui::ScrollArea::vertical()
+                    .id_salt("errors")
+                    .max_height(80.)
+                    .show(ui, |ui| {
+                        for e in errors {
+                            ui.colored_label(Color32::RED, e.to_string());
+                        }
+                    });
+            }
+            let symbols = &editor.symbols;
+            egui::CollapsingHeader::new(format!("Labels ({})", symbols.len()))
+                .id_salt("labels")
+                .show(ui, |ui| {
+                    if symbols.is_empty() {
+                        ui.weak("No labels defined");
+                    }
+                    egui::ScrollArea::vertical()
+                        .id_salt("labels")
+                        .max_height(100.)
+                        .show(ui, |ui| {
+                            for (name, addr) in symbols.iter() {
+                                ui.monospace(format!("{name:<16} {}", state.base.fmt(addr)));
+                            }
+                        });
+                });
+            let mut layouter =
+                |ui: &Ui, source: &str, wrap_width: f32| layout(ui, source, errors, wrap_width);
+            egui::ScrollArea::vertical()
+                .id_salt("source")
+                .show(ui, |ui| {
+                    let field = ui.add(
+                        TextEdit::multiline(&mut editor.source)
+                            .code_editor()
+                            .desired_rows(20)
+                            .desired_width(f32::INFINITY)
+                            .hint_text("LDA 128\nADD 129\nSTA 130\nHLT")
+                            .layouter(&mut layouter),
+                    );
+                    if field.has_focus() && ui.input_mut(|i| i.consume_shortcut(&ASSEMBLE)) {
+                        submit = true;
+                    }
+                    changed = field.changed();
+                });
+        });
+    state.editor.open = open;
+    if changed {
+        state.editor.check();
+    }
+    if submit {
+        assemble(ctx, state);
+    }
+}
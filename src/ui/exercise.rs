@@ -0,0 +1,93 @@
+//! The exercise window, checking the goal whenever memory changes.
+use egui::{Color32, RichText, Ui};
+
+use super::UiState;
+use crate::exercise::{Exercise, Goal};
+use crate::traps::Traps;
+
+/// An exercise being solved.
+pub struct Active {
+    pub exercise: Exercise,
+    /// The memory the goal was last checked with
+    checked: Vec<u8>,
+    goal: Goal,
+    pub open: bool,
+    /// The traps of the exercise, checked on steps and runs
+    pub traps: Traps,
+}
+
+/// Starts `exercise`, writing its data into memory.
+pub fn start(state: &mut UiState, exercise: Exercise) {
+    exercise.apply(&mut state.cpu);
+    let goal = exercise.check(&state.cpu);
+    let traps = Traps::new(exercise.traps.clone());
+    state.exercise = Some(Active {
+        exercise,
+        checked: state.cpu.memory().to_vec(),
+        goal,
+        open: true,
+        traps,
+    });
+}
+
+/// Checks the goal again if memory changed.
+pub fn update(state: &mut UiState) {
+    let Some(active) = &mut state.exercise else {
+        return;
+    };
+    if active.checked != state.cpu.memory() {
+        active.goal = active.exercise.check(&state.cpu);
+        active.checked.copy_from_slice(state.cpu.memory());
+    }
+}
+
+fn goal_text(goal: &Goal) -> RichText {
+    let text = RichText::new(format!("goal: {}/{}", goal.passed(), goal.cases));
+    if goal.met() {
+        text.color(Color32::GREEN)
+    } else {
+        text
+    }
+}
+
+/// Shows how many cases pass in the status bar.
+/// Clicking it opens the exercise window.
+pub fn status(ui: &mut Ui, state: &mut UiState) {
+    if let Some(active) = &mut state.exercise {
+        if ui.link(goal_text(&active.goal)).clicked() {
+            active.open = true;
+        }
+    }
+}
+
+/// Shows the exercise window, if it's open.
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let Some(active) = &mut state.exercise else {
+        return;
+    };
+    let exercise = &active.exercise;
+    let goal = &active.goal;
+    egui::Window::new(format!("Exercise: {}", exercise.title))
+        .open(&mut active.open)
+        .default_width(300.)
+        .show(ctx, |ui| {
+            if !exercise.text.is_empty() {
+                ui.label(&exercise.text);
+                ui.separator();
+            }
+            ui.label(goal_text(goal));
+            for case in &exercise.cases {
+                match goal.failed.iter().find(|(name, _)| *name == case.name) {
+                    Some((_, failures)) => {
+                        ui.colored_label(Color32::RED, format!("✘ {}", case.name));
+                        for f in failures {
+                            ui.weak(format!("    {f}"));
+                        }
+                    }
+                    None => {
+                        ui.label(format!("✔ {}", case.name));
+                    }
+                }
+            }
+        });
+}
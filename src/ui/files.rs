@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+
+use super::exercise;
+use super::pack;
+use super::tutorial::{self, Tutorial};
+use super::workspace::Workspace;
+use super::UiState;
+use crate::cpu::Neander;
+use crate::exercise::Exercise;
+use crate::image::{self, MemoryImage};
+use crate::logisim;
+use crate::srcmap::SourceMap;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Open,
+    ExportLogisim,
+    OpenWorkspace,
+    SaveWorkspace,
+    OpenTutorial,
+    OpenExercise,
+    OpenPack,
+    SaveState,
+    LoadState,
+}
+
+/// A window asking for the path of a file action.
+pub struct FilePrompt {
+    action: FileAction,
+    path: String,
+}
+impl FilePrompt {
+    pub fn new(action: FileAction) -> Self {
+        Self {
+            action,
+            path: String::new(),
+        }
+    }
+}
+
+/// Loads `path` into a fresh CPU, replacing the current one on success.
+pub fn open_file(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    match image::load(&path, state.cpu.memory().len()) {
+        Ok(loaded) => {
+            state.notes.info(ctx, format!("loaded {}", path.display()));
+            set_program(state, Some(path), loaded);
+        }
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+/// Replaces the CPU with a fresh one holding `image`, keeping the
+/// data of the exercise. `file` is where it can be reloaded from.
+pub fn set_program(
+    state: &mut UiState,
+    file: Option<PathBuf>,
+    (image, symbols, map): (MemoryImage, SymbolTable, SourceMap),
+) {
+    let mut cpu = Neander::new();
+    image.commit(cpu.memory_mut());
+    if let Some(active) = &state.exercise {
+        active.exercise.apply(&mut cpu);
+    }
+    state.cpu = cpu;
+    state.run = None;
+    state.writes.clear();
+    state.profile.clear();
+    state.timeline.timeline.clear();
+    state.history.history.clear();
+    state.console.set_program(file.clone(), symbols, map, image);
+    state.file = file;
+}
+
+fn export_logisim(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    let image = logisim::write_image(state.cpu.memory());
+    match std::fs::write(&path, image) {
+        Ok(()) => state
+            .notes
+            .info(ctx, format!("exported {}", path.display())),
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+fn open_workspace(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    let ws = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| Workspace::parse(&s));
+    match ws {
+        Ok(ws) => {
+            ws.apply(ctx, state);
+            state
+                .notes
+                .info(ctx, format!("opened workspace {}", path.display()));
+        }
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+fn save_workspace(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    let ws = Workspace::capture(ctx, state);
+    match std::fs::write(&path, ws.write()) {
+        Ok(()) => state
+            .notes
+            .info(ctx, format!("saved workspace {}", path.display())),
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+fn open_tutorial(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    let tutorial = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| Tutorial::parse(&s));
+    match tutorial {
+        Ok(tutorial) => tutorial::start(state, tutorial),
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+fn save_state(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    match state.cpu.save_state(&path) {
+        Ok(()) => state
+            .notes
+            .info(ctx, format!("saved state {}", path.display())),
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+fn load_state(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    match Neander::load_state(&path) {
+        Ok(cpu) => {
+            state.cpu = cpu;
+            state.run = None;
+            state.history.history.clear();
+            state
+                .notes
+                .info(ctx, format!("loaded state {}", path.display()));
+        }
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+fn open_exercise(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    match Exercise::load(&path) {
+        Ok(ex) => exercise::start(state, ex),
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+/// Shows the file prompt, if it's open.
+pub fn file_prompt(ctx: &egui::Context, state: &mut UiState) {
+    let Some(prompt) = &mut state.file_prompt else {
+        return;
+    };
+    let title = match prompt.action {
+        FileAction::Open => "Open memory file",
+        FileAction::ExportLogisim => "Export Logisim image",
+        FileAction::OpenWorkspace => "Open workspace",
+        FileAction::SaveWorkspace => "Save workspace",
+        FileAction::OpenTutorial => "Open tutorial",
+        FileAction::OpenExercise => "Open exercise",
+        FileAction::OpenPack => "Open course pack",
+        FileAction::SaveState => "Save machine state",
+        FileAction::LoadState => "Load machine state",
+    };
+    let mut open = true;
+    let mut submit = false;
+    egui::Window::new(title)
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Path:");
+                let field = ui.text_edit_singleline(&mut prompt.path);
+                submit = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                submit |= ui.button("OK").clicked();
+            });
+        });
+    if submit {
+        let action = prompt.action;
+        let path = PathBuf::from(prompt.path.trim());
+        state.file_prompt = None;
+        match action {
+            FileAction::Open => open_file(ctx, state, path),
+            FileAction::ExportLogisim => export_logisim(ctx, state, path),
+            FileAction::OpenWorkspace => open_workspace(ctx, state, path),
+            FileAction::SaveWorkspace => save_workspace(ctx, state, path),
+            FileAction::OpenTutorial => open_tutorial(ctx, state, path),
+            FileAction::OpenExercise => open_exercise(ctx, state, path),
+            FileAction::OpenPack => pack::open(ctx, state, path),
+            FileAction::SaveState => save_state(ctx, state, path),
+            FileAction::LoadState => load_state(ctx, state, path),
+        }
+    } else if !open {
+        state.file_prompt = None;
+    }
+}
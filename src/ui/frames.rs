@@ -0,0 +1,56 @@
+//! An overlay with frame times, to check that the simulator
+//! only repaints when something changes.
+use std::collections::VecDeque;
+
+use egui::{Align2, Context};
+
+/// A frame drawn by the app.
+struct Frame {
+    time: f64,
+    /// Seconds spent on the previous frame
+    cpu: Option<f32>,
+}
+
+#[derive(Default)]
+pub struct FrameStats {
+    pub open: bool,
+    /// The frames of the last second
+    frames: VecDeque<Frame>,
+}
+impl FrameStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Records a frame, given the time spent on the previous one.
+    pub fn record(&mut self, ctx: &Context, cpu: Option<f32>) {
+        let time = ctx.input(|i| i.time);
+        while self.frames.front().is_some_and(|f| time - f.time > 1.) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Frame { time, cpu });
+    }
+}
+
+/// Shows the overlay, if it's open. It doesn't repaint by itself,
+/// so while the app is idle it keeps showing the last frames.
+pub fn overlay(ctx: &Context, stats: &FrameStats) {
+    if !stats.open {
+        return;
+    }
+    let cpu: Vec<f32> = stats.frames.iter().filter_map(|f| f.cpu).collect();
+    let avg = cpu.iter().sum::<f32>() / cpu.len().max(1) as f32;
+    let max = cpu.iter().copied().fold(0., f32::max);
+    egui::Area::new(egui::Id::new("frame times"))
+        .anchor(Align2::RIGHT_TOP, [-10., 40.])
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.monospace(format!("frames in the last second: {}", stats.frames.len()));
+                ui.monospace(format!(
+                    "frame time: {:.2} ms avg, {:.2} ms max",
+                    avg * 1000.,
+                    max * 1000.
+                ));
+            });
+        });
+}
@@ -0,0 +1,88 @@
+//! A window listing the last steps, most recent first, with the
+//! registers before each. Clicking a step goes back to it.
+use egui::RichText;
+
+use super::UiState;
+use crate::cpu::Registers;
+use crate::disasm;
+use crate::history::History;
+
+/// Steps kept, the oldest are forgotten.
+const CAPACITY: usize = 1000;
+
+pub struct HistoryPanel {
+    pub open: bool,
+    pub history: History,
+}
+impl Default for HistoryPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            history: History::new(CAPACITY),
+        }
+    }
+}
+
+fn entry(state: &UiState, regs: &Registers) -> String {
+    let base = state.base;
+    let text = match regs.uop {
+        0 => disasm::decode_labeled(state.cpu.memory(), regs.pc, state.console.symbols()).text,
+        uop => format!("micro-operation {uop}"),
+    };
+    format!(
+        "{:>4}  {:<12} AC {:>4}  N {}  Z {}",
+        base.fmt(regs.pc),
+        text,
+        base.fmt(regs.acc as u8),
+        (regs.status & 2 != 0) as u8,
+        (regs.status & 1 != 0) as u8,
+    )
+}
+
+/// Shows the window, if it's open.
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let mut open = state.history.open;
+    let mut restore = None;
+    egui::Window::new("History")
+        .open(&mut open)
+        .default_width(320.)
+        .show(ctx, |ui| {
+            let history = &state.history.history;
+            let len = history.len();
+            ui.horizontal(|ui| {
+                ui.weak(format!("{len} steps kept"));
+                if ui.button("Clear").clicked() {
+                    state.history.history.clear();
+                }
+            });
+            let history = &state.history.history;
+            if history.is_empty() {
+                ui.weak("Step or run to record the steps");
+                return;
+            }
+            let snapshots: Vec<_> = history.snapshots().collect();
+            let row = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show_rows(ui, row, len, |ui, rows| {
+                    for row in rows {
+                        // most recent first
+                        let index = len - 1 - row;
+                        let text = format!("{:>5}  {}", index, entry(state, &snapshots[index]));
+                        let resp = ui
+                            .selectable_label(false, RichText::new(text).monospace())
+                            .on_hover_text("Go back to before this step");
+                        if resp.clicked() {
+                            restore = Some(index);
+                        }
+                    }
+                });
+        });
+    state.history.open = open;
+    if let Some(index) = restore {
+        let undone = state.history.history.undo_to(&mut state.cpu, index);
+        state.run = None;
+        state.narration.clear();
+        state.notes.info(ctx, format!("went back {undone} steps"));
+    }
+}
@@ -0,0 +1,70 @@
+use super::utils::base_override;
+use super::UiState;
+use crate::disasm;
+use egui::{Align, Color32, RichText, Sense, Ui, WidgetInfo, WidgetType};
+
+/// The color of addresses with a breakpoint.
+pub const BREAKPOINT: Color32 = Color32::from_rgb(220, 50, 50);
+
+/// Disassembly of the whole memory, with the PC marked. Clicking
+/// an address toggles a breakpoint, and its row turns red. For
+/// assembled programs, the source line of the PC is shown above,
+/// and the line of each instruction on hover.
+pub fn listing(ui: &mut Ui, state: &mut UiState) {
+    ui.horizontal(|ui| {
+        ui.heading("Disassembly");
+        base_override(ui, "listing base", &mut state.panel_bases.listing);
+    });
+    let base = state.listing_base();
+    if let Some(line) = state.console.source_map().describe(state.cpu.pc()) {
+        ui.label(RichText::new(format!("-> {line}")).monospace())
+            .on_hover_text("The source line of the next instruction");
+    }
+    egui::ScrollArea::vertical()
+        .id_salt("listing")
+        .show(ui, |ui| {
+            egui::Grid::new("listing").striped(true).show(ui, |ui| {
+                for line in disasm::disassemble(state.cpu.memory(), 0, 255) {
+                    let marker = if line.addr == state.cpu.pc() {
+                        "->"
+                    } else {
+                        ""
+                    };
+                    ui.label(RichText::new(marker).monospace());
+                    let brk = state.console.breakpoints()[line.addr as usize];
+                    let mut addr = RichText::new(base.fmt(line.addr)).monospace();
+                    addr = if brk {
+                        addr.color(BREAKPOINT)
+                    } else {
+                        addr.weak()
+                    };
+                    let resp = ui
+                        .add(egui::Label::new(addr).sense(Sense::click()))
+                        .on_hover_text("toggle a breakpoint");
+                    resp.widget_info(|| {
+                        let name = format!("breakpoint at {}, {}", line.addr, line.text);
+                        WidgetInfo::selected(WidgetType::Checkbox, true, brk, name)
+                    });
+                    if resp.clicked() {
+                        state.console.toggle_breakpoint(line.addr);
+                    }
+                    let hit = state.search.hit(line.addr);
+                    let mut text = RichText::new(&line.text).monospace();
+                    if brk {
+                        text = text.color(BREAKPOINT);
+                    }
+                    if let Some(color) = hit.color() {
+                        text = text.background_color(color);
+                    }
+                    let mut resp = ui.label(text);
+                    if let Some(source) = state.console.source_map().describe(line.addr) {
+                        resp = resp.on_hover_text(source);
+                    }
+                    if hit == super::search::Hit::Current && state.search.scroll {
+                        resp.scroll_to_me(Some(Align::Center));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
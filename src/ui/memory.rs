@@ -0,0 +1,148 @@
+use super::listing::BREAKPOINT;
+use super::search::Hit;
+use super::utils::base_override;
+use super::UiState;
+use crate::inspect;
+use egui::{Align, Color32, Key, Modifiers, RichText, Ui, WidgetInfo, WidgetType};
+
+/// A 16x16 grid with the whole memory. Clicking a cell selects it,
+/// and shift-clicking extends the selection up to the cell.
+/// Right-clicking toggles a breakpoint, shown in red, and hovering
+/// a cell shows what's known about it. With the heatmap on, cells
+/// are tinted by how many times they were executed.
+///
+/// Cells can be reached with Tab and the arrow keys, which select
+/// the focused cell, or extend the selection with Shift. B toggles
+/// a breakpoint at the focused cell.
+pub fn memory_grid(ui: &mut Ui, state: &mut UiState) {
+    let (start, end) = state.selected_range();
+    let base = state.memory_base();
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Selected: {} to {} ({} bytes)",
+            base.fmt(start),
+            base.fmt(end),
+            end as usize - start as usize + 1
+        ));
+        ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
+            base_override(ui, "memory base", &mut state.panel_bases.memory);
+            ui.label("Base:");
+        });
+    });
+    egui::ScrollArea::both().show(ui, |ui| {
+        egui::Grid::new("memory").striped(true).show(ui, |ui| {
+            ui.label("");
+            for col in 0..16_u8 {
+                ui.label(RichText::new(format!("{col:X}")).monospace().weak());
+            }
+            ui.end_row();
+            for row in 0..16_u8 {
+                ui.label(RichText::new(base.fmt(row * 16)).monospace().weak());
+                for col in 0..16_u8 {
+                    let addr = row * 16 + col;
+                    let selected = (start..=end).contains(&addr);
+                    let hit = state.search.hit(addr);
+                    let mut text =
+                        RichText::new(base.fmt_byte(state.cpu.ram(addr), state.dual)).monospace();
+                    if let Some(color) = hit.color() {
+                        text = text.background_color(color);
+                    } else if state.heatmap && state.profile.counts[addr as usize] > 0 {
+                        text = text.background_color(heat_color(state.profile.heat(addr)));
+                    }
+                    if state.console.breakpoints()[addr as usize] {
+                        text = text.color(BREAKPOINT);
+                    }
+                    let locked = state
+                        .exercise
+                        .as_ref()
+                        .is_some_and(|a| a.exercise.is_locked(addr));
+                    if locked {
+                        text = text.underline();
+                    }
+                    let resp = ui
+                        .selectable_label(selected, text)
+                        .on_hover_ui(|ui| cell_tooltip(ui, state, addr, locked));
+                    resp.widget_info(|| {
+                        let name = cell_name(state, addr, locked);
+                        WidgetInfo::selected(WidgetType::SelectableLabel, true, selected, name)
+                    });
+                    if resp.gained_focus() {
+                        resp.scroll_to_me(None);
+                        if ui.input(|i| i.modifiers.shift) {
+                            state.selection.1 = addr;
+                        } else {
+                            state.selection = (addr, addr);
+                        }
+                    }
+                    if resp.has_focus() && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::B))
+                    {
+                        state.console.toggle_breakpoint(addr);
+                    }
+                    let scroll = hit == Hit::Current && state.search.scroll;
+                    if scroll || state.scroll_to == Some(addr) {
+                        resp.scroll_to_me(Some(Align::Center));
+                    }
+                    if resp.secondary_clicked() {
+                        state.console.toggle_breakpoint(addr);
+                    }
+                    if resp.clicked() {
+                        if ui.input(|i| i.modifiers.shift) {
+                            state.selection.1 = addr;
+                        } else {
+                            state.selection = (addr, addr);
+                        }
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
+
+/// Everything known about the cell at `addr`, see `crate::inspect`.
+fn cell_tooltip(ui: &mut Ui, state: &UiState, addr: u8, locked: bool) {
+    let console = &state.console;
+    let lines = inspect::describe_byte(
+        state.cpu.memory(),
+        addr,
+        console.symbols(),
+        console.source_map(),
+        console.image(),
+    );
+    for line in lines {
+        ui.label(line);
+    }
+    if locked {
+        ui.label("Locked by the exercise");
+    }
+    if state.heatmap {
+        let count = state.profile.counts[addr as usize];
+        ui.label(format!(
+            "Executed {count} times since the program was loaded"
+        ));
+    }
+}
+
+/// From a faint yellow for cold cells to a strong red for the hottest.
+fn heat_color(heat: f32) -> Color32 {
+    let green = (200. * (1. - heat)) as u8;
+    let alpha = (60. + 140. * heat) as u8;
+    Color32::from_rgba_unmultiplied(255, green, 0, alpha)
+}
+
+/// What screen readers say for the cell at `addr`, like
+/// "address 128, value 10, breakpoint".
+fn cell_name(state: &UiState, addr: u8, locked: bool) -> String {
+    let value = state.cpu.ram(addr);
+    let mut name = format!("address {addr}, value {}", value as i8);
+    if addr == state.cpu.pc() {
+        name.push_str(", program counter");
+    }
+    if state.console.breakpoints()[addr as usize] {
+        name.push_str(", breakpoint");
+    }
+    if locked {
+        name.push_str(", locked");
+    }
+    name
+}
@@ -1,52 +1,321 @@
+mod actions;
+mod clipboard;
+mod console;
+mod controls;
+mod cosim;
+mod datapath;
+mod describe;
+mod editor;
+mod exercise;
+mod files;
+mod frames;
+mod history;
+mod listing;
+mod memory;
+mod notify;
+mod pack;
+mod palette;
+mod search;
+mod settings;
+mod timeline;
+mod tutorial;
 mod utils;
+mod workspace;
+mod writes;
+use tutorial::Target;
 use utils::*;
 
+use std::path::PathBuf;
+
 use crate::cpu::Neander;
+use crate::exercise::Exercise;
+use crate::io::Bus;
+use crate::pack::Pack;
+use crate::profile::Profile;
 
 pub struct UiState {
+    /// The base of panels without their own
     pub base: NumberBase,
+    pub panel_bases: PanelBases,
+    /// Whether bytes are shown along with their decimal values
+    pub dual: bool,
+    pub theme: settings::Theme,
     pub cpu: Neander,
+    /// The anchor and the end of the selected memory range
+    pub selection: (u8, u8),
+    /// An address the memory grid scrolls to in this frame
+    pub scroll_to: Option<u8>,
+    pub paste: Option<clipboard::PasteWindow>,
+    pub search: search::Search,
+    /// The file the program was loaded from
+    pub file: Option<PathBuf>,
+    pub file_prompt: Option<files::FilePrompt>,
+    pub run: Option<controls::Run>,
+    /// Whether runs execute `clock_hz` instructions per second
+    pub throttle: bool,
+    pub clock_hz: u32,
+    /// Whether micro-steps run a whole fetch, operand or execute phase
+    pub phase_step: bool,
+    /// Whether steps are described in a caption bar
+    pub narrate: bool,
+    /// The description of the last step
+    pub narration: String,
+    /// Executions of every address, shown over memory if `heatmap` is set
+    pub profile: Profile,
+    pub heatmap: bool,
+    /// Whether the machine is described in a window, for screen readers
+    pub describe: bool,
+    pub notes: notify::Notifications,
+    pub palette: palette::Palette,
+    pub console: console::Console,
+    pub editor: editor::Editor,
+    pub datapath: datapath::Datapath,
+    pub frames: frames::FrameStats,
+    pub writes: writes::Writes,
+    pub timeline: timeline::TimelinePanel,
+    pub history: history::HistoryPanel,
+    pub cosim: cosim::CoSimPanel,
+    pub tutorial: Option<tutorial::Progress>,
+    pub exercise: Option<exercise::Active>,
+    pub pack: Option<pack::Active>,
+    /// The devices the program runs with, shown in the console
+    pub bus: Bus,
 }
 impl UiState {
-    pub fn new() -> Self {
+    pub fn new(cpu: Neander, file: Option<PathBuf>) -> Self {
+        let console = console::Console::new(file.clone());
         Self {
+            bus: console.bus(),
+            console,
+            editor: editor::Editor::new(),
+            datapath: datapath::Datapath::new(),
+            frames: frames::FrameStats::new(),
+            writes: writes::Writes::new(),
+            timeline: timeline::TimelinePanel::default(),
+            history: history::HistoryPanel::default(),
+            cosim: cosim::CoSimPanel::default(),
+            profile: Profile::new(),
+            heatmap: false,
+            describe: false,
             base: NumberBase::Dec,
-            cpu: Neander::new(),
+            panel_bases: PanelBases::default(),
+            dual: false,
+            theme: settings::Theme::Dark,
+            cpu,
+            selection: (0, 0),
+            scroll_to: None,
+            paste: None,
+            search: search::Search::new(),
+            file,
+            file_prompt: None,
+            run: None,
+            throttle: false,
+            clock_hz: controls::DEFAULT_HZ,
+            phase_step: false,
+            narrate: false,
+            narration: String::new(),
+            notes: notify::Notifications::new(),
+            palette: palette::Palette::new(),
+            tutorial: None,
+            exercise: None,
+            pack: None,
         }
     }
+    pub fn memory_base(&self) -> NumberBase {
+        self.panel_bases.memory.unwrap_or(self.base)
+    }
+    pub fn register_base(&self) -> NumberBase {
+        self.panel_bases.registers.unwrap_or(self.base)
+    }
+    pub fn listing_base(&self) -> NumberBase {
+        self.panel_bases.listing.unwrap_or(self.base)
+    }
+    /// Returns the selected memory range, with start <= end.
+    pub fn selected_range(&self) -> (u8, u8) {
+        let (a, b) = self.selection;
+        (a.min(b), a.max(b))
+    }
 }
 
 pub struct NeanderSim {
     state: UiState,
+    /// Kept to save the zoom, which lives in the context
+    ctx: egui::Context,
+    /// The file and machine last given to the crash reporter
+    crash: (Option<PathBuf>, Neander),
 }
 
 impl NeanderSim {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        _cc.egui_ctx.set_pixels_per_point(1.2);
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        cpu: Neander,
+        file: Option<PathBuf>,
+        exercise: Option<Exercise>,
+        partner: Option<PathBuf>,
+        course: Option<Pack>,
+    ) -> Self {
+        let mut state = UiState::new(cpu, file);
+        if let Some(file) = state.file.clone() {
+            // the memory is loaded already, this keeps the labels and lines
+            if let Ok((image, symbols, map)) = Neander::new().load_file_image(&file) {
+                state.console.set_program(Some(file), symbols, map, image);
+            }
+        }
+        settings::Settings::load(cc.storage).apply(&cc.egui_ctx, &mut state);
+        if let Some(course) = course {
+            pack::start(&cc.egui_ctx, &mut state, course);
+        }
+        if let Some(ex) = exercise {
+            exercise::start(&mut state, ex);
+        }
+        if let Some(partner) = partner {
+            if let Err(e) = cosim::open_with(&mut state, &partner) {
+                state.notes.error(&cc.egui_ctx, e);
+            }
+        }
+        crate::crash::set_file(state.file.as_deref());
+        crate::crash::set_machine(&state.cpu);
         Self {
-            state: UiState::new(),
+            crash: (state.file.clone(), state.cpu.clone()),
+            state,
+            ctx: cc.egui_ctx.clone(),
+        }
+    }
+}
+
+impl NeanderSim {
+    /// Gives the crash reporter the file and machine when they
+    /// changed, by a step, a run, a load or an edit, since the
+    /// last frame. Comparing them is cheaper than encoding the
+    /// machine and taking the reporter's lock on every frame.
+    fn update_crash_context(&mut self) {
+        let (file, cpu) = &mut self.crash;
+        if *file != self.state.file {
+            file.clone_from(&self.state.file);
+            crate::crash::set_file(file.as_deref());
+        }
+        if *cpu != self.state.cpu {
+            cpu.clone_from(&self.state.cpu);
+            crate::crash::set_machine(cpu);
         }
     }
 }
 
 impl eframe::App for NeanderSim {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        settings::Settings::capture(&self.ctx, &self.state).save(storage);
+    }
+    /// Repaints happen on input, and when something asks for them:
+    /// a run in progress, an animation or a notification to hide.
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.state.frames.record(ctx, frame.info().cpu_usage);
+        self.update_crash_context();
+        actions::handle_shortcuts(ctx, &mut self.state);
+        controls::run_frame(ctx, &mut self.state);
+        exercise::update(&mut self.state);
         egui::TopBottomPanel::top("Options").show(ctx, |ui| {
-            utils::menu(ui, &mut self.state);
+            let menu = actions::menu_bar(ui, &mut self.state);
+            tutorial::outline(ui, &self.state, Target::Menu, menu.rect);
+            search::search_bar(ui, &mut self.state);
         });
-        egui::CentralPanel::default().show(ctx, |ui| {
+        egui::SidePanel::left("Registers").show(ctx, |ui| {
             ui.heading("NEANDER");
-            utils::cpu_state(ui, &self.state);
+            let regs = utils::cpu_state(ui, &mut self.state);
+            tutorial::outline(ui, &self.state, Target::Registers, regs.rect);
+            let controls = controls::controls(ui, &mut self.state);
+            tutorial::outline(ui, &self.state, Target::Controls, controls.rect);
+        });
+        egui::TopBottomPanel::bottom("Status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                controls::status(ui, &self.state);
+                exercise::status(ui, &mut self.state);
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    notify::status(ui, &mut self.state.notes);
+                });
+            });
+        });
+        controls::narration(ctx, &self.state);
+        console::console(ctx, &mut self.state);
+        egui::SidePanel::right("Disassembly").show(ctx, |ui| {
+            listing::listing(ui, &mut self.state);
+            tutorial::outline(ui, &self.state, Target::Listing, ui.min_rect());
         });
+        writes::panel(ctx, &mut self.state);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            memory::memory_grid(ui, &mut self.state);
+            tutorial::outline(ui, &self.state, Target::Memory, ui.min_rect());
+        });
+        clipboard::paste_window(ctx, &mut self.state);
+        files::file_prompt(ctx, &mut self.state);
+        palette::palette(ctx, &mut self.state);
+        editor::editor(ctx, &mut self.state);
+        datapath::window(ctx, &mut self.state);
+        timeline::window(ctx, &mut self.state);
+        history::window(ctx, &mut self.state);
+        cosim::window(ctx, &mut self.state);
+        describe::window(ctx, &mut self.state);
+        tutorial::show(ctx, &mut self.state);
+        exercise::window(ctx, &mut self.state);
+        pack::window(ctx, &mut self.state);
+        notify::show(ctx, &mut self.state.notes);
+        frames::overlay(ctx, &self.state.frames);
+        self.state.search.scroll = false;
+        self.state.scroll_to = None;
     }
 }
 
-pub fn run_ui() {
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_ui(
+    cpu: Neander,
+    file: Option<PathBuf>,
+    exercise: Option<Exercise>,
+    partner: Option<PathBuf>,
+    course: Option<Pack>,
+) -> eframe::Result {
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "Neander",
         native_options,
-        Box::new(|cc| Ok(Box::new(NeanderSim::new(cc)))),
+        Box::new(|cc| {
+            Ok(Box::new(NeanderSim::new(
+                cc, cpu, file, exercise, partner, course,
+            )))
+        }),
     )
-    .unwrap();
+}
+
+/// Starts the simulator in the canvas with id `canvas_id`. The web build
+/// is made with `trunk build --release`, using the `index.html` at the
+/// root of the repository.
+#[cfg(target_arch = "wasm32")]
+pub fn run_web(canvas_id: &str) {
+    use eframe::wasm_bindgen::JsCast;
+    use eframe::web_sys;
+
+    let canvas = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id(canvas_id))
+        .and_then(|c| c.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+        .expect("missing canvas element");
+    wasm_bindgen_futures::spawn_local(async move {
+        let res = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|cc| {
+                    Ok(Box::new(NeanderSim::new(
+                        cc,
+                        Neander::new(),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )))
+                }),
+            )
+            .await;
+        if let Err(e) = res {
+            web_sys::console::error_1(&e);
+        }
+    });
 }
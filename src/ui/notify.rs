@@ -0,0 +1,114 @@
+use egui::{Align2, Color32, Context, Ui};
+
+/// How long a toast stays on screen, in seconds.
+const TOAST_DURATION: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+impl Level {
+    fn color(self) -> Color32 {
+        match self {
+            Level::Info => Color32::LIGHT_GREEN,
+            Level::Warning => Color32::YELLOW,
+            Level::Error => Color32::LIGHT_RED,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub level: Level,
+    pub text: String,
+    /// When it was created, in egui time
+    pub time: f64,
+}
+
+/// Every notification of the session. Recent
+/// ones are shown as toasts.
+pub struct Notifications {
+    history: Vec<Notification>,
+    pub show_history: bool,
+}
+impl Notifications {
+    pub fn new() -> Self {
+        Self {
+            history: Vec::new(),
+            show_history: false,
+        }
+    }
+    pub fn push(&mut self, ctx: &Context, level: Level, text: impl Into<String>) {
+        let time = ctx.input(|i| i.time);
+        self.history.push(Notification {
+            level,
+            text: text.into(),
+            time,
+        });
+    }
+    pub fn info(&mut self, ctx: &Context, text: impl Into<String>) {
+        self.push(ctx, Level::Info, text);
+    }
+    pub fn error(&mut self, ctx: &Context, text: impl Into<String>) {
+        self.push(ctx, Level::Error, text);
+    }
+    pub fn last(&self) -> Option<&Notification> {
+        self.history.last()
+    }
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+}
+
+/// Shows the toasts of recent notifications in the bottom
+/// right corner, and the history window if it's open.
+pub fn show(ctx: &Context, notes: &mut Notifications) {
+    let now = ctx.input(|i| i.time);
+    let recent: Vec<_> = notes
+        .history
+        .iter()
+        .filter(|n| now - n.time < TOAST_DURATION)
+        .collect();
+    if let Some(oldest) = recent.first() {
+        let left = TOAST_DURATION - (now - oldest.time);
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(left));
+    }
+    egui::Area::new(egui::Id::new("toasts"))
+        .anchor(Align2::RIGHT_BOTTOM, [-10., -40.])
+        .interactable(false)
+        .show(ctx, |ui| {
+            for note in recent {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.colored_label(note.level.color(), &note.text);
+                });
+            }
+        });
+    egui::Window::new("Notifications")
+        .open(&mut notes.show_history)
+        .vscroll(true)
+        .show(ctx, |ui| {
+            if notes.history.is_empty() {
+                ui.label("no notifications");
+            }
+            for note in notes.history.iter().rev() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("[{:7.1}s]", note.time));
+                    ui.colored_label(note.level.color(), &note.text);
+                });
+            }
+        });
+}
+
+/// The part of the status bar with the last notification,
+/// which opens the history when clicked.
+pub fn status(ui: &mut Ui, notes: &mut Notifications) {
+    let text = match notes.last() {
+        Some(n) => format!("{} ({})", n.text, notes.len()),
+        None => "no notifications".to_string(),
+    };
+    if ui.link(text).clicked() {
+        notes.show_history = !notes.show_history;
+    }
+}
@@ -0,0 +1,103 @@
+//! The window of an open course pack, see `crate::pack`, listing
+//! its examples, exercises and tutorials to open.
+use std::path::PathBuf;
+
+use super::tutorial::{self, Tutorial};
+use super::{exercise, files, UiState};
+use crate::numfmt::{self, NumberFormat};
+use crate::pack::Pack;
+
+pub struct Active {
+    pub pack: Pack,
+    pub open: bool,
+}
+
+/// Opens `pack`, applying its profile.
+pub fn start(ctx: &egui::Context, state: &mut UiState, pack: Pack) {
+    let profile = &pack.manifest.profile;
+    if let Some(base) = profile.base() {
+        state.base = base;
+    }
+    if let Some(dual) = profile.dual {
+        state.dual = dual;
+    }
+    if let Some(group_binary) = profile.group_binary {
+        numfmt::set(NumberFormat {
+            group_binary,
+            ..numfmt::get()
+        });
+    }
+    let title = &pack.manifest.title;
+    state.notes.info(ctx, format!("opened course pack {title}"));
+    if let Some(warning) = pack.version_warning() {
+        state.notes.error(ctx, format!("warning: {warning}"));
+    }
+    state.pack = Some(Active { pack, open: true });
+}
+
+/// Opens the pack at `path`.
+pub fn open(ctx: &egui::Context, state: &mut UiState, path: PathBuf) {
+    match Pack::open(&path) {
+        Ok(pack) => start(ctx, state, pack),
+        Err(e) => state.notes.error(ctx, format!("error: {e}")),
+    }
+}
+
+/// What was clicked in the window.
+enum Open {
+    Example(String),
+    Exercise(String),
+    Tutorial(String),
+}
+
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let Some(active) = &mut state.pack else {
+        return;
+    };
+    let manifest = &active.pack.manifest;
+    let mut clicked = None;
+    egui::Window::new(format!("Course pack: {}", manifest.title))
+        .open(&mut active.open)
+        .default_width(300.)
+        .show(ctx, |ui| {
+            let mut section =
+                |ui: &mut egui::Ui, name: &str, paths: &[String], f: fn(String) -> Open| {
+                    if paths.is_empty() {
+                        return;
+                    }
+                    ui.strong(name);
+                    for path in paths {
+                        if ui.link(path).clicked() {
+                            clicked = Some(f(path.clone()));
+                        }
+                    }
+                };
+            section(ui, "Examples", &manifest.examples, Open::Example);
+            section(ui, "Exercises", &manifest.exercises, Open::Exercise);
+            section(ui, "Tutorials", &manifest.tutorials, Open::Tutorial);
+            if !manifest.specs.is_empty() {
+                ui.weak(format!(
+                    "{} specs, run with `neander test` once installed",
+                    manifest.specs.len()
+                ));
+            }
+        });
+    let Some(clicked) = clicked else {
+        return;
+    };
+    // the pack is small, and the state changes while opening
+    let pack = active.pack.clone();
+    let opened = match clicked {
+        Open::Example(path) => pack
+            .example(&path, state.cpu.memory().len())
+            .map(|loaded| files::set_program(state, None, loaded)),
+        Open::Exercise(path) => pack.exercise(&path).map(|ex| exercise::start(state, ex)),
+        Open::Tutorial(path) => pack
+            .text(&path)
+            .and_then(Tutorial::parse)
+            .map(|t| tutorial::start(state, t)),
+    };
+    if let Err(e) = opened {
+        state.notes.error(ctx, format!("error: {e}"));
+    }
+}
@@ -0,0 +1,209 @@
+//! The command palette, listing every action in `ACTIONS` and the
+//! debugger directives in `DIRECTIVES`, filtered by a fuzzy search.
+//! Directives run in the console; a query with arguments, like
+//! `mem 0..10`, is offered as a directive line of its own.
+use super::actions::{self, ActionInfo, ACTIONS};
+use super::{console, UiState};
+use crate::debugger::{parse_directive, DIRECTIVES};
+use egui::{Key, Modifiers};
+
+pub struct Palette {
+    open: bool,
+    query: String,
+    /// Index of the highlighted entry among the matches
+    selected: usize,
+}
+impl Palette {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+    pub fn open(&mut self) {
+        self.open = true;
+        self.query.clear();
+        self.selected = 0;
+    }
+}
+
+/// Scores how well `query` matches `name`, or `None` if it doesn't.
+/// Every query character must appear in order in the name, ignoring case.
+/// Consecutive characters and matches at word starts score higher.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last = None;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + name[pos..].iter().position(|&n| n == c)?;
+        score += 1;
+        if last == Some(found.wrapping_sub(1)) {
+            score += 5;
+        }
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// An entry of the palette.
+#[derive(Clone)]
+enum Entry {
+    Action(&'static ActionInfo),
+    /// A directive line, run in the console
+    Directive(String),
+}
+impl Entry {
+    fn name(&self) -> String {
+        match self {
+            Entry::Action(info) => info.name.to_string(),
+            Entry::Directive(line) => format!("Console: {line}"),
+        }
+    }
+}
+
+/// The actions and directives matching `query`, best first.
+fn matches(query: &str) -> Vec<Entry> {
+    let actions = ACTIONS.iter().map(Entry::Action);
+    let directives = DIRECTIVES.iter().map(|d| Entry::Directive(d.to_string()));
+    let mut found: Vec<_> = actions
+        .chain(directives)
+        .filter_map(|entry| Some((fuzzy_score(query, &entry.name())?, entry)))
+        .collect();
+    // stable, so equal scores keep the table order
+    found.sort_by_key(|(score, _)| -score);
+    let mut found: Vec<_> = found.into_iter().map(|(_, entry)| entry).collect();
+    let line = query.trim();
+    if line.contains(' ') && parse_directive(line).is_ok() {
+        found.insert(0, Entry::Directive(line.to_string()));
+    }
+    found
+}
+
+/// Runs a directive line in the console. A directive that needs
+/// arguments is left in the console's input to complete instead.
+fn run_directive(ctx: &egui::Context, state: &mut UiState, line: &str) {
+    if parse_directive(line).is_ok() {
+        console::run_line(ctx, state, line);
+    } else {
+        state.console.edit_line(&format!("{line} "));
+    }
+}
+
+/// Shows the palette, if it's open. Up and Down move the
+/// highlight, Enter performs the action and Escape closes.
+pub fn palette(ctx: &egui::Context, state: &mut UiState) {
+    if !state.palette.open {
+        return;
+    }
+    let found: Vec<_> = matches(&state.palette.query)
+        .into_iter()
+        .map(|entry| {
+            let enabled = match &entry {
+                Entry::Action(info) => actions::enabled(info.action, state),
+                Entry::Directive(_) => true,
+            };
+            (entry, enabled)
+        })
+        .collect();
+    let palette = &mut state.palette;
+    let (up, down, enter, escape) = ctx.input_mut(|i| {
+        (
+            i.consume_key(Modifiers::NONE, Key::ArrowUp),
+            i.consume_key(Modifiers::NONE, Key::ArrowDown),
+            i.consume_key(Modifiers::NONE, Key::Enter),
+            i.consume_key(Modifiers::NONE, Key::Escape),
+        )
+    });
+    if up {
+        palette.selected = palette.selected.saturating_sub(1);
+    }
+    if down {
+        palette.selected += 1;
+    }
+    palette.selected = palette.selected.min(found.len().saturating_sub(1));
+    let mut chosen = found
+        .get(palette.selected)
+        .filter(|_| enter)
+        .map(|(entry, _)| entry.clone());
+
+    egui::Window::new("Command palette")
+        .title_bar(false)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, [0., 40.])
+        .show(ctx, |ui| {
+            let field = ui.add(
+                egui::TextEdit::singleline(&mut palette.query)
+                    .hint_text("Type a command")
+                    .desired_width(300.),
+            );
+            if field.changed() {
+                palette.selected = 0;
+            }
+            field.request_focus();
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    for (i, (entry, enabled)) in found.iter().enumerate() {
+                        ui.add_enabled_ui(*enabled, |ui| {
+                            ui.horizontal(|ui| {
+                                let label =
+                                    ui.selectable_label(i == palette.selected, entry.name());
+                                if label.clicked() {
+                                    chosen = Some(entry.clone());
+                                }
+                                if i == palette.selected && (up || down) {
+                                    label.scroll_to_me(None);
+                                }
+                                if let Entry::Action(ActionInfo {
+                                    shortcut: Some(sc), ..
+                                }) = entry
+                                {
+                                    ui.weak(ui.ctx().format_shortcut(sc));
+                                }
+                            });
+                        });
+                    }
+                    if found.is_empty() {
+                        ui.weak("no matching commands");
+                    }
+                });
+        });
+    if escape || chosen.is_some() {
+        palette.open = false;
+    }
+    match chosen {
+        Some(Entry::Action(info)) => actions::perform(ctx, state, info.action),
+        Some(Entry::Directive(line)) => run_directive(ctx, state, &line),
+        None => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("", "Step").is_some());
+        assert!(fuzzy_score("stp", "Step").is_some());
+        assert!(fuzzy_score("pts", "Step").is_none());
+        assert!(fuzzy_score("STEP", "step").is_some());
+        // consecutive and word-start matches rank higher
+        assert!(fuzzy_score("ste", "Step") > fuzzy_score("ste", "Save workspace..."));
+        assert!(fuzzy_score("ow", "Open workspace...") > fuzzy_score("ow", "Copy as hex"));
+        let first = |query| matches(query)[0].name();
+        assert_eq!(first("ow"), "Open workspace...");
+        assert_eq!(first("console: mem"), "Console: mem");
+        // a directive with arguments comes first
+        assert_eq!(first(" mem 0..10 "), "Console: mem 0..10");
+        assert!(matches("step 1 2").is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use super::UiState;
+use crate::disasm;
+use egui::{Color32, Key, Modifiers, Ui};
+
+/// What the search query is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Hexadecimal bytes, with `??` matching any byte
+    Bytes,
+    /// Text encoded as ASCII
+    Ascii,
+    /// The disassembled instructions
+    Disassembly,
+}
+
+/// How a memory cell relates to the search results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    None,
+    Match,
+    /// Part of the result being navigated to
+    Current,
+}
+impl Hit {
+    pub fn color(self) -> Option<Color32> {
+        match self {
+            Hit::None => None,
+            Hit::Match => Some(Color32::from_rgb(90, 80, 0)),
+            Hit::Current => Some(Color32::from_rgb(200, 110, 0)),
+        }
+    }
+}
+
+pub struct Search {
+    open: bool,
+    /// Set when the query field should take focus
+    focus: bool,
+    query: String,
+    mode: SearchMode,
+    /// Start address and length of every match
+    results: Vec<(u8, u8)>,
+    current: usize,
+    error: Option<String>,
+    /// Set when panels should scroll to the current result
+    pub scroll: bool,
+}
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            focus: false,
+            query: String::new(),
+            mode: SearchMode::Bytes,
+            results: Vec::new(),
+            current: 0,
+            error: None,
+            scroll: false,
+        }
+    }
+    /// Opens the search bar, focusing the query field.
+    pub fn show(&mut self) {
+        self.open = true;
+        self.focus = true;
+    }
+    pub fn hit(&self, addr: u8) -> Hit {
+        if !self.open {
+            return Hit::None;
+        }
+        let contains = |(start, len): (u8, u8)| {
+            (start as usize..start as usize + len as usize).contains(&(addr as usize))
+        };
+        match self.results.get(self.current) {
+            Some(r) if contains(*r) => Hit::Current,
+            _ if self.results.iter().any(|r| contains(*r)) => Hit::Match,
+            _ => Hit::None,
+        }
+    }
+    fn update(&mut self, mem: &[u8]) {
+        self.error = None;
+        self.results = match self.mode {
+            SearchMode::Bytes => match parse_pattern(&self.query) {
+                Ok(p) => find_pattern(mem, &p),
+                Err(e) => {
+                    self.error = Some(e);
+                    Vec::new()
+                }
+            },
+            SearchMode::Ascii => {
+                let pattern: Vec<_> = self.query.bytes().map(Some).collect();
+                find_pattern(mem, &pattern)
+            }
+            SearchMode::Disassembly => find_disasm(mem, &self.query),
+        };
+        if self.current >= self.results.len() {
+            self.current = 0;
+        }
+    }
+    fn next(&mut self) {
+        if !self.results.is_empty() {
+            self.current = (self.current + 1) % self.results.len();
+            self.scroll = true;
+        }
+    }
+    fn prev(&mut self) {
+        if !self.results.is_empty() {
+            self.current = (self.current + self.results.len() - 1) % self.results.len();
+            self.scroll = true;
+        }
+    }
+}
+
+/// Parses hexadecimal bytes separated by whitespace,
+/// where `??` matches any byte.
+pub fn parse_pattern(query: &str) -> Result<Vec<Option<u8>>, String> {
+    query
+        .split_whitespace()
+        .map(|word| match word {
+            "??" => Ok(None),
+            _ => u8::from_str_radix(word.strip_prefix("0x").unwrap_or(word), 16)
+                .map(Some)
+                .map_err(|_| format!("invalid byte: {word}")),
+        })
+        .collect()
+}
+
+/// Returns every position where `pattern` occurs in memory.
+pub fn find_pattern(mem: &[u8], pattern: &[Option<u8>]) -> Vec<(u8, u8)> {
+    if pattern.is_empty() || pattern.len() > mem.len() {
+        return Vec::new();
+    }
+    mem.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, w)| {
+            w.iter()
+                .zip(pattern)
+                .all(|(b, p)| p.is_none_or(|p| p == *b))
+        })
+        .map(|(i, _)| (i as u8, pattern.len() as u8))
+        .collect()
+}
+
+/// Returns every instruction whose disassembly contains `query`, ignoring case.
+pub fn find_disasm(mem: &[u8], query: &str) -> Vec<(u8, u8)> {
+    let query = query.trim().to_uppercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    disasm::disassemble(mem, 0, 255)
+        .into_iter()
+        .filter(|l| l.text.to_uppercase().contains(&query))
+        .map(|l| (l.addr, l.bytes.len() as u8))
+        .collect()
+}
+
+/// The search bar, closed with Escape.
+pub fn search_bar(ui: &mut Ui, state: &mut UiState) {
+    let search = &mut state.search;
+    if !search.open {
+        return;
+    }
+    if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+        search.open = false;
+        return;
+    }
+    search.update(state.cpu.memory());
+    ui.horizontal(|ui| {
+        ui.label("Find:");
+        let field = ui.text_edit_singleline(&mut search.query);
+        if search.focus {
+            field.request_focus();
+            search.focus = false;
+        }
+        if field.changed() {
+            search.scroll = true;
+        }
+        if field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+            if ui.input(|i| i.modifiers.shift) {
+                search.prev();
+            } else {
+                search.next();
+            }
+            field.request_focus();
+        }
+        ui.radio_value(&mut search.mode, SearchMode::Bytes, "Bytes");
+        ui.radio_value(&mut search.mode, SearchMode::Ascii, "ASCII");
+        ui.radio_value(&mut search.mode, SearchMode::Disassembly, "Disassembly");
+        if ui.button("Previous").clicked() {
+            search.prev();
+        }
+        if ui.button("Next").clicked() {
+            search.next();
+        }
+        match &search.error {
+            Some(e) => ui.colored_label(Color32::RED, e),
+            None if search.results.is_empty() => ui.label("no results"),
+            None => ui.label(format!(
+                "{} of {}",
+                search.current + 1,
+                search.results.len()
+            )),
+        };
+        if ui.button("Close").clicked() {
+            search.open = false;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+
+    #[test]
+    fn test_find_pattern() {
+        let mut mem = [0_u8; 256];
+        mem[..6].copy_from_slice(&[LDA, 0x80, ADD, 0x80, b'h', b'i']);
+        let pattern = parse_pattern("?? 80").unwrap();
+        assert_eq!(find_pattern(&mem, &pattern), [(0, 2), (2, 2)]);
+        let ascii: Vec<_> = "hi".bytes().map(Some).collect();
+        assert_eq!(find_pattern(&mem, &ascii), [(4, 2)]);
+        assert!(parse_pattern("80 zz").is_err());
+    }
+    #[test]
+    fn test_find_disasm() {
+        let mut mem = [0_u8; 256];
+        mem[..4].copy_from_slice(&[NOT, LDA, 0x80, NOT]);
+        assert_eq!(find_disasm(&mem, "lda"), [(1, 2)]);
+        assert_eq!(find_disasm(&mem, "not"), [(0, 1), (3, 1)]);
+    }
+}
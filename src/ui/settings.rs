@@ -0,0 +1,134 @@
+//! Settings kept across runs in eframe's storage, along with
+//! the window and panel layout eframe saves by itself.
+use serde::{Deserialize, Serialize};
+
+use super::controls::{DEFAULT_HZ, MAX_HZ};
+use super::utils::{NumberBase, PanelBases};
+use super::UiState;
+use crate::numfmt::{self, NumberFormat};
+
+/// The storage key of the settings.
+const KEY: &str = "settings";
+pub const DEFAULT_ZOOM: f32 = 1.2;
+const ZOOM_STEP: f32 = 0.1;
+const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+impl Theme {
+    pub fn apply(self, ctx: &egui::Context) {
+        ctx.set_visuals(match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub base: NumberBase,
+    pub panel_bases: PanelBases,
+    pub dual: bool,
+    pub format: NumberFormat,
+    pub theme: Theme,
+    pub zoom: f32,
+    pub throttle: bool,
+    pub clock_hz: u32,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            base: NumberBase::Dec,
+            panel_bases: PanelBases::default(),
+            dual: false,
+            format: NumberFormat::from_env(),
+            theme: Theme::Dark,
+            zoom: DEFAULT_ZOOM,
+            throttle: false,
+            clock_hz: DEFAULT_HZ,
+        }
+    }
+}
+impl Settings {
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|s| eframe::get_value(s, KEY))
+            .unwrap_or_default()
+    }
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, KEY, self);
+    }
+    pub fn capture(ctx: &egui::Context, state: &UiState) -> Self {
+        Self {
+            base: state.base,
+            panel_bases: state.panel_bases,
+            dual: state.dual,
+            format: numfmt::get(),
+            theme: state.theme,
+            zoom: ctx.zoom_factor(),
+            throttle: state.throttle,
+            clock_hz: state.clock_hz,
+        }
+    }
+    pub fn apply(&self, ctx: &egui::Context, state: &mut UiState) {
+        state.base = self.base;
+        state.panel_bases = self.panel_bases;
+        state.dual = self.dual;
+        numfmt::set(self.format);
+        state.theme = self.theme;
+        self.theme.apply(ctx);
+        state.throttle = self.throttle;
+        state.clock_hz = self.clock_hz.clamp(1, MAX_HZ);
+        ctx.set_zoom_factor(self.zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end()));
+    }
+}
+
+pub fn set_theme(ctx: &egui::Context, state: &mut UiState, theme: Theme) {
+    state.theme = theme;
+    theme.apply(ctx);
+}
+
+/// Changes the zoom by `steps` steps, or resets it if `None`.
+pub fn zoom(ctx: &egui::Context, steps: Option<f32>) {
+    let zoom = match steps {
+        Some(steps) => ctx.zoom_factor() + steps * ZOOM_STEP,
+        None => DEFAULT_ZOOM,
+    };
+    ctx.set_zoom_factor(zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let settings = Settings {
+            base: NumberBase::Hex,
+            panel_bases: PanelBases {
+                memory: Some(NumberBase::Bin),
+                ..PanelBases::default()
+            },
+            dual: true,
+            format: NumberFormat {
+                group_binary: true,
+                ..NumberFormat::PLAIN
+            },
+            theme: Theme::Light,
+            zoom: 1.5,
+            throttle: true,
+            clock_hz: 10,
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(serde_json::from_str::<Settings>(&json).unwrap(), settings);
+        // settings saved by older versions miss some fields
+        let old: Settings = serde_json::from_str(r#"{"base": "Bin"}"#).unwrap();
+        assert_eq!(old.base, NumberBase::Bin);
+        assert_eq!(old.zoom, DEFAULT_ZOOM);
+        assert_eq!(old.clock_hz, DEFAULT_HZ);
+    }
+}
@@ -0,0 +1,67 @@
+//! A window plotting the memory accesses of the last steps,
+//! recorded while it's open. Micro-steps aren't recorded.
+use egui::{Color32, Pos2, Rect, Sense, Vec2};
+
+use super::UiState;
+use crate::timeline::{AccessKind, Timeline};
+
+/// Steps shown at once, the most recent ones.
+const WINDOW: u64 = 200;
+const SIZE: Vec2 = Vec2::new(400., 256.);
+
+#[derive(Default)]
+pub struct TimelinePanel {
+    pub open: bool,
+    pub timeline: Timeline,
+}
+
+fn color(kind: AccessKind) -> Color32 {
+    match kind {
+        AccessKind::Fetch => Color32::GRAY,
+        AccessKind::Read => Color32::from_rgb(70, 130, 180),
+        AccessKind::Write => Color32::from_rgb(178, 34, 34),
+    }
+}
+
+/// Shows the window, if it's open.
+pub fn window(ctx: &egui::Context, state: &mut UiState) {
+    let mut open = state.timeline.open;
+    egui::Window::new("Access timeline")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let timeline = &state.timeline.timeline;
+            ui.horizontal(|ui| {
+                for kind in [AccessKind::Fetch, AccessKind::Read, AccessKind::Write] {
+                    ui.colored_label(color(kind), kind.name());
+                }
+                ui.weak(format!("steps: {}", timeline.steps()));
+            });
+            let (resp, painter) = ui.allocate_painter(SIZE, Sense::hover());
+            resp.widget_info(|| {
+                let name = format!("plot of the memory accesses of the last {WINDOW} steps");
+                egui::WidgetInfo::labeled(egui::WidgetType::Other, true, name)
+            });
+            let rect = resp.rect;
+            painter.rect_filled(rect, 0., ui.visuals().extreme_bg_color);
+            let first = timeline.steps().saturating_sub(WINDOW);
+            let dot = Vec2::new(SIZE.x / WINDOW as f32, SIZE.y / 256.);
+            let start = timeline.accesses().partition_point(|a| a.step < first);
+            for a in &timeline.accesses()[start..] {
+                let pos = Pos2::new(
+                    rect.left() + (a.step - first) as f32 * dot.x,
+                    rect.top() + a.addr as f32 * dot.y,
+                );
+                painter.rect_filled(Rect::from_min_size(pos, dot), 0., color(a.kind));
+            }
+            if let Some(pos) = resp.hover_pos() {
+                let addr = ((pos.y - rect.top()) / dot.y) as u8;
+                let step = first + ((pos.x - rect.left()) / dot.x) as u64;
+                resp.on_hover_text(format!("step {step}, address {addr:02X}"));
+            }
+            if ui.button("Clear").clicked() {
+                state.timeline.timeline.clear();
+            }
+        });
+    state.timeline.open = open;
+}
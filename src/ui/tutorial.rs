@@ -0,0 +1,224 @@
+//! Guided tutorials. A tutorial is a TOML file with a list of
+//! steps, each explaining something, highlighting a part of the
+//! window and waiting until the simulator reaches a given state:
+//!
+//! ```toml
+//! title = "First steps"
+//! program = "0x20 128 0xF0 org 128 5"
+//!
+//! [[step]]
+//! text = "Press Step to execute LDA."
+//! highlight = "controls"
+//! check = { pc = 2, acc = 5 }
+//! ```
+//!
+//! `program` is an optional memfile loaded when the tutorial starts.
+//! Steps without a check wait for the Next button.
+use std::collections::BTreeMap;
+
+use egui::{Color32, Rect, Stroke, Ui};
+use serde::Deserialize;
+
+use super::UiState;
+use crate::cpu::Neander;
+use crate::spec;
+
+/// The tutorial shipped with the simulator.
+pub const BUILTIN: &str = include_str!("../../tutorials/first-steps.toml");
+
+/// A part of the window a step can point at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Menu,
+    Registers,
+    Controls,
+    Memory,
+    Listing,
+    Console,
+}
+
+/// Conditions on the simulator state. A step is
+/// complete when all conditions given hold.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Check {
+    pub pc: Option<u8>,
+    pub acc: Option<i8>,
+    pub zero: Option<bool>,
+    pub negative: Option<bool>,
+    /// Memory cells by address, as unsigned bytes
+    #[serde(default)]
+    pub memory: BTreeMap<String, u8>,
+    /// An address with a breakpoint set
+    pub breakpoint: Option<u8>,
+    pub console_open: Option<bool>,
+}
+impl Check {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    pub fn holds(&self, state: &UiState) -> bool {
+        let cpu = &state.cpu;
+        fn eq<T: PartialEq>(want: Option<T>, got: T) -> bool {
+            want.is_none_or(|w| w == got)
+        }
+        eq(self.pc, cpu.pc())
+            && eq(self.acc, cpu.acc())
+            && eq(self.zero, cpu.status_zero())
+            && eq(self.negative, cpu.status_negative())
+            && eq(self.console_open, state.console.open)
+            && self
+                .memory
+                .iter()
+                .all(|(a, &v)| spec::parse_addr(a).is_ok_and(|a| cpu.ram(a) == v))
+            && self
+                .breakpoint
+                .is_none_or(|b| state.console.breakpoints()[b as usize])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub text: String,
+    pub highlight: Option<Target>,
+    #[serde(default)]
+    pub check: Check,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tutorial {
+    pub title: String,
+    pub program: Option<String>,
+    #[serde(rename = "step")]
+    pub steps: Vec<Step>,
+}
+impl Tutorial {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tutorial: Self = toml::from_str(source).map_err(|e| e.to_string())?;
+        if let Some(program) = &tutorial.program {
+            Neander::load_memfile(program).map_err(|e| e.to_string())?;
+        }
+        for step in &tutorial.steps {
+            for addr in step.check.memory.keys() {
+                spec::parse_addr(addr)?;
+            }
+        }
+        Ok(tutorial)
+    }
+}
+
+/// A tutorial being followed.
+pub struct Progress {
+    tutorial: Tutorial,
+    /// The current step, or `steps.len()` when finished
+    step: usize,
+}
+
+/// Starts `tutorial`, loading its program.
+pub fn start(state: &mut UiState, tutorial: Tutorial) {
+    if let Some(program) = &tutorial.program {
+        // checked by Tutorial::parse
+        state.cpu = Neander::load_memfile(program).unwrap();
+        state.file = None;
+        state.run = None;
+        state.history.history.clear();
+    }
+    state.tutorial = Some(Progress { tutorial, step: 0 });
+}
+
+/// Outlines `rect` if the current step highlights `target`.
+pub fn outline(ui: &Ui, state: &UiState, target: Target, rect: Rect) {
+    let Some(progress) = &state.tutorial else {
+        return;
+    };
+    let step = progress.tutorial.steps.get(progress.step);
+    if step.is_some_and(|s| s.highlight == Some(target)) {
+        let stroke = Stroke::new(2., Color32::GOLD);
+        ui.painter().rect_stroke(rect, 4., stroke);
+    }
+}
+
+/// Advances past completed steps and shows the tutorial window.
+pub fn show(ctx: &egui::Context, state: &mut UiState) {
+    let Some(progress) = &state.tutorial else {
+        return;
+    };
+    let steps = &progress.tutorial.steps;
+    let mut step = progress.step;
+    while steps
+        .get(step)
+        .is_some_and(|s| !s.check.is_empty() && s.check.holds(state))
+    {
+        step += 1;
+    }
+    let mut open = true;
+    let mut next = false;
+    egui::Window::new(format!("Tutorial: {}", progress.tutorial.title))
+        .open(&mut open)
+        .collapsible(true)
+        .default_width(280.)
+        .show(ctx, |ui| match steps.get(step) {
+            Some(s) => {
+                ui.label(format!("Step {} of {}", step + 1, steps.len()));
+                ui.separator();
+                ui.label(&s.text);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if s.check.is_empty() {
+                        next = ui.button("Next").clicked();
+                    } else {
+                        // a spinner would repaint every frame while waiting
+                        ui.weak("waiting for you...");
+                        next = ui.small_button("Skip").clicked();
+                    }
+                });
+            }
+            None => {
+                ui.label("Tutorial complete!");
+            }
+        });
+    if next {
+        step += 1;
+    }
+    match &mut state.tutorial {
+        Some(_) if !open => state.tutorial = None,
+        Some(progress) => progress.step = step,
+        None => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let tutorial = Tutorial::parse(BUILTIN).unwrap();
+        assert!(!tutorial.steps.is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let tutorial = Tutorial::parse(
+            r#"
+            title = "t"
+            program = "0x20 128 0xF0 org 128 -5"
+            [[step]]
+            text = "step"
+            highlight = "controls"
+            check = { pc = 2, acc = -5, negative = true, memory = { 128 = 251 } }
+            "#,
+        )
+        .unwrap();
+        let mut state = UiState::new(Neander::new(), None);
+        start(&mut state, tutorial.clone());
+        let check = &tutorial.steps[0].check;
+        assert!(!check.holds(&state));
+        state.cpu.step();
+        assert!(check.holds(&state));
+        assert!(Tutorial::parse("title = \"t\"\nprogram = \"0x\"\nstep = []").is_err());
+    }
+}
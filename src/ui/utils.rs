@@ -1,55 +1,56 @@
 use super::UiState;
-use crate::cpu::Neander;
-use egui::{Color32, Pos2, Stroke, Ui, Vec2};
-use std::fmt::{Binary, Display, UpperHex};
+use crate::cpu::Registers;
+use egui::{Color32, Stroke, Ui, Vec2};
+use serde::{Deserialize, Serialize};
 
-/// What base the UI is shown in.
-/// Used to format numbers in its respective
-/// base.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum NumberBase {
-    Dec,
-    Hex,
-    Bin,
-}
-impl NumberBase {
-    pub fn fmt(self, val: impl Binary + UpperHex + Display) -> String {
-        match self {
-            Self::Dec => format!("{val:03}"),
-            Self::Bin => format!("{val:08b}"),
-            Self::Hex => format!("{val:02X}"),
-        }
-    }
+pub use crate::numfmt::NumberBase;
+
+/// Bases chosen for single panels. Panels without
+/// one use the global base of `UiState`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanelBases {
+    pub memory: Option<NumberBase>,
+    pub registers: Option<NumberBase>,
+    pub listing: Option<NumberBase>,
 }
-pub fn menu(ui: &mut Ui, state: &mut UiState) {
-    use egui::{menu, Button};
 
-    menu::bar(ui, |ui| {
-        ui.menu_button("Base", |ui| {
-            if ui.button("DEC").clicked() {
-                state.base = NumberBase::Dec;
-                ui.close_menu();
-            }
-            if ui.button("HEX").clicked() {
-                state.base = NumberBase::Hex;
-                ui.close_menu();
-            }
-            if ui.button("BIN").clicked() {
-                state.base = NumberBase::Bin;
-                ui.close_menu();
+/// A combo box choosing the base of a panel, or the global default.
+pub fn base_override(ui: &mut Ui, id: &str, base: &mut Option<NumberBase>) {
+    let text = base.map_or("default", NumberBase::name);
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(text)
+        .width(70.)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(base, None, "default");
+            for b in [NumberBase::Dec, NumberBase::Hex, NumberBase::Bin] {
+                ui.selectable_value(base, Some(b), b.name());
             }
-        })
-    });
+        });
 }
-pub fn cpu_state(ui: &mut Ui, state: &UiState) {
+
+pub fn cpu_state(ui: &mut Ui, state: &mut UiState) -> egui::Response {
+    let base = state.register_base();
+    // throttled runs show which registers the last instruction changed
+    let before = state.run.as_ref().and_then(|run| run.before);
+    let regs = state.cpu.registers();
+    let changed = |f: fn(&Registers) -> u8| before.is_some_and(|b| f(&b) != f(&regs));
     ui.group(|ui| {
+        ui.horizontal(|ui| {
+            ui.label("Base:");
+            base_override(ui, "register base", &mut state.panel_bases.registers);
+        });
         ui.horizontal(|ui| {
             ui.label("PC: ");
-            register(ui, state.base.fmt(state.cpu.pc()));
+            register(ui, base.fmt(state.cpu.pc()), changed(|r| r.pc));
         });
         ui.horizontal(|ui| {
             ui.label("AC: ");
-            register(ui, state.base.fmt(state.cpu.acc()));
+            let acc = state.cpu.acc();
+            let text = match state.dual {
+                true => base.fmt_dual(acc as u8),
+                false => base.fmt(acc),
+            };
+            register(ui, text, changed(|r| r.acc as u8));
         });
         ui.horizontal(|ui| {
             ui.label("STATUS: ");
@@ -58,31 +59,93 @@ pub fn cpu_state(ui: &mut Ui, state: &UiState) {
             status_flag(ui, "N: ", state.cpu.status_negative());
             //ui.code(self.cpu.status().to_string());
         });
-    });
+        for (name, mask) in [("Z", 1), ("N", 2)] {
+            ui.horizontal(|ui| {
+                ui.label(format!("{name}: "));
+                flag_strip(ui, name, &flag_history(state, mask));
+            });
+        }
+    })
+    .response
+}
+
+/// How many instructions the flag strips show.
+const FLAG_HISTORY: usize = 32;
+
+/// The values of the flags of the status register in `mask` over
+/// the last instructions, oldest first, ending with the current one.
+fn flag_history(state: &UiState, mask: u8) -> Vec<bool> {
+    let snapshots: Vec<_> = state
+        .history
+        .history
+        .snapshots()
+        .filter(|r| r.uop == 0)
+        .map(|r| r.status & mask != 0)
+        .collect();
+    let start = snapshots.len().saturating_sub(FLAG_HISTORY - 1);
+    let mut values = snapshots[start..].to_vec();
+    values.push(state.cpu.registers().status & mask != 0);
+    values
 }
 
-fn register(ui: &mut Ui, content: String) {
-    ui.label(
-        egui::RichText::new(content)
-            .code()
-            .color(egui::Color32::GREEN)
-            .size(18.),
+/// A strip showing a flag over time, high while it was set,
+/// with the current value on the right.
+fn flag_strip(ui: &mut Ui, name: &str, values: &[bool]) {
+    const WIDTH: f32 = 4.;
+    let size = Vec2::new(WIDTH * FLAG_HISTORY as f32, 10.);
+    let (rect, resp) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let stroke = Stroke::new(1.5, Color32::GREEN);
+    let y = |on: bool| {
+        if on {
+            rect.top() + 1.
+        } else {
+            rect.bottom() - 1.
+        }
+    };
+    // right-aligned, so the newest value stays in place
+    let x0 = rect.right() - WIDTH * values.len() as f32;
+    let painter = ui.painter();
+    for (i, &on) in values.iter().enumerate() {
+        let x = x0 + WIDTH * i as f32;
+        painter.line_segment([egui::pos2(x, y(on)), egui::pos2(x + WIDTH, y(on))], stroke);
+        if i > 0 && values[i - 1] != on {
+            painter.line_segment([egui::pos2(x, y(true)), egui::pos2(x, y(false))], stroke);
+        }
+    }
+    let changes = values.windows(2).filter(|w| w[0] != w[1]).count();
+    let text = format!(
+        "{name} over the last {} instructions, newest on the right. It changed {changes} times",
+        values.len()
     );
+    resp.on_hover_text(&text)
+        .widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Label, true, &text));
+}
+
+fn register(ui: &mut Ui, content: String, changed: bool) {
+    let color = match changed {
+        true => Color32::YELLOW,
+        false => Color32::GREEN,
+    };
+    ui.label(egui::RichText::new(content).code().color(color).size(18.));
 }
 
 fn status_flag(ui: &mut Ui, name: &str, on: bool) {
     ui.horizontal(|ui| {
         ui.label(name);
-        bit(ui, Color32::GREEN, on);
+        bit(ui, Color32::GREEN, on).widget_info(|| {
+            let state = if on { "set" } else { "clear" };
+            egui::WidgetInfo::labeled(egui::WidgetType::Label, true, format!("{name}{state}"))
+        });
     });
 }
 
-fn bit(ui: &mut Ui, color: egui::Color32, on: bool) {
+fn bit(ui: &mut Ui, color: egui::Color32, on: bool) -> egui::Response {
     let col = if on { color } else { Color32::WHITE };
     ui.allocate_ui(Vec2::new(9., 9.), |ui| {
         let pos = ui.next_widget_position();
         ui.add_space(2.);
         ui.painter().circle(pos, 5., col, Stroke::NONE);
         ui.add_space(2.);
-    });
+    })
+    .response
 }
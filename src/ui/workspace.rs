@@ -0,0 +1,292 @@
+//! Workspace files, saving a whole GUI session. The format is
+//! a header line followed by `key value` lines, the editor's source
+//! as the lines after a `source` line with their count, and the
+//! memory as a Logisim image after a `memory` line.
+use std::path::PathBuf;
+
+use super::utils::{NumberBase, PanelBases};
+use super::UiState;
+use crate::cpu::Registers;
+use crate::formats::WORKSPACE;
+use crate::logisim;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    pub file: Option<PathBuf>,
+    pub regs: Registers,
+    pub memory: Vec<u8>,
+    pub base: NumberBase,
+    pub panel_bases: PanelBases,
+    pub selection: (u8, u8),
+    pub zoom: f32,
+    pub windows: Windows,
+    pub source: String,
+    /// The addresses of the breakpoints, in order
+    pub breakpoints: Vec<u8>,
+}
+
+/// Which windows are open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Windows {
+    pub console: bool,
+    pub editor: bool,
+    pub datapath: bool,
+    pub writes: bool,
+    pub timeline: bool,
+    pub history: bool,
+}
+impl Windows {
+    fn names(&mut self) -> [(&'static str, &mut bool); 6] {
+        [
+            ("console", &mut self.console),
+            ("editor", &mut self.editor),
+            ("datapath", &mut self.datapath),
+            ("writes", &mut self.writes),
+            ("timeline", &mut self.timeline),
+            ("history", &mut self.history),
+        ]
+    }
+}
+
+impl Workspace {
+    pub fn capture(ctx: &egui::Context, state: &UiState) -> Self {
+        Self {
+            file: state.file.clone(),
+            regs: state.cpu.registers(),
+            memory: state.cpu.memory().to_vec(),
+            base: state.base,
+            panel_bases: state.panel_bases,
+            selection: state.selection,
+            zoom: ctx.pixels_per_point(),
+            windows: Windows {
+                console: state.console.open,
+                editor: state.editor.open,
+                datapath: state.datapath.open,
+                writes: state.writes.open,
+                timeline: state.timeline.open,
+                history: state.history.open,
+            },
+            source: state.editor.source().to_string(),
+            breakpoints: (0..=255)
+                .filter(|&addr| state.console.breakpoints()[addr as usize])
+                .collect(),
+        }
+    }
+    pub fn apply(self, ctx: &egui::Context, state: &mut UiState) {
+        state.file = self.file;
+        state.cpu.set_registers(self.regs);
+        state.cpu.memory_mut().copy_from_slice(&self.memory);
+        state.base = self.base;
+        state.panel_bases = self.panel_bases;
+        state.selection = self.selection;
+        state.run = None;
+        let w = self.windows;
+        state.console.open = w.console;
+        state.editor.open = w.editor;
+        state.datapath.open = w.datapath;
+        state.writes.open = w.writes;
+        state.timeline.open = w.timeline;
+        state.history.open = w.history;
+        state.editor.set_source(self.source);
+        let mut breakpoints = [false; 256];
+        for addr in self.breakpoints {
+            breakpoints[addr as usize] = true;
+        }
+        state.console.set_breakpoints(breakpoints);
+        ctx.set_pixels_per_point(self.zoom);
+    }
+
+    pub fn write(&self) -> String {
+        let mut out = format!("{}\n", WORKSPACE.header());
+        if let Some(file) = &self.file {
+            out.push_str(&format!("file {}\n", file.display()));
+        }
+        let r = &self.regs;
+        out.push_str(&format!(
+            "registers {} {} {} {} {} {} {}\n",
+            r.pc, r.acc, r.status, r.mar, r.mdr, r.ir, r.uop
+        ));
+        out.push_str(&format!("base {}\n", self.base.name()));
+        let panels = [
+            ("memory_base", self.panel_bases.memory),
+            ("register_base", self.panel_bases.registers),
+            ("listing_base", self.panel_bases.listing),
+        ];
+        for (key, base) in panels {
+            if let Some(base) = base {
+                out.push_str(&format!("{key} {}\n", base.name()));
+            }
+        }
+        out.push_str(&format!(
+            "selection {} {}\n",
+            self.selection.0, self.selection.1
+        ));
+        out.push_str(&format!("zoom {}\n", self.zoom));
+        let mut windows = self.windows;
+        let open: Vec<_> = windows
+            .names()
+            .into_iter()
+            .filter(|(_, open)| **open)
+            .map(|(name, _)| name)
+            .collect();
+        if !open.is_empty() {
+            out.push_str(&format!("windows {}\n", open.join(" ")));
+        }
+        if !self.breakpoints.is_empty() {
+            let addrs: Vec<_> = self.breakpoints.iter().map(u8::to_string).collect();
+            out.push_str(&format!("breakpoints {}\n", addrs.join(" ")));
+        }
+        if !self.source.is_empty() {
+            // split on '\n' rather than lines() to keep a final newline
+            let lines: Vec<_> = self.source.split('\n').collect();
+            out.push_str(&format!("source {}\n", lines.len()));
+            for line in lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out.push_str("memory\n");
+        out.push_str(&logisim::write_image(&self.memory));
+        out
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut lines = source.lines();
+        // version 2 only added entries, so version 1 reads the same
+        match lines.next().and_then(|line| WORKSPACE.parse_header(line)) {
+            Some(version) => version?,
+            None => return Err("not a workspace file".to_string()),
+        };
+        let mut ws = Self {
+            file: None,
+            regs: crate::cpu::Neander::new().registers(),
+            memory: vec![0; 256],
+            base: NumberBase::Dec,
+            panel_bases: PanelBases::default(),
+            selection: (0, 0),
+            zoom: 1.2,
+            windows: Windows::default(),
+            source: String::new(),
+            breakpoints: Vec::new(),
+        };
+        fn num<T: std::str::FromStr>(s: Option<&str>) -> Result<T, String> {
+            s.and_then(|s| s.parse().ok())
+                .ok_or_else(|| "invalid number in workspace".to_string())
+        }
+        fn base(s: &str) -> Result<NumberBase, String> {
+            NumberBase::from_name(s).ok_or_else(|| format!("invalid base: {s}"))
+        }
+        while let Some(line) = lines.next() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let mut args = value.split_whitespace();
+            match key {
+                "file" => ws.file = Some(PathBuf::from(value)),
+                "registers" => {
+                    ws.regs = Registers {
+                        pc: num(args.next())?,
+                        acc: num(args.next())?,
+                        status: num(args.next())?,
+                        mar: num(args.next())?,
+                        mdr: num(args.next())?,
+                        ir: num(args.next())?,
+                        uop: num(args.next())?,
+                    }
+                }
+                "base" => ws.base = base(value)?,
+                "memory_base" => ws.panel_bases.memory = Some(base(value)?),
+                "register_base" => ws.panel_bases.registers = Some(base(value)?),
+                "listing_base" => ws.panel_bases.listing = Some(base(value)?),
+                "selection" => ws.selection = (num(args.next())?, num(args.next())?),
+                "zoom" => ws.zoom = num(args.next())?,
+                "windows" => {
+                    for name in args {
+                        let (_, open) = ws
+                            .windows
+                            .names()
+                            .into_iter()
+                            .find(|(n, _)| *n == name)
+                            .ok_or_else(|| format!("unknown window: {name}"))?;
+                        *open = true;
+                    }
+                }
+                "breakpoints" => {
+                    ws.breakpoints = args.map(|a| num(Some(a))).collect::<Result<_, _>>()?;
+                    ws.breakpoints.sort_unstable();
+                    ws.breakpoints.dedup();
+                }
+                "source" => {
+                    let count: usize = num(args.next())?;
+                    let source: Vec<_> = lines.by_ref().take(count).collect();
+                    if source.len() < count {
+                        return Err("the workspace ends inside the source".to_string());
+                    }
+                    ws.source = source.join("\n");
+                }
+                "memory" => {
+                    let image: Vec<_> = lines.collect();
+                    logisim::parse_image(&mut ws.memory, &image.join("\n"))
+                        .map_err(|e| e.to_string())?;
+                    break;
+                }
+                "" => (),
+                _ => return Err(format!("unknown workspace entry: {key}")),
+            }
+        }
+        Ok(ws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut memory = vec![0; 256];
+        memory[..3].copy_from_slice(&[0x20, 0x80, 0xF0]);
+        let ws = Workspace {
+            file: Some(PathBuf::from("dir/my prog.mem")),
+            regs: Registers {
+                pc: 2,
+                acc: -5,
+                status: 2,
+                mar: 1,
+                mdr: 0x80,
+                ir: 0x20,
+                uop: 0,
+            },
+            memory,
+            base: NumberBase::Hex,
+            panel_bases: PanelBases {
+                registers: Some(NumberBase::Dec),
+                ..Default::default()
+            },
+            selection: (10, 4),
+            zoom: 1.5,
+            windows: Windows {
+                editor: true,
+                timeline: true,
+                ..Default::default()
+            },
+            source: "lda x\nmemory\n\nhlt\n".to_string(),
+            breakpoints: vec![4, 200],
+        };
+        let text = ws.write();
+        assert!(text.contains("\nwindows editor timeline\nbreakpoints 4 200\nsource 5\n"));
+        assert_eq!(Workspace::parse(&text), Ok(ws));
+        assert!(Workspace::parse("registers 1 2 3").is_err());
+        let old = Workspace::parse("neander-workspace 1\nzoom 1").unwrap();
+        assert_eq!(
+            (old.windows, old.source),
+            (Windows::default(), String::new())
+        );
+        let unknown = Workspace::parse("neander-workspace 2\nwindows console clock");
+        assert_eq!(unknown, Err("unknown window: clock".to_string()));
+        assert!(Workspace::parse("neander-workspace 2\nsource 3\nhlt").is_err());
+        let unsorted = Workspace::parse("neander-workspace 2\nbreakpoints 9 3 9").unwrap();
+        assert_eq!(unsorted.breakpoints, [3, 9]);
+        assert!(Workspace::parse("neander-workspace 2\nbreakpoints 256").is_err());
+        let newer = Workspace::parse("neander-workspace 3\nzoom 1").unwrap_err();
+        assert!(newer.contains("newer neander"), "{newer}");
+    }
+}
@@ -0,0 +1,134 @@
+//! A side panel with the last bytes the program stored, to see
+//! what it changed while running at speed.
+use std::collections::VecDeque;
+
+use egui::{Context, RichText};
+
+use super::UiState;
+use crate::cpu::Neander;
+use crate::hooks::Hooks;
+
+/// How many writes the panel keeps.
+const WRITES_LEN: usize = 64;
+
+/// A byte stored by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Write {
+    /// Address of the instruction
+    pc: u8,
+    addr: u8,
+    old: u8,
+    new: u8,
+}
+
+/// The last writes, newest first, collected as `Hooks`.
+#[derive(Default)]
+pub struct Writes {
+    pub open: bool,
+    writes: VecDeque<Write>,
+    /// Address of the instruction being executed
+    pc: u8,
+}
+impl Writes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn clear(&mut self) {
+        self.writes.clear();
+    }
+    fn push(&mut self, addr: u8, old: u8, new: u8) {
+        if self.writes.len() == WRITES_LEN {
+            self.writes.pop_back();
+        }
+        self.writes.push_front(Write {
+            pc: self.pc,
+            addr,
+            old,
+            new,
+        });
+    }
+}
+impl Hooks for Writes {
+    fn on_fetch(&mut self, _cpu: &Neander, pc: u8, _opcode: u8) {
+        self.pc = pc;
+    }
+    fn on_mem_write(&mut self, addr: u8, old: u8, new: u8) {
+        self.push(addr, old, new);
+    }
+}
+
+/// Shows the panel, if it's open. Clicking a write selects its
+/// address and scrolls the memory grid to it.
+pub fn panel(ctx: &Context, state: &mut UiState) {
+    if !state.writes.open {
+        return;
+    }
+    let base = state.memory_base();
+    egui::SidePanel::right("Writes").show(ctx, |ui| {
+        ui.heading("Recent writes");
+        if state.writes.writes.is_empty() {
+            ui.weak("Nothing stored yet");
+            return;
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("writes").striped(true).show(ui, |ui| {
+                for label in ["Addr", "Old", "New", "By"] {
+                    ui.label(RichText::new(label).weak());
+                }
+                ui.end_row();
+                for w in &state.writes.writes {
+                    let addr = ui
+                        .selectable_label(false, RichText::new(base.fmt(w.addr)).monospace())
+                        .on_hover_text("Show in memory");
+                    if addr.clicked() {
+                        state.selection = (w.addr, w.addr);
+                        state.scroll_to = Some(w.addr);
+                    }
+                    ui.monospace(base.fmt_byte(w.old, state.dual));
+                    ui.monospace(base.fmt_byte(w.new, state.dual));
+                    ui.monospace(base.fmt(w.pc))
+                        .on_hover_text("Address of the instruction");
+                    ui.end_row();
+                }
+            });
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instr::*;
+    use crate::cpu::ExecResult;
+
+    #[test]
+    fn test_writes() {
+        let mut cpu = Neander::new();
+        cpu.set_ram_slice(0, &[LDA, 128, STA, 129, NOT, STA, 129, HLT]);
+        cpu.set_ram(128, 5);
+        let mut writes = Writes::new();
+        while cpu.step_hooked(&mut writes) != ExecResult::Halted {}
+        let expected = [
+            Write {
+                pc: 5,
+                addr: 129,
+                old: 5,
+                new: 0xFA,
+            },
+            Write {
+                pc: 2,
+                addr: 129,
+                old: 0,
+                new: 5,
+            },
+        ];
+        assert!(writes.writes.iter().eq(&expected));
+
+        // the same, one micro-step at a time
+        let mut writes = Writes::new();
+        cpu.set_pc(0);
+        cpu.set_ram(129, 0);
+        while cpu.step_uop_hooked(&mut writes).1 != ExecResult::Halted {}
+        assert!(writes.writes.iter().eq(&expected));
+    }
+}
@@ -0,0 +1,99 @@
+//! Stepping the CPU must not allocate, so that long runs and the
+//! GUI run loop only spend time executing instructions.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use neander::cpu::instr::*;
+use neander::cpu::{ExecResult, Neander};
+use neander::history::History;
+use neander::io::Bus;
+use neander::predecode::Predecoded;
+use neander::profile::Profile;
+#[cfg(feature = "serde")]
+use neander::traps::{Condition, Trap, Traps};
+
+/// Counts the allocations of each thread.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Returns how many times `f` allocated.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn program() -> Neander {
+    let mut cpu = Neander::new();
+    cpu.set_ram_slice(0, &[LDA, 128, ADD, 129, STA, 128, JZ, 10, JMP, 2, HLT]);
+    cpu.set_ram_slice(128, &[0, 0xFF]);
+    cpu
+}
+
+#[test]
+fn test_no_allocations() {
+    assert_eq!(allocations(|| drop(vec![0_u8])), 1);
+
+    let mut cpu = program();
+    assert_eq!(allocations(|| cpu.run().unwrap()), 0);
+
+    let mut cpu = program();
+    assert_eq!(allocations(|| cpu.run_with_limit(10_000).unwrap()), 0);
+
+    let mut cpu = program();
+    let mut cache = Predecoded::new();
+    let n = allocations(|| while cpu.step_predecoded(&mut cache) != ExecResult::Halted {});
+    assert_eq!(n, 0);
+
+    let mut cpu = program();
+    let mut bus = Bus::new();
+    let n = allocations(|| while cpu.step_bus(&mut bus) != ExecResult::Halted {});
+    assert_eq!(n, 0);
+
+    let mut cpu = program();
+    let n = allocations(|| while cpu.step_uop().1 != ExecResult::Halted {});
+    assert_eq!(n, 0);
+
+    // the history allocates its whole capacity up front
+    let mut cpu = program();
+    let mut history = History::new(16);
+    let n = allocations(|| while history.step_bus(&mut cpu, &mut bus) != ExecResult::Halted {});
+    assert_eq!(n, 0);
+
+    // and the GUI steps it with hooks, which mustn't allocate either
+    let mut cpu = program();
+    let mut history = History::new(16);
+    let mut profile = Profile::new();
+    #[cfg(feature = "serde")]
+    let mut traps = Traps::new(vec![Trap {
+        message: "unreachable".to_string(),
+        condition: Condition::Write(200..=210),
+    }]);
+    // traps come with the files they're read from
+    #[cfg(not(feature = "serde"))]
+    let mut traps = ();
+    let n = allocations(|| {
+        let mut hooks = (&mut profile, &mut traps);
+        while history.step_bus_hooked(&mut cpu, &mut bus, &mut hooks) != ExecResult::Halted {}
+    });
+    assert_eq!(n, 0);
+    assert_eq!(profile.counts[0], 1);
+    #[cfg(feature = "serde")]
+    assert!(traps.tripped().is_none());
+}